@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::packer::{self, RawEntry, ZipWriter};
+use crate::unpacker;
+use crate::zip::structs::CentralDirFileHeader;
+use crate::zip::{compression_methods, extra_fields, general_bit_flags, wildcard};
+
+/// What zipalign uses unless told otherwise.
+pub const DEFAULT_ALIGNMENT: u64 = 4;
+/// For the native libraries of an APK, which get mapped straight from it.
+pub const PAGE_ALIGNMENT: u64 = 4096;
+
+#[derive(Debug, Clone)]
+pub struct AlignOptions {
+    /// Alignment of the stored entries no pattern matches.
+    pub alignment: u64,
+    /// Patterns with the alignment of the entries they match; the first match wins.
+    pub patterns: Vec<(String, u64)>,
+}
+
+impl Default for AlignOptions {
+    fn default() -> AlignOptions {
+        AlignOptions {
+            alignment: DEFAULT_ALIGNMENT,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+impl AlignOptions {
+    /// Fails on alignments over `extra_fields::MAX_ALIGNMENT`, which the archive can't record.
+    pub fn validate(&self) -> std::io::Result<()> {
+        let alignments = std::iter::once(&self.alignment)
+            .chain(self.patterns.iter().map(|(_, alignment)| alignment));
+
+        match alignments
+            .copied()
+            .find(|&alignment| alignment > extra_fields::MAX_ALIGNMENT)
+        {
+            Some(alignment) => Err(packer::alignment_too_large(alignment)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn alignment_for(&self, name: &str) -> u64 {
+        self.patterns
+            .iter()
+            .find(|(pattern, _)| wildcard::matches(pattern, name))
+            .map_or(self.alignment, |(_, alignment)| *alignment)
+    }
+}
+
+/// A stored entry whose data doesn't start where it should.
+#[derive(Debug, PartialEq)]
+pub struct MisalignedEntry {
+    pub name: String,
+    /// Where the data starts in the archive.
+    pub data_pos: u64,
+    pub alignment: u64,
+}
+
+//only stored data can be used in place, folders and empty files have none
+fn has_mappable_data(header: &CentralDirFileHeader) -> bool {
+    header.compression_method == compression_methods::STORE
+        && header.general_bit_flag & general_bit_flags::ENCRYPTED == 0
+        && header.compressed_size > 0
+}
+
+/// Copies the archive at `src_file` into `dst_file`, padding the local file headers
+/// of the stored entries so that their data is aligned. Returns how many entries were aligned.
+pub fn align_archive(
+    src_file: &Path,
+    dst_file: &Path,
+    options: &AlignOptions,
+) -> std::io::Result<usize> {
+    options.validate()?;
+
+    let mut source = BufReader::new(File::open(src_file)?);
+    let (end_of_central_dir, central_dir_file_headers) = unpacker::read_central_dir(&mut source)?;
+
+    let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(dst_file)?));
+    zip_writer.set_comment(end_of_central_dir.zip_file_comment);
+
+    let mut aligned = 0;
+
+    for header in &central_dir_file_headers {
+        if has_mappable_data(header) {
+            let alignment = options.alignment_for(&header.file_name);
+            zip_writer.copy_aligned_entry(&mut source, header, alignment)?;
+            aligned += 1;
+        } else {
+            zip_writer.copy_raw_entry(&mut source, header)?;
+        }
+    }
+
+    zip_writer.finish()?;
+
+    Ok(aligned)
+}
+
+/// Lists the stored entries of the archive at `src_file` whose data is not aligned.
+pub fn check_alignment(
+    src_file: &Path,
+    options: &AlignOptions,
+) -> std::io::Result<Vec<MisalignedEntry>> {
+    let mut source = BufReader::new(File::open(src_file)?);
+    let (_, central_dir_file_headers) = unpacker::read_central_dir(&mut source)?;
+
+    let mut misaligned = Vec::new();
+
+    for header in central_dir_file_headers {
+        if !has_mappable_data(&header) {
+            continue;
+        }
+
+        let alignment = options.alignment_for(&header.file_name);
+        let data_pos = RawEntry::read(&mut source, &header)?.data_pos;
+
+        if alignment > 1 && data_pos % alignment != 0 {
+            misaligned.push(MisalignedEntry {
+                name: header.file_name,
+                data_pos,
+                alignment,
+            });
+        }
+    }
+
+    Ok(misaligned)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::path::Path;
+
+    use crate::aligner::{AlignOptions, PAGE_ALIGNMENT};
+    use crate::packer::{self, PackOptions};
+    use crate::test_utils::unpack_and_compare;
+    use crate::unpacker::{self, UnpackOptions};
+
+    #[test]
+    fn align_stored_entries() {
+        let src = Path::new("test-data/unpack_store_0/input.zip");
+        let zip = Path::new("test-data/unpack_store_0/aligned.zip");
+        let options = AlignOptions {
+            patterns: vec![("*.docx".to_owned(), PAGE_ALIGNMENT)],
+            ..AlignOptions::default()
+        };
+
+        assert!(!super::check_alignment(src, &options).unwrap().is_empty());
+
+        let aligned = super::align_archive(src, zip, &options).unwrap();
+        assert!(aligned > 0);
+        assert_eq!(super::check_alignment(zip, &options).unwrap(), vec![]);
+
+        //page alignment of the rest is not what the archive was made for
+        let misaligned = super::check_alignment(
+            zip,
+            &AlignOptions {
+                alignment: PAGE_ALIGNMENT,
+                patterns: Vec::new(),
+            },
+        )
+        .unwrap();
+        assert!(misaligned
+            .iter()
+            .all(|entry| !entry.name.ends_with(".docx")));
+        assert!(!misaligned.is_empty());
+
+        //aligning again moves nothing
+        let again = Path::new("test-data/unpack_store_0/aligned_again.zip");
+        super::align_archive(zip, again, &options).unwrap();
+        assert_eq!(std::fs::read(zip).unwrap(), std::fs::read(again).unwrap());
+
+        unpack_and_compare(
+            zip,
+            &zip.with_extension(""),
+            Path::new("test-data/unpack_store_0/expected"),
+            &UnpackOptions::default(),
+        );
+
+        std::fs::remove_file(zip).unwrap();
+        std::fs::remove_file(again).unwrap();
+    }
+
+    #[test]
+    fn reject_unrecordable_alignment() {
+        let zip = Path::new("test-data/unpack_store_0/actual_too_aligned.zip");
+        let options = AlignOptions {
+            patterns: vec![("*.docx".to_owned(), 64 * 1024)],
+            ..AlignOptions::default()
+        };
+
+        let err = super::align_archive(
+            Path::new("test-data/unpack_store_0/input.zip"),
+            zip,
+            &options,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        let err = packer::pack_dir(
+            Path::new("test-data/pack_encrypted/expected"),
+            zip,
+            &PackOptions {
+                alignment: Some(options),
+                ..PackOptions::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        assert!(!zip.exists());
+        assert!(AlignOptions {
+            alignment: 32 * 1024,
+            patterns: Vec::new(),
+        }
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn pack_aligned() {
+        let src_dir = Path::new("test-data/pack_encrypted/expected");
+        let zip = Path::new("test-data/pack_encrypted/aligned.zip");
+        let options = AlignOptions {
+            alignment: 16,
+            patterns: vec![("*.bin".to_owned(), PAGE_ALIGNMENT)],
+        };
+
+        packer::pack_dir(
+            src_dir,
+            zip,
+            &PackOptions {
+                alignment: Some(options.clone()),
+                ..PackOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(super::check_alignment(zip, &options).unwrap(), vec![]);
+
+        //the padding stays out of the central dir
+        for header in unpacker::list_archive(zip).unwrap() {
+            assert!(header.extra_field.is_empty());
+        }
+
+        unpack_and_compare(
+            zip,
+            &zip.with_extension(""),
+            src_dir,
+            &UnpackOptions::default(),
+        );
+
+        std::fs::remove_file(zip).unwrap();
+    }
+}
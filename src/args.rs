@@ -1 +1,2 @@
 pub mod input_parser;
+pub mod password;
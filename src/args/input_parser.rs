@@ -1,22 +1,327 @@
+use crate::merger::ConflictPolicy;
+use crate::zip::extra_fields;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Extracts `in_file` into `out_folder`.
+    Unpack,
+    /// Stores `out_folder` in a new archive at `in_file`.
+    Pack,
+    /// Copies the entries of the archives in `params[1..]` into a new archive at `in_file`.
+    Merge,
+    /// Copies the entries of `out_folder` matching `include_patterns` into a new archive at `in_file`.
+    Subset,
+    /// Splits the archive or folder at `out_folder` into independent archives named after `in_file`.
+    Split,
+    /// Copies the archive at `out_folder` without what is in front of it into `in_file`.
+    StripPrefix,
+    /// Makes the offsets of `in_file` count what is in front of the archive.
+    FixOffsets,
+    /// Appends the archive or folder at `out_folder` to this executable, making `in_file` extract itself.
+    Sfx,
+    /// Rebuilds the damaged archive at `out_folder` from the entries that check out into `in_file`.
+    Repair,
+    /// Shows the comments of `in_file`, or changes one of them.
+    Comment,
+    /// Compresses `in_file` into the gzip file `out_folder`; `-` stands for stdin and stdout.
+    Gzip,
+    /// Decompresses the gzip file `in_file` into `out_folder`; `-` stands for stdin and stdout.
+    Gunzip,
+    /// Writes the entries of the archive `in_file` as a tar stream into `out_folder`; `-` stands for stdout.
+    ZipToTar,
+    /// Packs the tar stream `in_file` into a new archive at `out_folder`; `-` stands for stdin.
+    TarToZip,
+    /// Copies the archive at `out_folder` into `in_file` with the stored entries aligned,
+    /// or only checks `in_file` with `check`.
+    Align,
+}
+
 pub struct RunArguments {
+    pub command: Command,
     pub in_file: String,
     pub out_folder: String,
+    /// All the paths given after the command.
+    pub params: Vec<String>,
+    pub use_mmap: bool,
+    pub verify_checksums: bool,
+    pub password: Option<String>,
+    /// Encrypt with ZipCrypto instead of AES-256.
+    pub zip_crypto: bool,
+    /// Encrypt the entries when packing, only those matching `encrypt_patterns` if there are any.
+    pub encrypt: bool,
+    pub encrypt_patterns: Vec<String>,
+    pub conflict_policy: ConflictPolicy,
+    pub include_patterns: Vec<String>,
+    /// Split the packed archive into volumes of at most this many bytes.
+    pub volume_size: Option<u64>,
+    /// Largest size of the archives made by `split`.
+    pub part_size: Option<u64>,
+    /// Fill the earlier parts first instead of keeping the entries in order.
+    pub greedy: bool,
+    pub manifest: Option<String>,
+    /// Shell command a self-extractor runs after extracting.
+    pub run_command: Option<String>,
+    /// Entry whose comment `comment` shows or changes instead of the archive comment.
+    pub entry: Option<String>,
+    /// New comment, empty to clear it.
+    pub comment: Option<String>,
+    /// File to read the new comment from.
+    pub comment_file: Option<String>,
+    /// Compression level from 0 to 9; `tar-to-zip` stores the files without one.
+    pub level: Option<u32>,
+    /// Protect the gzip header with a CRC.
+    pub header_crc: bool,
+    /// Leave the name and modification time out of the gzip header.
+    pub no_name: bool,
+    /// Alignment of the stored entries no pattern of `align_patterns` matches.
+    pub alignment: Option<u64>,
+    pub align_patterns: Vec<(String, u64)>,
+    /// Check the alignment of an archive instead of changing it.
+    pub check: bool,
+}
+
+/// Arguments of a self-extractor run.
+pub struct SelfExtractorArguments {
+    pub list: bool,
+    pub test: bool,
+    /// Where to extract; a folder named after the executable when missing.
+    pub out_folder: Option<String>,
+    pub password: Option<String>,
+}
+
+//a byte count with an optional k, m or g suffix for KiB, MiB or GiB, as with zip -s
+fn parse_size(size: &str) -> Option<u64> {
+    let (digits, unit) = match size.char_indices().last()? {
+        (pos, 'k') | (pos, 'K') => (&size[..pos], 1 << 10),
+        (pos, 'm') | (pos, 'M') => (&size[..pos], 1 << 20),
+        (pos, 'g') | (pos, 'G') => (&size[..pos], 1 << 30),
+        _ => (size, 1),
+    };
+
+    digits.parse::<u64>().ok()?.checked_mul(unit)
 }
 
-pub fn parse_args(args: &Vec<String>) -> Result<RunArguments, &'static str> {
+//a power of two the archive can record, with the suffixes of `parse_size`
+fn parse_alignment(alignment: &str) -> Option<u64> {
+    parse_size(alignment).filter(|alignment| {
+        alignment.is_power_of_two() && *alignment <= extra_fields::MAX_ALIGNMENT
+    })
+}
+
+pub fn parse_args(args: &[String]) -> Result<RunArguments, &'static str> {
     let err_not_enough_params = "Not enough actual parameters.";
+    let err_unknown_option = "Unknown option.";
+    let err_unknown_policy = "Unknown conflict policy.";
+    let err_bad_size = "Bad volume size.";
+    let err_bad_part_size = "Bad part size.";
+    let err_bad_level = "Bad compression level.";
+    let err_bad_alignment = "Bad alignment.";
+
+    let mut params = Vec::new();
+    let mut use_mmap = false;
+    let mut verify_checksums = true;
+    let mut password = None;
+    let mut zip_crypto = false;
+    let mut encrypt_patterns = Vec::new();
+    let mut encrypt = false;
+    let mut conflict_policy = ConflictPolicy::Error;
+    let mut include_patterns = Vec::new();
+    let mut volume_size = None;
+    let mut part_size = None;
+    let mut greedy = false;
+    let mut manifest = None;
+    let mut run_command = None;
+    let mut entry = None;
+    let mut comment = None;
+    let mut comment_file = None;
+    let mut level = None;
+    let mut header_crc = false;
+    let mut no_name = false;
+    let mut alignment = None;
+    let mut align_patterns = Vec::new();
+    let mut check = false;
+
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "--mmap" => use_mmap = true,
+            "--no-verify" => verify_checksums = false,
+            option if option.starts_with("--password=") => {
+                password = Some(option["--password=".len()..].to_owned())
+            }
+            "--zipcrypto" => zip_crypto = true,
+            "--encrypt" => encrypt = true,
+            option if option.starts_with("--encrypt=") => {
+                encrypt = true;
+                encrypt_patterns.push(option["--encrypt=".len()..].to_owned());
+            }
+            option if option.starts_with("--on-conflict=") => {
+                conflict_policy = match &option["--on-conflict=".len()..] {
+                    "first" => ConflictPolicy::FirstWins,
+                    "last" => ConflictPolicy::LastWins,
+                    "error" => ConflictPolicy::Error,
+                    _ => return Err(err_unknown_policy),
+                }
+            }
+            option if option.starts_with("--include=") => {
+                include_patterns.push(option["--include=".len()..].to_owned())
+            }
+            option if option.starts_with("--volume-size=") => {
+                volume_size =
+                    Some(parse_size(&option["--volume-size=".len()..]).ok_or(err_bad_size)?)
+            }
+            option if option.starts_with("--part-size=") => {
+                part_size =
+                    Some(parse_size(&option["--part-size=".len()..]).ok_or(err_bad_part_size)?)
+            }
+            "--greedy" => greedy = true,
+            option if option.starts_with("--manifest=") => {
+                manifest = Some(option["--manifest=".len()..].to_owned())
+            }
+            option if option.starts_with("--run=") => {
+                run_command = Some(option["--run=".len()..].to_owned())
+            }
+            option if option.starts_with("--entry=") => {
+                entry = Some(option["--entry=".len()..].to_owned())
+            }
+            option if option.starts_with("--set=") => {
+                comment = Some(option["--set=".len()..].to_owned())
+            }
+            "--clear" => comment = Some(String::new()),
+            option if option.starts_with("--from-file=") => {
+                comment_file = Some(option["--from-file=".len()..].to_owned())
+            }
+            option if option.starts_with("--comment=") => {
+                comment = Some(option["--comment=".len()..].to_owned())
+            }
+            option if option.starts_with("--level=") => {
+                level = Some(
+                    option["--level=".len()..]
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|level| *level <= 9)
+                        .ok_or(err_bad_level)?,
+                )
+            }
+            "--header-crc" => header_crc = true,
+            "--no-name" => no_name = true,
+            option if option.starts_with("--align=") => {
+                let value = &option["--align=".len()..];
+
+                //a pattern followed by its alignment, or the alignment of everything else
+                match value.rsplit_once(':') {
+                    Some((pattern, size)) => align_patterns.push((
+                        pattern.to_owned(),
+                        parse_alignment(size).ok_or(err_bad_alignment)?,
+                    )),
+                    None => alignment = Some(parse_alignment(value).ok_or(err_bad_alignment)?),
+                }
+            }
+            "--check" => check = true,
+            option if option.starts_with("--") => return Err(err_unknown_option),
+            _ => params.push(arg),
+        }
+    }
+
+    let command = match params.first().map(|param| param.as_str()) {
+        Some("pack") => Command::Pack,
+        Some("merge") => Command::Merge,
+        Some("subset") => Command::Subset,
+        Some("split") => Command::Split,
+        Some("strip-prefix") => Command::StripPrefix,
+        Some("fix-offsets") => Command::FixOffsets,
+        Some("sfx") => Command::Sfx,
+        Some("repair") => Command::Repair,
+        Some("comment") => Command::Comment,
+        Some("gzip") => Command::Gzip,
+        Some("gunzip") => Command::Gunzip,
+        Some("zip-to-tar") => Command::ZipToTar,
+        Some("tar-to-zip") => Command::TarToZip,
+        Some("align") => Command::Align,
+        _ => Command::Unpack,
+    };
+
+    if command != Command::Unpack {
+        params.remove(0);
+    }
+
+    //fix-offsets, comment and align --check work on a single archive,
+    //gzip and gunzip name the output after the input
+    let out_folder = match params.get(1) {
+        Some(param) => param.to_string(),
+        None if [
+            Command::FixOffsets,
+            Command::Comment,
+            Command::Gzip,
+            Command::Gunzip,
+        ]
+        .contains(&command)
+            || command == Command::Align && check =>
+        {
+            String::new()
+        }
+        None => return Err(err_not_enough_params),
+    };
 
     let result = RunArguments {
-        in_file: args.get(1).ok_or(err_not_enough_params)?.to_owned(),
-        out_folder: args.get(2).ok_or(err_not_enough_params)?.to_owned(),
+        command,
+        in_file: params.first().ok_or(err_not_enough_params)?.to_string(),
+        out_folder,
+        use_mmap,
+        verify_checksums,
+        password,
+        zip_crypto,
+        params: params.iter().map(|param| param.to_string()).collect(),
+        encrypt_patterns,
+        encrypt,
+        conflict_policy,
+        include_patterns,
+        volume_size,
+        part_size,
+        greedy,
+        manifest,
+        run_command,
+        entry,
+        comment,
+        comment_file,
+        level,
+        header_crc,
+        no_name,
+        alignment,
+        align_patterns,
+        check,
     };
 
     Ok(result)
 }
 
+pub fn parse_self_extractor_args(args: &[String]) -> Result<SelfExtractorArguments, &'static str> {
+    let mut result = SelfExtractorArguments {
+        list: false,
+        test: false,
+        out_folder: None,
+        password: None,
+    };
+
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "--list" => result.list = true,
+            "--test" => result.test = true,
+            option if option.starts_with("--password=") => {
+                result.password = Some(option["--password=".len()..].to_owned())
+            }
+            option if option.starts_with("--") => return Err("Unknown option."),
+            _ if result.out_folder.is_some() => return Err("Too many actual parameters."),
+            _ => result.out_folder = Some(arg.to_owned()),
+        }
+    }
+
+    Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::args::input_parser::parse_args;
+    use crate::args::input_parser::{parse_args, parse_self_extractor_args, Command};
+    use crate::merger::ConflictPolicy;
 
     #[test]
     fn parse_src_file_path() {
@@ -27,10 +332,14 @@ mod tests {
         ];
 
         match parse_args(&args) {
-            Err(_) => assert!(false, "You shouldn't be there."),
+            Err(_) => panic!("You shouldn't be there."),
             Ok(args) => {
+                assert_eq!(args.command, Command::Unpack);
                 assert_eq!(args.in_file, "L:/tests/test1.zip".to_owned());
                 assert_eq!(args.out_folder, "X:/tests/test1".to_owned());
+                assert!(!args.use_mmap);
+                assert!(args.verify_checksums);
+                assert_eq!(args.password, None);
             }
         }
 
@@ -38,7 +347,468 @@ mod tests {
 
         match parse_args(&args) {
             Err(err) => assert_eq!(err, "Not enough actual parameters."),
-            Ok(_) => assert!(false, "You shouldn't be there."),
+            Ok(_) => panic!("You shouldn't be there."),
+        }
+    }
+
+    #[test]
+    fn parse_options() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "--mmap".to_owned(),
+            "L:/tests/test1.zip".to_owned(),
+            "--no-verify".to_owned(),
+            "X:/tests/test1".to_owned(),
+            "--password=pa55 w=rd".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.in_file, "L:/tests/test1.zip".to_owned());
+                assert_eq!(args.out_folder, "X:/tests/test1".to_owned());
+                assert!(args.use_mmap);
+                assert!(!args.verify_checksums);
+                assert_eq!(args.password, Some("pa55 w=rd".to_owned()));
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "--mapped".to_owned(),
+            "L:/tests/test1.zip".to_owned(),
+            "X:/tests/test1".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(err) => assert_eq!(err, "Unknown option."),
+            Ok(_) => panic!("You shouldn't be there."),
+        }
+    }
+
+    #[test]
+    fn parse_pack() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "pack".to_owned(),
+            "--zipcrypto".to_owned(),
+            "L:/tests/test1.zip".to_owned(),
+            "--encrypt=*.txt".to_owned(),
+            "--encrypt=docs/*".to_owned(),
+            "X:/tests/test1".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Pack);
+                assert_eq!(args.in_file, "L:/tests/test1.zip".to_owned());
+                assert_eq!(args.out_folder, "X:/tests/test1".to_owned());
+                assert!(args.zip_crypto);
+                assert!(args.encrypt);
+                assert_eq!(args.encrypt_patterns, vec!["*.txt", "docs/*"]);
+                assert_eq!(args.volume_size, None);
+            }
+        }
+
+        for (option, volume_size) in [
+            ("--volume-size=65536", Ok(Some(65536))),
+            ("--volume-size=95m", Ok(Some(95 * 1024 * 1024))),
+            ("--volume-size=2G", Ok(Some(2 * 1024 * 1024 * 1024))),
+            ("--volume-size=m", Err("Bad volume size.")),
+            ("--volume-size=", Err("Bad volume size.")),
+        ]
+        .iter()
+        {
+            let args = vec![
+                "path/to/exe".to_owned(),
+                "pack".to_owned(),
+                option.to_string(),
+                "L:/tests/test1.zip".to_owned(),
+                "X:/tests/test1".to_owned(),
+            ];
+
+            assert_eq!(parse_args(&args).map(|args| args.volume_size), *volume_size);
+        }
+    }
+
+    #[test]
+    fn parse_merge() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "merge".to_owned(),
+            "L:/tests/merged.zip".to_owned(),
+            "--on-conflict=last".to_owned(),
+            "L:/tests/test1.zip".to_owned(),
+            "L:/tests/test2.zip".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Merge);
+                assert_eq!(args.in_file, "L:/tests/merged.zip".to_owned());
+                assert_eq!(
+                    args.params,
+                    vec![
+                        "L:/tests/merged.zip",
+                        "L:/tests/test1.zip",
+                        "L:/tests/test2.zip"
+                    ]
+                );
+                assert_eq!(args.conflict_policy, ConflictPolicy::LastWins);
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "subset".to_owned(),
+            "L:/tests/subset.zip".to_owned(),
+            "L:/tests/test1.zip".to_owned(),
+            "--include=*.txt".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Subset);
+                assert_eq!(args.out_folder, "L:/tests/test1.zip".to_owned());
+                assert_eq!(args.conflict_policy, ConflictPolicy::Error);
+                assert_eq!(args.include_patterns, vec!["*.txt"]);
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "merge".to_owned(),
+            "--on-conflict=newest".to_owned(),
+            "L:/tests/merged.zip".to_owned(),
+            "L:/tests/test1.zip".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(err) => assert_eq!(err, "Unknown conflict policy."),
+            Ok(_) => panic!("You shouldn't be there."),
+        }
+    }
+
+    #[test]
+    fn parse_split() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "split".to_owned(),
+            "L:/tests/part.zip".to_owned(),
+            "--part-size=100m".to_owned(),
+            "--greedy".to_owned(),
+            "--manifest=L:/tests/parts.txt".to_owned(),
+            "L:/tests/test1.zip".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Split);
+                assert_eq!(args.in_file, "L:/tests/part.zip".to_owned());
+                assert_eq!(args.out_folder, "L:/tests/test1.zip".to_owned());
+                assert_eq!(args.part_size, Some(100 * 1024 * 1024));
+                assert!(args.greedy);
+                assert_eq!(args.manifest, Some("L:/tests/parts.txt".to_owned()));
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "split".to_owned(),
+            "--part-size=lots".to_owned(),
+            "L:/tests/part.zip".to_owned(),
+            "L:/tests/test1.zip".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(err) => assert_eq!(err, "Bad part size."),
+            Ok(_) => panic!("You shouldn't be there."),
+        }
+    }
+
+    #[test]
+    fn parse_prefix_commands() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "fix-offsets".to_owned(),
+            "L:/tests/setup.exe".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::FixOffsets);
+                assert_eq!(args.in_file, "L:/tests/setup.exe".to_owned());
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "repair".to_owned(),
+            "L:/tests/repaired.zip".to_owned(),
+            "L:/tests/truncated.zip".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Repair);
+                assert_eq!(args.out_folder, "L:/tests/truncated.zip".to_owned());
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "strip-prefix".to_owned(),
+            "L:/tests/setup.exe".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(err) => assert_eq!(err, "Not enough actual parameters."),
+            Ok(_) => panic!("You shouldn't be there."),
+        }
+    }
+
+    #[test]
+    fn parse_sfx() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "sfx".to_owned(),
+            "L:/tests/tools.run".to_owned(),
+            "L:/tests/tools".to_owned(),
+            "--run=./install.sh --prefix=/opt".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Sfx);
+                assert_eq!(args.in_file, "L:/tests/tools.run".to_owned());
+                assert_eq!(args.out_folder, "L:/tests/tools".to_owned());
+                assert_eq!(
+                    args.run_command,
+                    Some("./install.sh --prefix=/opt".to_owned())
+                );
+            }
+        }
+
+        let args = vec![
+            "tools.run".to_owned(),
+            "--test".to_owned(),
+            "/tmp/tools".to_owned(),
+        ];
+
+        match parse_self_extractor_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert!(args.test);
+                assert!(!args.list);
+                assert_eq!(args.out_folder, Some("/tmp/tools".to_owned()));
+            }
+        }
+
+        let args = vec!["tools.run".to_owned(), "a".to_owned(), "b".to_owned()];
+
+        match parse_self_extractor_args(&args) {
+            Err(err) => assert_eq!(err, "Too many actual parameters."),
+            Ok(_) => panic!("You shouldn't be there."),
+        }
+    }
+
+    #[test]
+    fn parse_comment() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "comment".to_owned(),
+            "L:/tests/release.zip".to_owned(),
+            "--entry=bin/tool".to_owned(),
+            "--set=built from 1a2b3c".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Comment);
+                assert_eq!(args.in_file, "L:/tests/release.zip".to_owned());
+                assert_eq!(args.entry, Some("bin/tool".to_owned()));
+                assert_eq!(args.comment, Some("built from 1a2b3c".to_owned()));
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "comment".to_owned(),
+            "--clear".to_owned(),
+            "L:/tests/release.zip".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.entry, None);
+                assert_eq!(args.comment, Some(String::new()));
+            }
+        }
+    }
+
+    #[test]
+    fn parse_gzip() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "gzip".to_owned(),
+            "--level=9".to_owned(),
+            "--comment=nightly build".to_owned(),
+            "--header-crc".to_owned(),
+            "L:/tests/build.log".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Gzip);
+                assert_eq!(args.in_file, "L:/tests/build.log".to_owned());
+                assert_eq!(args.out_folder, String::new());
+                assert_eq!(args.level, Some(9));
+                assert_eq!(args.comment, Some("nightly build".to_owned()));
+                assert!(args.header_crc);
+                assert!(!args.no_name);
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "gunzip".to_owned(),
+            "-".to_owned(),
+            "-".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Gunzip);
+                assert_eq!(args.in_file, "-".to_owned());
+                assert_eq!(args.out_folder, "-".to_owned());
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "gzip".to_owned(),
+            "--level=10".to_owned(),
+            "L:/tests/build.log".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(err) => assert_eq!(err, "Bad compression level."),
+            Ok(_) => panic!("You shouldn't be there."),
+        }
+    }
+
+    #[test]
+    fn parse_convert() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "tar-to-zip".to_owned(),
+            "-".to_owned(),
+            "L:/tests/release.zip".to_owned(),
+            "--level=6".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::TarToZip);
+                assert_eq!(args.in_file, "-".to_owned());
+                assert_eq!(args.out_folder, "L:/tests/release.zip".to_owned());
+                assert_eq!(args.level, Some(6));
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "zip-to-tar".to_owned(),
+            "L:/tests/release.zip".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(err) => assert_eq!(err, "Not enough actual parameters."),
+            Ok(_) => panic!("You shouldn't be there."),
+        }
+    }
+
+    #[test]
+    fn parse_align() {
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "align".to_owned(),
+            "--align=*.so:4k".to_owned(),
+            "--align=8".to_owned(),
+            "L:/tests/aligned.apk".to_owned(),
+            "L:/tests/app.apk".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert_eq!(args.command, Command::Align);
+                assert_eq!(args.in_file, "L:/tests/aligned.apk".to_owned());
+                assert_eq!(args.out_folder, "L:/tests/app.apk".to_owned());
+                assert_eq!(args.alignment, Some(8));
+                assert_eq!(args.align_patterns, vec![("*.so".to_owned(), 4096)]);
+                assert!(!args.check);
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "align".to_owned(),
+            "--check".to_owned(),
+            "L:/tests/app.apk".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(_) => panic!("You shouldn't be there."),
+            Ok(args) => {
+                assert!(args.check);
+                assert_eq!(args.out_folder, String::new());
+            }
+        }
+
+        let args = vec![
+            "path/to/exe".to_owned(),
+            "align".to_owned(),
+            "L:/tests/app.apk".to_owned(),
+        ];
+
+        match parse_args(&args) {
+            Err(err) => assert_eq!(err, "Not enough actual parameters."),
+            Ok(_) => panic!("You shouldn't be there."),
+        }
+
+        for bad in [
+            "--align=3",
+            "--align=*.so:0",
+            "--align=*.so:",
+            "--align=64k",
+            "--align=*.so:1m",
+        ]
+        .iter()
+        {
+            let args = vec![
+                "path/to/exe".to_owned(),
+                "align".to_owned(),
+                bad.to_string(),
+                "L:/tests/app.apk".to_owned(),
+            ];
+
+            match parse_args(&args) {
+                Err(err) => assert_eq!(err, "Bad alignment."),
+                Ok(_) => panic!("You shouldn't be there."),
+            }
         }
     }
 }
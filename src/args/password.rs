@@ -0,0 +1,62 @@
+use std::io::{BufRead, Write};
+
+/// Environment variable read when no password is given on the command line.
+pub const PASSWORD_ENV: &str = "SIMPZIP_PASSWORD";
+
+/// Asks for a password on the terminal without echoing it, or reads a line from stdin.
+pub fn prompt() -> std::io::Result<Vec<u8>> {
+    eprint!("Password: ");
+    std::io::stderr().flush()?;
+
+    let mut line = Vec::new();
+
+    if read_from_tty(&mut line).is_err() {
+        line.clear();
+        std::io::stdin().lock().read_until(b'\n', &mut line)?;
+    }
+
+    while line.last() == Some(&b'\n') || line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    Ok(line)
+}
+
+#[cfg(target_os = "linux")]
+fn read_from_tty(line: &mut Vec<u8>) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")?;
+    let fd = tty.as_raw_fd();
+
+    let mut saved = unsafe { std::mem::zeroed::<libc::termios>() };
+
+    if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut silent = saved;
+    silent.c_lflag &= !libc::ECHO;
+    silent.c_lflag |= libc::ECHONL;
+
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &silent) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let result = std::io::BufReader::new(&tty).read_until(b'\n', line);
+
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+
+    result.map(|_| ())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_from_tty(_line: &mut Vec<u8>) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "No terminal support.",
+    ))
+}
@@ -0,0 +1,6 @@
+pub mod adler32;
+pub mod crc32;
+pub mod crc64;
+pub mod sha1;
+pub mod sha256;
+pub mod xxhash64;
@@ -0,0 +1,75 @@
+const MODULUS: u32 = 65521;
+//largest number of bytes that can be summed before the sums may overflow 32 bits
+const MAX_RUN: usize = 5552;
+
+/// Adler-32 (RFC 1950), the checksum of zlib streams.
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    pub fn new() -> Adler32 {
+        Adler32 { a: 1, b: 0 }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(MAX_RUN) {
+            for byte in chunk {
+                self.a += *byte as u32;
+                self.b += self.a;
+            }
+
+            self.a %= MODULUS;
+            self.b %= MODULUS;
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Adler32 {
+        Adler32::new()
+    }
+}
+
+pub fn checksum(bytes: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(bytes);
+
+    adler.value()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checksums::adler32::{checksum, Adler32};
+
+    #[test]
+    fn check_value() {
+        assert_eq!(checksum(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(checksum(b""), 1);
+    }
+
+    #[test]
+    fn long_input() {
+        //enough 0xFF bytes to overflow the sums if they weren't reduced
+        let bytes = vec![0xFFu8; 100_000];
+
+        let mut adler = Adler32::new();
+        for chunk in bytes.chunks(7777) {
+            adler.update(chunk);
+        }
+
+        let (mut a, mut b) = (1u64, 0u64);
+        for byte in &bytes {
+            a = (a + *byte as u64) % 65521;
+            b = (b + a) % 65521;
+        }
+
+        assert_eq!(adler.value(), ((b << 16) | a) as u32);
+        assert_eq!(checksum(&bytes), adler.value());
+    }
+}
@@ -0,0 +1,185 @@
+use std::io::Write;
+
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+const fn make_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0u32; 256]; 8];
+
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+
+            bit += 1;
+        }
+
+        tables[0][n] = crc;
+        n += 1;
+    }
+
+    let mut n = 0;
+    while n < 256 {
+        let mut table = 1;
+        while table < 8 {
+            let prev = tables[table - 1][n];
+            tables[table][n] = (prev >> 8) ^ tables[0][(prev & 0xFF) as usize];
+
+            table += 1;
+        }
+
+        n += 1;
+    }
+
+    tables
+}
+
+//slicing-by-8: one lookup per byte, eight bytes per iteration
+static TABLES: [[u32; 256]; 8] = make_tables();
+
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        Crc32 { state: 0xFFFF_FFFF }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        let mut crc = self.state;
+
+        let mut chunks = bytes.chunks_exact(8);
+
+        for chunk in &mut chunks {
+            let low = crc ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+
+            crc = TABLES[7][(low & 0xFF) as usize]
+                ^ TABLES[6][((low >> 8) & 0xFF) as usize]
+                ^ TABLES[5][((low >> 16) & 0xFF) as usize]
+                ^ TABLES[4][(low >> 24) as usize]
+                ^ TABLES[3][chunk[4] as usize]
+                ^ TABLES[2][chunk[5] as usize]
+                ^ TABLES[1][chunk[6] as usize]
+                ^ TABLES[0][chunk[7] as usize];
+        }
+
+        for byte in chunks.remainder() {
+            crc = (crc >> 8) ^ TABLES[0][((crc ^ *byte as u32) & 0xFF) as usize];
+        }
+
+        self.state = crc;
+    }
+
+    pub fn value(&self) -> u32 {
+        !self.state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32::new()
+    }
+}
+
+/// One table step of CRC-32 without the initial and final inversion, as ZipCrypto uses it.
+pub fn update_byte(crc: u32, byte: u8) -> u32 {
+    (crc >> 8) ^ TABLES[0][((crc ^ byte as u32) & 0xFF) as usize]
+}
+
+pub fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+
+    crc.value()
+}
+
+/// Passes everything through to the inner writer, computing CRC-32 of the written bytes.
+pub struct Crc32Writer<W: Write> {
+    inner: W,
+    crc: Crc32,
+}
+
+impl<W: Write> Crc32Writer<W> {
+    pub fn new(inner: W) -> Crc32Writer<W> {
+        Crc32Writer {
+            inner,
+            crc: Crc32::new(),
+        }
+    }
+
+    pub fn crc(&self) -> u32 {
+        self.crc.value()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for Crc32Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc.update(&buf[..written]);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::checksums::crc32::{checksum, Crc32, Crc32Writer};
+
+    #[test]
+    fn check_value() {
+        assert_eq!(checksum(b"123456789"), 0xCBF43926);
+        assert_eq!(checksum(b""), 0);
+    }
+
+    #[test]
+    fn incremental_update() {
+        let bytes: Vec<u8> = (0..1000u32).map(|n| (n * 7 + 3) as u8).collect();
+
+        let mut crc = Crc32::new();
+        for chunk in bytes.chunks(13) {
+            crc.update(chunk);
+        }
+
+        let mut reference = 0xFFFF_FFFFu32;
+        for byte in &bytes {
+            reference ^= *byte as u32;
+            for _ in 0..8 {
+                reference = if reference & 1 != 0 {
+                    (reference >> 1) ^ 0xEDB8_8320
+                } else {
+                    reference >> 1
+                };
+            }
+        }
+
+        assert_eq!(crc.value(), !reference);
+        assert_eq!(checksum(&bytes), !reference);
+    }
+
+    #[test]
+    fn crc_writer() {
+        let mut writer = Crc32Writer::new(Vec::new());
+        writer.write_all(b"1234").unwrap();
+        writer.write_all(b"56789").unwrap();
+
+        assert_eq!(writer.crc(), 0xCBF43926);
+        assert_eq!(writer.into_inner(), b"123456789");
+    }
+}
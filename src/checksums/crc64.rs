@@ -0,0 +1,86 @@
+//ECMA-182, reflected; the variant used by XZ
+const POLYNOMIAL: u64 = 0xC96C_5795_D787_0F42;
+
+const fn make_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u64;
+
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+
+            bit += 1;
+        }
+
+        table[n] = crc;
+        n += 1;
+    }
+
+    table
+}
+
+static TABLE: [u64; 256] = make_table();
+
+pub struct Crc64 {
+    state: u64,
+}
+
+impl Crc64 {
+    pub fn new() -> Crc64 {
+        Crc64 { state: !0 }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        let mut crc = self.state;
+
+        for byte in bytes {
+            crc = (crc >> 8) ^ TABLE[((crc ^ *byte as u64) & 0xFF) as usize];
+        }
+
+        self.state = crc;
+    }
+
+    pub fn value(&self) -> u64 {
+        !self.state
+    }
+}
+
+impl Default for Crc64 {
+    fn default() -> Crc64 {
+        Crc64::new()
+    }
+}
+
+pub fn checksum(bytes: &[u8]) -> u64 {
+    let mut crc = Crc64::new();
+    crc.update(bytes);
+
+    crc.value()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checksums::crc64::{checksum, Crc64};
+
+    #[test]
+    fn check_value() {
+        assert_eq!(checksum(b"123456789"), 0x995D_C9BB_DF19_39FA);
+        assert_eq!(checksum(b""), 0);
+    }
+
+    #[test]
+    fn incremental_update() {
+        let mut crc = Crc64::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+
+        assert_eq!(crc.value(), checksum(b"123456789"));
+    }
+}
@@ -0,0 +1,175 @@
+pub const BLOCK_LEN: usize = 64;
+
+const INITIAL_STATE: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+pub const DIGEST_LEN: usize = 20;
+
+/// Streaming SHA-1 (FIPS 180-4).
+#[derive(Clone)]
+pub struct Sha1 {
+    state: [u32; 5],
+    block: [u8; BLOCK_LEN],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Sha1 {
+    pub fn new() -> Sha1 {
+        Sha1 {
+            state: INITIAL_STATE,
+            block: [0; BLOCK_LEN],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn compress(state: &mut [u32; 5], block: &[u8]) {
+        let mut w = [0u32; 80];
+
+        for (n, word) in block.chunks_exact(4).enumerate() {
+            w[n] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+
+        for n in 16..80 {
+            w[n] = (w[n - 3] ^ w[n - 8] ^ w[n - 14] ^ w[n - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = *state;
+
+        for (n, word) in w.iter().enumerate() {
+            let (f, k) = match n {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (word, value) in state.iter_mut().zip([a, b, c, d, e]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.total_len += bytes.len() as u64;
+
+        if self.block_len > 0 {
+            let take = std::cmp::min(BLOCK_LEN - self.block_len, bytes.len());
+
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&bytes[..take]);
+            self.block_len += take;
+            bytes = &bytes[take..];
+
+            if self.block_len < BLOCK_LEN {
+                return;
+            }
+
+            Sha1::compress(&mut self.state, &self.block);
+            self.block_len = 0;
+        }
+
+        let mut blocks = bytes.chunks_exact(BLOCK_LEN);
+
+        for block in &mut blocks {
+            Sha1::compress(&mut self.state, block);
+        }
+
+        let remainder = blocks.remainder();
+
+        self.block[..remainder.len()].copy_from_slice(remainder);
+        self.block_len = remainder.len();
+    }
+
+    pub fn finalize(mut self) -> [u8; DIGEST_LEN] {
+        let bit_len = self.total_len.wrapping_mul(8);
+
+        let mut padding = [0u8; BLOCK_LEN + 8];
+        padding[0] = 0x80;
+
+        let padding_len = if self.block_len < 56 {
+            56 - self.block_len
+        } else {
+            120 - self.block_len
+        };
+
+        self.update(&padding[..padding_len]);
+        self.update(&bit_len.to_be_bytes());
+
+        let mut digest = [0u8; DIGEST_LEN];
+
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state.iter()) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+
+        digest
+    }
+}
+
+impl Default for Sha1 {
+    fn default() -> Sha1 {
+        Sha1::new()
+    }
+}
+
+pub fn digest(bytes: &[u8]) -> [u8; DIGEST_LEN] {
+    let mut sha = Sha1::new();
+    sha.update(bytes);
+
+    sha.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checksums::sha1::{digest, Sha1};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn known_digests() {
+        assert_eq!(
+            hex(&digest(b"")),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        );
+        assert_eq!(
+            hex(&digest(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&digest(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let bytes = [b'a'; 1000];
+
+        let mut sha = Sha1::new();
+        for chunk in bytes.chunks(37) {
+            sha.update(chunk);
+        }
+
+        assert_eq!(sha.finalize(), digest(&bytes));
+        assert_eq!(
+            hex(&digest(&bytes)),
+            "291e9a6c66994949b57ba5e650361e98fc36b1ba"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+const PRIME_1: u64 = 11_400_714_785_074_694_791;
+const PRIME_2: u64 = 14_029_467_366_897_019_727;
+const PRIME_3: u64 = 1_609_587_929_392_839_161;
+const PRIME_4: u64 = 9_650_029_242_287_828_579;
+const PRIME_5: u64 = 2_870_177_450_012_600_261;
+
+const STRIPE_LEN: usize = 32;
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME_1)
+}
+
+fn merge_round(acc: u64, value: u64) -> u64 {
+    (acc ^ round(0, value))
+        .wrapping_mul(PRIME_1)
+        .wrapping_add(PRIME_4)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[..8]);
+
+    u64::from_le_bytes(word)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut word = [0u8; 4];
+    word.copy_from_slice(&bytes[..4]);
+
+    u32::from_le_bytes(word)
+}
+
+/// Streaming XXH64, as used by Zstandard frame checksums.
+pub struct XxHash64 {
+    seed: u64,
+    acc: [u64; 4],
+    stripe: [u8; STRIPE_LEN],
+    stripe_len: usize,
+    total_len: u64,
+}
+
+impl XxHash64 {
+    pub fn new(seed: u64) -> XxHash64 {
+        XxHash64 {
+            seed,
+            acc: [
+                seed.wrapping_add(PRIME_1).wrapping_add(PRIME_2),
+                seed.wrapping_add(PRIME_2),
+                seed,
+                seed.wrapping_sub(PRIME_1),
+            ],
+            stripe: [0u8; STRIPE_LEN],
+            stripe_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn consume_stripe(acc: &mut [u64; 4], stripe: &[u8]) {
+        for (lane, acc) in acc.iter_mut().enumerate() {
+            *acc = round(*acc, read_u64(&stripe[lane * 8..]));
+        }
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.total_len += bytes.len() as u64;
+
+        if self.stripe_len > 0 {
+            let take = std::cmp::min(STRIPE_LEN - self.stripe_len, bytes.len());
+
+            self.stripe[self.stripe_len..self.stripe_len + take].copy_from_slice(&bytes[..take]);
+            self.stripe_len += take;
+            bytes = &bytes[take..];
+
+            if self.stripe_len < STRIPE_LEN {
+                return;
+            }
+
+            let stripe = self.stripe;
+            XxHash64::consume_stripe(&mut self.acc, &stripe);
+            self.stripe_len = 0;
+        }
+
+        let mut stripes = bytes.chunks_exact(STRIPE_LEN);
+
+        for stripe in &mut stripes {
+            XxHash64::consume_stripe(&mut self.acc, stripe);
+        }
+
+        let rest = stripes.remainder();
+
+        self.stripe[..rest.len()].copy_from_slice(rest);
+        self.stripe_len = rest.len();
+    }
+
+    pub fn digest(&self) -> u64 {
+        let mut hash = if self.total_len >= STRIPE_LEN as u64 {
+            let [acc_1, acc_2, acc_3, acc_4] = self.acc;
+
+            let mut hash = acc_1
+                .rotate_left(1)
+                .wrapping_add(acc_2.rotate_left(7))
+                .wrapping_add(acc_3.rotate_left(12))
+                .wrapping_add(acc_4.rotate_left(18));
+
+            for acc in &self.acc {
+                hash = merge_round(hash, *acc);
+            }
+
+            hash
+        } else {
+            self.seed.wrapping_add(PRIME_5)
+        };
+
+        hash = hash.wrapping_add(self.total_len);
+
+        let mut rest = &self.stripe[..self.stripe_len];
+
+        while rest.len() >= 8 {
+            hash ^= round(0, read_u64(rest));
+            hash = hash
+                .rotate_left(27)
+                .wrapping_mul(PRIME_1)
+                .wrapping_add(PRIME_4);
+
+            rest = &rest[8..];
+        }
+
+        if rest.len() >= 4 {
+            hash ^= (read_u32(rest) as u64).wrapping_mul(PRIME_1);
+            hash = hash
+                .rotate_left(23)
+                .wrapping_mul(PRIME_2)
+                .wrapping_add(PRIME_3);
+
+            rest = &rest[4..];
+        }
+
+        for byte in rest {
+            hash ^= (*byte as u64).wrapping_mul(PRIME_5);
+            hash = hash.rotate_left(11).wrapping_mul(PRIME_1);
+        }
+
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME_3);
+        hash ^= hash >> 32;
+
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checksums::xxhash64::XxHash64;
+
+    fn hash(bytes: &[u8]) -> u64 {
+        let mut hasher = XxHash64::new(0);
+        hasher.update(bytes);
+
+        hasher.digest()
+    }
+
+    #[test]
+    fn known_values() {
+        assert_eq!(hash(b""), 0xEF46_DB37_51D8_E999);
+        assert_eq!(hash(b"a"), 0xD24E_C4F1_A98C_6E5B);
+        assert_eq!(hash(b"abc"), 0x44BC_2CF5_AD77_0999);
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let bytes: Vec<u8> = (0..1000u32).map(|n| (n * 13 + n / 5) as u8).collect();
+
+        let mut hasher = XxHash64::new(0);
+        for chunk in bytes.chunks(7) {
+            hasher.update(chunk);
+        }
+
+        assert_eq!(hasher.digest(), hash(&bytes));
+    }
+}
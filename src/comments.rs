@@ -0,0 +1,235 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::unpacker::{self, source::SplitSource};
+use crate::zip::structs::{CentralDirFileHeader, EndOfCentralDir};
+use crate::zip::{extra_fields, general_bit_flags, signatures};
+
+/// The comments of an archive as its central directory records them.
+#[derive(Debug, PartialEq)]
+pub struct Comments {
+    pub archive: String,
+    /// Names and comments of the entries that have one, in central directory order.
+    pub entries: Vec<(String, String)>,
+}
+
+fn too_long() -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::InvalidInput,
+        "Comments can't be longer than 65535 bytes.",
+    )
+}
+
+pub fn read_comments(path: &Path) -> std::io::Result<Comments> {
+    let (end_of_central_dir, central_dir_file_headers) =
+        unpacker::read_central_dir(&mut BufReader::new(File::open(path)?))?;
+
+    Ok(Comments {
+        archive: end_of_central_dir.zip_file_comment,
+        entries: central_dir_file_headers
+            .into_iter()
+            .filter(|header| !header.file_comment.is_empty())
+            .map(|header| (header.file_name, header.file_comment))
+            .collect(),
+    })
+}
+
+//writes the central dir and the end of central dir record over the old ones, leaving the entries alone
+fn rewrite_central_dir(
+    path: &Path,
+    edit: impl FnOnce(&mut EndOfCentralDir, &mut [CentralDirFileHeader]) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    if SplitSource::is_split(path) {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "Split archives can't be edited in place.",
+        ));
+    }
+
+    let mut source = BufReader::new(File::open(path)?);
+    let location = unpacker::locate_central_dir(&mut source)?;
+    let (mut end_of_central_dir, mut central_dir_file_headers) =
+        unpacker::read_central_dir(&mut source)?;
+
+    if location.zip64_end_pos.is_some() {
+        return Err(std::io::Error::other(
+            "ZIP64 archives can't be edited in place yet.",
+        ));
+    }
+
+    if location.central_dir_pos + end_of_central_dir.central_dir_size != location.end_pos {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "Central dir size doesn't match its records.",
+        ));
+    }
+
+    edit(&mut end_of_central_dir, &mut central_dir_file_headers)?;
+
+    let mut records = Vec::new();
+
+    //offsets are written back the way they were found, prefix or not
+    for header in &mut central_dir_file_headers {
+        header.local_header_rel_offset -= location.shift;
+        records.extend(header.to_bytes());
+    }
+
+    end_of_central_dir.central_dir_size = records.len() as u64;
+    end_of_central_dir.zip_file_comment_length = end_of_central_dir.zip_file_comment.len() as u16;
+    records.extend(end_of_central_dir.to_bytes());
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(location.central_dir_pos))?;
+    file.write_all(&records)?;
+    file.set_len(location.central_dir_pos + records.len() as u64)?;
+    file.sync_all()
+}
+
+/// Replaces the archive comment; an empty comment clears it.
+pub fn set_archive_comment(path: &Path, comment: &str) -> std::io::Result<()> {
+    if comment.len() > u16::MAX as usize {
+        return Err(too_long());
+    }
+
+    //the end of central dir record is found by its signature, so the comment can't have one
+    if comment
+        .as_bytes()
+        .windows(4)
+        .any(|window| window == signatures::SIGNATURE_CENTRAL_DIR_END.to_le_bytes())
+    {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "Archive comment can't contain an end of central dir signature.",
+        ));
+    }
+
+    rewrite_central_dir(path, |end_of_central_dir, _| {
+        end_of_central_dir.zip_file_comment = comment.to_owned();
+
+        Ok(())
+    })
+}
+
+/// Replaces the comment of the entry `name`; an empty comment clears it.
+pub fn set_entry_comment(path: &Path, name: &str, comment: &str) -> std::io::Result<()> {
+    if comment.len() > u16::MAX as usize {
+        return Err(too_long());
+    }
+
+    rewrite_central_dir(path, |_, central_dir_file_headers| {
+        let header = central_dir_file_headers
+            .iter_mut()
+            .find(|header| header.file_name == name)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("{} is not in the archive.", name),
+                )
+            })?;
+
+        let mut extra_field =
+            extra_fields::remove(&header.extra_field, extra_fields::UNICODE_COMMENT);
+
+        //without the UTF-8 flag the comment would be taken for code page 437
+        if !comment.is_ascii() && header.general_bit_flag & general_bit_flags::UTF8 == 0 {
+            extra_field.extend(extra_fields::unicode_block(
+                extra_fields::UNICODE_COMMENT,
+                comment.as_bytes(),
+                comment,
+            ));
+        }
+
+        if extra_field.len() > u16::MAX as usize {
+            return Err(too_long());
+        }
+
+        header.extra_field_length = extra_field.len() as u16;
+        header.extra_field = extra_field;
+        header.file_comment_length = comment.len() as u16;
+        header.file_comment = comment.to_owned();
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::Path;
+
+    use crate::comments::Comments;
+    use crate::unpacker::{self, UnpackOptions};
+    use crate::zip::extra_fields;
+
+    fn extra_field_of(zip: &Path, name: &str) -> Vec<u8> {
+        unpacker::list_archive(zip)
+            .unwrap()
+            .into_iter()
+            .find(|header| header.file_name == name)
+            .unwrap()
+            .extra_field
+    }
+
+    #[test]
+    fn edit_comments() {
+        let zip = Path::new("test-data/edit_archive/actual_comments.zip");
+        std::fs::copy("test-data/merge_archives/first.zip", zip).unwrap();
+
+        let data_len = unpacker::locate_central_dir(&mut BufReader::new(File::open(zip).unwrap()))
+            .unwrap()
+            .central_dir_pos as usize;
+        let data = std::fs::read(zip).unwrap()[..data_len].to_vec();
+
+        super::set_archive_comment(zip, "build 1234\ncommit abcdef\n").unwrap();
+        super::set_entry_comment(zip, "text.txt", "plain").unwrap();
+        super::set_entry_comment(zip, "folder_1/random.bin", "Größe: 70 kB").unwrap();
+
+        assert!(super::set_entry_comment(zip, "missing.txt", "").is_err());
+        assert!(super::set_archive_comment(zip, "PK\u{5}\u{6}").is_err());
+
+        assert_eq!(
+            super::read_comments(zip).unwrap(),
+            Comments {
+                archive: "build 1234\ncommit abcdef\n".to_owned(),
+                entries: vec![
+                    ("text.txt".to_owned(), "plain".to_owned()),
+                    ("folder_1/random.bin".to_owned(), "Größe: 70 kB".to_owned()),
+                ],
+            }
+        );
+
+        assert!(extra_fields::find(
+            &extra_field_of(zip, "folder_1/random.bin"),
+            extra_fields::UNICODE_COMMENT
+        )
+        .is_some());
+
+        super::set_archive_comment(zip, "").unwrap();
+        super::set_entry_comment(zip, "folder_1/random.bin", "").unwrap();
+
+        assert_eq!(
+            super::read_comments(zip).unwrap(),
+            Comments {
+                archive: String::new(),
+                entries: vec![("text.txt".to_owned(), "plain".to_owned())],
+            }
+        );
+
+        assert!(extra_fields::find(
+            &extra_field_of(zip, "folder_1/random.bin"),
+            extra_fields::UNICODE_COMMENT
+        )
+        .is_none());
+
+        //only the central dir was rewritten
+        assert_eq!(std::fs::read(zip).unwrap()[..data_len], data[..]);
+        assert_eq!(
+            unpacker::test_archive(zip, &UnpackOptions::default()).unwrap(),
+            5
+        );
+
+        std::fs::remove_file(zip).unwrap();
+    }
+}
@@ -0,0 +1,293 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::packer::{FileOptions, ZipWriter};
+use crate::tar::{EntryKind, TarHeader, TarReader, TarWriter};
+use crate::unpacker::algorithms::store;
+use crate::unpacker::source::{ArchiveSource, MappedSource, SplitSource};
+use crate::unpacker::{self, UnpackOptions};
+use crate::zip::structs::CentralDirFileHeader;
+use crate::zip::{dos_time, extra_fields, host_systems};
+
+const FILE_TYPE_MASK: u32 = 0o170000;
+const REGULAR_FILE: u32 = 0o100000;
+const DIRECTORY: u32 = 0o040000;
+const SYMLINK: u32 = 0o120000;
+
+//targets longer than this are not taken for symlinks
+const MAX_LINK_LEN: u64 = 4096;
+
+//file type and permission bits, made up for archives from elsewhere
+fn unix_mode(header: &CentralDirFileHeader, is_folder: bool) -> u32 {
+    let mode = header.external_file_attribs >> 16;
+
+    if header.version_made_by >> 8 == host_systems::UNIX && mode != 0 {
+        if mode & FILE_TYPE_MASK == 0 {
+            return mode | if is_folder { DIRECTORY } else { REGULAR_FILE };
+        }
+
+        return mode;
+    }
+
+    if is_folder {
+        DIRECTORY | 0o755
+    } else {
+        REGULAR_FILE | 0o644
+    }
+}
+
+//the extended timestamp is exact, the MS-DOS fields only to two seconds
+fn modification_time(header: &CentralDirFileHeader) -> u64 {
+    match extra_fields::modification_time(&header.extra_field) {
+        Some(mtime) => std::cmp::max(mtime, 0) as u64,
+        None => dos_time::to_unix_time(header.last_mod_file_date, header.last_mod_file_time),
+    }
+}
+
+fn write_tar_entries<S: ArchiveSource, W: Write>(
+    source: &mut S,
+    tar_writer: &mut TarWriter<W>,
+    options: &UnpackOptions,
+) -> std::io::Result<()> {
+    let mut buffer = vec![0u8; store::CHUNK_SIZE];
+    let mut password = options.password.clone();
+
+    unpacker::visit_entries(source, |source, header, local_file_header| {
+        let is_folder = unpacker::is_folder(local_file_header);
+        let mode = unix_mode(header, is_folder);
+
+        let mut tar_header = TarHeader {
+            name: header.file_name.clone(),
+            kind: EntryKind::File,
+            mode: mode & 0o7777,
+            mtime: modification_time(header),
+            size: 0,
+            link_name: String::new(),
+        };
+
+        match mode & FILE_TYPE_MASK {
+            DIRECTORY => {
+                tar_header.kind = EntryKind::Directory;
+                tar_writer.start_entry(&tar_header)
+            }
+            SYMLINK if header.uncompressed_size <= MAX_LINK_LEN => {
+                let mut target = Vec::new();
+                unpacker::unpack_entry_data(
+                    source,
+                    local_file_header,
+                    &mut target,
+                    &mut buffer,
+                    &mut password,
+                    options,
+                )?;
+
+                tar_header.kind = EntryKind::Symlink;
+                tar_header.link_name = String::from_utf8(target).map_err(|_| {
+                    std::io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Symlink target of {} is not UTF-8.", header.file_name),
+                    )
+                })?;
+                tar_writer.start_entry(&tar_header)
+            }
+            _ => {
+                tar_header.size = header.uncompressed_size;
+                tar_writer.start_entry(&tar_header)?;
+
+                unpacker::unpack_entry_data(
+                    source,
+                    local_file_header,
+                    tar_writer,
+                    &mut buffer,
+                    &mut password,
+                    options,
+                )
+                .map(|_| ())
+            }
+        }
+    })
+}
+
+/// Writes the entries of the archive at `src_file` as a tar stream into `writer`,
+/// with their names, modification times, Unix modes and symlinks.
+/// Entries without a Unix mode get 755 for folders and 644 for files.
+pub fn zip_to_tar<W: Write>(
+    src_file: &Path,
+    writer: W,
+    options: &UnpackOptions,
+) -> std::io::Result<W> {
+    let mut tar_writer = TarWriter::new(writer);
+
+    if SplitSource::is_split(src_file) {
+        write_tar_entries(&mut SplitSource::open(src_file)?, &mut tar_writer, options)?;
+    } else if options.use_mmap {
+        write_tar_entries(&mut MappedSource::open(src_file)?, &mut tar_writer, options)?;
+    } else {
+        let mut source = BufReader::new(File::open(src_file)?);
+        write_tar_entries(&mut source, &mut tar_writer, options)?;
+    }
+
+    tar_writer.finish()
+}
+
+//names in a ZIP are relative
+fn zip_name(tar_name: &str) -> &str {
+    let mut name = tar_name;
+
+    loop {
+        if let Some(rest) = name.strip_prefix("./") {
+            name = rest;
+        } else if let Some(rest) = name.strip_prefix('/') {
+            name = rest;
+        } else {
+            return name;
+        }
+    }
+}
+
+/// Packs the tar stream read from `reader` into a new archive at `dst_file`,
+/// compressed with Deflate when `compression_level` is given.
+/// Hard links, devices and FIFOs have no place in a ZIP and are left out; their names are returned.
+pub fn tar_to_zip<R: Read>(
+    reader: R,
+    dst_file: &Path,
+    compression_level: Option<u32>,
+) -> std::io::Result<Vec<String>> {
+    let mut tar_reader = TarReader::new(reader);
+    let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(dst_file)?));
+    let mut skipped = Vec::new();
+
+    while let Some(header) = tar_reader.next_entry()? {
+        let name = zip_name(&header.name);
+        let last_modified = UNIX_EPOCH + Duration::from_secs(header.mtime);
+
+        //the root folder itself
+        if name.is_empty() {
+            continue;
+        }
+
+        match header.kind {
+            EntryKind::Directory => {
+                zip_writer.add_unix_directory(name, last_modified, header.mode)?
+            }
+            EntryKind::File => zip_writer.add_file(
+                name,
+                &mut tar_reader,
+                &file_options(last_modified, compression_level, REGULAR_FILE | header.mode),
+            )?,
+            //the target is the data of the entry, as Info-ZIP stores it
+            EntryKind::Symlink => zip_writer.add_file(
+                name,
+                &mut header.link_name.as_bytes(),
+                &file_options(last_modified, None, SYMLINK | header.mode),
+            )?,
+            EntryKind::HardLink | EntryKind::Other(_) => skipped.push(header.name),
+        }
+    }
+
+    zip_writer.finish()?.flush()?;
+
+    Ok(skipped)
+}
+
+fn file_options(
+    last_modified: SystemTime,
+    compression_level: Option<u32>,
+    unix_mode: u32,
+) -> FileOptions {
+    FileOptions {
+        last_modified,
+        compression_level,
+        unix_mode: Some(unix_mode),
+        ..FileOptions::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::path::Path;
+
+    use crate::tar::{EntryKind, TarReader};
+    use crate::unpacker::{self, UnpackOptions};
+
+    fn tar_entries(tar: &[u8]) -> Vec<(String, EntryKind, u32, u64, String, Vec<u8>)> {
+        let mut reader = TarReader::new(tar);
+        let mut entries = Vec::new();
+
+        while let Some(header) = reader.next_entry().unwrap() {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            entries.push((
+                header.name,
+                header.kind,
+                header.mode,
+                header.mtime,
+                header.link_name,
+                data,
+            ));
+        }
+
+        entries
+    }
+
+    #[test]
+    fn tar_to_zip_and_back() {
+        let out_folder = Path::new("test-data/tar/actual");
+
+        if out_folder.exists() {
+            std::fs::remove_dir_all(out_folder).unwrap();
+        }
+
+        std::fs::create_dir(out_folder).unwrap();
+
+        let tar = std::fs::read("test-data/tar/gnu.tar").unwrap();
+        let expected = tar_entries(&tar);
+
+        for level in [None, Some(6)].iter() {
+            let zip_path = out_folder.join("converted.zip");
+            let skipped = super::tar_to_zip(&tar[..], &zip_path, *level).unwrap();
+            assert!(skipped.is_empty());
+
+            let headers = unpacker::list_archive(&zip_path).unwrap();
+            assert_eq!(headers.len(), expected.len());
+            assert_eq!(headers[2].external_file_attribs >> 16, 0o120777);
+
+            assert_eq!(
+                unpacker::test_archive(&zip_path, &UnpackOptions::default()).unwrap(),
+                3
+            );
+
+            let back = super::zip_to_tar(&zip_path, Vec::new(), &UnpackOptions::default()).unwrap();
+            assert_eq!(tar_entries(&back), expected);
+        }
+
+        std::fs::remove_dir_all(out_folder).unwrap();
+    }
+
+    #[test]
+    fn zip_without_unix_modes() {
+        let tar = super::zip_to_tar(
+            Path::new("test-data/unpack_store_0/input.zip"),
+            Vec::new(),
+            &UnpackOptions::default(),
+        )
+        .unwrap();
+        let entries = tar_entries(&tar);
+
+        assert!(!entries.is_empty());
+
+        for (name, kind, mode, _, _, data) in &entries {
+            if name.ends_with('/') {
+                assert_eq!((*kind, *mode), (EntryKind::Directory, 0o755));
+            } else {
+                assert_eq!((*kind, *mode), (EntryKind::File, 0o644));
+
+                let expected = Path::new("test-data/unpack_store_0/expected").join(name);
+                assert_eq!(data, &std::fs::read(expected).unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod aes;
+pub mod hmac;
+pub mod pbkdf2;
+pub mod random;
+pub mod winzip_aes;
+pub mod zip_crypto;
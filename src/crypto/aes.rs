@@ -0,0 +1,163 @@
+pub const BLOCK_LEN: usize = 16;
+
+const S_BOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const ROUND_CONSTANTS: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+fn xtime(byte: u8) -> u8 {
+    (byte << 1) ^ if byte & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+/// AES block encryption (FIPS 197) with a 128, 192 or 256-bit key.
+#[derive(Clone)]
+pub struct Aes {
+    round_keys: Vec<[u8; BLOCK_LEN]>,
+}
+
+impl Aes {
+    /// Returns None unless the key is 16, 24 or 32 bytes long.
+    pub fn new(key: &[u8]) -> Option<Aes> {
+        let key_words = match key.len() {
+            16 | 24 | 32 => key.len() / 4,
+            _ => return None,
+        };
+        let rounds = key_words + 6;
+
+        let mut words: Vec<[u8; 4]> = key
+            .chunks_exact(4)
+            .map(|word| [word[0], word[1], word[2], word[3]])
+            .collect();
+
+        for n in key_words..4 * (rounds + 1) {
+            let mut temp = words[n - 1];
+
+            if n % key_words == 0 {
+                temp = [
+                    S_BOX[temp[1] as usize] ^ ROUND_CONSTANTS[n / key_words - 1],
+                    S_BOX[temp[2] as usize],
+                    S_BOX[temp[3] as usize],
+                    S_BOX[temp[0] as usize],
+                ];
+            } else if key_words > 6 && n % key_words == 4 {
+                temp = temp.map(|byte| S_BOX[byte as usize]);
+            }
+
+            let previous = words[n - key_words];
+            words.push([
+                previous[0] ^ temp[0],
+                previous[1] ^ temp[1],
+                previous[2] ^ temp[2],
+                previous[3] ^ temp[3],
+            ]);
+        }
+
+        let round_keys = words
+            .chunks_exact(4)
+            .map(|round| {
+                let mut round_key = [0u8; BLOCK_LEN];
+
+                for (bytes, word) in round_key.chunks_exact_mut(4).zip(round) {
+                    bytes.copy_from_slice(word);
+                }
+
+                round_key
+            })
+            .collect();
+
+        Some(Aes { round_keys })
+    }
+
+    fn add_round_key(state: &mut [u8; BLOCK_LEN], round_key: &[u8; BLOCK_LEN]) {
+        for (byte, key) in state.iter_mut().zip(round_key.iter()) {
+            *byte ^= key;
+        }
+    }
+
+    //SubBytes and ShiftRows in one pass; the state is stored column by column
+    fn sub_shift(state: &mut [u8; BLOCK_LEN]) {
+        let old = *state;
+
+        for column in 0..4 {
+            for row in 0..4 {
+                state[4 * column + row] = S_BOX[old[4 * ((column + row) % 4) + row] as usize];
+            }
+        }
+    }
+
+    fn mix_columns(state: &mut [u8; BLOCK_LEN]) {
+        for column in state.chunks_exact_mut(4) {
+            let [a, b, c, d] = [column[0], column[1], column[2], column[3]];
+            let all = a ^ b ^ c ^ d;
+
+            column[0] ^= all ^ xtime(a ^ b);
+            column[1] ^= all ^ xtime(b ^ c);
+            column[2] ^= all ^ xtime(c ^ d);
+            column[3] ^= all ^ xtime(d ^ a);
+        }
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8; BLOCK_LEN]) {
+        let last = self.round_keys.len() - 1;
+
+        Aes::add_round_key(block, &self.round_keys[0]);
+
+        for round_key in &self.round_keys[1..last] {
+            Aes::sub_shift(block);
+            Aes::mix_columns(block);
+            Aes::add_round_key(block, round_key);
+        }
+
+        Aes::sub_shift(block);
+        Aes::add_round_key(block, &self.round_keys[last]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::aes::Aes;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn encrypt(key_len: u8) -> String {
+        let key: Vec<u8> = (0..key_len).collect();
+        let mut block = [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+            0xee, 0xff,
+        ];
+
+        Aes::new(&key).unwrap().encrypt_block(&mut block);
+
+        hex(&block)
+    }
+
+    #[test]
+    fn fips_197_vectors() {
+        assert_eq!(encrypt(16), "69c4e0d86a7b0430d8cdb78070b4c55a");
+        assert_eq!(encrypt(24), "dda97ca4864cdfe06eaf70a0ec0d7191");
+        assert_eq!(encrypt(32), "8ea2b7ca516745bfeafc49904b496089");
+    }
+
+    #[test]
+    fn reject_bad_key_length() {
+        assert!(Aes::new(&[0; 20]).is_none());
+    }
+}
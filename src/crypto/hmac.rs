@@ -0,0 +1,74 @@
+use crate::checksums::sha1::{self, Sha1};
+
+/// Streaming HMAC-SHA1 (RFC 2104).
+#[derive(Clone)]
+pub struct HmacSha1 {
+    inner: Sha1,
+    outer: Sha1,
+}
+
+impl HmacSha1 {
+    pub fn new(key: &[u8]) -> HmacSha1 {
+        let mut block = [0u8; sha1::BLOCK_LEN];
+
+        if key.len() > sha1::BLOCK_LEN {
+            block[..sha1::DIGEST_LEN].copy_from_slice(&sha1::digest(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha1::new();
+        let mut outer = Sha1::new();
+
+        inner.update(&block.map(|byte| byte ^ 0x36));
+        outer.update(&block.map(|byte| byte ^ 0x5c));
+
+        HmacSha1 { inner, outer }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.inner.update(bytes);
+    }
+
+    pub fn finalize(self) -> [u8; sha1::DIGEST_LEN] {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+
+        outer.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::hmac::HmacSha1;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn mac(key: &[u8], data: &[u8]) -> String {
+        let mut hmac = HmacSha1::new(key);
+        hmac.update(data);
+
+        hex(&hmac.finalize())
+    }
+
+    #[test]
+    fn rfc_2202_vectors() {
+        assert_eq!(
+            mac(&[0x0b; 20], b"Hi There"),
+            "b617318655057264e28bc0b6fb378c8ef146be00"
+        );
+        assert_eq!(
+            mac(b"Jefe", b"what do ya want for nothing?"),
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
+        );
+        assert_eq!(
+            mac(
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ),
+            "aa4ae5e15272d00e95705637ce8a3b55ed402112"
+        );
+    }
+}
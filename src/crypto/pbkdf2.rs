@@ -0,0 +1,64 @@
+use crate::checksums::sha1;
+use crate::crypto::hmac::HmacSha1;
+
+/// Fills `output` with a PBKDF2-HMAC-SHA1 key (RFC 8018).
+pub fn pbkdf2_hmac_sha1(password: &[u8], salt: &[u8], iterations: u32, output: &mut [u8]) {
+    let keyed = HmacSha1::new(password);
+
+    for (index, chunk) in output.chunks_mut(sha1::DIGEST_LEN).enumerate() {
+        let mut hmac = keyed.clone();
+        hmac.update(salt);
+        hmac.update(&(index as u32 + 1).to_be_bytes());
+
+        let mut block = hmac.finalize();
+        let mut result = block;
+
+        for _ in 1..iterations {
+            let mut hmac = keyed.clone();
+            hmac.update(&block);
+            block = hmac.finalize();
+
+            for (byte, value) in result.iter_mut().zip(block.iter()) {
+                *byte ^= value;
+            }
+        }
+
+        chunk.copy_from_slice(&result[..chunk.len()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::pbkdf2::pbkdf2_hmac_sha1;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn rfc_6070_vectors() {
+        let mut key = [0u8; 20];
+
+        pbkdf2_hmac_sha1(b"password", b"salt", 1, &mut key);
+        assert_eq!(hex(&key), "0c60c80f961f0e71f3a9b524af6012062fe037a6");
+
+        pbkdf2_hmac_sha1(b"password", b"salt", 2, &mut key);
+        assert_eq!(hex(&key), "ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957");
+
+        pbkdf2_hmac_sha1(b"password", b"salt", 4096, &mut key);
+        assert_eq!(hex(&key), "4b007901b765489abead49d926f721d065a429c1");
+
+        let mut key = [0u8; 25];
+
+        pbkdf2_hmac_sha1(
+            b"passwordPASSWORDpassword",
+            b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+            4096,
+            &mut key,
+        );
+        assert_eq!(
+            hex(&key),
+            "3d2eec4fe41c849b80c8d83662c0e44a8b291a964cf2f07038"
+        );
+    }
+}
@@ -0,0 +1,22 @@
+/// Fills `bytes` from the operating system's random source.
+#[cfg(unix)]
+pub fn fill(bytes: &mut [u8]) -> std::io::Result<()> {
+    use std::io::Read;
+
+    std::fs::File::open("/dev/urandom")?.read_exact(bytes)
+}
+
+/// Fills `bytes` from the randomly keyed hasher of the standard library.
+#[cfg(not(unix))]
+pub fn fill(bytes: &mut [u8]) -> std::io::Result<()> {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    for (n, chunk) in bytes.chunks_mut(8).enumerate() {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_usize(n);
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes()[..chunk.len()]);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,348 @@
+use std::io::{Read, Write};
+
+use crate::crypto::aes::{self, Aes};
+use crate::crypto::hmac::HmacSha1;
+use crate::crypto::{pbkdf2, random};
+use crate::stream_utils::byte_writers;
+use crate::zip::extra_fields;
+
+pub const PASSWORD_VERIFIER_LEN: usize = 2;
+pub const AUTH_CODE_LEN: usize = 10;
+
+const KEY_ITERATIONS: u32 = 1000;
+
+/// Contents of the WinZip AES extra field (0x9901).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AesExtraField {
+    /// 1 for AE-1, 2 for AE-2 which leaves the CRC-32 out.
+    pub vendor_version: u16,
+    /// 1, 2 or 3 for 128, 192 or 256-bit keys.
+    pub strength: u8,
+    /// Method the data was compressed with before encryption.
+    pub compression_method: u16,
+}
+
+impl AesExtraField {
+    pub fn from_extra_field(extra_field: &[u8]) -> Option<AesExtraField> {
+        let data = extra_fields::find(extra_field, extra_fields::WINZIP_AES)?;
+
+        if data.len() != 7 || &data[2..4] != b"AE" {
+            return None;
+        }
+
+        let result = AesExtraField {
+            vendor_version: u16::from_le_bytes([data[0], data[1]]),
+            strength: data[4],
+            compression_method: u16::from_le_bytes([data[5], data[6]]),
+        };
+
+        if (1..=2).contains(&result.vendor_version) && (1..=3).contains(&result.strength) {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    /// Serializes the field as an extra field block, header included.
+    pub fn to_extra_field(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        byte_writers::write_to(&mut bytes, extra_fields::WINZIP_AES);
+        byte_writers::write_to(&mut bytes, 7u16);
+        byte_writers::write_to(&mut bytes, self.vendor_version);
+        bytes.extend_from_slice(b"AE");
+        bytes.push(self.strength);
+        byte_writers::write_to(&mut bytes, self.compression_method);
+
+        bytes
+    }
+
+    pub fn salt_len(&self) -> usize {
+        4 + 4 * self.strength as usize
+    }
+
+    pub fn key_len(&self) -> usize {
+        8 + 8 * self.strength as usize
+    }
+
+    /// Bytes the encryption adds around the compressed data.
+    pub fn overhead(&self) -> usize {
+        self.salt_len() + PASSWORD_VERIFIER_LEN + AUTH_CODE_LEN
+    }
+
+    pub fn crc_used(&self) -> bool {
+        self.vendor_version == 1
+    }
+}
+
+/// AES in the little-endian counter mode WinZip uses; the counter starts at 1.
+struct AesCtr {
+    aes: Aes,
+    counter: u128,
+    keystream: [u8; aes::BLOCK_LEN],
+    keystream_pos: usize,
+}
+
+impl AesCtr {
+    fn new(aes: Aes) -> AesCtr {
+        AesCtr {
+            aes,
+            counter: 0,
+            keystream: [0; aes::BLOCK_LEN],
+            keystream_pos: aes::BLOCK_LEN,
+        }
+    }
+
+    fn apply(&mut self, bytes: &mut [u8]) {
+        for byte in bytes.iter_mut() {
+            if self.keystream_pos == aes::BLOCK_LEN {
+                self.counter = self.counter.wrapping_add(1);
+                self.keystream = self.counter.to_le_bytes();
+                self.aes.encrypt_block(&mut self.keystream);
+                self.keystream_pos = 0;
+            }
+
+            *byte ^= self.keystream[self.keystream_pos];
+            self.keystream_pos += 1;
+        }
+    }
+}
+
+/// Derives the encryption key, authentication key and password verifier from the salt.
+fn derive_keys(
+    password: &[u8],
+    salt: &[u8],
+    extra_field: &AesExtraField,
+) -> (AesCtr, HmacSha1, [u8; PASSWORD_VERIFIER_LEN]) {
+    let key_len = extra_field.key_len();
+    let mut keys = vec![0u8; 2 * key_len + PASSWORD_VERIFIER_LEN];
+    pbkdf2::pbkdf2_hmac_sha1(password, salt, KEY_ITERATIONS, &mut keys);
+
+    (
+        AesCtr::new(Aes::new(&keys[..key_len]).unwrap()),
+        HmacSha1::new(&keys[key_len..2 * key_len]),
+        [keys[2 * key_len], keys[2 * key_len + 1]],
+    )
+}
+
+/// Decrypts AES-CTR data and authenticates it with HMAC-SHA1 as it is read.
+pub struct WinZipAesReader<R: Read> {
+    inner: R,
+    ctr: AesCtr,
+    hmac: HmacSha1,
+    remaining: u64,
+}
+
+impl<R: Read> WinZipAesReader<R> {
+    /// Reads the salt and password verifier in front of `data_len` bytes of ciphertext;
+    /// None means a wrong password.
+    pub fn new(
+        mut inner: R,
+        password: &[u8],
+        extra_field: &AesExtraField,
+        data_len: u64,
+    ) -> std::io::Result<Option<WinZipAesReader<R>>> {
+        let mut salt = vec![0u8; extra_field.salt_len()];
+        inner.read_exact(&mut salt)?;
+
+        let mut verifier = [0u8; PASSWORD_VERIFIER_LEN];
+        inner.read_exact(&mut verifier)?;
+
+        let (ctr, hmac, expected_verifier) = derive_keys(password, &salt, extra_field);
+
+        if verifier != expected_verifier {
+            return Ok(None);
+        }
+
+        Ok(Some(WinZipAesReader {
+            inner,
+            ctr,
+            hmac,
+            remaining: data_len,
+        }))
+    }
+
+    /// Reads whatever ciphertext is left and checks the authentication code behind it.
+    pub fn finish(mut self) -> std::io::Result<bool> {
+        std::io::copy(&mut self, &mut std::io::sink())?;
+
+        let mut auth_code = [0u8; AUTH_CODE_LEN];
+        self.inner.read_exact(&mut auth_code)?;
+
+        Ok(self.hmac.finalize()[..AUTH_CODE_LEN] == auth_code)
+    }
+}
+
+impl<R: Read> Read for WinZipAesReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let limit = std::cmp::min(buf.len() as u64, self.remaining) as usize;
+        let read = self.inner.read(&mut buf[..limit])?;
+
+        if read == 0 && limit > 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Encrypted data is truncated.",
+            ));
+        }
+
+        self.hmac.update(&buf[..read]);
+        self.remaining -= read as u64;
+        self.ctr.apply(&mut buf[..read]);
+
+        Ok(read)
+    }
+}
+
+/// Encrypts everything written with AES-CTR and authenticates the ciphertext with HMAC-SHA1.
+pub struct WinZipAesWriter<W: Write> {
+    inner: W,
+    ctr: AesCtr,
+    hmac: HmacSha1,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> WinZipAesWriter<W> {
+    /// Writes a random salt and the password verifier in front of the ciphertext.
+    pub fn new(
+        mut inner: W,
+        password: &[u8],
+        extra_field: &AesExtraField,
+    ) -> std::io::Result<WinZipAesWriter<W>> {
+        let mut salt = vec![0u8; extra_field.salt_len()];
+        random::fill(&mut salt)?;
+
+        let (ctr, hmac, verifier) = derive_keys(password, &salt, extra_field);
+
+        inner.write_all(&salt)?;
+        inner.write_all(&verifier)?;
+
+        Ok(WinZipAesWriter {
+            inner,
+            ctr,
+            hmac,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Writes the authentication code behind the ciphertext.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.inner
+            .write_all(&self.hmac.finalize()[..AUTH_CODE_LEN])?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for WinZipAesWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.clear();
+        self.buffer.extend_from_slice(buf);
+        self.ctr.apply(&mut self.buffer);
+        self.hmac.update(&self.buffer);
+        self.inner.write_all(&self.buffer)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::crypto::winzip_aes::{AesExtraField, WinZipAesReader, WinZipAesWriter};
+
+    //"hello, winzip aes! " three times, AES-256, salt 0..16, password "secret"
+    const ENCRYPTED: [u8; 85] = [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 163, 54, 50, 220, 82, 44, 33, 185,
+        148, 146, 237, 30, 216, 221, 180, 244, 52, 107, 130, 68, 200, 2, 196, 241, 203, 147, 92,
+        196, 65, 179, 232, 165, 96, 187, 212, 86, 118, 183, 119, 245, 177, 215, 6, 159, 115, 91,
+        76, 210, 165, 236, 163, 221, 238, 183, 96, 15, 53, 205, 228, 34, 51, 233, 186, 143, 180,
+        147, 4, 94, 126,
+    ];
+
+    const EXTRA_FIELD: AesExtraField = AesExtraField {
+        vendor_version: 2,
+        strength: 3,
+        compression_method: 0,
+    };
+
+    #[test]
+    fn parse_extra_field() {
+        let extra_field = [
+            0x01, 0x99, 0x07, 0x00, 0x02, 0x00, b'A', b'E', 0x03, 0x00, 0x00,
+        ];
+
+        assert_eq!(
+            AesExtraField::from_extra_field(&extra_field),
+            Some(EXTRA_FIELD)
+        );
+        assert_eq!(EXTRA_FIELD.to_extra_field(), extra_field);
+        assert_eq!(EXTRA_FIELD.overhead(), 28);
+        assert!(!EXTRA_FIELD.crc_used());
+
+        let mut bad_strength = extra_field;
+        bad_strength[8] = 4;
+        assert_eq!(AesExtraField::from_extra_field(&bad_strength), None);
+    }
+
+    #[test]
+    fn decrypt_and_authenticate() {
+        let data_len = (ENCRYPTED.len() - EXTRA_FIELD.overhead()) as u64;
+
+        let mut reader = WinZipAesReader::new(&ENCRYPTED[..], b"secret", &EXTRA_FIELD, data_len)
+            .unwrap()
+            .unwrap();
+
+        let mut data = [0u8; 20];
+        reader.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"hello, winzip aes! h");
+        assert!(reader.finish().unwrap());
+
+        let mut tampered = ENCRYPTED;
+        tampered[40] ^= 1;
+
+        let reader = WinZipAesReader::new(&tampered[..], b"secret", &EXTRA_FIELD, data_len)
+            .unwrap()
+            .unwrap();
+        assert!(!reader.finish().unwrap());
+    }
+
+    #[test]
+    fn reject_wrong_password() {
+        let data_len = (ENCRYPTED.len() - EXTRA_FIELD.overhead()) as u64;
+
+        assert!(
+            WinZipAesReader::new(&ENCRYPTED[..], b"Secret", &EXTRA_FIELD, data_len)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn encrypt_round_trip() {
+        let extra_field = AesExtraField {
+            strength: 1,
+            ..EXTRA_FIELD
+        };
+
+        let mut writer = WinZipAesWriter::new(Vec::new(), b"secret", &extra_field).unwrap();
+        writer.write_all(b"hello, ").unwrap();
+        writer.write_all(b"winzip aes").unwrap();
+        let encrypted = writer.finish().unwrap();
+
+        assert_eq!(encrypted.len(), 17 + extra_field.overhead());
+
+        let mut reader = WinZipAesReader::new(&encrypted[..], b"secret", &extra_field, 17)
+            .unwrap()
+            .unwrap();
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"hello, winzip aes");
+        assert!(reader.finish().unwrap());
+    }
+}
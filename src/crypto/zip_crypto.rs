@@ -0,0 +1,199 @@
+use std::io::{Read, Write};
+
+use crate::checksums::crc32;
+use crate::crypto::random;
+
+/// Size of the encryption header in front of the entry data.
+pub const HEADER_LEN: usize = 12;
+
+/// The three keys of the traditional PKWARE stream cipher.
+#[derive(Clone)]
+pub struct Keys {
+    key0: u32,
+    key1: u32,
+    key2: u32,
+}
+
+impl Keys {
+    pub fn new(password: &[u8]) -> Keys {
+        let mut keys = Keys {
+            key0: 0x1234_5678,
+            key1: 0x2345_6789,
+            key2: 0x3456_7890,
+        };
+
+        for byte in password {
+            keys.update(*byte);
+        }
+
+        keys
+    }
+
+    /// Decrypts the encryption header and checks its last byte; None means a wrong password.
+    pub fn from_header(password: &[u8], header: &[u8; HEADER_LEN], check_byte: u8) -> Option<Keys> {
+        let mut keys = Keys::new(password);
+        let mut header = *header;
+
+        keys.decrypt(&mut header);
+
+        if header[HEADER_LEN - 1] == check_byte {
+            Some(keys)
+        } else {
+            None
+        }
+    }
+
+    /// Encrypts a random header ending in `check_byte` and returns it with the keys that follow it.
+    pub fn with_header(
+        password: &[u8],
+        check_byte: u8,
+    ) -> std::io::Result<(Keys, [u8; HEADER_LEN])> {
+        let mut keys = Keys::new(password);
+        let mut header = [0u8; HEADER_LEN];
+
+        random::fill(&mut header[..HEADER_LEN - 1])?;
+        header[HEADER_LEN - 1] = check_byte;
+
+        keys.encrypt(&mut header);
+
+        Ok((keys, header))
+    }
+
+    fn update(&mut self, byte: u8) {
+        self.key0 = crc32::update_byte(self.key0, byte);
+        self.key1 = self
+            .key1
+            .wrapping_add(self.key0 & 0xFF)
+            .wrapping_mul(134_775_813)
+            .wrapping_add(1);
+        self.key2 = crc32::update_byte(self.key2, (self.key1 >> 24) as u8);
+    }
+
+    fn stream_byte(&self) -> u8 {
+        let temp = (self.key2 | 2) & 0xFFFF;
+
+        ((temp * (temp ^ 1)) >> 8) as u8
+    }
+
+    pub fn encrypt(&mut self, bytes: &mut [u8]) {
+        for byte in bytes.iter_mut() {
+            let stream_byte = self.stream_byte();
+            self.update(*byte);
+            *byte ^= stream_byte;
+        }
+    }
+
+    pub fn decrypt(&mut self, bytes: &mut [u8]) {
+        for byte in bytes.iter_mut() {
+            *byte ^= self.stream_byte();
+            self.update(*byte);
+        }
+    }
+}
+
+/// Decrypts everything read from the inner reader.
+pub struct ZipCryptoReader<R: Read> {
+    inner: R,
+    keys: Keys,
+}
+
+impl<R: Read> ZipCryptoReader<R> {
+    pub fn new(inner: R, keys: Keys) -> ZipCryptoReader<R> {
+        ZipCryptoReader { inner, keys }
+    }
+}
+
+impl<R: Read> Read for ZipCryptoReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.keys.decrypt(&mut buf[..read]);
+
+        Ok(read)
+    }
+}
+
+/// Encrypts everything written before passing it to the inner writer.
+pub struct ZipCryptoWriter<W: Write> {
+    inner: W,
+    keys: Keys,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> ZipCryptoWriter<W> {
+    pub fn new(inner: W, keys: Keys) -> ZipCryptoWriter<W> {
+        ZipCryptoWriter {
+            inner,
+            keys,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for ZipCryptoWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.clear();
+        self.buffer.extend_from_slice(buf);
+        self.keys.encrypt(&mut self.buffer);
+        self.inner.write_all(&self.buffer)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+    use std::io::{Read, Write};
+
+    use crate::crypto::zip_crypto::{Keys, ZipCryptoReader, ZipCryptoWriter, HEADER_LEN};
+
+    //"hello, zipcrypto" behind a header ending in the high byte of its CRC-32 (0xE387993B), password "secret"
+    const ENCRYPTED: [u8; 28] = [
+        200, 136, 194, 191, 13, 227, 164, 3, 244, 21, 50, 58, 253, 183, 102, 146, 128, 126, 114,
+        120, 221, 108, 224, 167, 99, 68, 105, 240,
+    ];
+
+    #[test]
+    fn decrypt_entry() {
+        let header: &[u8; HEADER_LEN] = ENCRYPTED[..HEADER_LEN].try_into().unwrap();
+        let keys = Keys::from_header(b"secret", header, 0xE3).unwrap();
+
+        let mut data = Vec::new();
+        ZipCryptoReader::new(&ENCRYPTED[HEADER_LEN..], keys)
+            .read_to_end(&mut data)
+            .unwrap();
+
+        assert_eq!(data, b"hello, zipcrypto");
+    }
+
+    #[test]
+    fn reject_wrong_password() {
+        let header: &[u8; HEADER_LEN] = ENCRYPTED[..HEADER_LEN].try_into().unwrap();
+
+        assert!(Keys::from_header(b"Secret", header, 0xE3).is_none());
+    }
+
+    #[test]
+    fn encrypt_round_trip() {
+        let (keys, header) = Keys::with_header(b"secret", 0xE3).unwrap();
+
+        let mut encrypted = header.to_vec();
+        let mut writer = ZipCryptoWriter::new(&mut encrypted, keys);
+        writer.write_all(b"hello, ").unwrap();
+        writer.write_all(b"zipcrypto").unwrap();
+
+        let header: &[u8; HEADER_LEN] = encrypted[..HEADER_LEN].try_into().unwrap();
+        let keys = Keys::from_header(b"secret", header, 0xE3).unwrap();
+
+        let mut data = Vec::new();
+        ZipCryptoReader::new(&encrypted[HEADER_LEN..], keys)
+            .read_to_end(&mut data)
+            .unwrap();
+
+        assert_eq!(data, b"hello, zipcrypto");
+    }
+}
@@ -0,0 +1,407 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::packer::{FileOptions, ZipWriter};
+use crate::unpacker;
+use crate::zip::structs::CentralDirFileHeader;
+
+enum PlannedEntry {
+    Existing(CentralDirFileHeader),
+    NewFile {
+        name: String,
+        data: Box<dyn Read>,
+        options: FileOptions,
+    },
+    NewDirectory {
+        name: String,
+        last_modified: SystemTime,
+    },
+}
+
+impl PlannedEntry {
+    fn name(&self) -> &str {
+        match self {
+            PlannedEntry::Existing(central_dir_file_header) => &central_dir_file_header.file_name,
+            PlannedEntry::NewFile { name, .. } | PlannedEntry::NewDirectory { name, .. } => name,
+        }
+    }
+
+    fn set_name(&mut self, new_name: String) {
+        match self {
+            PlannedEntry::Existing(central_dir_file_header) => {
+                central_dir_file_header.file_name_length = new_name.len() as u16;
+                central_dir_file_header.file_name = new_name;
+            }
+            PlannedEntry::NewFile { name, .. } | PlannedEntry::NewDirectory { name, .. } => {
+                *name = new_name
+            }
+        }
+    }
+}
+
+fn not_found(name: &str) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::NotFound,
+        format!("{} is not in the archive.", name),
+    )
+}
+
+fn already_exists(name: &str) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        format!("{} is already in the archive.", name),
+    )
+}
+
+//a folder entry stands for everything under it
+fn is_under(name: &str, target: &str) -> bool {
+    name == target || target.ends_with('/') && name.starts_with(target)
+}
+
+/// Queues changes to an existing archive and applies them all at once on `commit`.
+/// Entries that are left alone are copied without being decompressed.
+pub struct ZipEditor {
+    path: PathBuf,
+    comment: String,
+    password: Option<Vec<u8>>,
+    entries: Vec<PlannedEntry>,
+}
+
+impl ZipEditor {
+    pub fn open(path: &Path) -> std::io::Result<ZipEditor> {
+        let (end_of_central_dir, central_dir_file_headers) =
+            unpacker::read_central_dir(&mut BufReader::new(File::open(path)?))?;
+
+        Ok(ZipEditor {
+            path: path.to_owned(),
+            comment: end_of_central_dir.zip_file_comment,
+            password: None,
+            entries: central_dir_file_headers
+                .into_iter()
+                .map(PlannedEntry::Existing)
+                .collect(),
+        })
+    }
+
+    /// Names of the entries the archive will have after `commit`.
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.iter().map(|entry| entry.name()).collect()
+    }
+
+    /// Password for the added files that are encrypted.
+    pub fn set_password(&mut self, password: Option<Vec<u8>>) {
+        self.password = password;
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name() == name)
+    }
+
+    pub fn add_file(
+        &mut self,
+        name: &str,
+        data: Box<dyn Read>,
+        options: FileOptions,
+    ) -> std::io::Result<()> {
+        if self.position(name).is_some() {
+            return Err(already_exists(name));
+        }
+
+        self.entries.push(PlannedEntry::NewFile {
+            name: name.to_owned(),
+            data,
+            options,
+        });
+
+        Ok(())
+    }
+
+    pub fn add_directory(&mut self, name: &str, last_modified: SystemTime) -> std::io::Result<()> {
+        let name = if name.ends_with('/') {
+            name.to_owned()
+        } else {
+            format!("{}/", name)
+        };
+
+        if self.position(&name).is_some() {
+            return Err(already_exists(&name));
+        }
+
+        self.entries.push(PlannedEntry::NewDirectory {
+            name,
+            last_modified,
+        });
+
+        Ok(())
+    }
+
+    /// Swaps the contents of a file, keeping its place in the archive.
+    pub fn replace_file(
+        &mut self,
+        name: &str,
+        data: Box<dyn Read>,
+        options: FileOptions,
+    ) -> std::io::Result<()> {
+        let position = self.position(name).ok_or_else(|| not_found(name))?;
+
+        self.entries[position] = PlannedEntry::NewFile {
+            name: name.to_owned(),
+            data,
+            options,
+        };
+
+        Ok(())
+    }
+
+    /// Deletes an entry; deleting a folder deletes everything under it.
+    pub fn delete(&mut self, name: &str) -> std::io::Result<()> {
+        let entries_before = self.entries.len();
+
+        self.entries.retain(|entry| !is_under(entry.name(), name));
+
+        if self.entries.len() == entries_before {
+            return Err(not_found(name));
+        }
+
+        Ok(())
+    }
+
+    /// Renames an entry; renaming a folder moves everything under it.
+    pub fn rename(&mut self, from: &str, to: &str) -> std::io::Result<()> {
+        if from.ends_with('/') != to.ends_with('/') {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Folders can only be renamed to folder names ending with a slash.",
+            ));
+        }
+
+        if self.position(from).is_none() {
+            return Err(not_found(from));
+        }
+
+        let mut renamed = Vec::new();
+
+        for (position, entry) in self.entries.iter().enumerate() {
+            if is_under(entry.name(), from) {
+                let new_name = format!("{}{}", to, &entry.name()[from.len()..]);
+
+                if self
+                    .position(&new_name)
+                    .is_some_and(|other| !is_under(self.entries[other].name(), from))
+                {
+                    return Err(already_exists(&new_name));
+                }
+
+                renamed.push((position, new_name));
+            }
+        }
+
+        for (position, new_name) in renamed {
+            self.entries[position].set_name(new_name);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the edited archive next to the original and moves it over the original.
+    pub fn commit(mut self) -> std::io::Result<()> {
+        let file_name = self
+            .path
+            .file_name()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Not a file path."))?;
+        let temp_path = self
+            .path
+            .with_file_name(format!(".{}.edit", file_name.to_string_lossy()));
+
+        //the new file would otherwise get the default permissions
+        let permissions = std::fs::metadata(&self.path)?.permissions();
+
+        let result = self.write_to(&temp_path).and_then(|temp_file| {
+            temp_file.set_permissions(permissions)?;
+            temp_file.sync_all()?;
+            std::fs::rename(&temp_path, &self.path)
+        });
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+
+        result
+    }
+
+    fn write_to(&mut self, temp_path: &Path) -> std::io::Result<File> {
+        let mut source = BufReader::new(File::open(&self.path)?);
+        let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(temp_path)?));
+
+        zip_writer.set_password(self.password.clone());
+        zip_writer.set_comment(self.comment.clone());
+
+        for entry in self.entries.iter_mut() {
+            match entry {
+                PlannedEntry::Existing(central_dir_file_header) => {
+                    zip_writer.copy_raw_entry(&mut source, central_dir_file_header)?
+                }
+                PlannedEntry::NewFile {
+                    name,
+                    data,
+                    options,
+                } => zip_writer.add_file(name, data.as_mut(), options)?,
+                PlannedEntry::NewDirectory {
+                    name,
+                    last_modified,
+                } => zip_writer.add_directory(name, *last_modified)?,
+            }
+        }
+
+        zip_writer
+            .finish()?
+            .into_inner()
+            .map_err(|err| err.into_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::Path;
+
+    use crate::editor::ZipEditor;
+    use crate::packer::FileOptions;
+    use crate::unpacker::{self, UnpackOptions};
+    use crate::zip::compression_methods;
+
+    fn fresh_dir(dir: &Path) {
+        if dir.exists() {
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        std::fs::create_dir(dir).unwrap();
+    }
+
+    #[test]
+    fn edit_archive() {
+        let test_dir = Path::new("test-data/edit_archive");
+        let zip = test_dir.join("actual.zip");
+        let out_folder = test_dir.join("actual");
+
+        std::fs::copy(test_dir.join("input.zip"), &zip).unwrap();
+        fresh_dir(&out_folder);
+
+        let mut editor = ZipEditor::open(&zip).unwrap();
+
+        editor.delete("short.txt").unwrap();
+        editor.rename("folder_1/random.bin", "random.bin").unwrap();
+        editor.rename("folder_1/folder_11/", "folder_2/").unwrap();
+        editor
+            .replace_file(
+                "text.txt",
+                Box::new(&b"replaced text\n"[..]),
+                FileOptions::default(),
+            )
+            .unwrap();
+        editor
+            .add_directory("added", std::time::SystemTime::now())
+            .unwrap();
+        editor
+            .add_file(
+                "added/new.txt",
+                Box::new(&b"new file\n"[..]),
+                FileOptions::default(),
+            )
+            .unwrap();
+
+        assert!(editor.delete("short.txt").is_err());
+        assert!(editor.rename("random.bin", "text.txt").is_err());
+        assert!(editor.rename("folder_2/", "folder_3").is_err());
+        assert_eq!(
+            editor.names(),
+            [
+                "text.txt",
+                "folder_1/",
+                "folder_1/empty.txt",
+                "folder_2/",
+                "folder_2/runs.bin",
+                "random.bin",
+                "added/",
+                "added/new.txt"
+            ]
+        );
+
+        editor.commit().unwrap();
+
+        //untouched entries keep their compressed data
+        let (_, headers) =
+            unpacker::read_central_dir(&mut BufReader::new(File::open(&zip).unwrap())).unwrap();
+        let random_bin = headers
+            .iter()
+            .find(|header| header.file_name == "random.bin")
+            .unwrap();
+        assert_eq!(random_bin.compression_method, compression_methods::DEFLATE);
+
+        unpacker::unpack_archive(&zip, &out_folder, &UnpackOptions::default()).unwrap();
+
+        assert!(!dir_diff::is_different(&out_folder, test_dir.join("expected")).unwrap());
+
+        std::fs::remove_dir_all(&out_folder).unwrap();
+        std::fs::remove_file(&zip).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn edit_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let zip = Path::new("test-data/edit_archive/actual_permissions.zip");
+
+        std::fs::copy("test-data/edit_archive/input.zip", zip).unwrap();
+        std::fs::set_permissions(zip, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let mut editor = ZipEditor::open(zip).unwrap();
+        editor.delete("short.txt").unwrap();
+        editor.commit().unwrap();
+
+        let mode = std::fs::metadata(zip).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        std::fs::remove_file(zip).unwrap();
+    }
+
+    #[test]
+    fn edit_keeps_data_descriptors() {
+        let test_dir = Path::new("test-data/edit_archive");
+        let zip = test_dir.join("actual_zipcrypto.zip");
+        let out_folder = test_dir.join("actual_zipcrypto");
+
+        std::fs::copy("test-data/unpack_zipcrypto/input.zip", &zip).unwrap();
+        fresh_dir(&out_folder);
+
+        let mut editor = ZipEditor::open(&zip).unwrap();
+        editor.delete("folder_1/").unwrap();
+        editor.rename("short.txt", "renamed.txt").unwrap();
+        editor.commit().unwrap();
+
+        unpacker::unpack_archive(
+            &zip,
+            &out_folder,
+            &UnpackOptions {
+                password: Some("pa55 wörd".as_bytes().to_vec()),
+                ..UnpackOptions::default()
+            },
+        )
+        .unwrap();
+
+        for (actual, expected) in [("renamed.txt", "short.txt"), ("text.txt", "text.txt")].iter() {
+            assert_eq!(
+                std::fs::read(out_folder.join(actual)).unwrap(),
+                std::fs::read(Path::new("test-data/unpack_zipcrypto/expected").join(expected))
+                    .unwrap()
+            );
+        }
+
+        std::fs::remove_dir_all(&out_folder).unwrap();
+        std::fs::remove_file(&zip).unwrap();
+    }
+}
@@ -0,0 +1,387 @@
+use std::io::{BufRead, ErrorKind, Write};
+
+use crate::checksums::crc32::{self, Crc32, Crc32Writer};
+use crate::packer::deflate::Deflater;
+use crate::unpacker::algorithms::deflate::{BitReader, Inflater, DEFLATE};
+
+pub use crate::packer::deflate::DEFAULT_LEVEL;
+
+const MAGIC: [u8; 2] = [0x1F, 0x8B];
+const METHOD_DEFLATE: u8 = 8;
+
+const FLAG_HEADER_CRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+const RESERVED_FLAGS: u8 = 0xE0;
+
+/// Operating system value of a header written without knowing it.
+pub const OS_UNKNOWN: u8 = 255;
+pub const OS_UNIX: u8 = 3;
+
+/// The header of a gzip member (RFC 1952).
+#[derive(Debug, Clone, PartialEq)]
+pub struct GzipHeader {
+    /// Name of the original file, without its path.
+    pub file_name: Option<String>,
+    pub comment: Option<String>,
+    /// Extra field, as its subfields laid out one after the other.
+    pub extra: Option<Vec<u8>>,
+    /// Modification time of the original file in seconds since the epoch, 0 when there is none.
+    pub mtime: u32,
+    pub os: u8,
+    /// Whether the header is followed by the low 16 bits of its CRC-32.
+    pub header_crc: bool,
+}
+
+impl Default for GzipHeader {
+    fn default() -> GzipHeader {
+        GzipHeader {
+            file_name: None,
+            comment: None,
+            extra: None,
+            mtime: 0,
+            os: OS_UNKNOWN,
+            header_crc: false,
+        }
+    }
+}
+
+fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("gzip: {}", reason))
+}
+
+//names and comments are ISO 8859-1, where every byte is the code point of the same value
+fn to_latin1(text: &str) -> std::io::Result<Vec<u8>> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0 => Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "gzip: names and comments can't contain a zero byte.",
+            )),
+            1..=0xFF => Ok(c as u8),
+            _ => Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("gzip: {} is not in ISO 8859-1.", c),
+            )),
+        })
+        .collect()
+}
+
+fn from_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
+
+impl GzipHeader {
+    //`extra_flags` tells how hard the data was compressed
+    fn to_bytes(&self, extra_flags: u8) -> std::io::Result<Vec<u8>> {
+        let mut flags = 0;
+        let mut fields = Vec::new();
+
+        if let Some(extra) = &self.extra {
+            if extra.len() > u16::MAX as usize {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "gzip: extra field is longer than 65535 bytes.",
+                ));
+            }
+
+            flags |= FLAG_EXTRA;
+            fields.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+            fields.extend_from_slice(extra);
+        }
+
+        if let Some(file_name) = &self.file_name {
+            flags |= FLAG_NAME;
+            fields.extend(to_latin1(file_name)?);
+            fields.push(0);
+        }
+
+        if let Some(comment) = &self.comment {
+            flags |= FLAG_COMMENT;
+            fields.extend(to_latin1(comment)?);
+            fields.push(0);
+        }
+
+        if self.header_crc {
+            flags |= FLAG_HEADER_CRC;
+        }
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(METHOD_DEFLATE);
+        bytes.push(flags);
+        bytes.extend_from_slice(&self.mtime.to_le_bytes());
+        bytes.push(extra_flags);
+        bytes.push(self.os);
+        bytes.extend(fields);
+
+        if self.header_crc {
+            let crc = crc32::checksum(&bytes) as u16;
+            bytes.extend_from_slice(&crc.to_le_bytes());
+        }
+
+        Ok(bytes)
+    }
+
+    //the header bits are read through the bit reader that the compressed data follows in
+    fn read<R: BufRead>(bits: &mut BitReader<R>, magic: u32) -> std::io::Result<GzipHeader> {
+        let mut crc = Crc32::new();
+
+        let mut fixed = [0u8; 10];
+        fixed[0] = magic as u8;
+
+        for byte in &mut fixed[1..] {
+            *byte = bits.read(8)? as u8;
+        }
+
+        crc.update(&fixed);
+
+        if fixed[..2] != MAGIC {
+            return Err(invalid_data("not in gzip format."));
+        }
+
+        if fixed[2] != METHOD_DEFLATE {
+            return Err(invalid_data("unknown compression method."));
+        }
+
+        let flags = fixed[3];
+
+        if flags & RESERVED_FLAGS != 0 {
+            return Err(invalid_data("reserved flags are set."));
+        }
+
+        let mut header = GzipHeader {
+            mtime: u32::from_le_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]),
+            os: fixed[9],
+            header_crc: flags & FLAG_HEADER_CRC != 0,
+            ..GzipHeader::default()
+        };
+
+        if flags & FLAG_EXTRA != 0 {
+            let len = read_bytes(bits, &mut crc, 2)?;
+            header.extra = Some(read_bytes(
+                bits,
+                &mut crc,
+                u16::from_le_bytes([len[0], len[1]]) as usize,
+            )?);
+        }
+
+        if flags & FLAG_NAME != 0 {
+            header.file_name = Some(read_text(bits, &mut crc)?);
+        }
+
+        if flags & FLAG_COMMENT != 0 {
+            header.comment = Some(read_text(bits, &mut crc)?);
+        }
+
+        if header.header_crc && bits.read(16)? != crc.value() & 0xFFFF {
+            return Err(invalid_data("header CRC doesn't match."));
+        }
+
+        Ok(header)
+    }
+}
+
+fn read_bytes<R: BufRead>(
+    bits: &mut BitReader<R>,
+    crc: &mut Crc32,
+    len: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(len);
+
+    for _ in 0..len {
+        bytes.push(bits.read(8)? as u8);
+    }
+
+    crc.update(&bytes);
+
+    Ok(bytes)
+}
+
+//zero-terminated text
+fn read_text<R: BufRead>(bits: &mut BitReader<R>, crc: &mut Crc32) -> std::io::Result<String> {
+    let mut text = Vec::new();
+
+    loop {
+        let byte = bits.read(8)? as u8;
+        crc.update(&[byte]);
+
+        if byte == 0 {
+            return Ok(from_latin1(&text));
+        }
+
+        text.push(byte);
+    }
+}
+
+/// Writes a single gzip member; what is written comes out compressed on the inner writer.
+pub struct GzipWriter<W: Write> {
+    deflater: Deflater<W>,
+    crc: Crc32,
+    len: u32,
+}
+
+impl<W: Write> GzipWriter<W> {
+    pub fn new(mut writer: W, header: &GzipHeader, level: u32) -> std::io::Result<GzipWriter<W>> {
+        let extra_flags = match level {
+            9 => 2,
+            1 => 4,
+            _ => 0,
+        };
+
+        let bytes = header.to_bytes(extra_flags)?;
+        writer.write_all(&bytes)?;
+
+        Ok(GzipWriter {
+            deflater: Deflater::new(writer, level),
+            crc: Crc32::new(),
+            len: 0,
+        })
+    }
+
+    /// Ends the member with its CRC-32 and length, and returns the inner writer.
+    pub fn finish(self) -> std::io::Result<W> {
+        let mut writer = self.deflater.finish()?;
+
+        writer.write_all(&self.crc.value().to_le_bytes())?;
+        writer.write_all(&self.len.to_le_bytes())?;
+        writer.flush()?;
+
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for GzipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.deflater.write(buf)?;
+
+        self.crc.update(&buf[..written]);
+        //the length is kept modulo 2^32
+        self.len = self.len.wrapping_add(written as u32);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.deflater.flush()
+    }
+}
+
+/// Decompresses every member of a gzip stream into `writer`, checking their CRC-32 and length.
+/// Returns the headers of the members.
+pub fn decompress<R: BufRead, W: Write>(
+    reader: R,
+    writer: &mut W,
+) -> std::io::Result<Vec<GzipHeader>> {
+    let mut bits = BitReader::new(reader);
+    let mut headers = Vec::new();
+
+    while let Some(magic) = bits.try_read(8)? {
+        let header = GzipHeader::read(&mut bits, magic)?;
+
+        let mut inflater = Inflater::with_bits(bits, Crc32Writer::new(&mut *writer), &DEFLATE);
+        inflater.inflate()?;
+
+        let total_out = inflater.total_out();
+        let (rest, crc_writer) = inflater.into_parts()?;
+        bits = rest;
+        bits.align_to_byte();
+
+        if bits.read(32)? != crc_writer.crc() {
+            return Err(invalid_data("CRC doesn't match."));
+        }
+
+        if bits.read(32)? != total_out as u32 {
+            return Err(invalid_data("length doesn't match."));
+        }
+
+        headers.push(header);
+    }
+
+    if headers.is_empty() {
+        return Err(std::io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "gzip: input is empty.",
+        ));
+    }
+
+    writer.flush()?;
+
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::gzip::{GzipHeader, GzipWriter, OS_UNIX};
+
+    fn compress(data: &[u8], header: &GzipHeader, level: u32) -> Vec<u8> {
+        let mut writer = GzipWriter::new(Vec::new(), header, level).unwrap();
+        writer.write_all(data).unwrap();
+
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn decompress_members() {
+        //gzip -n of two files concatenated, the second one with its name
+        let input = std::fs::read("test-data/gzip/members.gz").unwrap();
+        let mut out = Vec::new();
+
+        let headers = super::decompress(&input[..], &mut out).unwrap();
+
+        assert_eq!(
+            out,
+            std::fs::read("test-data/gzip/expected/members.txt").unwrap()
+        );
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].file_name, None);
+        assert_eq!(headers[1].file_name, Some("second.txt".to_owned()));
+
+        let mut corrupted = input.clone();
+        let crc_pos = corrupted.len() - 8;
+        corrupted[crc_pos] ^= 1;
+        assert!(super::decompress(&corrupted[..], &mut Vec::new()).is_err());
+
+        //trailing garbage and truncation
+        let mut garbage = input.clone();
+        garbage.extend_from_slice(b"garbage");
+        assert!(super::decompress(&garbage[..], &mut Vec::new()).is_err());
+        assert!(super::decompress(&input[..input.len() - 1], &mut Vec::new()).is_err());
+        assert!(super::decompress(&b""[..], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = GzipHeader {
+            file_name: Some("Größe.txt".to_owned()),
+            comment: Some("made on a Tuesday".to_owned()),
+            extra: Some(b"AP\x04\x00data".to_vec()),
+            mtime: 1_700_000_000,
+            os: OS_UNIX,
+            header_crc: true,
+        };
+        let data = std::fs::read("test-data/sfx_archive/expected/text.txt").unwrap();
+
+        let mut input = compress(&data, &header, 9);
+        input.extend(compress(b"", &GzipHeader::default(), 1));
+
+        let mut out = Vec::new();
+        let headers = super::decompress(&input[..], &mut out).unwrap();
+
+        assert_eq!(out, data);
+        assert_eq!(headers, vec![header, GzipHeader::default()]);
+
+        //the header CRC covers the name
+        input[20] ^= 1;
+        assert!(super::decompress(&input[..], &mut Vec::new()).is_err());
+
+        assert!(GzipHeader {
+            file_name: Some("日本.txt".to_owned()),
+            ..GzipHeader::default()
+        }
+        .to_bytes(0)
+        .is_err());
+    }
+}
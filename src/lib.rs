@@ -0,0 +1,21 @@
+pub mod aligner;
+pub mod args;
+pub mod checksums;
+pub mod comments;
+pub mod convert;
+pub mod crypto;
+pub mod editor;
+pub mod gzip;
+pub mod merger;
+pub mod packer;
+pub mod repair;
+pub mod sfx;
+pub mod splitter;
+pub mod stream_utils;
+pub mod tar;
+pub mod unpacker;
+pub mod zip;
+pub mod zlib;
+
+#[cfg(test)]
+mod test_utils;
@@ -1,17 +1,23 @@
 use std::env;
-use std::path::Path;
-use std::time::Instant;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
-mod args;
-mod stream_utils;
-mod unpacker;
-mod zip;
-
-use args::input_parser;
-
-fn main() -> std::io::Result<()> {
-    let args = input_parser::parse_args(&env::args().collect()).unwrap();
+use simpzip::aligner::{self, AlignOptions};
+use simpzip::args::input_parser::{self, Command, RunArguments};
+use simpzip::args::password;
+use simpzip::comments;
+use simpzip::convert;
+use simpzip::gzip::{self, GzipHeader, GzipWriter};
+use simpzip::merger::{self, MergeOptions};
+use simpzip::packer::{self, Encryption, PackOptions};
+use simpzip::repair;
+use simpzip::sfx::{self, SelfExtractor};
+use simpzip::splitter::{self, Packing, SplitOptions};
+use simpzip::unpacker::{self, UnpackOptions};
 
+fn unpack(args: RunArguments, password: Option<Vec<u8>>) -> std::io::Result<()> {
     println!("\nSource ZIP: {}", args.in_file);
     println!("Output dir: {}\n", args.out_folder);
 
@@ -26,11 +32,561 @@ fn main() -> std::io::Result<()> {
 
     println!("Unpacking...\n");
 
+    std::fs::create_dir(out_folder)?;
+
+    let options = UnpackOptions {
+        use_mmap: args.use_mmap,
+        verify_checksums: args.verify_checksums,
+        password_prompt: if password.is_none() {
+            Some(password::prompt)
+        } else {
+            None
+        },
+        password,
+        into_existing_dir: false,
+    };
+
+    unpacker::unpack_archive(Path::new(&args.in_file), out_folder, &options)
+}
+
+fn pack_options(args: &RunArguments, password: Option<Vec<u8>>) -> std::io::Result<PackOptions> {
+    //a password given for unpacking is not a reason to encrypt
+    let password = match password {
+        _ if !args.encrypt => None,
+        None => Some(password::prompt()?),
+        password => password,
+    };
+
+    Ok(PackOptions {
+        password,
+        encrypt: args.encrypt,
+        encryption: Some(if args.zip_crypto {
+            Encryption::ZipCrypto
+        } else {
+            Encryption::Aes256
+        }),
+        encrypt_patterns: args.encrypt_patterns.clone(),
+        volume_size: args.volume_size,
+        alignment: align_options(args),
+    })
+}
+
+fn align_options(args: &RunArguments) -> Option<AlignOptions> {
+    if args.alignment.is_none() && args.align_patterns.is_empty() {
+        return None;
+    }
+
+    Some(AlignOptions {
+        alignment: args.alignment.unwrap_or(aligner::DEFAULT_ALIGNMENT),
+        patterns: args.align_patterns.clone(),
+    })
+}
+
+fn pack(args: RunArguments, password: Option<Vec<u8>>) -> std::io::Result<()> {
+    println!("\nSource dir: {}", args.out_folder);
+    println!("Output ZIP: {}\n", args.in_file);
+
+    let options = pack_options(&args, password)?;
+
+    println!("Packing...\n");
+
+    packer::pack_dir(
+        Path::new(&args.out_folder),
+        Path::new(&args.in_file),
+        &options,
+    )
+}
+
+fn merge(args: RunArguments) -> std::io::Result<()> {
+    let src_files = args.params[1..]
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    println!("\nSource ZIPs: {}", args.params[1..].join(", "));
+    println!("Output ZIP: {}\n", args.in_file);
+
+    println!("Merging...\n");
+
+    merger::merge_archives(
+        &src_files,
+        Path::new(&args.in_file),
+        &MergeOptions {
+            conflict_policy: args.conflict_policy,
+            include_patterns: args.include_patterns,
+        },
+    )
+}
+
+fn subset(args: RunArguments) -> std::io::Result<()> {
+    println!("\nSource ZIP: {}", args.out_folder);
+    println!("Output ZIP: {}\n", args.in_file);
+
+    println!("Copying...\n");
+
+    merger::subset_archive(
+        Path::new(&args.out_folder),
+        Path::new(&args.in_file),
+        &args.include_patterns,
+    )
+}
+
+fn split(args: RunArguments, password: Option<Vec<u8>>) -> std::io::Result<()> {
+    println!("\nSource: {}", args.out_folder);
+    println!("Output ZIPs: {}\n", args.in_file);
+
+    let src = Path::new(&args.out_folder);
+    let options = SplitOptions {
+        part_size: args.part_size.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Part size is missing.")
+        })?,
+        packing: if args.greedy {
+            Packing::Greedy
+        } else {
+            Packing::InOrder
+        },
+        manifest: args.manifest.as_ref().map(PathBuf::from),
+    };
+
+    println!("Splitting...\n");
+
+    let parts = if src.is_dir() {
+        splitter::split_dir(
+            src,
+            Path::new(&args.in_file),
+            &pack_options(&args, password)?,
+            &options,
+        )?
+    } else {
+        splitter::split_archive(src, Path::new(&args.in_file), &options)?
+    };
+
+    for part in parts {
+        println!("{}", part.display());
+    }
+
+    Ok(())
+}
+
+fn strip_prefix(args: RunArguments) -> std::io::Result<()> {
+    println!("\nSource ZIP: {}", args.out_folder);
+    println!("Output ZIP: {}\n", args.in_file);
+
+    let prefix = sfx::find_prefix(Path::new(&args.out_folder))?;
+    println!("Prefix: {} bytes\n", prefix.len);
+
+    sfx::strip_prefix(Path::new(&args.out_folder), Path::new(&args.in_file))
+}
+
+fn fix_offsets(args: RunArguments) -> std::io::Result<()> {
+    println!("\nZIP: {}\n", args.in_file);
+
+    let prefix = sfx::find_prefix(Path::new(&args.in_file))?;
+    println!("Prefix: {} bytes", prefix.len);
+
+    match sfx::fix_offsets(Path::new(&args.in_file))? {
+        0 => println!("Offsets are already right.\n"),
+        shift => println!("Offsets moved by {} bytes.\n", shift),
+    }
+
+    Ok(())
+}
+
+fn repair(args: RunArguments, password: Option<Vec<u8>>) -> std::io::Result<()> {
+    println!("\nDamaged ZIP: {}", args.out_folder);
+    println!("Output ZIP: {}\n", args.in_file);
+
+    let options = UnpackOptions {
+        password_prompt: if password.is_none() {
+            Some(password::prompt)
+        } else {
+            None
+        },
+        password,
+        ..UnpackOptions::default()
+    };
+
+    println!("Repairing...\n");
+
+    let report = repair::repair_archive(
+        Path::new(&args.out_folder),
+        Path::new(&args.in_file),
+        &options,
+    )?;
+
+    if !report.central_dir_found {
+        println!("Central dir is missing, everything was found by scanning.");
+    }
+
+    for damaged in &report.damaged {
+        println!(
+            "Damaged: {} at {}: {}",
+            damaged.name, damaged.pos, damaged.reason
+        );
+    }
+
+    for name in &report.orphans {
+        println!("Orphan: {}", name);
+    }
+
+    for name in &report.lost {
+        println!("Lost: {}", name);
+    }
+
+    println!("\nRecovered {} entries.\n", report.recovered.len());
+
+    Ok(())
+}
+
+fn comment(args: RunArguments) -> std::io::Result<()> {
+    let zip = Path::new(&args.in_file);
+
+    let new_comment = match &args.comment_file {
+        Some(comment_file) => Some(std::fs::read_to_string(comment_file)?),
+        None => args.comment.clone(),
+    };
+
+    match (new_comment, &args.entry) {
+        (Some(new_comment), Some(entry)) => comments::set_entry_comment(zip, entry, &new_comment),
+        (Some(new_comment), None) => comments::set_archive_comment(zip, &new_comment),
+        (None, Some(entry)) => {
+            let comments = comments::read_comments(zip)?;
+
+            if let Some((_, comment)) = comments.entries.iter().find(|(name, _)| name == entry) {
+                println!("{}", comment);
+            }
+
+            Ok(())
+        }
+        (None, None) => {
+            let comments = comments::read_comments(zip)?;
+
+            println!("{}", comments.archive);
+
+            for (name, comment) in &comments.entries {
+                println!("{}: {}", name, comment);
+            }
+
+            Ok(())
+        }
+    }
+}
+
+const STDIO_PATH: &str = "-";
+
+fn gzip(args: RunArguments) -> std::io::Result<()> {
+    let mut header = GzipHeader {
+        comment: args.comment.clone(),
+        header_crc: args.header_crc,
+        os: if cfg!(unix) {
+            gzip::OS_UNIX
+        } else {
+            gzip::OS_UNKNOWN
+        },
+        ..GzipHeader::default()
+    };
+
+    let reader: Box<dyn Read> = if args.in_file == STDIO_PATH {
+        Box::new(std::io::stdin().lock())
+    } else {
+        let src = Path::new(&args.in_file);
+        let file = File::open(src)?;
+
+        if !args.no_name {
+            header.file_name = src
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+            header.mtime = file
+                .metadata()?
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |mtime| mtime.as_secs() as u32);
+        }
+
+        Box::new(file)
+    };
+
+    let writer: Box<dyn Write> = match args.out_folder.as_str() {
+        STDIO_PATH => Box::new(std::io::stdout().lock()),
+        "" if args.in_file == STDIO_PATH => Box::new(std::io::stdout().lock()),
+        "" => Box::new(File::create(format!("{}.gz", args.in_file))?),
+        dst => Box::new(File::create(dst)?),
+    };
+
+    let mut gzip_writer = GzipWriter::new(
+        BufWriter::new(writer),
+        &header,
+        args.level.unwrap_or(gzip::DEFAULT_LEVEL),
+    )?;
+
+    std::io::copy(&mut BufReader::new(reader), &mut gzip_writer)?;
+    gzip_writer.finish()?;
+
+    Ok(())
+}
+
+fn gunzip(args: RunArguments) -> std::io::Result<()> {
+    let reader: Box<dyn Read> = if args.in_file == STDIO_PATH {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(File::open(&args.in_file)?)
+    };
+
+    let dst = match args.out_folder.as_str() {
+        STDIO_PATH => None,
+        "" if args.in_file == STDIO_PATH => None,
+        "" => match args.in_file.strip_suffix(".gz") {
+            Some(dst) if !dst.is_empty() => Some(PathBuf::from(dst)),
+            _ => match args.in_file.strip_suffix(".tgz") {
+                Some(stem) => Some(PathBuf::from(format!("{}.tar", stem))),
+                None => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "Output file is missing and the input doesn't end with .gz.",
+                    ))
+                }
+            },
+        },
+        dst => Some(PathBuf::from(dst)),
+    };
+
+    match dst {
+        None => {
+            gzip::decompress(BufReader::new(reader), &mut std::io::stdout().lock())?;
+        }
+        Some(dst) => {
+            let mut writer = BufWriter::new(File::create(&dst)?);
+            let headers = gzip::decompress(BufReader::new(reader), &mut writer)?;
+
+            let file = writer.into_inner().map_err(|err| err.into_error())?;
+
+            //the first member names the original file
+            if headers[0].mtime != 0 {
+                file.set_modified(UNIX_EPOCH + Duration::from_secs(headers[0].mtime as u64))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn zip_to_tar(args: RunArguments, password: Option<Vec<u8>>) -> std::io::Result<()> {
+    let options = UnpackOptions {
+        use_mmap: args.use_mmap,
+        verify_checksums: args.verify_checksums,
+        password_prompt: if password.is_none() {
+            Some(password::prompt)
+        } else {
+            None
+        },
+        password,
+        into_existing_dir: false,
+    };
+
+    let writer: Box<dyn Write> = match args.out_folder.as_str() {
+        STDIO_PATH => Box::new(std::io::stdout().lock()),
+        dst => Box::new(File::create(dst)?),
+    };
+
+    convert::zip_to_tar(Path::new(&args.in_file), BufWriter::new(writer), &options)?.flush()
+}
+
+fn tar_to_zip(args: RunArguments) -> std::io::Result<()> {
+    println!("\nSource tar: {}", args.in_file);
+    println!("Output ZIP: {}\n", args.out_folder);
+
+    let reader: Box<dyn Read> = if args.in_file == STDIO_PATH {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(File::open(&args.in_file)?)
+    };
+
+    println!("Converting...\n");
+
+    let skipped = convert::tar_to_zip(
+        BufReader::new(reader),
+        Path::new(&args.out_folder),
+        args.level,
+    )?;
+
+    for name in &skipped {
+        println!(
+            "Skipped: {} is neither a file, a folder nor a symlink.",
+            name
+        );
+    }
+
+    Ok(())
+}
+
+fn align(args: RunArguments) -> std::io::Result<()> {
+    let options = align_options(&args).unwrap_or_default();
+
+    if args.check {
+        println!("\nZIP: {}\n", args.in_file);
+
+        let misaligned = aligner::check_alignment(Path::new(&args.in_file), &options)?;
+
+        for entry in &misaligned {
+            println!(
+                "Misaligned: {} at {}, not a multiple of {}",
+                entry.name, entry.data_pos, entry.alignment
+            );
+        }
+
+        if !misaligned.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} entries are not aligned.", misaligned.len()),
+            ));
+        }
+
+        println!("All stored entries are aligned.\n");
+
+        return Ok(());
+    }
+
+    println!("\nSource ZIP: {}", args.out_folder);
+    println!("Output ZIP: {}\n", args.in_file);
+
+    let aligned = aligner::align_archive(
+        Path::new(&args.out_folder),
+        Path::new(&args.in_file),
+        &options,
+    )?;
+    println!("Aligned {} stored entries.\n", aligned);
+
+    Ok(())
+}
+
+fn build_sfx(args: RunArguments, password: Option<Vec<u8>>) -> std::io::Result<()> {
+    println!("\nSource: {}", args.out_folder);
+    println!("Output executable: {}\n", args.in_file);
+
+    let stub = env::current_exe()?;
+    let src = Path::new(&args.out_folder);
+    let run_command = args.run_command.as_deref();
+
+    println!("Building...\n");
+
+    if src.is_dir() {
+        sfx::build_self_extractor_from_dir(
+            &stub,
+            src,
+            Path::new(&args.in_file),
+            &pack_options(&args, password)?,
+            run_command,
+        )
+    } else {
+        sfx::build_self_extractor(&stub, src, Path::new(&args.in_file), run_command)
+    }
+}
+
+//what the executable does when it has an archive appended
+fn run_sfx(exe: &Path, self_extractor: SelfExtractor) -> std::io::Result<()> {
+    let args = input_parser::parse_self_extractor_args(&env::args().collect::<Vec<_>>()).unwrap();
+
+    let password = args
+        .password
+        .clone()
+        .or_else(|| env::var(password::PASSWORD_ENV).ok())
+        .map(String::into_bytes);
+
+    let options = UnpackOptions {
+        password_prompt: if password.is_none() {
+            Some(password::prompt)
+        } else {
+            None
+        },
+        password,
+        //the default folder may be there from an earlier run, and "." holds the executable itself
+        into_existing_dir: true,
+        ..UnpackOptions::default()
+    };
+
+    if args.list {
+        for header in unpacker::list_archive(exe)? {
+            println!("{:>12}  {}", header.uncompressed_size, header.file_name);
+        }
+
+        return Ok(());
+    }
+
+    if args.test {
+        let tested = unpacker::test_archive(exe, &options)?;
+        println!("No errors in {} files.", tested);
+
+        return Ok(());
+    }
+
+    let out_folder = match args.out_folder {
+        Some(out_folder) => PathBuf::from(out_folder),
+        None => PathBuf::from(exe.file_stem().unwrap_or_default()),
+    };
+
+    if !out_folder.exists() {
+        std::fs::create_dir_all(&out_folder)?;
+    }
+
+    println!("Extracting to {}...", out_folder.display());
+
+    unpacker::unpack_archive(exe, &out_folder, &options)?;
+
+    if let Some(run_command) = self_extractor.run_command {
+        println!("Running {}...", run_command);
+
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&run_command)
+            .current_dir(&out_folder)
+            .status()?;
+
+        if !status.success() {
+            return Err(std::io::Error::other(format!(
+                "{} failed with {}.",
+                run_command, status
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> std::io::Result<()> {
+    if let Ok(exe) = env::current_exe() {
+        if let Ok(Some(self_extractor)) = sfx::find_self_extractor(&exe) {
+            return run_sfx(&exe, self_extractor);
+        }
+    }
+
+    let args = input_parser::parse_args(&env::args().collect::<Vec<_>>()).unwrap();
+
     let start_time = Instant::now();
 
-    std::fs::create_dir(out_folder)?;
+    let password = args
+        .password
+        .clone()
+        .or_else(|| env::var(password::PASSWORD_ENV).ok())
+        .map(String::into_bytes);
 
-    unpacker::unpack_archive(Path::new(&args.in_file), out_folder)?;
+    match args.command {
+        Command::Unpack => unpack(args, password)?,
+        Command::Pack => pack(args, password)?,
+        Command::Merge => merge(args)?,
+        Command::Subset => subset(args)?,
+        Command::Split => split(args, password)?,
+        Command::StripPrefix => strip_prefix(args)?,
+        Command::FixOffsets => fix_offsets(args)?,
+        Command::Sfx => build_sfx(args, password)?,
+        Command::Repair => repair(args, password)?,
+        Command::Comment => comment(args)?,
+        //the output may be stdout, which only gets the data
+        Command::Gzip => return gzip(args),
+        Command::Gunzip => return gunzip(args),
+        Command::ZipToTar => return zip_to_tar(args, password),
+        Command::TarToZip => tar_to_zip(args)?,
+        Command::Align => align(args)?,
+    }
 
     println!(
         "Time spent: {} sec",
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::packer::ZipWriter;
+use crate::unpacker;
+use crate::zip::structs::CentralDirFileHeader;
+use crate::zip::wildcard;
+
+/// What to do when several archives have an entry with the same name.
+/// Folders are never a conflict, the first one is kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    FirstWins,
+    LastWins,
+    Error,
+}
+
+#[derive(Debug)]
+pub struct MergeOptions {
+    pub conflict_policy: ConflictPolicy,
+    /// Patterns of the entries to take; all of them when empty.
+    pub include_patterns: Vec<String>,
+}
+
+impl Default for MergeOptions {
+    fn default() -> MergeOptions {
+        MergeOptions {
+            conflict_policy: ConflictPolicy::Error,
+            include_patterns: Vec::new(),
+        }
+    }
+}
+
+/// Keeps the entries matching the patterns along with the folders they are in.
+fn select(
+    central_dir_file_headers: Vec<CentralDirFileHeader>,
+    include_patterns: &[String],
+) -> Vec<CentralDirFileHeader> {
+    if include_patterns.is_empty() {
+        return central_dir_file_headers;
+    }
+
+    let mut wanted = HashSet::new();
+
+    for header in &central_dir_file_headers {
+        if include_patterns
+            .iter()
+            .any(|pattern| wildcard::matches(pattern, &header.file_name))
+        {
+            wanted.insert(header.file_name.clone());
+
+            for (pos, _) in header.file_name.match_indices('/') {
+                wanted.insert(header.file_name[..=pos].to_owned());
+            }
+        }
+    }
+
+    central_dir_file_headers
+        .into_iter()
+        .filter(|header| wanted.contains(&header.file_name))
+        .collect()
+}
+
+fn is_same_file(first: &Path, second: &Path) -> bool {
+    match (first.canonicalize(), second.canonicalize()) {
+        (Ok(first), Ok(second)) => first == second,
+        _ => false,
+    }
+}
+
+/// Copies the entries of `src_files` into a new archive without decompressing them.
+/// The archive comment of the first source is kept.
+pub fn merge_archives(
+    src_files: &[PathBuf],
+    dst_file: &Path,
+    options: &MergeOptions,
+) -> std::io::Result<()> {
+    if src_files
+        .iter()
+        .any(|src_file| is_same_file(src_file, dst_file))
+    {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "Output archive is one of the sources.",
+        ));
+    }
+
+    let mut sources = Vec::new();
+    let mut comment = None;
+    let mut planned: Vec<(usize, CentralDirFileHeader)> = Vec::new();
+    let mut positions = HashMap::new();
+
+    for (source_index, src_file) in src_files.iter().enumerate() {
+        let mut source = BufReader::new(File::open(src_file)?);
+        let (end_of_central_dir, central_dir_file_headers) =
+            unpacker::read_central_dir(&mut source)?;
+
+        comment.get_or_insert(end_of_central_dir.zip_file_comment);
+        sources.push(source);
+
+        for header in select(central_dir_file_headers, &options.include_patterns) {
+            match positions.get(&header.file_name) {
+                None => {
+                    positions.insert(header.file_name.clone(), planned.len());
+                    planned.push((source_index, header));
+                }
+                Some(_) if header.file_name.ends_with('/') => {}
+                Some(&position) => match options.conflict_policy {
+                    ConflictPolicy::FirstWins => {}
+                    ConflictPolicy::LastWins => planned[position] = (source_index, header),
+                    ConflictPolicy::Error => {
+                        return Err(std::io::Error::new(
+                            ErrorKind::AlreadyExists,
+                            format!("{} is in more than one archive.", header.file_name),
+                        ))
+                    }
+                },
+            }
+        }
+    }
+
+    let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(dst_file)?));
+    zip_writer.set_comment(comment.unwrap_or_default());
+
+    for (source_index, header) in &planned {
+        zip_writer.copy_raw_entry(&mut sources[*source_index], header)?;
+    }
+
+    zip_writer.finish()?;
+
+    Ok(())
+}
+
+/// Copies the entries matching `include_patterns` into a new archive without decompressing them.
+pub fn subset_archive(
+    src_file: &Path,
+    dst_file: &Path,
+    include_patterns: &[String],
+) -> std::io::Result<()> {
+    merge_archives(
+        &[src_file.to_owned()],
+        dst_file,
+        &MergeOptions {
+            include_patterns: include_patterns.to_vec(),
+            ..MergeOptions::default()
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::merger::{ConflictPolicy, MergeOptions};
+    use crate::test_utils;
+    use crate::unpacker::UnpackOptions;
+
+    fn unpack_and_compare(zip: &Path, expected_dir: &str) {
+        test_utils::unpack_and_compare(
+            zip,
+            &zip.with_extension(""),
+            &Path::new("test-data/merge_archives").join(expected_dir),
+            &UnpackOptions::default(),
+        );
+
+        std::fs::remove_file(zip).unwrap();
+    }
+
+    fn sources() -> Vec<PathBuf> {
+        vec![
+            PathBuf::from("test-data/merge_archives/first.zip"),
+            PathBuf::from("test-data/merge_archives/second.zip"),
+        ]
+    }
+
+    #[test]
+    fn merge_with_policies() {
+        for (policy, expected_dir) in [
+            (ConflictPolicy::FirstWins, "expected_first_wins"),
+            (ConflictPolicy::LastWins, "expected_last_wins"),
+        ]
+        .iter()
+        {
+            let zip =
+                Path::new("test-data/merge_archives").join(format!("actual_{:?}.zip", policy));
+
+            super::merge_archives(
+                &sources(),
+                &zip,
+                &MergeOptions {
+                    conflict_policy: *policy,
+                    ..MergeOptions::default()
+                },
+            )
+            .unwrap();
+
+            unpack_and_compare(&zip, expected_dir);
+        }
+    }
+
+    #[test]
+    fn merge_conflict_error() {
+        let zip = Path::new("test-data/merge_archives/actual_error.zip");
+
+        let err = super::merge_archives(&sources(), zip, &MergeOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+
+        let err =
+            super::merge_archives(&sources(), &sources()[1], &MergeOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn subset() {
+        let zip = Path::new("test-data/merge_archives/actual_subset.zip");
+
+        super::subset_archive(&sources()[0], zip, &["*.bin".to_owned()]).unwrap();
+
+        unpack_and_compare(zip, "expected_subset");
+    }
+}
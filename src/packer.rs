@@ -0,0 +1,1197 @@
+pub(crate) mod deflate;
+pub mod sink;
+
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::aligner::AlignOptions;
+use crate::checksums::crc32::Crc32;
+use crate::crypto::winzip_aes::{AesExtraField, WinZipAesWriter};
+use crate::crypto::zip_crypto::{self, ZipCryptoWriter};
+use crate::stream_utils::{byte_writers, stream_nav};
+use crate::unpacker;
+use crate::zip::structs::{
+    CentralDirFileHeader, EndOfCentralDir, LocalFileHeader, Zip64EndOfCentralDirLocator,
+};
+use crate::zip::{
+    compression_methods, dos_time, extra_fields, feature_versions, general_bit_flags, host_systems,
+    signatures, wildcard,
+};
+
+use deflate::Deflater;
+use sink::{ArchiveSink, SplitWriter};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const VERSION_STORE: u16 = 10;
+const VERSION_AES: u16 = 51;
+//upper byte 0 is MS-DOS, lower byte 63 is version 6.3 of the specification
+pub(crate) const VERSION_MADE_BY: u16 = 63;
+
+pub(crate) const DOS_DIRECTORY_ATTRIB: u32 = 0x10;
+const UNIX_DIRECTORY: u32 = 0o040000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encryption {
+    /// Traditional PKWARE encryption, readable by legacy tools but weak.
+    ZipCrypto,
+    /// WinZip AE-2 with a 256-bit key.
+    Aes256,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileOptions {
+    pub last_modified: SystemTime,
+    pub encryption: Option<Encryption>,
+    /// Deflate level from 0 to 9; the data is stored when there is none.
+    pub compression_level: Option<u32>,
+    /// Unix file type and permission bits, recorded along with the exact modification time.
+    pub unix_mode: Option<u32>,
+    /// Makes stored data start at a multiple of this many bytes, as zipalign does.
+    /// Compressed and encrypted data is left where it is.
+    pub alignment: Option<u64>,
+}
+
+impl Default for FileOptions {
+    fn default() -> FileOptions {
+        FileOptions {
+            last_modified: SystemTime::now(),
+            encryption: None,
+            compression_level: None,
+            unix_mode: None,
+            alignment: None,
+        }
+    }
+}
+
+/// Writes entries one after another and the central directory on `finish`.
+pub struct ZipWriter<W: ArchiveSink> {
+    writer: W,
+    password: Option<Vec<u8>>,
+    comment: String,
+    entries: Vec<CentralDirFileHeader>,
+}
+
+fn name_flag(name: &str) -> u16 {
+    if name.is_ascii() {
+        0
+    } else {
+        general_bit_flags::UTF8
+    }
+}
+
+pub(crate) fn alignment_too_large(alignment: u64) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "Alignment of {} bytes can't be recorded in the archive.",
+            alignment
+        ),
+    )
+}
+
+fn too_large() -> std::io::Error {
+    std::io::Error::other("Entries of 4 GiB or more can't be written yet.")
+}
+
+const END_OF_CENTRAL_DIR_LEN: u64 = 22;
+//the ZIP64 end of central dir record and its locator
+const ZIP64_RECORDS_LEN: u64 = 56 + 20;
+
+//the central dir file header as written, with the values that don't fit it in a ZIP64 extra field
+fn with_zip64_extra_field(header: &CentralDirFileHeader) -> CentralDirFileHeader {
+    let mut zip64 = Vec::new();
+
+    for value in [
+        header.uncompressed_size,
+        header.compressed_size,
+        header.local_header_rel_offset,
+    ]
+    .iter()
+    {
+        if *value >= extra_fields::ZIP64_MARKER {
+            byte_writers::write_to(&mut zip64, *value);
+        }
+    }
+
+    if header.disk_number_start as u64 >= extra_fields::ZIP64_SHORT_MARKER {
+        byte_writers::write_to(&mut zip64, header.disk_number_start);
+    }
+
+    let mut header = header.clone();
+    //a copied entry brings the ZIP64 extra field of its old archive along
+    let mut extra_field = extra_fields::remove(&header.extra_field, extra_fields::ZIP64);
+
+    if !zip64.is_empty() {
+        let mut block = Vec::new();
+        byte_writers::write_to(&mut block, extra_fields::ZIP64);
+        byte_writers::write_to(&mut block, zip64.len() as u16);
+        block.extend(zip64);
+        block.extend(extra_field);
+
+        extra_field = block;
+        header.version_to_extract =
+            std::cmp::max(header.version_to_extract, feature_versions::ZIP64);
+    }
+
+    header.extra_field_length = extra_field.len() as u16;
+    header.extra_field = extra_field;
+
+    header
+}
+
+impl<W: ArchiveSink> ZipWriter<W> {
+    pub fn new(writer: W) -> ZipWriter<W> {
+        ZipWriter {
+            writer,
+            password: None,
+            comment: String::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Password for the entries added with encryption.
+    pub fn set_password(&mut self, password: Option<Vec<u8>>) {
+        self.password = password;
+    }
+
+    /// Archive comment written on `finish`.
+    pub fn set_comment(&mut self, comment: String) {
+        self.comment = comment;
+    }
+
+    //starts a local file header, on the volume it must fit in
+    fn start_entry(&mut self, local_file_header: &LocalFileHeader) -> std::io::Result<u64> {
+        let local_header_len =
+            30 + local_file_header.file_name.len() + local_file_header.extra_field.len();
+        self.writer.keep_together(local_header_len as u64)?;
+
+        let offset = stream_nav::current_position(&mut self.writer)?;
+        self.writer.write_all(&local_file_header.to_bytes())?;
+
+        Ok(offset)
+    }
+
+    //pads the extra field of a local file header about to be started so that its data is aligned
+    fn align_data(
+        &mut self,
+        local_file_header: &mut LocalFileHeader,
+        alignment: u64,
+    ) -> std::io::Result<()> {
+        if alignment <= 1 {
+            return Ok(());
+        }
+
+        if alignment > extra_fields::MAX_ALIGNMENT {
+            return Err(alignment_too_large(alignment));
+        }
+
+        let mut extra_field = extra_fields::remove_padding(&local_file_header.extra_field);
+        let header_len = (30
+            + local_file_header.file_name.len()
+            + extra_field.len()
+            + extra_fields::ALIGNMENT_BLOCK_LEN) as u64;
+
+        //the volume the header starts on is the one mapped
+        self.writer.keep_together(header_len + alignment)?;
+
+        let offset = stream_nav::current_position(&mut self.writer)?;
+        let data_pos = self.writer.volume_position(offset).1 + header_len;
+        let padding = (alignment - data_pos % alignment) % alignment;
+
+        extra_field.extend(extra_fields::alignment_block(alignment, padding as usize));
+
+        if extra_field.len() > u16::MAX as usize {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Alignment of {} bytes doesn't fit the extra field.",
+                    alignment
+                ),
+            ));
+        }
+
+        local_file_header.extra_field_length = extra_field.len() as u16;
+        local_file_header.extra_field = extra_field;
+
+        Ok(())
+    }
+
+    fn push_entry(
+        &mut self,
+        local_file_header: LocalFileHeader,
+        offset: u64,
+        dos_attribs: u32,
+        unix_mode: Option<u32>,
+    ) -> std::io::Result<()> {
+        let (disk_number_start, local_header_rel_offset) = self.writer.volume_position(offset);
+
+        //Unix modes live in the upper half of the external attributes
+        let (version_made_by, external_file_attribs) = match unix_mode {
+            Some(mode) => (
+                host_systems::UNIX << 8 | VERSION_MADE_BY,
+                mode << 16 | dos_attribs,
+            ),
+            None => (host_systems::MS_DOS << 8 | VERSION_MADE_BY, dos_attribs),
+        };
+
+        //padding only matters in front of the data
+        let extra_field =
+            extra_fields::remove(&local_file_header.extra_field, extra_fields::ALIGNMENT);
+
+        self.entries.push(CentralDirFileHeader {
+            version_made_by,
+            version_to_extract: local_file_header.version_to_extract,
+            general_bit_flag: local_file_header.general_bit_flag,
+            compression_method: local_file_header.compression_method,
+            last_mod_file_time: local_file_header.last_mod_file_time,
+            last_mod_file_date: local_file_header.last_mod_file_date,
+            crc_32: local_file_header.crc_32,
+            compressed_size: local_file_header.compressed_size,
+            uncompressed_size: local_file_header.uncompressed_size,
+            file_name_length: local_file_header.file_name_length,
+            extra_field_length: extra_field.len() as u16,
+            file_comment_length: 0,
+            disk_number_start,
+            internal_file_attribs: 0,
+            external_file_attribs,
+            local_header_rel_offset,
+            file_name: local_file_header.file_name,
+            extra_field,
+            file_comment: String::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Adds a folder; a trailing slash is appended to the name when missing.
+    pub fn add_directory(&mut self, name: &str, last_modified: SystemTime) -> std::io::Result<()> {
+        self.add_directory_entry(name, last_modified, None)
+    }
+
+    /// Like `add_directory`, recording Unix permission bits.
+    pub fn add_unix_directory(
+        &mut self,
+        name: &str,
+        last_modified: SystemTime,
+        mode: u32,
+    ) -> std::io::Result<()> {
+        self.add_directory_entry(name, last_modified, Some(UNIX_DIRECTORY | mode & 0o7777))
+    }
+
+    fn add_directory_entry(
+        &mut self,
+        name: &str,
+        last_modified: SystemTime,
+        unix_mode: Option<u32>,
+    ) -> std::io::Result<()> {
+        let mut file_name = name.to_owned();
+
+        if !file_name.ends_with('/') {
+            file_name.push('/');
+        }
+
+        let (date, time) = dos_time::from_system_time(last_modified);
+        let extra_field = unix_extra_field(last_modified, unix_mode);
+
+        let local_file_header = LocalFileHeader {
+            version_to_extract: feature_versions::DIR_OR_DEFLATE,
+            general_bit_flag: name_flag(&file_name),
+            compression_method: compression_methods::STORE,
+            last_mod_file_time: time,
+            last_mod_file_date: date,
+            crc_32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            file_name_length: file_name.len() as u16,
+            extra_field_length: extra_field.len() as u16,
+            file_name,
+            extra_field,
+        };
+
+        let offset = self.start_entry(&local_file_header)?;
+
+        self.push_entry(local_file_header, offset, DOS_DIRECTORY_ATTRIB, unix_mode)
+    }
+
+    /// Adds a file with the contents of `reader`, stored unless a compression level is given.
+    pub fn add_file(
+        &mut self,
+        name: &str,
+        reader: &mut dyn Read,
+        options: &FileOptions,
+    ) -> std::io::Result<()> {
+        let (date, time) = dos_time::from_system_time(options.last_modified);
+
+        let (data_method, data_version) = match options.compression_level {
+            Some(_) => (
+                compression_methods::DEFLATE,
+                feature_versions::DIR_OR_DEFLATE,
+            ),
+            None => (compression_methods::STORE, VERSION_STORE),
+        };
+
+        let aes_extra_field = AesExtraField {
+            vendor_version: 2,
+            strength: 3,
+            compression_method: data_method,
+        };
+
+        let (version_to_extract, general_bit_flag, compression_method, mut extra_field) =
+            match options.encryption {
+                None => (data_version, 0, data_method, Vec::new()),
+                //the CRC is only known afterwards, so the header is checked against the time field
+                Some(Encryption::ZipCrypto) => (
+                    feature_versions::DIR_OR_DEFLATE,
+                    general_bit_flags::ENCRYPTED | general_bit_flags::DATA_DESCRIPTOR,
+                    data_method,
+                    Vec::new(),
+                ),
+                Some(Encryption::Aes256) => (
+                    VERSION_AES,
+                    general_bit_flags::ENCRYPTED,
+                    compression_methods::AES,
+                    aes_extra_field.to_extra_field(),
+                ),
+            };
+
+        extra_field.extend(unix_extra_field(options.last_modified, options.unix_mode));
+
+        let mut local_file_header = LocalFileHeader {
+            version_to_extract,
+            general_bit_flag: general_bit_flag | name_flag(name),
+            compression_method,
+            last_mod_file_time: time,
+            last_mod_file_date: date,
+            crc_32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            file_name_length: name.len() as u16,
+            extra_field_length: extra_field.len() as u16,
+            file_name: name.to_owned(),
+            extra_field,
+        };
+
+        if let (Some(alignment), None, compression_methods::STORE) =
+            (options.alignment, options.encryption, compression_method)
+        {
+            self.align_data(&mut local_file_header, alignment)?;
+        }
+
+        let offset = self.start_entry(&local_file_header)?;
+        let data_pos = stream_nav::current_position(&mut self.writer)?;
+
+        let password = match (options.encryption, &self.password) {
+            (None, _) => &[][..],
+            (Some(_), Some(password)) => password.as_slice(),
+            (Some(_), None) => {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "Encryption needs a password.",
+                ))
+            }
+        };
+
+        let mut crc = Crc32::new();
+
+        let level = options.compression_level;
+
+        let uncompressed_size = match options.encryption {
+            None => compress_with_crc(reader, &mut self.writer, level, &mut crc)?,
+            Some(Encryption::ZipCrypto) => {
+                let (keys, header) = zip_crypto::Keys::with_header(password, (time >> 8) as u8)?;
+                self.writer.write_all(&header)?;
+
+                compress_with_crc(
+                    reader,
+                    &mut ZipCryptoWriter::new(&mut self.writer, keys),
+                    level,
+                    &mut crc,
+                )?
+            }
+            Some(Encryption::Aes256) => {
+                let mut aes_writer =
+                    WinZipAesWriter::new(&mut self.writer, password, &aes_extra_field)?;
+                let uncompressed_size =
+                    compress_with_crc(reader, &mut aes_writer, level, &mut crc)?;
+                aes_writer.finish()?;
+
+                uncompressed_size
+            }
+        };
+
+        let data_end = stream_nav::current_position(&mut self.writer)?;
+
+        //AE-2 leaves the CRC out so that it can't leak anything about the plain data
+        local_file_header.crc_32 = if options.encryption == Some(Encryption::Aes256) {
+            0
+        } else {
+            crc.value()
+        };
+        local_file_header.compressed_size = data_end - data_pos;
+        local_file_header.uncompressed_size = uncompressed_size;
+
+        if local_file_header.compressed_size >= extra_fields::ZIP64_MARKER
+            || uncompressed_size >= extra_fields::ZIP64_MARKER
+        {
+            return Err(too_large());
+        }
+
+        if general_bit_flag & general_bit_flags::DATA_DESCRIPTOR != 0 {
+            let mut data_descriptor = Vec::new();
+
+            byte_writers::write_to(&mut data_descriptor, signatures::SIGNATURE_DATA_DESCRIPTOR);
+            byte_writers::write_to(&mut data_descriptor, local_file_header.crc_32);
+            byte_writers::write_to(
+                &mut data_descriptor,
+                local_file_header.compressed_size as u32,
+            );
+            byte_writers::write_to(&mut data_descriptor, uncompressed_size as u32);
+
+            self.writer.keep_together(data_descriptor.len() as u64)?;
+            self.writer.write_all(&data_descriptor)?;
+        }
+
+        //the sizes are filled in even with a data descriptor, readers that scan local headers need them
+        let end = stream_nav::current_position(&mut self.writer)?;
+        self.writer.seek(SeekFrom::Start(offset))?;
+        self.writer.write_all(&local_file_header.to_bytes())?;
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        self.push_entry(local_file_header, offset, 0, options.unix_mode)
+    }
+
+    /// Copies an entry of another archive as it is, under the name given in its central dir file header.
+    pub fn copy_raw_entry<R: Read + Seek>(
+        &mut self,
+        source: &mut R,
+        central_dir_file_header: &CentralDirFileHeader,
+    ) -> std::io::Result<()> {
+        self.copy_entry(source, central_dir_file_header, None)
+    }
+
+    /// Like `copy_raw_entry`, padding the local file header so that the data starts
+    /// at a multiple of `alignment` bytes, whatever the data is.
+    pub fn copy_aligned_entry<R: Read + Seek>(
+        &mut self,
+        source: &mut R,
+        central_dir_file_header: &CentralDirFileHeader,
+        alignment: u64,
+    ) -> std::io::Result<()> {
+        self.copy_entry(source, central_dir_file_header, Some(alignment))
+    }
+
+    fn copy_entry<R: Read + Seek>(
+        &mut self,
+        source: &mut R,
+        central_dir_file_header: &CentralDirFileHeader,
+        alignment: Option<u64>,
+    ) -> std::io::Result<()> {
+        let mut raw_entry = RawEntry::read(source, central_dir_file_header)?;
+
+        if let Some(alignment) = alignment {
+            self.align_data(&mut raw_entry.local_file_header, alignment)?;
+        }
+
+        let mut central_dir_file_header = central_dir_file_header.clone();
+        central_dir_file_header.general_bit_flag = raw_entry.local_file_header.general_bit_flag
+            & general_bit_flags::UTF8
+            | central_dir_file_header.general_bit_flag & !general_bit_flags::UTF8;
+
+        let offset = self.start_entry(&raw_entry.local_file_header)?;
+        let (disk_number_start, local_header_rel_offset) = self.writer.volume_position(offset);
+
+        source.seek(SeekFrom::Start(raw_entry.data_pos))?;
+
+        if std::io::copy(&mut source.take(raw_entry.copy_len), &mut self.writer)?
+            < raw_entry.copy_len
+        {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!(
+                    "Data of {} is truncated.",
+                    central_dir_file_header.file_name
+                ),
+            ));
+        }
+
+        central_dir_file_header.disk_number_start = disk_number_start;
+        central_dir_file_header.local_header_rel_offset = local_header_rel_offset;
+        self.entries.push(central_dir_file_header);
+
+        Ok(())
+    }
+
+    /// Writes the central directory and returns the underlying writer.
+    /// The ZIP64 end of central dir record and its locator are added when a count,
+    /// an offset or a disk number doesn't fit the end of central dir record.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        let mut central_dir_offset = None;
+        let mut entry_disks = Vec::with_capacity(self.entries.len());
+
+        for entry in &self.entries {
+            let bytes = with_zip64_extra_field(entry).to_bytes();
+            self.writer.keep_together(bytes.len() as u64)?;
+
+            let offset = stream_nav::current_position(&mut self.writer)?;
+            central_dir_offset.get_or_insert(offset);
+            entry_disks.push(self.writer.volume_position(offset).0);
+
+            self.writer.write_all(&bytes)?;
+        }
+
+        let end_len = END_OF_CENTRAL_DIR_LEN + self.comment.len() as u64;
+        self.writer.keep_together(end_len)?;
+
+        let end_pos = stream_nav::current_position(&mut self.writer)?;
+        let central_dir_offset = central_dir_offset.unwrap_or(end_pos);
+        let mut end_of_central_dir =
+            self.end_of_central_dir(central_dir_offset, end_pos, &entry_disks);
+
+        if end_of_central_dir.needs_zip64() {
+            //the locator has to be right in front of the end of central dir record
+            self.writer.keep_together(ZIP64_RECORDS_LEN + end_len)?;
+
+            end_of_central_dir = self.end_of_central_dir(central_dir_offset, end_pos, &entry_disks);
+
+            let (zip64_end_disk, zip64_end_offset) = self.writer.volume_position(end_pos);
+            let locator = Zip64EndOfCentralDirLocator {
+                number_of_disk_with_zip64_end: zip64_end_disk,
+                zip64_end_offset,
+                total_number_of_disks: zip64_end_disk + 1,
+            };
+
+            self.writer
+                .write_all(&end_of_central_dir.to_zip64_bytes())?;
+            self.writer.write_all(&locator.to_bytes())?;
+        }
+
+        self.writer.write_all(&end_of_central_dir.to_bytes())?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    //the central dir ends at `end_pos`, the records after it start on the volume of that position
+    fn end_of_central_dir(
+        &self,
+        central_dir_offset: u64,
+        end_pos: u64,
+        entry_disks: &[u32],
+    ) -> EndOfCentralDir {
+        let (this_disk, _) = self.writer.volume_position(end_pos);
+        let (central_dir_disk, central_dir_rel_offset) =
+            self.writer.volume_position(central_dir_offset);
+
+        EndOfCentralDir {
+            number_of_this_disk: this_disk,
+            number_of_disk_with_start_central_dir: central_dir_disk,
+            total_entries_in_central_dir_on_this_disk: entry_disks
+                .iter()
+                .filter(|&&disk| disk == this_disk)
+                .count() as u64,
+            total_entries_in_central_dir: self.entries.len() as u64,
+            central_dir_size: end_pos - central_dir_offset,
+            central_dir_offset_from_starting_disk_num: central_dir_rel_offset,
+            zip_file_comment_length: self.comment.len() as u16,
+            zip_file_comment: self.comment.clone(),
+        }
+    }
+}
+
+/// An entry of another archive as `ZipWriter::copy_raw_entry` copies it.
+pub(crate) struct RawEntry {
+    /// The local file header, renamed after the central dir file header.
+    pub local_file_header: LocalFileHeader,
+    /// Where the data starts in the source.
+    pub data_pos: u64,
+    //the data and the data descriptor following it
+    copy_len: u64,
+}
+
+impl RawEntry {
+    pub fn read<R: Read + Seek>(
+        source: &mut R,
+        central_dir_file_header: &CentralDirFileHeader,
+    ) -> std::io::Result<RawEntry> {
+        source.seek(SeekFrom::Start(
+            central_dir_file_header.local_header_rel_offset,
+        ))?;
+
+        if unpacker::read_signature(source)? != signatures::SIGNATURE_FILE_HEADER {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Bad local file header signature for {}.",
+                    central_dir_file_header.file_name
+                ),
+            ));
+        }
+
+        let mut local_file_header = unpacker::read_local_file_header(source)?;
+        let data_pos = stream_nav::current_position(source)?;
+        let data_len = central_dir_file_header.compressed_size;
+
+        //the data descriptor follows the data, its signature is optional
+        let data_descriptor_len =
+            if central_dir_file_header.general_bit_flag & general_bit_flags::DATA_DESCRIPTOR != 0 {
+                let size_len =
+                    if extra_fields::find(&local_file_header.extra_field, extra_fields::ZIP64)
+                        .is_some()
+                    {
+                        8
+                    } else {
+                        4
+                    };
+
+                source.seek(SeekFrom::Start(data_pos + data_len))?;
+
+                if unpacker::read_signature(source)? == signatures::SIGNATURE_DATA_DESCRIPTOR {
+                    8 + 2 * size_len
+                } else {
+                    4 + 2 * size_len
+                }
+            } else {
+                0
+            };
+
+        local_file_header.file_name = central_dir_file_header.file_name.clone();
+        local_file_header.file_name_length = local_file_header.file_name.len() as u16;
+        local_file_header.general_bit_flag = local_file_header.general_bit_flag
+            & !general_bit_flags::UTF8
+            | name_flag(&local_file_header.file_name);
+
+        Ok(RawEntry {
+            local_file_header,
+            data_pos,
+            copy_len: data_len + data_descriptor_len,
+        })
+    }
+
+    /// Bytes the entry takes in front of the central directory.
+    pub fn local_len(&self) -> u64 {
+        (30 + self.local_file_header.file_name.len() + self.local_file_header.extra_field.len())
+            as u64
+            + self.copy_len
+    }
+}
+
+fn copy_with_crc(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    crc: &mut Crc32,
+) -> std::io::Result<u64> {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut copied = 0;
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(copied),
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        crc.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+}
+
+//the data compressed on the way when there is a level
+fn compress_with_crc(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    level: Option<u32>,
+    crc: &mut Crc32,
+) -> std::io::Result<u64> {
+    match level {
+        Some(level) => {
+            let mut deflater = Deflater::new(writer, level);
+            let copied = copy_with_crc(reader, &mut deflater, crc)?;
+            deflater.finish()?;
+
+            Ok(copied)
+        }
+        None => copy_with_crc(reader, writer, crc),
+    }
+}
+
+//DOS times only go down to two seconds, entries with Unix modes get the exact time as well
+fn unix_extra_field(last_modified: SystemTime, unix_mode: Option<u32>) -> Vec<u8> {
+    match unix_mode {
+        Some(_) => extra_fields::modification_time_block(
+            last_modified
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |mtime| mtime.as_secs() as i64),
+        ),
+        None => Vec::new(),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PackOptions {
+    pub password: Option<Vec<u8>>,
+    /// Encrypt the entries, only those matching `encrypt_patterns` if there are any.
+    pub encrypt: bool,
+    pub encryption: Option<Encryption>,
+    /// Patterns of the entries to encrypt; all of them when empty.
+    pub encrypt_patterns: Vec<String>,
+    /// Split the archive into volumes of at most this many bytes.
+    pub volume_size: Option<u64>,
+    /// Align the data of the entries, which are stored unless encrypted.
+    pub alignment: Option<AlignOptions>,
+}
+
+impl PackOptions {
+    fn encryption_for(&self, name: &str) -> Option<Encryption> {
+        if !self.encrypt {
+            return None;
+        }
+
+        if self.encrypt_patterns.is_empty()
+            || self
+                .encrypt_patterns
+                .iter()
+                .any(|pattern| wildcard::matches(pattern, name))
+        {
+            Some(self.encryption.unwrap_or(Encryption::Aes256))
+        } else {
+            None
+        }
+    }
+}
+
+fn pack_entries<W: ArchiveSink>(
+    zip_writer: &mut ZipWriter<W>,
+    dir: &Path,
+    prefix: &str,
+    options: &PackOptions,
+) -> std::io::Result<()> {
+    let mut children = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    children.sort_by_key(|child| child.file_name());
+
+    for child in children {
+        let name = format!("{}{}", prefix, child.file_name().to_string_lossy());
+        let metadata = child.metadata()?;
+        let last_modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+
+        if metadata.is_dir() {
+            zip_writer.add_directory(&name, last_modified)?;
+            pack_entries(zip_writer, &child.path(), &format!("{}/", name), options)?;
+        } else {
+            zip_writer.add_file(
+                &name,
+                &mut File::open(child.path())?,
+                &FileOptions {
+                    last_modified,
+                    encryption: options.encryption_for(&name),
+                    alignment: options
+                        .alignment
+                        .as_ref()
+                        .map(|alignment| alignment.alignment_for(&name)),
+                    ..FileOptions::default()
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn pack_into<W: ArchiveSink>(
+    writer: W,
+    src_dir: &Path,
+    options: &PackOptions,
+) -> std::io::Result<W> {
+    let mut zip_writer = ZipWriter::new(writer);
+    zip_writer.set_password(options.password.clone());
+
+    pack_entries(&mut zip_writer, src_dir, "", options)?;
+
+    zip_writer.finish()
+}
+
+/// Stores the contents of `src_dir` in a new archive at `dst_file`.
+/// With a volume size, the volumes before the last one go next to it as `.z01`, `.z02`, ...
+pub fn pack_dir(src_dir: &Path, dst_file: &Path, options: &PackOptions) -> std::io::Result<()> {
+    if let Some(alignment) = &options.alignment {
+        alignment.validate()?;
+    }
+
+    match options.volume_size {
+        Some(volume_size) => pack_into(
+            SplitWriter::create(dst_file, volume_size)?,
+            src_dir,
+            options,
+        )?
+        .finish(),
+        None => {
+            pack_into(BufWriter::new(File::create(dst_file)?), src_dir, options)?;
+
+            Ok(())
+        }
+    }
+}
+
+/// Packs `src_dir` into a temporary archive next to `dst_file`, named after it with `suffix`,
+/// hands its path to `use_archive` and removes it again.
+pub(crate) fn pack_dir_to_temp<T>(
+    src_dir: &Path,
+    dst_file: &Path,
+    suffix: &str,
+    options: &PackOptions,
+    use_archive: impl FnOnce(&Path) -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    //whatever uses the archive reads it as a single file
+    if options.volume_size.is_some() {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "A temporary archive can't be split into volumes.",
+        ));
+    }
+
+    let file_name = dst_file
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Not a file path."))?;
+    let temp_path = dst_file.with_file_name(format!(".{}.{}", file_name.to_string_lossy(), suffix));
+
+    let result = pack_dir(src_dir, &temp_path, options).and_then(|_| use_archive(&temp_path));
+
+    let _ = std::fs::remove_file(&temp_path);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{BufWriter, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+    use std::path::Path;
+    use std::time::UNIX_EPOCH;
+
+    use crate::packer::sink::ArchiveSink;
+    use crate::packer::{Encryption, FileOptions, PackOptions, ZipWriter};
+    use crate::unpacker::source::ArchiveSource;
+    use crate::unpacker::{self, UnpackOptions};
+    use crate::zip::general_bit_flags;
+    use crate::zip::structs::CentralDirFileHeader;
+
+    //an archive that starts more than 4 GiB into the stream, without writing all that;
+    //split into volumes of `volume_size`, it starts on volume 70000
+    struct FarStream {
+        cursor: Cursor<Vec<u8>>,
+        base: u64,
+        volume_size: Option<u64>,
+    }
+
+    impl Read for FarStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.cursor.read(buf)
+        }
+    }
+
+    impl Write for FarStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.cursor.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for FarStream {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            let pos = match pos {
+                SeekFrom::Start(pos) => {
+                    SeekFrom::Start(pos.checked_sub(self.base).ok_or_else(|| {
+                        std::io::Error::new(ErrorKind::InvalidInput, "Before the archive.")
+                    })?)
+                }
+                pos => pos,
+            };
+
+            Ok(self.base + self.cursor.seek(pos)?)
+        }
+    }
+
+    impl ArchiveSink for FarStream {
+        fn volume_position(&self, pos: u64) -> (u32, u64) {
+            match self.volume_size {
+                Some(volume_size) => ((pos / volume_size) as u32, pos % volume_size),
+                None => (0, pos),
+            }
+        }
+    }
+
+    impl ArchiveSource for FarStream {
+        fn volume_start(&self, disk: u32) -> Option<u64> {
+            match self.volume_size {
+                Some(volume_size) => Some(disk as u64 * volume_size),
+                None => (disk == 0).then_some(0),
+            }
+        }
+    }
+
+    fn pack_and_compare(actual_dir: &str, options: &PackOptions) -> Vec<CentralDirFileHeader> {
+        let test_dir = Path::new("test-data/pack_encrypted");
+        let out_folder = test_dir.join(actual_dir);
+        let zip = test_dir.join(format!("{}.zip", actual_dir));
+
+        if out_folder.exists() {
+            std::fs::remove_dir_all(&out_folder).unwrap();
+        }
+
+        std::fs::create_dir(&out_folder).unwrap();
+
+        super::pack_dir(&test_dir.join("expected"), &zip, options).unwrap();
+
+        unpacker::unpack_archive(
+            &zip,
+            &out_folder,
+            &UnpackOptions {
+                password: options.password.clone(),
+                ..UnpackOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(!dir_diff::is_different(&out_folder, test_dir.join("expected")).unwrap());
+
+        if options.encrypt {
+            std::fs::remove_dir_all(&out_folder).unwrap();
+            std::fs::create_dir(&out_folder).unwrap();
+
+            let err =
+                unpacker::unpack_archive(&zip, &out_folder, &UnpackOptions::default()).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        }
+
+        let headers = unpacker::list_archive(&zip).unwrap();
+
+        std::fs::remove_dir_all(&out_folder).unwrap();
+        std::fs::remove_file(&zip).unwrap();
+
+        headers
+    }
+
+    fn is_encrypted(header: &CentralDirFileHeader) -> bool {
+        header.general_bit_flag & general_bit_flags::ENCRYPTED != 0
+    }
+
+    #[test]
+    fn pack_plain() {
+        pack_and_compare("actual_plain", &PackOptions::default());
+    }
+
+    #[test]
+    fn pack_aes() {
+        pack_and_compare(
+            "actual_aes",
+            &PackOptions {
+                password: Some(b"secret".to_vec()),
+                encrypt: true,
+                ..PackOptions::default()
+            },
+        );
+    }
+
+    #[test]
+    fn pack_password_without_encrypt() {
+        //a password kept around for unpacking doesn't encrypt anything by itself
+        let headers = pack_and_compare(
+            "actual_password_only",
+            &PackOptions {
+                password: Some(b"secret".to_vec()),
+                ..PackOptions::default()
+            },
+        );
+
+        assert!(headers.iter().all(|header| !is_encrypted(header)));
+    }
+
+    #[test]
+    fn pack_zipcrypto_selected() {
+        let headers = pack_and_compare(
+            "actual_zipcrypto",
+            &PackOptions {
+                password: Some(b"secret".to_vec()),
+                encrypt: true,
+                encryption: Some(Encryption::ZipCrypto),
+                encrypt_patterns: vec!["*.txt".to_owned()],
+                ..PackOptions::default()
+            },
+        );
+
+        assert!(headers
+            .iter()
+            .any(|header| header.file_name.ends_with(".txt")));
+        assert!(headers
+            .iter()
+            .any(|header| !header.file_name.ends_with(".txt")));
+
+        for header in &headers {
+            assert_eq!(
+                is_encrypted(header),
+                header.file_name.ends_with(".txt"),
+                "{}",
+                header.file_name
+            );
+        }
+    }
+
+    #[test]
+    fn pack_split() {
+        let test_dir = Path::new("test-data/pack_encrypted");
+
+        pack_and_compare(
+            "actual_split",
+            &PackOptions {
+                password: Some(b"secret".to_vec()),
+                encrypt: true,
+                encryption: Some(Encryption::ZipCrypto),
+                encrypt_patterns: vec!["*.txt".to_owned()],
+                volume_size: Some(64 * 1024),
+                alignment: None,
+            },
+        );
+
+        for volume in ["actual_split.z01", "actual_split.z02", "actual_split.z03"].iter() {
+            let volume = test_dir.join(volume);
+
+            assert_eq!(std::fs::metadata(&volume).unwrap().len(), 64 * 1024);
+            std::fs::remove_file(&volume).unwrap();
+        }
+
+        assert!(!test_dir.join("actual_split.z04").exists());
+
+        //everything fits in the first volume
+        let zip = test_dir.join("actual_single_volume.zip");
+
+        super::pack_dir(
+            &test_dir.join("expected/folder_1/folder_11"),
+            &zip,
+            &PackOptions {
+                volume_size: Some(64 * 1024),
+                ..PackOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(&std::fs::read(&zip).unwrap()[..4], b"PK00");
+        assert!(!zip.with_extension("z01").exists());
+
+        std::fs::remove_file(&zip).unwrap();
+    }
+
+    #[test]
+    fn zip64_disks_and_offsets() {
+        let volume_size = 64 * 1024;
+        let base = 70000 * volume_size;
+        //more than the end of central dir record and the longest comment, where readers look for it
+        let data = (0..70000u32).map(|n| n as u8).collect::<Vec<_>>();
+
+        for split in [false, true].iter() {
+            let mut zip_writer = ZipWriter::new(FarStream {
+                cursor: Cursor::new(Vec::new()),
+                base,
+                volume_size: if *split { Some(volume_size) } else { None },
+            });
+            zip_writer.add_directory("folder/", UNIX_EPOCH).unwrap();
+            zip_writer
+                .add_file("folder/data.bin", &mut &data[..], &FileOptions::default())
+                .unwrap();
+            let mut stream = zip_writer.finish().unwrap();
+
+            let bytes = stream.cursor.get_ref().clone();
+            let end = &bytes[bytes.len() - 22..];
+            let locator = &bytes[bytes.len() - 42..bytes.len() - 22];
+            let central_dir_pos = base + 37 + 45 + data.len() as u64;
+
+            assert_eq!(end[8..12], [2, 0, 2, 0]);
+            assert_eq!(locator[..4], [0x50, 0x4B, 0x06, 0x07]);
+
+            //in volumes the offsets are small, but the disk numbers only fit the ZIP64 record
+            let (last_disk, central_dir_offset) = if *split {
+                assert_eq!(end[4..8], [0xFF; 4]);
+                assert_eq!(locator[16..20], 70002u32.to_le_bytes());
+
+                (70001, central_dir_pos % volume_size)
+            } else {
+                assert_eq!(end[4..8], [0; 4]);
+                assert_eq!(end[16..20], [0xFF; 4]);
+                assert_eq!(locator[16..20], 1u32.to_le_bytes());
+
+                (0, central_dir_pos)
+            };
+
+            let location = unpacker::locate_central_dir(&mut stream).unwrap();
+            assert_eq!(
+                location.zip64_end_pos,
+                Some(base + bytes.len() as u64 - 22 - 20 - 56)
+            );
+            assert_eq!(location.central_dir_pos, central_dir_pos);
+            assert_eq!(location.shift, 0);
+
+            let (end_of_central_dir, headers) = unpacker::read_central_dir(&mut stream).unwrap();
+            assert_eq!(end_of_central_dir.number_of_this_disk, last_disk);
+            assert_eq!(
+                end_of_central_dir.central_dir_offset_from_starting_disk_num,
+                central_dir_offset
+            );
+            assert_eq!(end_of_central_dir.total_entries_in_central_dir, 2);
+            assert_eq!(headers[0].local_header_rel_offset, base);
+            assert_eq!(headers[1].local_header_rel_offset, base + 37);
+
+            let mut unpacked = Vec::new();
+            let mut buffer = vec![0u8; 1024];
+
+            unpacker::visit_entries(&mut stream, |stream, header, local_file_header| {
+                assert_eq!(header.version_to_extract, 45);
+
+                if header.file_name == "folder/data.bin" {
+                    unpacker::unpack_entry_data(
+                        stream,
+                        local_file_header,
+                        &mut unpacked,
+                        &mut buffer,
+                        &mut None,
+                        &UnpackOptions::default(),
+                    )?;
+                }
+
+                Ok(())
+            })
+            .unwrap();
+
+            assert_eq!(unpacked, data);
+        }
+    }
+
+    #[test]
+    fn zip64_entry_count() {
+        let zip = Path::new("test-data/pack_encrypted/actual_many_entries.zip");
+
+        let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(zip).unwrap()));
+
+        for n in 0..70000 {
+            zip_writer
+                .add_directory(&format!("{}/", n), UNIX_EPOCH)
+                .unwrap();
+        }
+
+        zip_writer.finish().unwrap();
+
+        let bytes = std::fs::read(zip).unwrap();
+        assert_eq!(bytes[bytes.len() - 12..bytes.len() - 10], [0xFF, 0xFF]);
+
+        let headers = unpacker::list_archive(zip).unwrap();
+        assert_eq!(headers.len(), 70000);
+        assert_eq!(headers[69999].file_name, "69999/");
+
+        std::fs::remove_file(zip).unwrap();
+    }
+}
@@ -0,0 +1,708 @@
+use std::io::Write;
+
+use crate::unpacker::algorithms::deflate::{
+    CODE_LENGTH_ORDER, DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA,
+};
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+//input taken per block, and symbols after which a block is closed early
+const BLOCK_SIZE: usize = 64 * 1024;
+const MAX_BLOCK_TOKENS: usize = 16 * 1024;
+const MAX_STORED_LEN: usize = 0xFFFF;
+
+const END_OF_BLOCK: usize = 256;
+const LITERAL_CODES: usize = 286;
+const DISTANCE_CODES: usize = 30;
+const MAX_CODE_LEN: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LEN: u8 = 7;
+
+/// Compression levels as zlib numbers them: 0 only stores, 9 searches hardest.
+pub const DEFAULT_LEVEL: u32 = 6;
+
+//how hard a level looks for matches: chain length, good enough length and lazy matching
+const LEVELS: [(usize, usize, bool); 10] = [
+    (0, 0, false),
+    (4, 8, false),
+    (8, 16, false),
+    (32, 32, false),
+    (16, 16, true),
+    (32, 32, true),
+    (128, 128, true),
+    (256, 128, true),
+    (1024, 258, true),
+    (4096, 258, true),
+];
+
+//a literal when `dist` is 0, a match of `value` bytes otherwise
+#[derive(Clone, Copy)]
+struct Token {
+    value: u16,
+    dist: u16,
+}
+
+/// LSB-first bit writer collecting whole bytes in a buffer.
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.bit_count;
+        self.bit_count += count;
+
+        while self.bit_count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit_count > 0 {
+            self.write_bits(0, 8 - self.bit_count);
+        }
+    }
+}
+
+//code index and extra bits of a match length or distance
+fn find_code<T: Copy + Into<u32>>(bases: &[T], value: u32) -> usize {
+    bases.partition_point(|&base| base.into() <= value) - 1
+}
+
+/// Lengths of a length-limited Huffman code for `freqs`, found with the package-merge algorithm.
+/// At least two symbols get a code, as some decoders insist on a complete code.
+fn code_lengths(freqs: &[u32], limit: u8) -> Vec<u8> {
+    let mut leaves = freqs
+        .iter()
+        .enumerate()
+        .filter(|(_, freq)| **freq > 0)
+        .map(|(symbol, freq)| (*freq as u64, vec![symbol as u16]))
+        .collect::<Vec<_>>();
+
+    let unused = freqs.iter().enumerate().filter(|(_, freq)| **freq == 0);
+    let missing = 2usize.saturating_sub(leaves.len());
+
+    for (symbol, _) in unused.take(missing) {
+        leaves.push((1, vec![symbol as u16]));
+    }
+
+    leaves.sort_by_key(|(weight, _)| *weight);
+
+    let mut list = leaves.clone();
+
+    for _ in 1..limit {
+        let packages = list.chunks_exact(2).map(|pair| {
+            let mut symbols = pair[0].1.clone();
+            symbols.extend_from_slice(&pair[1].1);
+
+            (pair[0].0 + pair[1].0, symbols)
+        });
+
+        let mut merged = Vec::with_capacity(list.len() + leaves.len());
+        let mut packages = packages.peekable();
+
+        for leaf in &leaves {
+            while let Some(package) = packages.next_if(|package| package.0 < leaf.0) {
+                merged.push(package);
+            }
+
+            merged.push(leaf.clone());
+        }
+
+        merged.extend(packages);
+        list = merged;
+    }
+
+    let mut lengths = vec![0u8; freqs.len()];
+
+    for (_, symbols) in &list[..2 * leaves.len() - 2] {
+        for symbol in symbols {
+            lengths[*symbol as usize] += 1;
+        }
+    }
+
+    lengths
+}
+
+//canonical codes, bit-reversed for writing LSB first
+fn codes(lengths: &[u8]) -> Vec<u16> {
+    let mut counts = [0u16; MAX_CODE_LEN as usize + 1];
+
+    for len in lengths {
+        counts[*len as usize] += 1;
+    }
+
+    counts[0] = 0;
+
+    let mut next_code = [0u16; MAX_CODE_LEN as usize + 2];
+
+    for len in 1..=MAX_CODE_LEN as usize {
+        next_code[len + 1] = (next_code[len] + counts[len]) << 1;
+    }
+
+    lengths
+        .iter()
+        .map(|&len| {
+            if len == 0 {
+                return 0;
+            }
+
+            let code = next_code[len as usize];
+            next_code[len as usize] += 1;
+
+            code.reverse_bits() >> (16 - len)
+        })
+        .collect()
+}
+
+fn fixed_lengths() -> (Vec<u8>, Vec<u8>) {
+    let mut literal_lengths = vec![0u8; 288];
+
+    literal_lengths[..144].fill(8);
+    literal_lengths[144..256].fill(9);
+    literal_lengths[256..280].fill(7);
+    literal_lengths[280..].fill(8);
+
+    (literal_lengths, vec![5; DISTANCE_CODES])
+}
+
+//the code lengths of a dynamic block, run-length encoded as (symbol, extra bits)
+fn encode_lengths(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut encoded = Vec::new();
+    let mut pos = 0;
+
+    while pos < lengths.len() {
+        let value = lengths[pos];
+        let mut run = lengths[pos..]
+            .iter()
+            .take_while(|&&len| len == value)
+            .count();
+        pos += run;
+
+        if value == 0 {
+            while run >= 11 {
+                let len = std::cmp::min(run, 138);
+                encoded.push((18, (len - 11) as u8));
+                run -= len;
+            }
+
+            if run >= 3 {
+                encoded.push((17, (run - 3) as u8));
+                run = 0;
+            }
+        } else {
+            encoded.push((value, 0));
+            run -= 1;
+
+            while run >= 3 {
+                let len = std::cmp::min(run, 6);
+                encoded.push((16, (len - 3) as u8));
+                run -= len;
+            }
+        }
+
+        encoded.extend(std::iter::repeat_n((value, 0), run));
+    }
+
+    encoded
+}
+
+/// Raw Deflate (RFC 1951) compressor; what is written comes out compressed on the inner writer.
+pub struct Deflater<W: Write> {
+    writer: W,
+    bits: BitWriter,
+    max_chain: usize,
+    nice_len: usize,
+    lazy: bool,
+    //recent input kept for matches, followed by the input not compressed yet
+    data: Vec<u8>,
+    data_offset: u64,
+    cursor: usize,
+    block_start: usize,
+    tokens: Vec<Token>,
+    //latest position + 1 of every hash, and the previous position + 1 with the same hash
+    head: Vec<u64>,
+    prev: Vec<u64>,
+}
+
+impl<W: Write> Deflater<W> {
+    pub fn new(writer: W, level: u32) -> Deflater<W> {
+        let (max_chain, nice_len, lazy) = LEVELS[std::cmp::min(level, 9) as usize];
+
+        Deflater {
+            writer,
+            bits: BitWriter {
+                out: Vec::new(),
+                bits: 0,
+                bit_count: 0,
+            },
+            max_chain,
+            nice_len,
+            lazy,
+            data: Vec::new(),
+            data_offset: 0,
+            cursor: 0,
+            block_start: 0,
+            tokens: Vec::new(),
+            head: vec![0; 1 << HASH_BITS],
+            prev: vec![0; WINDOW_SIZE],
+        }
+    }
+
+    /// Lets the data refer back into `dictionary` without it being written; to be called before any data.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        let dictionary = &dictionary[dictionary.len().saturating_sub(WINDOW_SIZE)..];
+
+        self.data
+            .splice(self.cursor..self.cursor, dictionary.iter().copied());
+
+        for pos in self.cursor..self.cursor + dictionary.len() {
+            self.insert(pos);
+        }
+
+        self.cursor += dictionary.len();
+        self.block_start = self.cursor;
+    }
+
+    fn hash(&self, pos: usize) -> usize {
+        let bytes = [self.data[pos], self.data[pos + 1], self.data[pos + 2], 0];
+
+        (u32::from_le_bytes(bytes).wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, pos: usize) {
+        if pos + MIN_MATCH > self.data.len() {
+            return;
+        }
+
+        let hash = self.hash(pos);
+        let abs_pos = self.data_offset + pos as u64;
+
+        self.prev[abs_pos as usize % WINDOW_SIZE] = self.head[hash];
+        self.head[hash] = abs_pos + 1;
+    }
+
+    //the longest earlier match for the bytes at `pos` that beats `min_len`, as (length, distance)
+    fn find_match(&self, pos: usize, min_len: usize) -> (usize, usize) {
+        let max_len = std::cmp::min(MAX_MATCH, self.data.len() - pos);
+
+        if max_len < MIN_MATCH || self.max_chain == 0 {
+            return (0, 0);
+        }
+
+        let abs_pos = self.data_offset + pos as u64;
+        let mut best = (min_len, 0);
+        let mut candidate = self.head[self.hash(pos)];
+        let mut chain = self.max_chain;
+
+        while candidate > 0 && chain > 0 {
+            let candidate_pos = candidate - 1;
+
+            if candidate_pos < self.data_offset
+                || candidate_pos >= abs_pos
+                || abs_pos - candidate_pos > WINDOW_SIZE as u64
+            {
+                break;
+            }
+
+            let start = (candidate_pos - self.data_offset) as usize;
+
+            if best.0 < max_len && self.data[start + best.0] == self.data[pos + best.0] {
+                let len = self.data[start..start + max_len]
+                    .iter()
+                    .zip(&self.data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                if len > best.0 {
+                    best = (len, (abs_pos - candidate_pos) as usize);
+
+                    if len >= self.nice_len || len == max_len {
+                        break;
+                    }
+                }
+            }
+
+            let next = self.prev[candidate_pos as usize % WINDOW_SIZE];
+
+            if next >= candidate {
+                break;
+            }
+
+            candidate = next;
+            chain -= 1;
+        }
+
+        if best.1 == 0 {
+            (0, 0)
+        } else {
+            best
+        }
+    }
+
+    //turns the input into tokens up to `keep` bytes before its end; matches may reach into those
+    fn tokenize(&mut self, keep: usize) -> std::io::Result<()> {
+        //a match found one byte ahead while deciding about the previous one
+        let mut next_match = None;
+
+        while self.cursor + keep < self.data.len() {
+            let pos = self.cursor;
+
+            let (len, dist) = match next_match.take() {
+                Some(found) => found,
+                None => self.find_match(pos, MIN_MATCH - 1),
+            };
+
+            self.insert(pos);
+
+            if len >= MIN_MATCH
+                && self.lazy
+                && len < self.nice_len
+                && pos + 1 + keep < self.data.len()
+            {
+                let (next_len, next_dist) = self.find_match(pos + 1, len);
+
+                if next_len > len {
+                    self.tokens.push(Token {
+                        value: self.data[pos] as u16,
+                        dist: 0,
+                    });
+                    self.cursor += 1;
+                    next_match = Some((next_len, next_dist));
+
+                    continue;
+                }
+            }
+
+            if len >= MIN_MATCH {
+                self.tokens.push(Token {
+                    value: len as u16,
+                    dist: dist as u16,
+                });
+
+                for skipped in pos + 1..pos + len {
+                    self.insert(skipped);
+                }
+
+                self.cursor += len;
+            } else {
+                self.tokens.push(Token {
+                    value: self.data[pos] as u16,
+                    dist: 0,
+                });
+                self.cursor += 1;
+            }
+
+            if self.tokens.len() >= MAX_BLOCK_TOKENS || self.cursor - self.block_start >= BLOCK_SIZE
+            {
+                self.write_block(false)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_tokens(&mut self, literal_lengths: &[u8], distance_lengths: &[u8]) {
+        let literal_codes = codes(literal_lengths);
+        let distance_codes = codes(distance_lengths);
+
+        for token in &self.tokens {
+            if token.dist == 0 {
+                let symbol = token.value as usize;
+                self.bits
+                    .write_bits(literal_codes[symbol] as u32, literal_lengths[symbol] as u32);
+                continue;
+            }
+
+            let len = token.value as u32;
+            let code = find_code(&LENGTH_BASE[..29], len);
+            let symbol = 257 + code;
+            self.bits
+                .write_bits(literal_codes[symbol] as u32, literal_lengths[symbol] as u32);
+            self.bits
+                .write_bits(len - LENGTH_BASE[code] as u32, LENGTH_EXTRA[code] as u32);
+
+            let dist = token.dist as u32;
+            let code = find_code(&DISTANCE_BASE[..DISTANCE_CODES], dist);
+            self.bits
+                .write_bits(distance_codes[code] as u32, distance_lengths[code] as u32);
+            self.bits
+                .write_bits(dist - DISTANCE_BASE[code], DISTANCE_EXTRA[code] as u32);
+        }
+
+        self.bits.write_bits(
+            literal_codes[END_OF_BLOCK] as u32,
+            literal_lengths[END_OF_BLOCK] as u32,
+        );
+    }
+
+    //bits the tokens take with the given code lengths, extra bits included
+    fn tokens_cost(&self, literal_lengths: &[u8], distance_lengths: &[u8]) -> u64 {
+        let mut cost = literal_lengths[END_OF_BLOCK] as u64;
+
+        for token in &self.tokens {
+            if token.dist == 0 {
+                cost += literal_lengths[token.value as usize] as u64;
+            } else {
+                let len_code = find_code(&LENGTH_BASE[..29], token.value as u32);
+                let dist_code = find_code(&DISTANCE_BASE[..DISTANCE_CODES], token.dist as u32);
+
+                cost += (literal_lengths[257 + len_code]
+                    + LENGTH_EXTRA[len_code]
+                    + distance_lengths[dist_code]
+                    + DISTANCE_EXTRA[dist_code]) as u64;
+            }
+        }
+
+        cost
+    }
+
+    fn write_stored(&mut self, last: bool) {
+        let block = &self.data[self.block_start..self.cursor];
+        let mut chunks = block.chunks(MAX_STORED_LEN).peekable();
+
+        //an empty block still needs its header
+        if chunks.peek().is_none() {
+            self.bits.write_bits(last as u32, 3);
+            self.bits.align_to_byte();
+            self.bits.write_bits(0, 16);
+            self.bits.write_bits(0xFFFF, 16);
+        }
+
+        while let Some(chunk) = chunks.next() {
+            let final_chunk = last && chunks.peek().is_none();
+
+            self.bits.write_bits(final_chunk as u32, 3);
+            self.bits.align_to_byte();
+            self.bits.write_bits(chunk.len() as u32, 16);
+            self.bits.write_bits(!chunk.len() as u32 & 0xFFFF, 16);
+            self.bits.out.extend_from_slice(chunk);
+        }
+    }
+
+    //writes the tokens as the cheapest of a stored, a fixed and a dynamic block
+    fn write_block(&mut self, last: bool) -> std::io::Result<()> {
+        let mut literal_freqs = vec![0u32; LITERAL_CODES];
+        let mut distance_freqs = vec![0u32; DISTANCE_CODES];
+
+        literal_freqs[END_OF_BLOCK] = 1;
+
+        for token in &self.tokens {
+            if token.dist == 0 {
+                literal_freqs[token.value as usize] += 1;
+            } else {
+                literal_freqs[257 + find_code(&LENGTH_BASE[..29], token.value as u32)] += 1;
+                distance_freqs[find_code(&DISTANCE_BASE[..DISTANCE_CODES], token.dist as u32)] += 1;
+            }
+        }
+
+        let literal_lengths = code_lengths(&literal_freqs, MAX_CODE_LEN);
+        let distance_lengths = code_lengths(&distance_freqs, MAX_CODE_LEN);
+
+        let literal_count = std::cmp::max(
+            257,
+            literal_lengths
+                .iter()
+                .rposition(|&len| len > 0)
+                .unwrap_or(0)
+                + 1,
+        );
+        let distance_count = distance_lengths
+            .iter()
+            .rposition(|&len| len > 0)
+            .unwrap_or(0)
+            + 1;
+
+        let mut all_lengths = literal_lengths[..literal_count].to_vec();
+        all_lengths.extend_from_slice(&distance_lengths[..distance_count]);
+        let encoded_lengths = encode_lengths(&all_lengths);
+
+        let mut code_length_freqs = vec![0u32; 19];
+        for (symbol, _) in &encoded_lengths {
+            code_length_freqs[*symbol as usize] += 1;
+        }
+
+        let code_length_lengths = code_lengths(&code_length_freqs, MAX_CODE_LENGTH_CODE_LEN);
+        let code_length_count = std::cmp::max(
+            4,
+            CODE_LENGTH_ORDER
+                .iter()
+                .rposition(|&symbol| code_length_lengths[symbol] > 0)
+                .unwrap_or(0)
+                + 1,
+        );
+
+        let header_cost = 14
+            + 3 * code_length_count as u64
+            + encoded_lengths
+                .iter()
+                .map(|(symbol, _)| {
+                    code_length_lengths[*symbol as usize] as u64
+                        + match symbol {
+                            16 => 2,
+                            17 => 3,
+                            18 => 7,
+                            _ => 0,
+                        }
+                })
+                .sum::<u64>();
+        let dynamic_cost = header_cost + self.tokens_cost(&literal_lengths, &distance_lengths);
+
+        let (fixed_literal_lengths, fixed_distance_lengths) = fixed_lengths();
+        let fixed_cost = self.tokens_cost(&fixed_literal_lengths, &fixed_distance_lengths);
+
+        let block_len = (self.cursor - self.block_start) as u64;
+        let stored_cost = (block_len + 5 * (block_len / MAX_STORED_LEN as u64 + 1)) * 8 + 7;
+
+        if self.max_chain == 0 || stored_cost <= std::cmp::min(dynamic_cost, fixed_cost) {
+            self.write_stored(last);
+        } else if fixed_cost <= dynamic_cost {
+            self.bits.write_bits(2 | last as u32, 3);
+            self.write_tokens(&fixed_literal_lengths, &fixed_distance_lengths);
+        } else {
+            self.bits.write_bits(4 | last as u32, 3);
+            self.bits.write_bits((literal_count - 257) as u32, 5);
+            self.bits.write_bits((distance_count - 1) as u32, 5);
+            self.bits.write_bits((code_length_count - 4) as u32, 4);
+
+            for symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+                self.bits.write_bits(code_length_lengths[*symbol] as u32, 3);
+            }
+
+            let code_length_codes = codes(&code_length_lengths);
+
+            for (symbol, extra) in &encoded_lengths {
+                let symbol = *symbol as usize;
+                self.bits.write_bits(
+                    code_length_codes[symbol] as u32,
+                    code_length_lengths[symbol] as u32,
+                );
+
+                match symbol {
+                    16 => self.bits.write_bits(*extra as u32, 2),
+                    17 => self.bits.write_bits(*extra as u32, 3),
+                    18 => self.bits.write_bits(*extra as u32, 7),
+                    _ => {}
+                }
+            }
+
+            self.write_tokens(&literal_lengths, &distance_lengths);
+        }
+
+        self.tokens.clear();
+        self.block_start = self.cursor;
+
+        self.writer.write_all(&self.bits.out)?;
+        self.bits.out.clear();
+
+        //only a window's worth of history is needed for matches
+        if self.cursor > WINDOW_SIZE + BLOCK_SIZE {
+            let drop = self.cursor - WINDOW_SIZE;
+
+            self.data.drain(..drop);
+            self.data_offset += drop as u64;
+            self.cursor -= drop;
+            self.block_start -= drop;
+        }
+
+        Ok(())
+    }
+
+    /// Compresses what is left, ends the stream and returns the inner writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.tokenize(0)?;
+
+        //the last block may end up empty when the one before filled up right at the end
+        self.write_block(true)?;
+
+        self.bits.align_to_byte();
+        self.writer.write_all(&self.bits.out)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for Deflater<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.data.extend_from_slice(buf);
+
+        //matches need to see ahead, so the last bytes wait for more input
+        if self.data.len() - self.cursor >= BLOCK_SIZE + MAX_MATCH {
+            self.tokenize(MAX_MATCH)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::packer::deflate::Deflater;
+    use crate::unpacker::algorithms::deflate::{Inflater, DEFLATE};
+
+    fn round_trip(data: &[u8], level: u32) -> usize {
+        let mut deflater = Deflater::new(Vec::new(), level);
+
+        //odd chunks, so that blocks don't line up with writes
+        for chunk in data.chunks(10_007) {
+            deflater.write_all(chunk).unwrap();
+        }
+
+        let compressed = deflater.finish().unwrap();
+
+        let mut out = Vec::new();
+        Inflater::new(&compressed[..], &mut out, &DEFLATE)
+            .inflate()
+            .unwrap();
+
+        assert_eq!(out, data);
+
+        compressed.len()
+    }
+
+    #[test]
+    fn compress_levels() {
+        let text = std::fs::read("test-data/sfx_archive/expected/text.txt").unwrap();
+        let random = std::fs::read("test-data/sfx_archive/expected/folder_1/random.bin").unwrap();
+        let runs =
+            std::fs::read("test-data/sfx_archive/expected/folder_1/folder_11/runs.bin").unwrap();
+
+        let mut mixed = Vec::new();
+        for _ in 0..4 {
+            mixed.extend_from_slice(&text);
+            mixed.extend_from_slice(&random);
+            mixed.extend_from_slice(&runs);
+        }
+
+        for level in [0, 1, 6, 9].iter() {
+            round_trip(b"", *level);
+            round_trip(b"a", *level);
+
+            let text_len = round_trip(&text, *level);
+            let random_len = round_trip(&random, *level);
+            let runs_len = round_trip(&runs, *level);
+            round_trip(&mixed, *level);
+
+            if *level > 0 {
+                assert!(text_len < text.len() / 2);
+                assert!(runs_len < runs.len() / 10);
+            }
+
+            //incompressible data costs little more than storing it
+            assert!(random_len < random.len() + random.len() / 100 + 16);
+        }
+    }
+}
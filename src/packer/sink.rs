@@ -0,0 +1,193 @@
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::zip::signatures;
+
+//written instead of the split signature when everything fit in one volume
+const SIGNATURE_SINGLE_VOLUME: u32 = 0x30304b50;
+
+/// Smallest volume size accepted for split archives.
+pub const MIN_VOLUME_SIZE: u64 = 64 * 1024;
+
+pub trait ArchiveSink: Write + Seek {
+    /// Makes sure the next `len` bytes written at the end of the stream land on one volume.
+    fn keep_together(&mut self, _len: u64) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Volume number of a position in the stream and the offset within that volume.
+    fn volume_position(&self, pos: u64) -> (u32, u64) {
+        (0, pos)
+    }
+}
+
+impl<W: Write + Seek> ArchiveSink for BufWriter<W> {}
+
+/// Writes one stream over volumes of at most `volume_size` bytes (`.z01`, `.z02`, ..., `.zip`).
+pub struct SplitWriter {
+    path: PathBuf,
+    volume_size: u64,
+    volumes: Vec<BufWriter<File>>,
+    //start of every volume in the stream
+    starts: Vec<u64>,
+    len: u64,
+    position: u64,
+    current: usize,
+    //the current volume isn't at `position` anymore
+    moved: bool,
+}
+
+impl SplitWriter {
+    fn volume_path(&self, disk: usize) -> PathBuf {
+        self.path.with_extension(format!("z{:02}", disk + 1))
+    }
+
+    /// Creates the first volume; the last one is renamed to `path` on `finish`.
+    pub fn create(path: &Path, volume_size: u64) -> std::io::Result<SplitWriter> {
+        if volume_size < MIN_VOLUME_SIZE {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Volumes must be at least {} bytes.", MIN_VOLUME_SIZE),
+            ));
+        }
+
+        let mut split_writer = SplitWriter {
+            path: path.to_owned(),
+            volume_size,
+            volumes: Vec::new(),
+            starts: Vec::new(),
+            len: 0,
+            position: 0,
+            current: 0,
+            moved: false,
+        };
+
+        split_writer.start_volume()?;
+        split_writer.write_all(&signatures::SIGNATURE_SPLIT_ARCHIVE.to_le_bytes())?;
+
+        Ok(split_writer)
+    }
+
+    fn start_volume(&mut self) -> std::io::Result<()> {
+        if self.volumes.len() > u32::MAX as usize {
+            return Err(std::io::Error::other("Too many volumes."));
+        }
+
+        let volume = File::create(self.volume_path(self.volumes.len()))?;
+
+        self.volumes.push(BufWriter::new(volume));
+        self.starts.push(self.len);
+        self.current = self.volumes.len() - 1;
+        self.moved = false;
+
+        Ok(())
+    }
+
+    fn volume_at(&self, pos: u64) -> usize {
+        self.starts.partition_point(|&start| start <= pos) - 1
+    }
+
+    /// Flushes the volumes and gives the last one the name of the archive.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        //a single volume is a plain archive, only marked as having been written for splitting
+        if self.volumes.len() == 1 {
+            self.seek(SeekFrom::Start(0))?;
+            self.write_all(&SIGNATURE_SINGLE_VOLUME.to_le_bytes())?;
+        }
+
+        self.flush()?;
+
+        std::fs::rename(self.volume_path(self.volumes.len() - 1), &self.path)
+    }
+}
+
+impl Write for SplitWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.position > self.len {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Can't write past the end of a split archive.",
+            ));
+        }
+
+        let mut volume = self.volume_at(self.position);
+
+        let mut end = match self.starts.get(volume + 1) {
+            Some(&next_start) => next_start,
+            None => self.starts[volume] + self.volume_size,
+        };
+
+        if self.position == end && volume == self.volumes.len() - 1 {
+            self.start_volume()?;
+            volume += 1;
+            end = self.position + self.volume_size;
+        }
+
+        if self.moved || volume != self.current {
+            self.volumes[volume].seek(SeekFrom::Start(self.position - self.starts[volume]))?;
+            self.current = volume;
+            self.moved = false;
+        }
+
+        //writes stop at the end of the volume; the next write goes on with the next one
+        let len = std::cmp::min(buf.len() as u64, end - self.position) as usize;
+        let written = self.volumes[volume].write(&buf[..len])?;
+
+        self.position += written as u64;
+        self.len = std::cmp::max(self.len, self.position);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        for volume in &mut self.volumes {
+            volume.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Seek for SplitWriter {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        }
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Invalid seek position."))?;
+
+        if new_position != self.position {
+            self.position = new_position;
+            self.moved = true;
+        }
+
+        Ok(self.position)
+    }
+}
+
+impl ArchiveSink for SplitWriter {
+    fn keep_together(&mut self, len: u64) -> std::io::Result<()> {
+        if len > self.volume_size {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Record is larger than a volume.",
+            ));
+        }
+
+        if self.position == self.len
+            && self.position - self.starts[self.starts.len() - 1] + len > self.volume_size
+        {
+            self.start_volume()?;
+        }
+
+        Ok(())
+    }
+
+    fn volume_position(&self, pos: u64) -> (u32, u64) {
+        let volume = self.volume_at(pos);
+
+        (volume as u32, pos - self.starts[volume])
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Cursor, ErrorKind, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::crypto::winzip_aes::AesExtraField;
+use crate::packer::{self, RawEntry, ZipWriter};
+use crate::stream_utils::byte_readers;
+use crate::unpacker::source::{ArchiveSource, MappedSource};
+use crate::unpacker::{self, UnpackOptions};
+use crate::zip::structs::{CentralDirFileHeader, LocalFileHeader};
+use crate::zip::{extra_fields, general_bit_flags, signatures};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// A local file header that doesn't lead to a valid entry.
+#[derive(Debug)]
+pub struct DamagedEntry {
+    /// Position of the local file header.
+    pub pos: u64,
+    pub name: String,
+    pub reason: String,
+}
+
+/// What `repair_archive` salvaged.
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    /// Whether the central directory of the damaged archive could be read at all.
+    pub central_dir_found: bool,
+    /// Entries written to the repaired archive, in the order they were found.
+    pub recovered: Vec<String>,
+    /// Recovered entries the central directory doesn't list.
+    pub orphans: Vec<String>,
+    /// Entries the central directory lists but which couldn't be recovered.
+    pub lost: Vec<String>,
+    pub damaged: Vec<DamagedEntry>,
+}
+
+struct Validator {
+    options: UnpackOptions,
+    password: Option<Vec<u8>>,
+    null_file: File,
+    buffer: Vec<u8>,
+}
+
+fn find_signature(bytes: &[u8], from: usize, signature: u32) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(4)
+        .position(|window| window == signature.to_le_bytes())
+        .map(|pos| from + pos)
+}
+
+fn has_signature(bytes: &[u8], pos: usize, signature: u32) -> bool {
+    bytes.get(pos..pos + 4) == Some(&signature.to_le_bytes()[..])
+}
+
+//data descriptors that could end the data starting at `data_pos`, nearest first,
+//as (CRC-32, compressed size, uncompressed size)
+fn find_data_descriptors(
+    bytes: &[u8],
+    data_pos: usize,
+    size_len: usize,
+) -> impl Iterator<Item = (u32, u64, u64)> + '_ {
+    let fields_len = 4 + 2 * size_len;
+
+    (data_pos..bytes.len()).filter_map(move |pos| {
+        //the signature is optional; without it the descriptor is right in front of the next record
+        let (data_end, mut offset) =
+            if has_signature(bytes, pos, signatures::SIGNATURE_DATA_DESCRIPTOR) {
+                (pos, pos + 4)
+            } else if (has_signature(bytes, pos, signatures::SIGNATURE_FILE_HEADER)
+                || has_signature(bytes, pos, signatures::SIGNATURE_CENTRAL_DIR_HEADER))
+                && pos >= data_pos + fields_len
+            {
+                (pos - fields_len, pos - fields_len)
+            } else {
+                return None;
+            };
+
+        if offset + fields_len > bytes.len() {
+            return None;
+        }
+
+        let crc_32 = byte_readers::read_to::<u32>(bytes, &mut offset).ok()?;
+        let (compressed_size, uncompressed_size) = if size_len == 8 {
+            (
+                byte_readers::read_to::<u64>(bytes, &mut offset).ok()?,
+                byte_readers::read_to::<u64>(bytes, &mut offset).ok()?,
+            )
+        } else {
+            (
+                byte_readers::read_to::<u32>(bytes, &mut offset).ok()? as u64,
+                byte_readers::read_to::<u32>(bytes, &mut offset).ok()? as u64,
+            )
+        };
+
+        (compressed_size == (data_end - data_pos) as u64).then_some((
+            crc_32,
+            compressed_size,
+            uncompressed_size,
+        ))
+    })
+}
+
+//the central dir file header of an entry found only by its local file header
+fn central_dir_file_header(
+    local_file_header: &LocalFileHeader,
+    pos: u64,
+    (crc_32, compressed_size, uncompressed_size): (u32, u64, u64),
+) -> CentralDirFileHeader {
+    CentralDirFileHeader {
+        version_made_by: packer::VERSION_MADE_BY,
+        version_to_extract: local_file_header.version_to_extract,
+        general_bit_flag: local_file_header.general_bit_flag,
+        compression_method: local_file_header.compression_method,
+        last_mod_file_time: local_file_header.last_mod_file_time,
+        last_mod_file_date: local_file_header.last_mod_file_date,
+        crc_32,
+        compressed_size,
+        uncompressed_size,
+        file_name_length: local_file_header.file_name_length,
+        extra_field_length: local_file_header.extra_field_length,
+        file_comment_length: 0,
+        disk_number_start: 0,
+        internal_file_attribs: 0,
+        external_file_attribs: if unpacker::is_folder(local_file_header) {
+            packer::DOS_DIRECTORY_ATTRIB
+        } else {
+            0
+        },
+        local_header_rel_offset: pos,
+        file_name: local_file_header.file_name.clone(),
+        extra_field: local_file_header.extra_field.clone(),
+        file_comment: String::new(),
+    }
+}
+
+impl Validator {
+    /// Decompresses the entry `header` describes and checks it against its CRC-32.
+    /// Returns the bytes it takes in front of the central directory.
+    fn validate<S: ArchiveSource>(
+        &mut self,
+        source: &mut S,
+        stream_len: u64,
+        header: &CentralDirFileHeader,
+    ) -> std::io::Result<u64> {
+        let raw_entry = RawEntry::read(source, header)?;
+        let len = raw_entry.local_len();
+        let data_pos = raw_entry.data_pos;
+
+        if header.local_header_rel_offset + len > stream_len {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Entry is truncated.",
+            ));
+        }
+
+        let mut local_file_header = raw_entry.local_file_header;
+        local_file_header.crc_32 = header.crc_32;
+        local_file_header.compressed_size = header.compressed_size;
+        local_file_header.uncompressed_size = header.uncompressed_size;
+
+        if unpacker::is_folder(&local_file_header) {
+            return if header.compressed_size == 0 {
+                Ok(len)
+            } else {
+                Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Folder has data.",
+                ))
+            };
+        }
+
+        source.seek(SeekFrom::Start(data_pos))?;
+
+        let crc = unpacker::unpack_entry_data(
+            source,
+            &local_file_header,
+            &mut self.null_file,
+            &mut self.buffer,
+            &mut self.password,
+            &self.options,
+        )?;
+
+        //entries with a data descriptor are not checked by the unpacker
+        let crc_used = AesExtraField::from_extra_field(&local_file_header.extra_field)
+            .is_none_or(|aes_extra_field| aes_extra_field.crc_used());
+
+        if local_file_header.general_bit_flag & general_bit_flags::DATA_DESCRIPTOR != 0
+            && crc_used
+            && crc != header.crc_32
+        {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("CRC-32 mismatch in {}.", header.file_name),
+            ));
+        }
+
+        Ok(len)
+    }
+}
+
+//tries what the central dir says about the entry at `pos` or else every data descriptor that fits
+fn recover_entry<S: ArchiveSource>(
+    source: &mut S,
+    bytes: &[u8],
+    pos: u64,
+    local_file_header: &LocalFileHeader,
+    listed: Option<&CentralDirFileHeader>,
+    validator: &mut Validator,
+) -> std::io::Result<(CentralDirFileHeader, u64)> {
+    let stream_len = bytes.len() as u64;
+
+    let candidates: Box<dyn Iterator<Item = CentralDirFileHeader>> = match listed {
+        Some(header) => Box::new(std::iter::once(header.clone())),
+        None if local_file_header.general_bit_flag & general_bit_flags::DATA_DESCRIPTOR != 0 => {
+            let data_pos = pos as usize
+                + 30
+                + local_file_header.file_name.len()
+                + local_file_header.extra_field.len();
+            let size_len = if extra_fields::find(
+                &local_file_header.extra_field,
+                extra_fields::ZIP64,
+            )
+            .is_some()
+            {
+                8
+            } else {
+                4
+            };
+
+            Box::new(
+                find_data_descriptors(bytes, data_pos, size_len)
+                    .map(move |totals| central_dir_file_header(local_file_header, pos, totals)),
+            )
+        }
+        None => Box::new(std::iter::once(central_dir_file_header(
+            local_file_header,
+            pos,
+            (
+                local_file_header.crc_32,
+                local_file_header.compressed_size,
+                local_file_header.uncompressed_size,
+            ),
+        ))),
+    };
+
+    let mut last_err = std::io::Error::new(ErrorKind::InvalidData, "No data descriptor found.");
+
+    for header in candidates {
+        match validator.validate(source, stream_len, &header) {
+            Ok(len) => return Ok((header, len)),
+            Err(err) => last_err = err,
+        }
+    }
+
+    Err(last_err)
+}
+
+/// Scans `src_file` for local file headers and copies every entry whose data checks out into `dst_file`,
+/// with a new central directory. The central directory of `src_file` is only used when it can be read.
+pub fn repair_archive(
+    src_file: &Path,
+    dst_file: &Path,
+    options: &UnpackOptions,
+) -> std::io::Result<RepairReport> {
+    if src_file.canonicalize().ok() == dst_file.canonicalize().ok() {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "Repaired archive would overwrite the damaged one.",
+        ));
+    }
+
+    let mapped = MappedSource::open(src_file)?;
+    let bytes = mapped.mapped_bytes().unwrap_or_default();
+    //read through the same mapping that is scanned
+    let mut source = Cursor::new(bytes);
+
+    let mut report = RepairReport::default();
+    let mut comment = String::new();
+    //central dir file headers by the position of their local file header
+    let mut listed = HashMap::new();
+
+    if let Ok((end_of_central_dir, central_dir_file_headers)) =
+        unpacker::read_central_dir(&mut source)
+    {
+        report.central_dir_found = true;
+        comment = end_of_central_dir.zip_file_comment;
+        listed = central_dir_file_headers
+            .into_iter()
+            .map(|header| (header.local_header_rel_offset, header))
+            .collect();
+    }
+
+    let mut validator = Validator {
+        options: UnpackOptions {
+            verify_checksums: true,
+            password: None,
+            ..*options
+        },
+        password: options.password.clone(),
+        null_file: File::create(unpacker::NULL_DEVICE)?,
+        buffer: vec![0u8; BUFFER_SIZE],
+    };
+
+    let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(dst_file)?));
+    zip_writer.set_comment(comment);
+
+    let mut recovered_positions = HashSet::new();
+    let mut next_pos = 0;
+
+    while let Some(pos) = find_signature(bytes, next_pos, signatures::SIGNATURE_FILE_HEADER) {
+        //what can't be read as a header is taken for garbage, the scan goes on right after it
+        next_pos = pos + 1;
+
+        source.seek(SeekFrom::Start(pos as u64 + 4))?;
+
+        let local_file_header = match unpacker::read_local_file_header(&mut source) {
+            Ok(local_file_header) => local_file_header,
+            Err(_) => continue,
+        };
+
+        let listed_header = listed.get(&(pos as u64));
+
+        match recover_entry(
+            &mut source,
+            bytes,
+            pos as u64,
+            &local_file_header,
+            listed_header,
+            &mut validator,
+        ) {
+            Ok((header, len)) => {
+                zip_writer.copy_raw_entry(&mut source, &header)?;
+
+                if report.central_dir_found && listed_header.is_none() {
+                    report.orphans.push(header.file_name.clone());
+                }
+
+                report.recovered.push(header.file_name);
+                recovered_positions.insert(pos as u64);
+                next_pos = pos + len as usize;
+            }
+            Err(err) => report.damaged.push(DamagedEntry {
+                pos: pos as u64,
+                name: local_file_header.file_name,
+                reason: err.to_string(),
+            }),
+        }
+    }
+
+    zip_writer.finish()?;
+
+    let mut lost = listed
+        .into_iter()
+        .filter(|(pos, _)| !recovered_positions.contains(pos))
+        .collect::<Vec<_>>();
+    lost.sort_by_key(|(pos, _)| *pos);
+    report.lost = lost
+        .into_iter()
+        .map(|(_, header)| header.file_name)
+        .collect();
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::unpacker::{self, UnpackOptions};
+
+    #[test]
+    fn repair_truncated_archive() {
+        let test_dir = Path::new("test-data/repair_archive");
+        let zip = test_dir.join("actual_truncated.zip");
+        let out_folder = test_dir.join("actual_truncated");
+
+        let report = super::repair_archive(
+            &test_dir.join("truncated.zip"),
+            &zip,
+            &UnpackOptions::default(),
+        )
+        .unwrap();
+
+        assert!(!report.central_dir_found);
+        assert_eq!(
+            report.recovered,
+            vec![
+                "short.txt",
+                "folder_1/",
+                "folder_1/empty.txt",
+                "folder_1/folder_11/",
+                "folder_1/folder_11/runs.bin",
+            ]
+        );
+        assert!(report.orphans.is_empty());
+
+        //the garbage in between doesn't even look like a header, only the damaged entries are reported
+        let damaged = report
+            .damaged
+            .iter()
+            .map(|damaged| damaged.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(damaged, vec!["text.txt", "folder_1/random.bin"]);
+        assert!(report.damaged[0].reason.contains("CRC-32"));
+
+        if out_folder.exists() {
+            std::fs::remove_dir_all(&out_folder).unwrap();
+        }
+
+        std::fs::create_dir(&out_folder).unwrap();
+
+        unpacker::unpack_archive(&zip, &out_folder, &UnpackOptions::default()).unwrap();
+
+        assert!(!dir_diff::is_different(&out_folder, test_dir.join("expected_truncated")).unwrap());
+
+        std::fs::remove_dir_all(&out_folder).unwrap();
+        std::fs::remove_file(&zip).unwrap();
+    }
+
+    #[test]
+    fn repair_orphans_with_data_descriptors() {
+        let test_dir = Path::new("test-data/repair_archive");
+        let zip = test_dir.join("actual_orphan.zip");
+
+        let report = super::repair_archive(
+            &test_dir.join("orphan.zip"),
+            &zip,
+            &UnpackOptions::default(),
+        )
+        .unwrap();
+
+        assert!(report.central_dir_found);
+        assert_eq!(report.recovered.len(), 7);
+        assert_eq!(report.orphans, vec!["text.txt"]);
+        assert!(report.lost.is_empty());
+        assert!(report.damaged.is_empty());
+
+        let names = unpacker::list_archive(&zip)
+            .unwrap()
+            .into_iter()
+            .map(|header| header.file_name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, report.recovered);
+        assert_eq!(
+            unpacker::test_archive(&zip, &UnpackOptions::default()).unwrap(),
+            5
+        );
+
+        std::fs::remove_file(&zip).unwrap();
+    }
+}
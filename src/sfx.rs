@@ -0,0 +1,328 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::packer::{self, PackOptions, ZipWriter};
+use crate::unpacker;
+use crate::zip::extra_fields;
+
+//ends the block between the executable and the archive of a self-extractor
+const SELF_EXTRACTOR_MAGIC: &[u8; 8] = b"SPZSFX01";
+
+/// What sits in front of an archive, such as a self-extractor stub or an installer script.
+#[derive(Debug, PartialEq)]
+pub struct Prefix {
+    /// Bytes in front of the first entry.
+    pub len: u64,
+    /// How far the offsets in the central directory are off; 0 when they account for the prefix.
+    pub shift: u64,
+}
+
+pub fn find_prefix(path: &Path) -> std::io::Result<Prefix> {
+    let mut source = BufReader::new(File::open(path)?);
+
+    let shift = unpacker::locate_central_dir(&mut source)?.shift;
+    let len = unpacker::first_entry_pos(&mut source)?;
+
+    Ok(Prefix { len, shift })
+}
+
+/// Copies the archive without what is in front of it into `dst_file`.
+pub fn strip_prefix(src_file: &Path, dst_file: &Path) -> std::io::Result<()> {
+    let mut source = BufReader::new(File::open(src_file)?);
+    let (end_of_central_dir, central_dir_file_headers) = unpacker::read_central_dir(&mut source)?;
+
+    let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(dst_file)?));
+    zip_writer.set_comment(end_of_central_dir.zip_file_comment);
+
+    for header in &central_dir_file_headers {
+        zip_writer.copy_raw_entry(&mut source, header)?;
+    }
+
+    zip_writer.finish()?;
+
+    Ok(())
+}
+
+/// Rewrites the central directory offsets so that they count the prefix, as `zip -A` does.
+/// Returns by how much they were off.
+pub fn fix_offsets(path: &Path) -> std::io::Result<u64> {
+    let mut source = BufReader::new(File::open(path)?);
+    let location = unpacker::locate_central_dir(&mut source)?;
+
+    if location.shift == 0 {
+        return Ok(0);
+    }
+
+    let (mut end_of_central_dir, central_dir_file_headers) =
+        unpacker::read_central_dir(&mut source)?;
+
+    //same sizes as before, so the records are overwritten where they are
+    let mut records = Vec::new();
+
+    for header in &central_dir_file_headers {
+        if header.local_header_rel_offset >= extra_fields::ZIP64_MARKER {
+            return Err(std::io::Error::other(
+                "ZIP64 offsets can't be rewritten yet.",
+            ));
+        }
+
+        records.extend(header.to_bytes());
+    }
+
+    if location.zip64_end_pos.is_some() || location.central_dir_pos >= extra_fields::ZIP64_MARKER {
+        return Err(std::io::Error::other(
+            "ZIP64 offsets can't be rewritten yet.",
+        ));
+    }
+
+    end_of_central_dir.central_dir_offset_from_starting_disk_num = location.central_dir_pos;
+
+    if location.central_dir_pos + records.len() as u64 != location.end_pos {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "Central dir size doesn't match its records.",
+        ));
+    }
+
+    records.extend(end_of_central_dir.to_bytes());
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(location.central_dir_pos))?;
+    file.write_all(&records)?;
+    file.sync_all()?;
+
+    Ok(location.shift)
+}
+
+/// What `build_self_extractor` put in front of the archive.
+#[derive(Debug, PartialEq)]
+pub struct SelfExtractor {
+    /// Length of the executable the archive was appended to.
+    pub stub_len: u64,
+    /// Shell command to run in the output dir once everything is extracted.
+    pub run_command: Option<String>,
+}
+
+/// Finds out whether `path` is a self-extractor made by `build_self_extractor`.
+pub fn find_self_extractor(path: &Path) -> std::io::Result<Option<SelfExtractor>> {
+    let mut source = BufReader::new(File::open(path)?);
+
+    //the block is the command, its length and the magic, right in front of the first entry
+    let block_end = match unpacker::first_entry_pos(&mut source) {
+        Ok(pos) if pos >= 12 => pos,
+        _ => return Ok(None),
+    };
+
+    let mut tail = [0u8; 12];
+    source.seek(SeekFrom::Start(block_end - 12))?;
+    source.read_exact(&mut tail)?;
+
+    if &tail[4..] != SELF_EXTRACTOR_MAGIC {
+        return Ok(None);
+    }
+
+    let command_len = u32::from_le_bytes([tail[0], tail[1], tail[2], tail[3]]) as u64;
+    let stub_len = (block_end - 12)
+        .checked_sub(command_len)
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidData, "Bad self-extractor block."))?;
+
+    let mut command = vec![0u8; command_len as usize];
+    source.seek(SeekFrom::Start(stub_len))?;
+    source.read_exact(&mut command)?;
+
+    let run_command = String::from_utf8(command)
+        .map_err(|_| std::io::Error::new(ErrorKind::InvalidData, "Bad self-extractor command."))?;
+
+    Ok(Some(SelfExtractor {
+        stub_len,
+        run_command: Some(run_command).filter(|command| !command.is_empty()),
+    }))
+}
+
+/// Writes `stub` followed by the entries of `src_file` into `dst_file`, with offsets counting the stub.
+/// When `stub` is a self-extractor already, only its executable part is taken.
+pub fn build_self_extractor(
+    stub: &Path,
+    src_file: &Path,
+    dst_file: &Path,
+    run_command: Option<&str>,
+) -> std::io::Result<()> {
+    if [stub, src_file]
+        .iter()
+        .any(|path| path.canonicalize().ok() == dst_file.canonicalize().ok())
+    {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            "Self-extractor would overwrite its own input.",
+        ));
+    }
+
+    let stub_len = match find_self_extractor(stub)? {
+        Some(self_extractor) => self_extractor.stub_len,
+        None => std::fs::metadata(stub)?.len(),
+    };
+
+    let mut source = BufReader::new(File::open(src_file)?);
+    let (end_of_central_dir, central_dir_file_headers) = unpacker::read_central_dir(&mut source)?;
+
+    let mut writer = BufWriter::new(File::create(dst_file)?);
+
+    if std::io::copy(&mut File::open(stub)?.take(stub_len), &mut writer)? < stub_len {
+        return Err(std::io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "Stub is truncated.",
+        ));
+    }
+
+    let command = run_command.unwrap_or_default().as_bytes();
+    writer.write_all(command)?;
+    writer.write_all(&(command.len() as u32).to_le_bytes())?;
+    writer.write_all(SELF_EXTRACTOR_MAGIC)?;
+
+    let mut zip_writer = ZipWriter::new(writer);
+    zip_writer.set_comment(end_of_central_dir.zip_file_comment);
+
+    for header in &central_dir_file_headers {
+        zip_writer.copy_raw_entry(&mut source, header)?;
+    }
+
+    let dst = zip_writer
+        .finish()?
+        .into_inner()
+        .map_err(|err| err.into_error())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        dst.set_permissions(std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    dst.sync_all()
+}
+
+/// Packs `src_dir` and builds a self-extractor of the result as `build_self_extractor` does.
+pub fn build_self_extractor_from_dir(
+    stub: &Path,
+    src_dir: &Path,
+    dst_file: &Path,
+    pack_options: &PackOptions,
+    run_command: Option<&str>,
+) -> std::io::Result<()> {
+    packer::pack_dir_to_temp(src_dir, dst_file, "sfx", pack_options, |temp_path| {
+        build_self_extractor(stub, temp_path, dst_file, run_command)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::sfx::{Prefix, SelfExtractor};
+    use crate::test_utils;
+    use crate::unpacker::UnpackOptions;
+
+    fn unpack_and_compare(zip: &Path) {
+        test_utils::unpack_and_compare(
+            zip,
+            &zip.with_extension(""),
+            Path::new("test-data/sfx_archive/expected"),
+            &UnpackOptions::default(),
+        );
+
+        std::fs::remove_file(zip).unwrap();
+    }
+
+    #[test]
+    fn find_prefix() {
+        assert_eq!(
+            super::find_prefix(Path::new("test-data/sfx_archive/input.zip")).unwrap(),
+            Prefix { len: 78, shift: 78 }
+        );
+        assert_eq!(
+            super::find_prefix(Path::new("test-data/sfx_archive/input_adjusted.zip")).unwrap(),
+            Prefix { len: 78, shift: 0 }
+        );
+        assert_eq!(
+            super::find_prefix(Path::new("test-data/merge_archives/first.zip")).unwrap(),
+            Prefix { len: 0, shift: 0 }
+        );
+    }
+
+    #[test]
+    fn strip_prefix() {
+        let zip = Path::new("test-data/sfx_archive/actual_stripped.zip");
+
+        super::strip_prefix(Path::new("test-data/sfx_archive/input.zip"), zip).unwrap();
+
+        assert_eq!(
+            super::find_prefix(zip).unwrap(),
+            Prefix { len: 0, shift: 0 }
+        );
+        unpack_and_compare(zip);
+    }
+
+    #[test]
+    fn fix_offsets() {
+        let zip = Path::new("test-data/sfx_archive/actual_fixed.zip");
+
+        std::fs::copy("test-data/sfx_archive/input.zip", zip).unwrap();
+
+        assert_eq!(super::fix_offsets(zip).unwrap(), 78);
+        assert_eq!(super::fix_offsets(zip).unwrap(), 0);
+        assert_eq!(
+            std::fs::read(zip).unwrap(),
+            std::fs::read("test-data/sfx_archive/input_adjusted.zip").unwrap()
+        );
+
+        unpack_and_compare(zip);
+    }
+
+    #[test]
+    fn build_self_extractor() {
+        let stub = Path::new("test-data/streams/streams_0.txt");
+        let zip = Path::new("test-data/sfx_archive/actual_sfx.run");
+        let rebuilt = Path::new("test-data/sfx_archive/actual_sfx_rebuilt.zip");
+
+        assert_eq!(super::find_self_extractor(stub).unwrap(), None);
+        assert_eq!(
+            super::find_self_extractor(Path::new("test-data/sfx_archive/input.zip")).unwrap(),
+            None
+        );
+
+        super::build_self_extractor(
+            stub,
+            Path::new("test-data/merge_archives/first.zip"),
+            zip,
+            Some("./setup.sh --quiet"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            super::find_self_extractor(zip).unwrap(),
+            Some(SelfExtractor {
+                stub_len: 8,
+                run_command: Some("./setup.sh --quiet".to_owned()),
+            })
+        );
+        assert_eq!(
+            super::find_prefix(zip).unwrap(),
+            Prefix { len: 38, shift: 0 }
+        );
+
+        //only the executable part of a self-extractor is reused
+        super::build_self_extractor(zip, zip, rebuilt, None).unwrap();
+
+        assert_eq!(
+            super::find_self_extractor(rebuilt).unwrap(),
+            Some(SelfExtractor {
+                stub_len: 8,
+                run_command: None,
+            })
+        );
+
+        std::fs::remove_file(zip).unwrap();
+        unpack_and_compare(rebuilt);
+    }
+}
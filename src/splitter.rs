@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::packer::{self, PackOptions, RawEntry, ZipWriter};
+use crate::unpacker;
+use crate::zip::structs::CentralDirFileHeader;
+
+const END_OF_CENTRAL_DIR_LEN: u64 = 22;
+
+/// How entries are spread over the parts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Packing {
+    /// Entries keep their order, a new part is started when the next one doesn't fit.
+    InOrder,
+    /// Every entry goes to the first part that still has room for it.
+    Greedy,
+}
+
+#[derive(Debug)]
+pub struct SplitOptions {
+    /// Largest size of a part in bytes.
+    pub part_size: u64,
+    pub packing: Packing,
+    /// Where to list which part holds which entry.
+    pub manifest: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct Part {
+    entries: Vec<usize>,
+    names: HashSet<String>,
+    len: u64,
+}
+
+impl Part {
+    //bytes the entries in `needed` would add, leaving out the folders already there
+    fn added_len(&self, needed: &[usize], entries: &[(CentralDirFileHeader, u64)]) -> u64 {
+        needed
+            .iter()
+            .filter(|&&index| !self.names.contains(&entries[index].0.file_name))
+            .map(|&index| entries[index].1)
+            .sum()
+    }
+
+    fn add(&mut self, needed: &[usize], entries: &[(CentralDirFileHeader, u64)]) {
+        self.len += self.added_len(needed, entries);
+
+        for &index in needed {
+            if self.names.insert(entries[index].0.file_name.clone()) {
+                self.entries.push(index);
+            }
+        }
+    }
+}
+
+/// `archive.zip` becomes `archive_1.zip`, `archive_2.zip`, ...
+fn part_path(dst_file: &Path, number: usize) -> PathBuf {
+    let stem = dst_file.file_stem().unwrap_or_default().to_string_lossy();
+
+    match dst_file.extension() {
+        Some(extension) => dst_file.with_file_name(format!(
+            "{}_{}.{}",
+            stem,
+            number,
+            extension.to_string_lossy()
+        )),
+        None => dst_file.with_file_name(format!("{}_{}", stem, number)),
+    }
+}
+
+//folders that exist as entries, outermost first
+fn folders_of(name: &str, positions: &HashMap<&str, usize>) -> Vec<usize> {
+    name.trim_end_matches('/')
+        .match_indices('/')
+        .filter_map(|(pos, _)| positions.get(&name[..=pos]).copied())
+        .collect()
+}
+
+/// Copies the entries of `src_file` into independent archives of at most `part_size` bytes each,
+/// without decompressing them. Every part also gets the folders of its files.
+/// Returns the paths of the parts, named after `dst_file` with a number appended.
+pub fn split_archive(
+    src_file: &Path,
+    dst_file: &Path,
+    options: &SplitOptions,
+) -> std::io::Result<Vec<PathBuf>> {
+    let mut source = BufReader::new(File::open(src_file)?);
+    let (end_of_central_dir, central_dir_file_headers) = unpacker::read_central_dir(&mut source)?;
+
+    let mut entries = Vec::with_capacity(central_dir_file_headers.len());
+
+    for header in central_dir_file_headers {
+        let len =
+            RawEntry::read(&mut source, &header)?.local_len() + header.to_bytes().len() as u64;
+        entries.push((header, len));
+    }
+
+    let positions = entries
+        .iter()
+        .enumerate()
+        .map(|(index, (header, _))| (header.file_name.as_str(), index))
+        .collect::<HashMap<_, _>>();
+
+    //folders holding files are only written along with those files
+    let filled_folders = entries
+        .iter()
+        .filter(|(header, _)| !header.file_name.ends_with('/'))
+        .flat_map(|(header, _)| folders_of(&header.file_name, &positions))
+        .collect::<HashSet<_>>();
+
+    let room = options
+        .part_size
+        .saturating_sub(END_OF_CENTRAL_DIR_LEN + end_of_central_dir.zip_file_comment.len() as u64);
+    let mut parts: Vec<Part> = Vec::new();
+
+    for (index, (header, _)) in entries.iter().enumerate() {
+        if filled_folders.contains(&index) {
+            continue;
+        }
+
+        let mut needed = folders_of(&header.file_name, &positions);
+        needed.push(index);
+
+        if Part::default().added_len(&needed, &entries) > room {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} doesn't fit in a part of {} bytes.",
+                    header.file_name, options.part_size
+                ),
+            ));
+        }
+
+        let fits = |part: &&mut Part| part.len + part.added_len(&needed, &entries) <= room;
+
+        let part = match options.packing {
+            Packing::InOrder => parts.last_mut().filter(fits),
+            Packing::Greedy => parts.iter_mut().find(fits),
+        };
+
+        match part {
+            Some(part) => part.add(&needed, &entries),
+            None => {
+                let mut part = Part::default();
+                part.add(&needed, &entries);
+                parts.push(part);
+            }
+        }
+    }
+
+    if parts.is_empty() {
+        parts.push(Part::default());
+    }
+
+    let mut part_paths = Vec::with_capacity(parts.len());
+    let mut manifest = String::new();
+
+    for (number, part) in parts.iter().enumerate() {
+        let part_path = part_path(dst_file, number + 1);
+        let part_name = part_path.file_name().unwrap_or_default().to_string_lossy();
+
+        let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(&part_path)?));
+        zip_writer.set_comment(end_of_central_dir.zip_file_comment.clone());
+
+        for &index in &part.entries {
+            zip_writer.copy_raw_entry(&mut source, &entries[index].0)?;
+            manifest.push_str(&format!("{}\t{}\n", part_name, entries[index].0.file_name));
+        }
+
+        zip_writer.finish()?;
+        part_paths.push(part_path);
+    }
+
+    if let Some(manifest_path) = &options.manifest {
+        let mut manifest_file = File::create(manifest_path)?;
+        manifest_file.write_all(manifest.as_bytes())?;
+    }
+
+    Ok(part_paths)
+}
+
+/// Packs `src_dir` and splits the result as `split_archive` does.
+pub fn split_dir(
+    src_dir: &Path,
+    dst_file: &Path,
+    pack_options: &PackOptions,
+    options: &SplitOptions,
+) -> std::io::Result<Vec<PathBuf>> {
+    packer::pack_dir_to_temp(src_dir, dst_file, "split", pack_options, |temp_path| {
+        split_archive(temp_path, dst_file, options)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::merger::{self, MergeOptions};
+    use crate::packer::PackOptions;
+    use crate::splitter::{Packing, SplitOptions};
+    use crate::unpacker::{self, UnpackOptions};
+
+    //joins the parts again, which also checks that each of them is a valid archive
+    fn join_and_compare(
+        actual_dir: &str,
+        parts: &[PathBuf],
+        expected_dir: &Path,
+        options: &UnpackOptions,
+    ) {
+        let out_folder = Path::new("test-data/split_archive").join(actual_dir);
+        let zip = out_folder.with_extension("zip");
+
+        merger::merge_archives(parts, &zip, &MergeOptions::default()).unwrap();
+
+        if out_folder.exists() {
+            std::fs::remove_dir_all(&out_folder).unwrap();
+        }
+
+        std::fs::create_dir(&out_folder).unwrap();
+
+        unpacker::unpack_archive(&zip, &out_folder, options).unwrap();
+
+        assert!(!dir_diff::is_different(&out_folder, expected_dir).unwrap());
+
+        std::fs::remove_dir_all(&out_folder).unwrap();
+        std::fs::remove_file(&zip).unwrap();
+
+        for part in parts {
+            std::fs::remove_file(part).unwrap();
+        }
+    }
+
+    #[test]
+    fn split_dir_with_manifest() {
+        let test_dir = Path::new("test-data/split_archive");
+        let src_dir = Path::new("test-data/pack_encrypted/expected");
+        let manifest = test_dir.join("actual_manifest.txt");
+
+        std::fs::create_dir_all(test_dir).unwrap();
+
+        for (packing, short_txt_part) in [(Packing::InOrder, 2), (Packing::Greedy, 1)].iter() {
+            let parts = super::split_dir(
+                src_dir,
+                &test_dir.join("actual.zip"),
+                &PackOptions::default(),
+                &SplitOptions {
+                    part_size: 100 * 1024,
+                    packing: *packing,
+                    manifest: Some(manifest.clone()),
+                },
+            )
+            .unwrap();
+
+            assert_eq!(parts.len(), 3);
+            assert_eq!(parts[0], test_dir.join("actual_1.zip"));
+
+            for part in &parts {
+                assert!(std::fs::metadata(part).unwrap().len() <= 100 * 1024);
+            }
+
+            let manifest = std::fs::read_to_string(&manifest).unwrap();
+            assert!(manifest.contains(&format!("actual_{}.zip\tshort.txt\n", short_txt_part)));
+            assert!(manifest.contains("actual_2.zip\tfolder_1/\n"));
+            assert!(manifest.contains("actual_2.zip\tfolder_1/random.bin\n"));
+
+            join_and_compare("actual_joined", &parts, src_dir, &UnpackOptions::default());
+        }
+
+        assert!(!test_dir.join(".actual.zip.split").exists());
+        std::fs::remove_file(&manifest).unwrap();
+    }
+
+    #[test]
+    fn split_encrypted_archive() {
+        let test_dir = Path::new("test-data/split_archive");
+        let src_file = Path::new("test-data/unpack_zipcrypto/input.zip");
+
+        std::fs::create_dir_all(test_dir).unwrap();
+
+        let err = super::split_archive(
+            src_file,
+            &test_dir.join("actual_small.zip"),
+            &SplitOptions {
+                part_size: 64 * 1024,
+                packing: Packing::Greedy,
+                manifest: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        let parts = super::split_archive(
+            src_file,
+            &test_dir.join("actual_encrypted.zip"),
+            &SplitOptions {
+                part_size: 72 * 1024,
+                packing: Packing::InOrder,
+                manifest: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(parts.len(), 2);
+
+        join_and_compare(
+            "actual_joined_encrypted",
+            &parts,
+            Path::new("test-data/unpack_zipcrypto/expected"),
+            &UnpackOptions {
+                password: Some("pa55 wörd".as_bytes().to_vec()),
+                ..UnpackOptions::default()
+            },
+        );
+    }
+}
@@ -1,2 +1,3 @@
 pub mod byte_readers;
+pub mod byte_writers;
 pub mod stream_nav;
@@ -1,5 +1,5 @@
 use std::convert::TryInto;
-use std::io::{Error, ErrorKind};
+use std::io::Error;
 use std::mem::size_of;
 
 pub trait FromLeBytes {
@@ -31,7 +31,7 @@ pub fn read_to<T: FromLeBytes>(bytes: &[u8], offset: &mut usize) -> std::io::Res
 
     match bytes[begin..end].try_into() {
         Ok(slice) => Ok(T::from(slice)),
-        Err(err) => Err(Error::new(ErrorKind::Other, err.to_string())),
+        Err(err) => Err(Error::other(err.to_string())),
     }
 }
 
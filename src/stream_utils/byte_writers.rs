@@ -0,0 +1,44 @@
+pub trait ToLeBytes {
+    fn append_to(self, bytes: &mut Vec<u8>);
+}
+
+impl ToLeBytes for u16 {
+    fn append_to(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl ToLeBytes for u32 {
+    fn append_to(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl ToLeBytes for u64 {
+    fn append_to(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+pub fn write_to<T: ToLeBytes>(bytes: &mut Vec<u8>, value: T) {
+    value.append_to(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream_utils::byte_writers::write_to;
+
+    #[test]
+    fn write_mixed() {
+        let mut bytes = Vec::new();
+
+        write_to(&mut bytes, 9042u16);
+        write_to(&mut bytes, 4242424242u32);
+        write_to(&mut bytes, 948049258822893319u64);
+
+        assert_eq!(
+            bytes,
+            [82, 35, 178, 65, 222, 252, 7, 255, 181, 37, 199, 37, 40, 13]
+        );
+    }
+}
@@ -1,10 +1,10 @@
 use std::io::{Seek, SeekFrom};
 
-pub fn current_position<T: Seek>(stream: &mut T) -> std::io::Result<u64> {
-    stream.seek(SeekFrom::Current(0))
+pub fn current_position<T: Seek + ?Sized>(stream: &mut T) -> std::io::Result<u64> {
+    stream.stream_position()
 }
 
-pub fn length<T: Seek>(stream: &mut T) -> std::io::Result<u64> {
+pub fn length<T: Seek + ?Sized>(stream: &mut T) -> std::io::Result<u64> {
     let old_pos = current_position(stream)?;
     let len = stream.seek(SeekFrom::End(0))?;
     stream.seek(SeekFrom::Start(old_pos))?;
@@ -21,15 +21,10 @@ mod tests {
 
     #[test]
     fn stream_navigation() {
-        let test_file = File::open("test-data/streams/streams_0.txt");
+        let test_file =
+            File::open("test-data/streams/streams_0.txt").expect("Couldn't open test data file.");
 
-        let mut reader = match test_file {
-            Ok(file) => BufReader::new(file),
-            Err(_) => {
-                assert!(false, "Couldn't open test data file.");
-                BufReader::new(File::open("").unwrap())
-            }
-        };
+        let mut reader = BufReader::new(test_file);
 
         let stream_len = length(&mut reader).unwrap_or(0);
 
@@ -38,7 +33,7 @@ mod tests {
         match reader.read_exact(&mut [0u8; 4]) {
             Ok(_) => {}
             Err(_) => {
-                assert!(false, "Couldn't navigate over the stream.");
+                panic!("Couldn't navigate over the stream.");
             }
         }
 
@@ -0,0 +1,728 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::unpacker::EntrySink;
+
+const BLOCK_SIZE: usize = 512;
+
+const NAME_LEN: usize = 100;
+const PREFIX_LEN: usize = 155;
+//largest value of the 12 byte octal fields
+const MAX_OCTAL_11: u64 = 0o77777777777;
+
+const MAGIC_USTAR: &[u8; 6] = b"ustar\0";
+const MAGIC_GNU: &[u8; 6] = b"ustar ";
+
+const TYPE_FILE: u8 = b'0';
+//what pre-POSIX archives have for regular files
+const TYPE_OLD_FILE: u8 = 0;
+const TYPE_HARD_LINK: u8 = b'1';
+const TYPE_SYMLINK: u8 = b'2';
+const TYPE_DIRECTORY: u8 = b'5';
+const TYPE_PAX_HEADER: u8 = b'x';
+const TYPE_PAX_GLOBAL_HEADER: u8 = b'g';
+const TYPE_GNU_LONG_NAME: u8 = b'L';
+const TYPE_GNU_LONG_LINK: u8 = b'K';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    HardLink,
+    /// Devices, FIFOs and other types with their type flag.
+    Other(u8),
+}
+
+/// An entry of a tar archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TarHeader {
+    /// Path of the entry; folders end with a slash.
+    pub name: String,
+    pub kind: EntryKind,
+    /// Permission bits.
+    pub mode: u32,
+    /// Modification time in seconds since the epoch.
+    pub mtime: u64,
+    /// Length of the data following the header; only files have data.
+    pub size: u64,
+    /// Target of a symlink or a hard link.
+    pub link_name: String,
+}
+
+fn invalid_data(reason: String) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("tar: {}", reason))
+}
+
+fn padding(len: u64) -> usize {
+    (BLOCK_SIZE - (len % BLOCK_SIZE as u64) as usize) % BLOCK_SIZE
+}
+
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+}
+
+//header checksum: the sum of the header bytes with the checksum field taken as spaces
+fn checksum(block: &[u8]) -> u64 {
+    block[..148].iter().map(|&byte| byte as u64).sum::<u64>()
+        + 8 * b' ' as u64
+        + block[156..].iter().map(|&byte| byte as u64).sum::<u64>()
+}
+
+//a pax record is "<length> <key>=<value>\n", the length counting itself
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    let len = key.len() + value.len() + 3;
+    let mut total = len + len.to_string().len();
+
+    if total.to_string().len() != len.to_string().len() {
+        total += 1;
+    }
+
+    format!("{} {}={}\n", total, key, value).into_bytes()
+}
+
+//the longest start of `text` that fits `len` bytes
+fn truncate(text: &str, len: usize) -> &str {
+    let mut end = std::cmp::min(text.len(), len);
+
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &text[..end]
+}
+
+//the ustar prefix and name fields for `name`, when it fits them
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= NAME_LEN {
+        return Some(("", name));
+    }
+
+    //a trailing slash belongs to the name part
+    let search_end = std::cmp::min(name.len() - 1, PREFIX_LEN + 1);
+
+    name[..search_end]
+        .rmatch_indices('/')
+        .map(|(pos, _)| (&name[..pos], &name[pos + 1..]))
+        .find(|(prefix, rest)| prefix.len() <= PREFIX_LEN && rest.len() <= NAME_LEN)
+}
+
+/// Writes a ustar archive, with pax extended headers for what doesn't fit the ustar fields.
+pub struct TarWriter<W: Write> {
+    writer: W,
+    //data of the current entry still expected
+    bytes_left: u64,
+    padding: usize,
+}
+
+impl<W: Write> TarWriter<W> {
+    pub fn new(writer: W) -> TarWriter<W> {
+        TarWriter {
+            writer,
+            bytes_left: 0,
+            padding: 0,
+        }
+    }
+
+    fn write_header_block(
+        &mut self,
+        name: &str,
+        type_flag: u8,
+        header: &TarHeader,
+        size: u64,
+    ) -> std::io::Result<()> {
+        let mut block = [0u8; BLOCK_SIZE];
+        //what doesn't fit is in a pax header before this one
+        let (prefix, name) = split_name(name).unwrap_or(("", truncate(name, NAME_LEN)));
+        let link_name = truncate(&header.link_name, NAME_LEN);
+
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut block[100..108], (header.mode & 0o7777) as u64);
+        write_octal(&mut block[108..116], 0);
+        write_octal(&mut block[116..124], 0);
+        write_octal(&mut block[124..136], std::cmp::min(size, MAX_OCTAL_11));
+        write_octal(
+            &mut block[136..148],
+            std::cmp::min(header.mtime, MAX_OCTAL_11),
+        );
+        block[156] = type_flag;
+        block[157..157 + link_name.len()].copy_from_slice(link_name.as_bytes());
+        block[257..263].copy_from_slice(MAGIC_USTAR);
+        block[263..265].copy_from_slice(b"00");
+        block[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        let checksum = format!("{:06o}\0 ", checksum(&block));
+        block[148..156].copy_from_slice(checksum.as_bytes());
+
+        self.writer.write_all(&block)
+    }
+
+    /// Writes the header of an entry; its `size` bytes of data are to be written next.
+    pub fn start_entry(&mut self, header: &TarHeader) -> std::io::Result<()> {
+        if self.bytes_left > 0 {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "tar: data of the previous entry is missing.",
+            ));
+        }
+
+        let type_flag = match header.kind {
+            EntryKind::File => TYPE_FILE,
+            EntryKind::Directory => TYPE_DIRECTORY,
+            EntryKind::Symlink => TYPE_SYMLINK,
+            EntryKind::HardLink => TYPE_HARD_LINK,
+            EntryKind::Other(type_flag) => type_flag,
+        };
+
+        let mut name = header.name.clone();
+
+        if header.kind == EntryKind::Directory && !name.ends_with('/') {
+            name.push('/');
+        }
+
+        let size = if header.kind == EntryKind::File {
+            header.size
+        } else {
+            0
+        };
+
+        let mut records = Vec::new();
+
+        if split_name(&name).is_none() {
+            records.extend(pax_record("path", &name));
+        }
+
+        if header.link_name.len() > NAME_LEN {
+            records.extend(pax_record("linkpath", &header.link_name));
+        }
+
+        if size > MAX_OCTAL_11 {
+            records.extend(pax_record("size", &size.to_string()));
+        }
+
+        if header.mtime > MAX_OCTAL_11 {
+            records.extend(pax_record("mtime", &header.mtime.to_string()));
+        }
+
+        if !records.is_empty() {
+            //what ustar readers extract the extended header as
+            let pax_name = format!("PaxHeaders/{}", name.trim_end_matches('/'));
+
+            self.write_header_block(
+                truncate(&pax_name, NAME_LEN),
+                TYPE_PAX_HEADER,
+                header,
+                records.len() as u64,
+            )?;
+            self.writer.write_all(&records)?;
+            self.writer
+                .write_all(&[0u8; BLOCK_SIZE][..padding(records.len() as u64)])?;
+        }
+
+        self.write_header_block(&name, type_flag, header, size)?;
+
+        self.bytes_left = size;
+        self.padding = padding(size);
+
+        if size == 0 {
+            self.end_entry()?;
+        }
+
+        Ok(())
+    }
+
+    fn end_entry(&mut self) -> std::io::Result<()> {
+        self.writer.write_all(&[0u8; BLOCK_SIZE][..self.padding])?;
+        self.padding = 0;
+
+        Ok(())
+    }
+
+    /// Ends the archive with two empty blocks and returns the inner writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if self.bytes_left > 0 {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "tar: data of the last entry is missing.",
+            ));
+        }
+
+        self.writer.write_all(&[0u8; 2 * BLOCK_SIZE])?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for TarWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.len() as u64 > self.bytes_left {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "tar: more data than the header says.",
+            ));
+        }
+
+        let written = self.writer.write(buf)?;
+        self.bytes_left -= written as u64;
+
+        if self.bytes_left == 0 && written > 0 {
+            self.end_entry()?;
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> EntrySink for TarWriter<W> {}
+
+fn read_text(field: &[u8]) -> String {
+    let end = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn read_number(field: &[u8]) -> std::io::Result<u64> {
+    //GNU base-256 for values the octal digits can't hold
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..]
+            .iter()
+            .fold((field[0] & 0x7F) as u64, |value, &byte| {
+                value << 8 | byte as u64
+            }));
+    }
+
+    let digits = read_text(field);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+
+    if digits.is_empty() {
+        return Ok(0);
+    }
+
+    u64::from_str_radix(digits, 8).map_err(|_| invalid_data(format!("bad number {}.", digits)))
+}
+
+//"<length> <key>=<value>\n" records, the values that matter to a header
+fn parse_pax_records(data: &[u8], header: &mut TarHeader) -> std::io::Result<()> {
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|&byte| byte == b' ')
+            .ok_or_else(|| invalid_data("bad pax record.".to_owned()))?;
+        let len = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|len| len.parse::<usize>().ok())
+            .filter(|len| *len > space + 1 && *len <= rest.len())
+            .ok_or_else(|| invalid_data("bad pax record length.".to_owned()))?;
+
+        let record = &rest[space + 1..len - 1];
+        rest = &rest[len..];
+
+        let equals = match record.iter().position(|&byte| byte == b'=') {
+            Some(equals) => equals,
+            None => continue,
+        };
+        let value = String::from_utf8_lossy(&record[equals + 1..]).into_owned();
+
+        match &record[..equals] {
+            b"path" => header.name = value,
+            b"linkpath" => header.link_name = value,
+            b"size" => {
+                header.size = value
+                    .parse()
+                    .map_err(|_| invalid_data("bad pax size.".to_owned()))?
+            }
+            //fractions of seconds are dropped
+            b"mtime" => {
+                header.mtime = value
+                    .split('.')
+                    .next()
+                    .and_then(|seconds| seconds.parse().ok())
+                    .unwrap_or(0)
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the entries of a ustar, pax or GNU tar archive one after another.
+pub struct TarReader<R: Read> {
+    reader: R,
+    //data of the current entry not read yet, and the padding after it
+    bytes_left: u64,
+    padding: usize,
+    //pax values for every entry that follows
+    global: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> TarReader<R> {
+    pub fn new(reader: R) -> TarReader<R> {
+        TarReader {
+            reader,
+            bytes_left: 0,
+            padding: 0,
+            global: Vec::new(),
+            done: false,
+        }
+    }
+
+    fn skip(&mut self, len: u64) -> std::io::Result<()> {
+        let skipped = std::io::copy(&mut (&mut self.reader).take(len), &mut std::io::sink())?;
+
+        if skipped < len {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "tar: archive is truncated.",
+            ));
+        }
+
+        Ok(())
+    }
+
+    //false at the end of the input
+    fn read_block(&mut self, block: &mut [u8; BLOCK_SIZE]) -> std::io::Result<bool> {
+        let mut filled = 0;
+
+        while filled < BLOCK_SIZE {
+            match self.reader.read(&mut block[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => {
+                    return Err(std::io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "tar: archive is truncated.",
+                    ))
+                }
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn read_data(&mut self, len: u64) -> std::io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut data)?;
+
+        if (data.len() as u64) < len {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "tar: archive is truncated.",
+            ));
+        }
+
+        self.skip(padding(len) as u64)?;
+
+        Ok(data)
+    }
+
+    /// Header of the next entry, whose data can then be read from the reader itself.
+    /// Whatever is left of the data of the previous entry is skipped.
+    pub fn next_entry(&mut self) -> std::io::Result<Option<TarHeader>> {
+        self.skip(self.bytes_left + self.padding as u64)?;
+        self.bytes_left = 0;
+        self.padding = 0;
+
+        let mut pax = Vec::new();
+        let mut long_name = None;
+        let mut long_link = None;
+        let mut block = [0u8; BLOCK_SIZE];
+
+        loop {
+            if self.done || !self.read_block(&mut block)? {
+                self.done = true;
+
+                return Ok(None);
+            }
+
+            //one zero block ends the archive, the second one is not always there
+            if block.iter().all(|&byte| byte == 0) {
+                self.done = true;
+
+                return Ok(None);
+            }
+
+            let expected = read_number(&block[148..156])?;
+
+            if expected != checksum(&block) {
+                return Err(invalid_data("header checksum doesn't match.".to_owned()));
+            }
+
+            let type_flag = block[156];
+            let size = read_number(&block[124..136])?;
+
+            match type_flag {
+                TYPE_PAX_HEADER => pax.extend(self.read_data(size)?),
+                TYPE_PAX_GLOBAL_HEADER => {
+                    let data = self.read_data(size)?;
+                    self.global.extend(data);
+                }
+                TYPE_GNU_LONG_NAME => long_name = Some(read_text(&self.read_data(size)?)),
+                TYPE_GNU_LONG_LINK => long_link = Some(read_text(&self.read_data(size)?)),
+                _ => break,
+            }
+        }
+
+        let mut name = read_text(&block[..100]);
+        let magic = &block[257..263];
+
+        if magic == MAGIC_USTAR && block[345] != 0 {
+            name = format!("{}/{}", read_text(&block[345..500]), name);
+        } else if magic != MAGIC_USTAR && magic != MAGIC_GNU && &magic[..5] != b"ustar" {
+            //V7 archives have no magic, anything else is not a tar
+            if !block[257..].iter().all(|&byte| byte == 0) {
+                return Err(invalid_data("unknown header format.".to_owned()));
+            }
+        }
+
+        let type_flag = block[156];
+        let kind = match type_flag {
+            //V7 marks folders with a trailing slash on a regular file
+            TYPE_FILE | TYPE_OLD_FILE if name.ends_with('/') => EntryKind::Directory,
+            TYPE_FILE | TYPE_OLD_FILE | b'7' => EntryKind::File,
+            TYPE_DIRECTORY => EntryKind::Directory,
+            TYPE_SYMLINK => EntryKind::Symlink,
+            TYPE_HARD_LINK => EntryKind::HardLink,
+            other => EntryKind::Other(other),
+        };
+
+        let mut header = TarHeader {
+            name,
+            kind,
+            mode: read_number(&block[100..108])? as u32 & 0o7777,
+            mtime: read_number(&block[136..148])?,
+            size: read_number(&block[124..136])?,
+            link_name: read_text(&block[157..257]),
+        };
+
+        let global = std::mem::take(&mut self.global);
+        parse_pax_records(&global, &mut header)?;
+        self.global = global;
+        parse_pax_records(&pax, &mut header)?;
+
+        if let Some(long_name) = long_name {
+            header.name = long_name;
+        }
+
+        if let Some(long_link) = long_link {
+            header.link_name = long_link;
+        }
+
+        //only files have data, whatever the size field says
+        header.size = match header.kind {
+            EntryKind::File | EntryKind::Other(_) => header.size,
+            _ => 0,
+        };
+
+        self.bytes_left = header.size;
+        self.padding = padding(header.size);
+
+        Ok(Some(header))
+    }
+}
+
+impl<R: Read> Read for TarReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = std::cmp::min(buf.len() as u64, self.bytes_left) as usize;
+
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let read = self.reader.read(&mut buf[..len])?;
+
+        if read == 0 {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "tar: archive is truncated.",
+            ));
+        }
+
+        self.bytes_left -= read as u64;
+
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::tar::{EntryKind, TarHeader, TarReader, TarWriter};
+
+    fn header(name: &str, kind: EntryKind, size: u64) -> TarHeader {
+        TarHeader {
+            name: name.to_owned(),
+            kind,
+            mode: 0o644,
+            mtime: 1_700_000_000,
+            size,
+            link_name: String::new(),
+        }
+    }
+
+    #[test]
+    fn read_gnu_tar() {
+        //tar --format=gnu with a long name, a symlink and a folder
+        let input = std::fs::read("test-data/tar/gnu.tar").unwrap();
+        let mut reader = TarReader::new(&input[..]);
+        let mut entries = Vec::new();
+
+        while let Some(header) = reader.next_entry().unwrap() {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            entries.push((header, data));
+        }
+
+        let names = entries
+            .iter()
+            .map(|(header, _)| header.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "dir/",
+                "dir/hello.txt",
+                "dir/link",
+                &format!("dir/{}.txt", "long_name_".repeat(15)),
+            ]
+        );
+
+        assert_eq!(entries[0].0.kind, EntryKind::Directory);
+        assert_eq!(entries[0].0.mode, 0o755);
+        assert_eq!(entries[1].1, b"hello\n");
+        assert_eq!(entries[1].0.mtime, 1_600_000_000);
+        assert_eq!(entries[2].0.kind, EntryKind::Symlink);
+        assert_eq!(entries[2].0.link_name, "hello.txt");
+        assert_eq!(entries[3].1, b"long\n");
+    }
+
+    #[test]
+    fn read_old_style_folders() {
+        let mut archive = Vec::new();
+
+        //V7 headers without a magic, then a ustar one with the old type flag
+        for (name, magic, size) in [
+            ("dir/", &[0u8; 8], 0),
+            ("dir/file.txt", &[0u8; 8], 3),
+            ("other/", b"ustar\x0000", 0),
+        ]
+        .iter()
+        {
+            let mut block = [0u8; 512];
+            block[..name.len()].copy_from_slice(name.as_bytes());
+            super::write_octal(&mut block[100..108], 0o755);
+            super::write_octal(&mut block[124..136], *size);
+            super::write_octal(&mut block[136..148], 1_700_000_000);
+            block[257..265].copy_from_slice(*magic);
+
+            let checksum = format!("{:06o}\0 ", super::checksum(&block));
+            block[148..156].copy_from_slice(checksum.as_bytes());
+
+            archive.extend_from_slice(&block);
+
+            if *size > 0 {
+                let mut data = [0u8; 512];
+                data[..3].copy_from_slice(b"abc");
+                archive.extend_from_slice(&data);
+            }
+        }
+
+        archive.extend_from_slice(&[0u8; 1024]);
+
+        let mut reader = TarReader::new(&archive[..]);
+        let mut entries = Vec::new();
+
+        while let Some(header) = reader.next_entry().unwrap() {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            entries.push((header.name, header.kind, data));
+        }
+
+        assert_eq!(
+            entries,
+            vec![
+                ("dir/".to_owned(), EntryKind::Directory, Vec::new()),
+                ("dir/file.txt".to_owned(), EntryKind::File, b"abc".to_vec()),
+                ("other/".to_owned(), EntryKind::Directory, Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let long_name = format!("{}/file.txt", "folder".repeat(30));
+        let nested_name = format!("{}/{}", "a".repeat(120), "b".repeat(90));
+
+        let mut writer = TarWriter::new(Vec::new());
+        writer
+            .start_entry(&header("dir", EntryKind::Directory, 0))
+            .unwrap();
+        writer
+            .start_entry(&header("dir/data.bin", EntryKind::File, 1000))
+            .unwrap();
+        writer.write_all(&[7u8; 1000]).unwrap();
+        writer
+            .start_entry(&header(&long_name, EntryKind::File, 3))
+            .unwrap();
+        writer.write_all(b"abc").unwrap();
+        writer
+            .start_entry(&header(&nested_name, EntryKind::File, 0))
+            .unwrap();
+        writer
+            .start_entry(&TarHeader {
+                link_name: "x".repeat(150),
+                ..header("dir/link", EntryKind::Symlink, 0)
+            })
+            .unwrap();
+        let archive = writer.finish().unwrap();
+
+        let mut short = TarWriter::new(Vec::new());
+        short
+            .start_entry(&header("short.txt", EntryKind::File, 2))
+            .unwrap();
+        assert!(short.write_all(b"too long").is_err());
+        assert!(short.finish().is_err());
+
+        assert_eq!(archive.len() % 512, 0);
+
+        let mut reader = TarReader::new(&archive[..]);
+        let mut headers = Vec::new();
+
+        while let Some(header) = reader.next_entry().unwrap() {
+            headers.push(header);
+        }
+
+        assert_eq!(
+            headers,
+            vec![
+                header("dir/", EntryKind::Directory, 0),
+                header("dir/data.bin", EntryKind::File, 1000),
+                header(&long_name, EntryKind::File, 3),
+                header(&nested_name, EntryKind::File, 0),
+                TarHeader {
+                    link_name: "x".repeat(150),
+                    ..header("dir/link", EntryKind::Symlink, 0)
+                },
+            ]
+        );
+
+        //a broken checksum
+        let mut corrupted = archive.clone();
+        corrupted[0] ^= 1;
+        assert!(TarReader::new(&corrupted[..]).next_entry().is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use std::path::Path;
+
+use crate::unpacker::{self, UnpackOptions};
+
+/// Unpacks `zip` into a fresh `out_folder`, checks it against `expected_dir` and removes it again.
+pub(crate) fn unpack_and_compare(
+    zip: &Path,
+    out_folder: &Path,
+    expected_dir: &Path,
+    options: &UnpackOptions,
+) {
+    if out_folder.exists() && !out_folder.is_dir() {
+        panic!("Output dir is not a dir.");
+    } else if out_folder.exists() {
+        std::fs::remove_dir_all(out_folder).unwrap();
+    }
+
+    std::fs::create_dir(out_folder).unwrap();
+
+    unpacker::unpack_archive(zip, out_folder, options).unwrap();
+
+    assert!(!dir_diff::is_different(out_folder, expected_dir).unwrap());
+
+    std::fs::remove_dir_all(out_folder).unwrap();
+}
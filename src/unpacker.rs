@@ -1,22 +1,127 @@
-mod algorithms;
+pub(crate) mod algorithms;
+pub mod source;
 
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
+use crate::checksums::crc32::{Crc32, Crc32Writer};
+use crate::crypto::winzip_aes::{AesExtraField, WinZipAesReader};
+use crate::crypto::zip_crypto::{self, ZipCryptoReader};
 use crate::stream_utils::{byte_readers, stream_nav};
-use crate::zip::structs::{CentralDirFileHeader, EndOfCentralDir, LocalFileHeader};
-use crate::zip::{compression_methods, feature_versions, signatures};
+use crate::zip::structs::{
+    CentralDirFileHeader, EndOfCentralDir, LocalFileHeader, Zip64EndOfCentralDirLocator,
+};
+use crate::zip::{
+    compression_methods, extra_fields, feature_versions, general_bit_flags, signatures,
+};
+
+use algorithms::store;
+use source::{ArchiveSource, MappedSource, SplitSource};
+
+#[derive(Debug)]
+pub struct UnpackOptions {
+    /// Map the whole archive into memory instead of reading it through a buffered file.
+    /// Split archives are always read through files.
+    pub use_mmap: bool,
+    /// Check every extracted entry against the CRC-32 recorded in its header.
+    pub verify_checksums: bool,
+    /// Password for encrypted entries.
+    pub password: Option<Vec<u8>>,
+    /// Asked once for a password when an encrypted entry is met and none was given.
+    pub password_prompt: Option<fn() -> std::io::Result<Vec<u8>>>,
+    /// Unpack into a dir that already has files in it; existing folders are kept, files are overwritten.
+    pub into_existing_dir: bool,
+}
+
+impl Default for UnpackOptions {
+    fn default() -> UnpackOptions {
+        UnpackOptions {
+            use_mmap: false,
+            verify_checksums: true,
+            password: None,
+            password_prompt: None,
+            into_existing_dir: false,
+        }
+    }
+}
+
+/// Where the data of an entry is unpacked to.
+pub trait EntrySink: Write {
+    /// The underlying file, if the kernel can copy into it directly.
+    fn file(&mut self) -> Option<&mut File> {
+        None
+    }
+}
+
+impl EntrySink for File {
+    fn file(&mut self) -> Option<&mut File> {
+        Some(self)
+    }
+}
 
-fn read_signature<T: Read>(reader: &mut T) -> std::io::Result<u32> {
+impl EntrySink for Vec<u8> {}
+
+pub(crate) fn read_signature<T: Read>(reader: &mut T) -> std::io::Result<u32> {
     let mut bytes = [0u8; 4];
     reader.read_exact(&mut bytes)?;
 
     byte_readers::read_to::<u32>(&bytes, &mut 0)
 }
 
-fn read_local_file_header<T: Read + Seek>(reader: &mut T) -> std::io::Result<LocalFileHeader> {
-    const FIRST_CHUNK_SIZE: usize = 34;
+fn zip64_extra_too_short() -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, "ZIP64 extra field is too short.")
+}
+
+/// Replaces saturated 32-bit values with their 64-bit counterparts from the ZIP64 extra field,
+/// and a saturated 16-bit disk number with the 32-bit one that follows them.
+/// `values` must be listed in the order the extra field stores them.
+fn apply_zip64_extra(
+    extra_field: &[u8],
+    values: &mut [&mut u64],
+    disk: Option<&mut u32>,
+) -> std::io::Result<()> {
+    let disk = disk.filter(|disk| **disk as u64 == extra_fields::ZIP64_SHORT_MARKER);
+
+    if disk.is_none()
+        && !values
+            .iter()
+            .any(|value| **value == extra_fields::ZIP64_MARKER)
+    {
+        return Ok(());
+    }
+
+    let zip64 = extra_fields::find(extra_field, extra_fields::ZIP64)
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidData, "Missing ZIP64 extra field."))?;
+
+    let mut offset = 0;
+
+    for value in values.iter_mut() {
+        if **value == extra_fields::ZIP64_MARKER {
+            if offset + 8 > zip64.len() {
+                return Err(zip64_extra_too_short());
+            }
+
+            **value = byte_readers::read_to::<u64>(zip64, &mut offset)?;
+        }
+    }
+
+    if let Some(disk) = disk {
+        if offset + 4 > zip64.len() {
+            return Err(zip64_extra_too_short());
+        }
+
+        *disk = byte_readers::read_to::<u32>(zip64, &mut offset)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn read_local_file_header<T: Read + Seek>(
+    reader: &mut T,
+) -> std::io::Result<LocalFileHeader> {
+    const FIRST_CHUNK_SIZE: usize = 26;
 
     let mut bytes = [0; FIRST_CHUNK_SIZE];
 
@@ -24,37 +129,18 @@ fn read_local_file_header<T: Read + Seek>(reader: &mut T) -> std::io::Result<Loc
 
     let mut offset = 0;
 
-    let ver_to_extract;
     let file_name_len;
+    let extra_field_len;
 
-    let result = LocalFileHeader {
-        version_to_extract: {
-            ver_to_extract = byte_readers::read_to::<u16>(&bytes, &mut offset)?;
-
-            ver_to_extract
-        },
-
+    let mut result = LocalFileHeader {
+        version_to_extract: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         general_bit_flag: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         compression_method: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         last_mod_file_time: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         last_mod_file_date: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         crc_32: byte_readers::read_to::<u32>(&bytes, &mut offset)?,
-
-        compressed_size: {
-            if ver_to_extract >= feature_versions::ZIP64 {
-                byte_readers::read_to::<u64>(&bytes, &mut offset)?
-            } else {
-                byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64
-            }
-        },
-
-        uncompressed_size: {
-            if ver_to_extract >= feature_versions::ZIP64 {
-                byte_readers::read_to::<u64>(&bytes, &mut offset)?
-            } else {
-                byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64
-            }
-        },
+        compressed_size: byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64,
+        uncompressed_size: byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64,
 
         file_name_length: {
             file_name_len = byte_readers::read_to::<u16>(&bytes, &mut offset)?;
@@ -62,29 +148,42 @@ fn read_local_file_header<T: Read + Seek>(reader: &mut T) -> std::io::Result<Loc
             file_name_len
         },
 
-        extra_field_length: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
+        extra_field_length: {
+            extra_field_len = byte_readers::read_to::<u16>(&bytes, &mut offset)?;
 
-        file_name: {
-            if ver_to_extract < feature_versions::ZIP64 {
-                reader.seek(SeekFrom::Current(-8))?;
-            }
+            extra_field_len
+        },
 
+        file_name: {
             let mut bytes = vec![0u8; file_name_len as usize];
             reader.read_exact(&mut bytes)?;
 
-            String::from_utf8(bytes).unwrap() //TODO: convert to I/O Error
+            String::from_utf8(bytes).map_err(|_| {
+                std::io::Error::new(ErrorKind::InvalidData, "File name is not valid UTF-8.")
+            })?
+        },
+
+        extra_field: {
+            let mut bytes = vec![0u8; extra_field_len as usize];
+            reader.read_exact(&mut bytes)?;
+
+            bytes
         },
     };
 
-    reader.seek(SeekFrom::Current(result.extra_field_length as i64))?;
+    apply_zip64_extra(
+        &result.extra_field,
+        &mut [&mut result.uncompressed_size, &mut result.compressed_size],
+        None,
+    )?;
 
     Ok(result)
 }
 
-fn read_central_dir_file_header<T: Read + Seek>(
+pub(crate) fn read_central_dir_file_header<T: Read + Seek>(
     reader: &mut T,
 ) -> std::io::Result<CentralDirFileHeader> {
-    const FIRST_CHUNK_SIZE: usize = 50;
+    const FIRST_CHUNK_SIZE: usize = 42;
 
     let mut bytes = [0; FIRST_CHUNK_SIZE];
 
@@ -92,46 +191,22 @@ fn read_central_dir_file_header<T: Read + Seek>(
 
     let mut offset = 0;
 
-    let ver_to_extract;
     let file_name_len;
     let extra_field_len;
     let file_comment_len;
 
-    let result = CentralDirFileHeader {
+    let mut result = CentralDirFileHeader {
         version_made_by: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
-        version_to_extract: {
-            ver_to_extract = byte_readers::read_to::<u16>(&bytes, &mut offset)?;
-
-            ver_to_extract
-        },
-
+        version_to_extract: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         general_bit_flag: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         compression_method: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         last_mod_file_time: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         last_mod_file_date: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         crc_32: byte_readers::read_to::<u32>(&bytes, &mut offset)?,
-
-        compressed_size: {
-            if ver_to_extract >= feature_versions::ZIP64 {
-                byte_readers::read_to::<u64>(&bytes, &mut offset)?
-            } else {
-                byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64
-            }
-        },
-
-        uncompressed_size: {
-            if ver_to_extract >= feature_versions::ZIP64 {
-                byte_readers::read_to::<u64>(&bytes, &mut offset)?
-            } else {
-                byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64
-            }
-        },
+        compressed_size: byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64,
+        uncompressed_size: byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64,
 
         file_name_length: {
-            if ver_to_extract < feature_versions::ZIP64 as u16 {
-                reader.seek(SeekFrom::Current(-8))?;
-            }
-
             file_name_len = byte_readers::read_to::<u16>(&bytes, &mut offset)?;
 
             file_name_len
@@ -149,33 +224,59 @@ fn read_central_dir_file_header<T: Read + Seek>(
             file_comment_len
         },
 
-        disk_number_start: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
+        disk_number_start: byte_readers::read_to::<u16>(&bytes, &mut offset)? as u32,
         internal_file_attribs: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
         external_file_attribs: byte_readers::read_to::<u32>(&bytes, &mut offset)?,
-        local_header_rel_offset: byte_readers::read_to::<u32>(&bytes, &mut offset)?,
+        local_header_rel_offset: byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64,
 
         file_name: {
             let mut bytes = vec![0u8; file_name_len as usize];
             reader.read_exact(&mut bytes)?;
 
-            //TODO: use extra field; skipping it for now
-            reader.seek(SeekFrom::Current(extra_field_len as i64))?;
-
-            String::from_utf8(bytes).unwrap() //TODO: convert to I/O Error
+            String::from_utf8(bytes).map_err(|_| {
+                std::io::Error::new(ErrorKind::InvalidData, "File name is not valid UTF-8.")
+            })?
         },
 
-        file_comment: {
-            let mut bytes = vec![0u8; file_comment_len as usize];
+        extra_field: {
+            let mut bytes = vec![0u8; extra_field_len as usize];
             reader.read_exact(&mut bytes)?;
 
-            String::from_utf8(bytes).unwrap() //TODO: convert to I/O Error
+            bytes
         },
+
+        //set below, the Unicode comment extra field takes precedence
+        file_comment: String::new(),
     };
 
+    let mut file_comment = vec![0u8; file_comment_len as usize];
+    reader.read_exact(&mut file_comment)?;
+
+    result.file_comment = match extra_fields::unicode_text(
+        &result.extra_field,
+        extra_fields::UNICODE_COMMENT,
+        &file_comment,
+    ) {
+        Some(unicode_comment) => unicode_comment,
+        None => String::from_utf8(file_comment).map_err(|_| {
+            std::io::Error::new(ErrorKind::InvalidData, "File comment is not valid UTF-8.")
+        })?,
+    };
+
+    apply_zip64_extra(
+        &result.extra_field,
+        &mut [
+            &mut result.uncompressed_size,
+            &mut result.compressed_size,
+            &mut result.local_header_rel_offset,
+        ],
+        Some(&mut result.disk_number_start),
+    )?;
+
     Ok(result)
 }
 
-fn read_end_of_central_dir<T: Read>(reader: &mut T) -> std::io::Result<EndOfCentralDir> {
+pub(crate) fn read_end_of_central_dir<T: Read>(reader: &mut T) -> std::io::Result<EndOfCentralDir> {
     const FIRST_CHUNK_SIZE: usize = 18;
 
     let mut bytes = [0; FIRST_CHUNK_SIZE];
@@ -187,18 +288,19 @@ fn read_end_of_central_dir<T: Read>(reader: &mut T) -> std::io::Result<EndOfCent
     let zip_file_comment_len;
 
     let result = EndOfCentralDir {
-        number_of_this_disk: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
-        number_of_disk_with_start_central_dir: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
+        number_of_this_disk: byte_readers::read_to::<u16>(&bytes, &mut offset)? as u32,
+        number_of_disk_with_start_central_dir: byte_readers::read_to::<u16>(&bytes, &mut offset)?
+            as u32,
         total_entries_in_central_dir_on_this_disk: byte_readers::read_to::<u16>(
             &bytes,
             &mut offset,
-        )?,
-        total_entries_in_central_dir: byte_readers::read_to::<u16>(&bytes, &mut offset)?,
-        central_dir_size: byte_readers::read_to::<u32>(&bytes, &mut offset)?,
+        )? as u64,
+        total_entries_in_central_dir: byte_readers::read_to::<u16>(&bytes, &mut offset)? as u64,
+        central_dir_size: byte_readers::read_to::<u32>(&bytes, &mut offset)? as u64,
         central_dir_offset_from_starting_disk_num: byte_readers::read_to::<u32>(
             &bytes,
             &mut offset,
-        )?,
+        )? as u64,
 
         zip_file_comment_length: {
             zip_file_comment_len = byte_readers::read_to::<u16>(&bytes, &mut offset)?;
@@ -210,85 +312,559 @@ fn read_end_of_central_dir<T: Read>(reader: &mut T) -> std::io::Result<EndOfCent
             let mut bytes = vec![0u8; zip_file_comment_len as usize];
             reader.read_exact(&mut bytes)?;
 
-            String::from_utf8(bytes).unwrap() //TODO: convert to I/O Error
+            String::from_utf8(bytes).map_err(|_| {
+                std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Archive comment is not valid UTF-8.",
+                )
+            })?
         },
     };
 
     Ok(result)
 }
 
-fn is_folder(local_file_header: &LocalFileHeader) -> bool {
-    local_file_header.compression_method == compression_methods::STORE
-        && local_file_header.version_to_extract == feature_versions::DIR_OR_DEFLATE
+/// Reads the ZIP64 end of central dir locator right in front of the record at `end_pos`, if there is one.
+pub(crate) fn read_zip64_locator<T: Read + Seek>(
+    reader: &mut T,
+    end_pos: u64,
+) -> std::io::Result<Option<Zip64EndOfCentralDirLocator>> {
+    const LOCATOR_LEN: usize = 20;
+
+    let locator_pos = match end_pos.checked_sub(LOCATOR_LEN as u64) {
+        Some(locator_pos) => locator_pos,
+        None => return Ok(None),
+    };
+
+    let mut bytes = [0u8; LOCATOR_LEN];
+
+    reader.seek(SeekFrom::Start(locator_pos))?;
+    reader.read_exact(&mut bytes)?;
+
+    let mut offset = 0;
+
+    if byte_readers::read_to::<u32>(&bytes, &mut offset)?
+        != signatures::SIGNATURE_ZIP64_CENTRAL_DIR_END_LOCATOR
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(Zip64EndOfCentralDirLocator {
+        number_of_disk_with_zip64_end: byte_readers::read_to::<u32>(&bytes, &mut offset)?,
+        zip64_end_offset: byte_readers::read_to::<u64>(&bytes, &mut offset)?,
+        total_number_of_disks: byte_readers::read_to::<u32>(&bytes, &mut offset)?,
+    }))
 }
 
-pub fn unpack_archive(src_file: &Path, out_dir: &Path) -> std::io::Result<()> {
-    if let Some(_) = out_dir.read_dir()?.next() {
+/// Takes the values of `end_of_central_dir` from the ZIP64 end of central dir record that follows.
+fn read_zip64_end_of_central_dir<T: Read>(
+    reader: &mut T,
+    end_of_central_dir: &mut EndOfCentralDir,
+) -> std::io::Result<()> {
+    const RECORD_LEN: usize = 56;
+
+    let mut bytes = [0u8; RECORD_LEN];
+
+    reader.read_exact(&mut bytes)?;
+
+    let mut offset = 0;
+
+    if byte_readers::read_to::<u32>(&bytes, &mut offset)?
+        != signatures::SIGNATURE_ZIP64_CENTRAL_DIR_END
+    {
         return Err(std::io::Error::new(
-            ErrorKind::Other,
-            "Output dir is not empty.",
+            ErrorKind::InvalidData,
+            "Bad ZIP64 end of central dir signature.",
         ));
+    }
+
+    //the size of the record and the versions
+    offset += 12;
+
+    end_of_central_dir.number_of_this_disk = byte_readers::read_to::<u32>(&bytes, &mut offset)?;
+    end_of_central_dir.number_of_disk_with_start_central_dir =
+        byte_readers::read_to::<u32>(&bytes, &mut offset)?;
+    end_of_central_dir.total_entries_in_central_dir_on_this_disk =
+        byte_readers::read_to::<u64>(&bytes, &mut offset)?;
+    end_of_central_dir.total_entries_in_central_dir =
+        byte_readers::read_to::<u64>(&bytes, &mut offset)?;
+    end_of_central_dir.central_dir_size = byte_readers::read_to::<u64>(&bytes, &mut offset)?;
+    end_of_central_dir.central_dir_offset_from_starting_disk_num =
+        byte_readers::read_to::<u64>(&bytes, &mut offset)?;
+
+    Ok(())
+}
+
+/// Finds the end of central dir record, which can be followed by a comment of up to 64 KiB.
+pub(crate) fn find_end_of_central_dir<T: Read + Seek>(reader: &mut T) -> std::io::Result<u64> {
+    const RECORD_LEN: u64 = 22;
+
+    let stream_len = stream_nav::length(reader)?;
+    let tail_len = std::cmp::min(stream_len, RECORD_LEN + u16::MAX as u64);
+    let tail_pos = stream_len - tail_len;
+
+    let mut tail = vec![0u8; tail_len as usize];
+    reader.seek(SeekFrom::Start(tail_pos))?;
+    reader.read_exact(&mut tail)?;
+
+    //the last record whose comment ends exactly at the end of the stream
+    (0..tail.len().saturating_sub(RECORD_LEN as usize - 1))
+        .rev()
+        .find(|&pos| {
+            tail[pos..pos + 4] == signatures::SIGNATURE_CENTRAL_DIR_END.to_le_bytes()
+                && pos
+                    + RECORD_LEN as usize
+                    + u16::from_le_bytes([tail[pos + 20], tail[pos + 21]]) as usize
+                    == tail.len()
+        })
+        .map(|pos| tail_pos + pos as u64)
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidData, "End of central dir not found."))
+}
+
+fn volume_start<S: ArchiveSource>(source: &S, disk: u32) -> std::io::Result<u64> {
+    source.volume_start(disk).ok_or_else(|| {
+        std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Volume {} of the archive is not available.", disk + 1),
+        )
+    })
+}
+
+/// Where the central directory is, as found from the end of central dir record.
+pub(crate) struct CentralDirLocation {
+    pub end_of_central_dir: EndOfCentralDir,
+    pub end_pos: u64,
+    /// Where the ZIP64 end of central dir record is, if the archive has one.
+    pub zip64_end_pos: Option<u64>,
+    pub central_dir_pos: u64,
+    /// Bytes put in front of the archive without correcting its offsets, as with some self-extractors.
+    pub shift: u64,
+}
+
+pub(crate) fn locate_central_dir<S: ArchiveSource>(
+    reader: &mut S,
+) -> std::io::Result<CentralDirLocation> {
+    let end_pos = find_end_of_central_dir(reader)?;
+    reader.seek(SeekFrom::Start(end_pos + 4))?;
+
+    let mut end_of_central_dir = read_end_of_central_dir(reader)?;
+    let mut zip64_end_pos = None;
+
+    if let Some(locator) = read_zip64_locator(reader, end_pos)? {
+        let pos =
+            volume_start(reader, locator.number_of_disk_with_zip64_end)? + locator.zip64_end_offset;
+
+        reader.seek(SeekFrom::Start(pos))?;
+        read_zip64_end_of_central_dir(reader, &mut end_of_central_dir)?;
+
+        zip64_end_pos = Some(pos);
+    }
+
+    let declared_pos = volume_start(
+        reader,
+        end_of_central_dir.number_of_disk_with_start_central_dir,
+    )? + end_of_central_dir.central_dir_offset_from_starting_disk_num;
+
+    //the central dir ends where the records start, anything in front of the archive pushes both
+    let records_pos = zip64_end_pos.unwrap_or(end_pos);
+    let shift = match records_pos.checked_sub(end_of_central_dir.central_dir_size) {
+        Some(actual_pos)
+            if end_of_central_dir.number_of_this_disk == 0
+                && end_of_central_dir.number_of_disk_with_start_central_dir == 0 =>
+        {
+            actual_pos.saturating_sub(declared_pos)
+        }
+        _ => 0,
+    };
+
+    Ok(CentralDirLocation {
+        end_of_central_dir,
+        end_pos,
+        zip64_end_pos,
+        central_dir_pos: declared_pos + shift,
+        shift,
+    })
+}
+
+/// Reads the end of central dir record and all the central dir file headers it points to.
+/// Local header offsets are turned into positions in `reader`, with the disk numbers cleared.
+pub(crate) fn read_central_dir<S: ArchiveSource>(
+    reader: &mut S,
+) -> std::io::Result<(EndOfCentralDir, Vec<CentralDirFileHeader>)> {
+    let location = locate_central_dir(reader)?;
+    let end_of_central_dir = location.end_of_central_dir;
+
+    reader.seek(SeekFrom::Start(location.central_dir_pos))?;
+
+    //the count is only trusted as far as the headers are really there
+    let mut headers = Vec::with_capacity(std::cmp::min(
+        end_of_central_dir.total_entries_in_central_dir,
+        u16::MAX as u64,
+    ) as usize);
+
+    for _ in 0..end_of_central_dir.total_entries_in_central_dir {
+        if read_signature(reader)? != signatures::SIGNATURE_CENTRAL_DIR_HEADER {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "Bad central dir file header signature.",
+            ));
+        }
+
+        let mut header = read_central_dir_file_header(reader)?;
+        header.local_header_rel_offset +=
+            volume_start(reader, header.disk_number_start)? + location.shift;
+        header.disk_number_start = 0;
+
+        headers.push(header);
+    }
+
+    Ok((end_of_central_dir, headers))
+}
+
+/// Position of the first entry, past any prefix such as a self-extractor stub.
+pub(crate) fn first_entry_pos<S: ArchiveSource>(reader: &mut S) -> std::io::Result<u64> {
+    let (_, headers) = read_central_dir(reader)?;
+
+    match headers
+        .iter()
+        .map(|header| header.local_header_rel_offset)
+        .min()
+    {
+        Some(pos) => Ok(pos),
+        None => Ok(locate_central_dir(reader)?.central_dir_pos),
+    }
+}
+
+pub(crate) fn is_folder(local_file_header: &LocalFileHeader) -> bool {
+    //encryption also needs version 2.0, so encrypted stored files would look like folders
+    local_file_header.compression_method == compression_methods::STORE
+        && (local_file_header.file_name.ends_with('/')
+            || local_file_header.version_to_extract == feature_versions::DIR_OR_DEFLATE
+                && local_file_header.general_bit_flag & general_bit_flags::ENCRYPTED == 0)
+}
+
+fn entry_slice(bytes: &[u8], data_pos: u64, data_len: u64) -> std::io::Result<&[u8]> {
+    data_pos
+        .checked_add(data_len)
+        .filter(|data_end| *data_end <= bytes.len() as u64)
+        .map(|data_end| &bytes[data_pos as usize..data_end as usize])
+        .ok_or_else(|| {
+            std::io::Error::new(ErrorKind::UnexpectedEof, "Entry data is out of bounds.")
+        })
+}
+
+fn unpack_stored_data<S: ArchiveSource, O: EntrySink>(
+    source: &mut S,
+    data_len: u64,
+    out: &mut O,
+    buffer: &mut [u8],
+    mut crc: Option<&mut Crc32>,
+) -> std::io::Result<()> {
+    let data_pos = stream_nav::current_position(source)?;
+
+    if let Some(bytes) = source.mapped_bytes() {
+        let data = entry_slice(bytes, data_pos, data_len)?;
+
+        if let Some(crc) = crc {
+            crc.update(data);
+        }
+
+        return out.write_all(data);
+    }
+
+    let mut copied = 0;
+
+    if let (Some(src_file), Some(out_file)) = (source.file(), out.file()) {
+        copied = store::copy_in_kernel(src_file, data_pos, out_file, data_len)?;
+
+        if let Some(crc) = crc.as_deref_mut() {
+            store::checksum_file_range(src_file, data_pos, copied, buffer, crc)?;
+        }
+    }
+
+    source.seek(SeekFrom::Start(data_pos + copied))?;
+
+    store::copy_buffered(source, out, data_len - copied, buffer, crc)
+}
+
+fn entry_password(
+    local_file_header: &LocalFileHeader,
+    password: &mut Option<Vec<u8>>,
+    options: &UnpackOptions,
+) -> std::io::Result<Vec<u8>> {
+    if password.is_none() {
+        if let Some(prompt) = options.password_prompt {
+            *password = Some(prompt()?);
+        }
+    }
+
+    password.clone().ok_or_else(|| {
+        std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "{} is encrypted and no password was given.",
+                local_file_header.file_name
+            ),
+        )
+    })
+}
+
+fn wrong_password(local_file_header: &LocalFileHeader) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::PermissionDenied,
+        format!("Wrong password for {}.", local_file_header.file_name),
+    )
+}
+
+fn read_encryption_header(
+    reader: &mut dyn Read,
+    local_file_header: &LocalFileHeader,
+    password: &[u8],
+) -> std::io::Result<zip_crypto::Keys> {
+    let mut header = [0u8; zip_crypto::HEADER_LEN];
+    reader.read_exact(&mut header)?;
+
+    //with a data descriptor the CRC is not known up front, so the header is checked against the time field
+    let check_byte = if local_file_header.general_bit_flag & general_bit_flags::DATA_DESCRIPTOR != 0
+    {
+        (local_file_header.last_mod_file_time >> 8) as u8
+    } else {
+        (local_file_header.crc_32 >> 24) as u8
+    };
+
+    zip_crypto::Keys::from_header(password, &header, check_byte)
+        .ok_or_else(|| wrong_password(local_file_header))
+}
+
+fn decompress_entry(
+    decompressor: &dyn algorithms::Decompressor,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    local_file_header: &LocalFileHeader,
+    aes_extra_field: Option<&AesExtraField>,
+    password: &mut Option<Vec<u8>>,
+    options: &UnpackOptions,
+) -> std::io::Result<()> {
+    let data_len = local_file_header.compressed_size;
+
+    if local_file_header.general_bit_flag & general_bit_flags::ENCRYPTED == 0 {
+        return decompressor.decompress(reader, writer, &data_len);
+    }
+
+    let password = entry_password(local_file_header, password, options)?;
+    let overhead = match aes_extra_field {
+        Some(aes_extra_field) => aes_extra_field.overhead(),
+        None => zip_crypto::HEADER_LEN,
+    };
+    let data_len = data_len.checked_sub(overhead as u64).ok_or_else(|| {
+        std::io::Error::new(ErrorKind::InvalidData, "Encrypted entry is too short.")
+    })?;
+
+    match aes_extra_field {
+        Some(aes_extra_field) => {
+            let mut aes_reader =
+                WinZipAesReader::new(reader, &password, aes_extra_field, data_len)?
+                    .ok_or_else(|| wrong_password(local_file_header))?;
+
+            decompressor.decompress(&mut aes_reader, writer, &data_len)?;
+
+            if aes_reader.finish()? {
+                Ok(())
+            } else {
+                Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Authentication code mismatch in {}.",
+                        local_file_header.file_name
+                    ),
+                ))
+            }
+        }
+        None => {
+            let keys = read_encryption_header(reader, local_file_header, &password)?;
+
+            decompressor.decompress(&mut ZipCryptoReader::new(reader, keys), writer, &data_len)
+        }
+    }
+}
+
+/// Writes the data of an entry into `out` and returns its CRC-32.
+/// The CRC-32 is only computed for stored entries when checksums are verified.
+pub(crate) fn unpack_entry_data<S: ArchiveSource, O: EntrySink>(
+    source: &mut S,
+    local_file_header: &LocalFileHeader,
+    out: &mut O,
+    buffer: &mut [u8],
+    password: &mut Option<Vec<u8>>,
+    options: &UnpackOptions,
+) -> std::io::Result<u32> {
+    let data_pos = stream_nav::current_position(source)?;
+    let data_len = local_file_header.compressed_size;
+    let encrypted = local_file_header.general_bit_flag & general_bit_flags::ENCRYPTED != 0;
+    let aes_extra_field = if local_file_header.compression_method == compression_methods::AES {
+        Some(
+            AesExtraField::from_extra_field(&local_file_header.extra_field).ok_or_else(|| {
+                std::io::Error::new(ErrorKind::InvalidData, "Invalid WinZip AES extra field.")
+            })?,
+        )
+    } else {
+        None
     };
 
-    let src_file = File::open(src_file)?;
-    let mut src_file_reader = BufReader::new(src_file);
+    let crc = if local_file_header.compression_method == compression_methods::STORE && !encrypted {
+        let mut crc = Crc32::new();
+        let crc_to_update = if options.verify_checksums {
+            Some(&mut crc)
+        } else {
+            None
+        };
+
+        unpack_stored_data(source, data_len, out, buffer, crc_to_update)?;
+
+        crc.value()
+    } else {
+        let decompressor =
+            algorithms::decompressor(local_file_header).map_err(std::io::Error::other)?;
+
+        let mut writer = Crc32Writer::new(BufWriter::new(out));
+
+        match source.mapped_bytes() {
+            Some(bytes) => {
+                let mut data = entry_slice(bytes, data_pos, data_len)?;
+
+                decompress_entry(
+                    decompressor.as_ref(),
+                    &mut data,
+                    &mut writer,
+                    local_file_header,
+                    aes_extra_field.as_ref(),
+                    password,
+                    options,
+                )?;
+            }
+            None => decompress_entry(
+                decompressor.as_ref(),
+                source,
+                &mut writer,
+                local_file_header,
+                aes_extra_field.as_ref(),
+                password,
+                options,
+            )?,
+        }
+
+        writer.flush()?;
+        writer.crc()
+    };
+
+    source.seek(SeekFrom::Start(data_pos + data_len))?;
+
+    //AE-2 stores no CRC-32 and relies on the authentication code instead
+    let crc_known = local_file_header.general_bit_flag & general_bit_flags::DATA_DESCRIPTOR == 0
+        && aes_extra_field.is_none_or(|aes_extra_field| aes_extra_field.crc_used());
+
+    if options.verify_checksums && crc_known && crc != local_file_header.crc_32 {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("CRC-32 mismatch in {}.", local_file_header.file_name),
+        ));
+    }
+
+    Ok(crc)
+}
+
+fn take_central_dir_sizes(
+    local_file_header: &mut LocalFileHeader,
+    central_dir_file_header: &CentralDirFileHeader,
+) {
+    local_file_header.crc_32 = central_dir_file_header.crc_32;
+    local_file_header.compressed_size = central_dir_file_header.compressed_size;
+    local_file_header.uncompressed_size = central_dir_file_header.uncompressed_size;
+}
+
+fn unpack_entries<S: ArchiveSource>(
+    source: &mut S,
+    out_dir: &Path,
+    options: &UnpackOptions,
+) -> std::io::Result<()> {
+    let stream_len = stream_nav::length(source)?;
+    let mut buffer = vec![0u8; store::CHUNK_SIZE];
+    let mut password = options.password.clone();
+
+    //archives without a usable central dir are still scanned from the start
+    let start_pos = first_entry_pos(source).unwrap_or(0);
+
+    //a data descriptor leaves the sizes in the local header at zero, the central dir has them
+    let central_dir_file_headers: HashMap<u64, CentralDirFileHeader> = read_central_dir(source)
+        .map(|(_, headers)| {
+            headers
+                .into_iter()
+                .map(|header| (header.local_header_rel_offset, header))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if start_pos > 0 {
+        println!("Skipping {} bytes in front of the first entry.", start_pos);
+    }
 
-    let stream_len = stream_nav::length(&mut src_file_reader)?;
+    source.seek(SeekFrom::Start(start_pos))?;
 
-    while stream_nav::current_position(&mut src_file_reader)? < stream_len {
-        let signature = read_signature(&mut src_file_reader)?;
+    while stream_nav::current_position(source)? < stream_len {
+        let header_pos = stream_nav::current_position(source)?;
+        let signature = read_signature(source)?;
 
         if signature == signatures::SIGNATURE_FILE_HEADER {
-            match read_local_file_header(&mut src_file_reader) {
-                Ok(local_file_header) => {
+            match read_local_file_header(source) {
+                Ok(mut local_file_header) => {
+                    if let Some(header) = central_dir_file_headers.get(&header_pos) {
+                        take_central_dir_sizes(&mut local_file_header, header);
+                    }
+
                     if is_folder(&local_file_header) {
-                        std::fs::create_dir(out_dir.join(local_file_header.file_name))?;
+                        let folder = out_dir.join(local_file_header.file_name);
+
+                        match std::fs::create_dir(&folder) {
+                            Err(err)
+                                if options.into_existing_dir
+                                    && err.kind() == ErrorKind::AlreadyExists
+                                    && folder.is_dir() => {}
+                            result => result?,
+                        }
                     } else {
                         let mut out_file =
                             File::create(out_dir.join(&local_file_header.file_name))?;
-                        let mut buf_writer = BufWriter::new(&mut out_file);
-                        let stream_pos = stream_nav::current_position(&mut src_file_reader)?;
-
-                        //TODO: extremely inefficient on a large amount of small files
-                        algorithms::decompressor(&local_file_header.compression_method)
-                            .unwrap()
-                            .decompress(
-                                &mut src_file_reader,
-                                &mut buf_writer,
-                                &stream_pos,
-                                &local_file_header.compressed_size,
-                            )?;
+
+                        unpack_entry_data(
+                            source,
+                            &local_file_header,
+                            &mut out_file,
+                            &mut buffer,
+                            &mut password,
+                            options,
+                        )?;
                     }
                 }
                 Err(err) => {
-                    println!(
-                        "Error reading local file header. Reason: {}",
-                        err.to_string()
-                    );
+                    println!("Error reading local file header. Reason: {}", err);
                 }
             }
         }
 
         if signature == signatures::SIGNATURE_CENTRAL_DIR_HEADER {
-            match read_central_dir_file_header(&mut src_file_reader) {
+            match read_central_dir_file_header(source) {
                 Ok(_central_dir_file_header) => {}
                 Err(err) => {
-                    println!(
-                        "Error reading central dir file header. Reason: {}",
-                        err.to_string()
-                    );
+                    println!("Error reading central dir file header. Reason: {}", err);
                 }
             }
         }
 
         if signature == signatures::SIGNATURE_CENTRAL_DIR_END {
-            match read_end_of_central_dir(&mut src_file_reader) {
+            match read_end_of_central_dir(source) {
                 Ok(_end_of_central_dir) => {}
                 Err(err) => {
-                    println!(
-                        "Error reading end of central dir. Reason: {}",
-                        err.to_string()
-                    );
+                    println!("Error reading end of central dir. Reason: {}", err);
                 }
             }
         }
@@ -296,31 +872,151 @@ pub fn unpack_archive(src_file: &Path, out_dir: &Path) -> std::io::Result<()> {
 
     println!(
         "Read {} out of {}",
-        stream_nav::current_position(&mut src_file_reader)?,
+        stream_nav::current_position(source)?,
         stream_len
     );
 
     Ok(())
 }
 
+pub fn unpack_archive(
+    src_file: &Path,
+    out_dir: &Path,
+    options: &UnpackOptions,
+) -> std::io::Result<()> {
+    if !options.into_existing_dir && out_dir.read_dir()?.next().is_some() {
+        return Err(std::io::Error::other("Output dir is not empty."));
+    };
+
+    if SplitSource::is_split(src_file) {
+        unpack_entries(&mut SplitSource::open(src_file)?, out_dir, options)
+    } else if options.use_mmap {
+        unpack_entries(&mut MappedSource::open(src_file)?, out_dir, options)
+    } else {
+        let src_file = File::open(src_file)?;
+
+        unpack_entries(&mut BufReader::new(src_file), out_dir, options)
+    }
+}
+
+/// Lists the entries of an archive as its central directory records them.
+pub fn list_archive(src_file: &Path) -> std::io::Result<Vec<CentralDirFileHeader>> {
+    let (_, headers) = if SplitSource::is_split(src_file) {
+        read_central_dir(&mut SplitSource::open(src_file)?)?
+    } else {
+        read_central_dir(&mut BufReader::new(File::open(src_file)?))?
+    };
+
+    Ok(headers)
+}
+
+#[cfg(unix)]
+pub(crate) const NULL_DEVICE: &str = "/dev/null";
+#[cfg(windows)]
+pub(crate) const NULL_DEVICE: &str = "NUL";
+
+/// Calls `visit` for every entry the central dir lists, with `source` at the start of its data.
+/// The local file header gets the sizes and the CRC-32 from the central dir file header,
+/// which has them even when a data descriptor follows the data.
+pub(crate) fn visit_entries<S: ArchiveSource>(
+    source: &mut S,
+    mut visit: impl FnMut(&mut S, &CentralDirFileHeader, &LocalFileHeader) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let (_, headers) = read_central_dir(source)?;
+
+    for header in &headers {
+        source.seek(SeekFrom::Start(header.local_header_rel_offset))?;
+
+        if read_signature(source)? != signatures::SIGNATURE_FILE_HEADER {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Bad local file header signature for {}.", header.file_name),
+            ));
+        }
+
+        let mut local_file_header = read_local_file_header(source)?;
+        take_central_dir_sizes(&mut local_file_header, header);
+
+        visit(source, header, &local_file_header)?;
+    }
+
+    Ok(())
+}
+
+fn test_entries<S: ArchiveSource>(
+    source: &mut S,
+    options: &UnpackOptions,
+) -> std::io::Result<usize> {
+    let mut null_file = File::create(NULL_DEVICE)?;
+    let mut buffer = vec![0u8; store::CHUNK_SIZE];
+    let mut password = options.password.clone();
+    let mut tested = 0;
+
+    visit_entries(source, |source, _, local_file_header| {
+        if !is_folder(local_file_header) {
+            unpack_entry_data(
+                source,
+                local_file_header,
+                &mut null_file,
+                &mut buffer,
+                &mut password,
+                options,
+            )?;
+
+            tested += 1;
+        }
+
+        Ok(())
+    })?;
+
+    Ok(tested)
+}
+
+/// Decompresses every file of an archive without writing it anywhere and checks its CRC-32.
+/// Returns the number of files tested.
+pub fn test_archive(src_file: &Path, options: &UnpackOptions) -> std::io::Result<usize> {
+    let options = UnpackOptions {
+        verify_checksums: true,
+        password: options.password.clone(),
+        ..*options
+    };
+
+    if SplitSource::is_split(src_file) {
+        test_entries(&mut SplitSource::open(src_file)?, &options)
+    } else if options.use_mmap {
+        test_entries(&mut MappedSource::open(src_file)?, &options)
+    } else {
+        test_entries(&mut BufReader::new(File::open(src_file)?), &options)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use dir_diff;
+    use std::io::ErrorKind;
     use std::path::Path;
 
+    use crate::packer::{self, Encryption, PackOptions};
+    use crate::test_utils;
+    use crate::unpacker::UnpackOptions;
+
     #[test]
     fn unpack_store_0() {
         let out_folder = Path::new("test-data/unpack_store_0/actual");
 
         if out_folder.exists() && !out_folder.is_dir() {
-            assert!(false, "Output dir is not a dir.");
+            panic!("Output dir is not a dir.");
         } else if out_folder.exists() {
             std::fs::remove_dir_all(out_folder).unwrap();
         }
 
         std::fs::create_dir(out_folder).unwrap();
 
-        super::unpack_archive(Path::new("test-data/unpack_store_0/input.zip"), out_folder).unwrap();
+        super::unpack_archive(
+            Path::new("test-data/unpack_store_0/input.zip"),
+            out_folder,
+            &UnpackOptions::default(),
+        )
+        .unwrap();
 
         assert!(!dir_diff::is_different(
             out_folder,
@@ -331,32 +1027,388 @@ mod tests {
         std::fs::remove_dir_all(out_folder).unwrap();
     }
 
+    fn unpack_and_compare(test_dir: &str, actual_dir: &str, options: &UnpackOptions) {
+        let test_dir = Path::new(test_dir);
+
+        test_utils::unpack_and_compare(
+            &test_dir.join("input.zip"),
+            &test_dir.join(actual_dir),
+            &test_dir.join("expected"),
+            options,
+        );
+    }
+
     #[test]
-    fn unpack_deflate_normal_dict32kb_word32() {
-        unimplemented!("test is broken until Deflate is implemented");
+    fn unpack_store_0_mmap() {
+        unpack_and_compare(
+            "test-data/unpack_store_0",
+            "actual_mmap",
+            &UnpackOptions {
+                use_mmap: true,
+                ..UnpackOptions::default()
+            },
+        );
+    }
 
-        /*let out_folder = Path::new("test-data/unpack_deflate/normal_dict32kb_word32/actual");
+    #[test]
+    fn unpack_deflate64() {
+        unpack_and_compare(
+            "test-data/unpack_deflate64",
+            "actual",
+            &UnpackOptions::default(),
+        );
+    }
 
-        if out_folder.exists() && !out_folder.is_dir() {
-            assert!(false, "Output dir is not a dir.");
-        } else if out_folder.exists() {
+    #[test]
+    fn unpack_bzip2() {
+        unpack_and_compare(
+            "test-data/unpack_bzip2",
+            "actual",
+            &UnpackOptions::default(),
+        );
+    }
+
+    #[test]
+    fn unpack_bzip2_mmap() {
+        unpack_and_compare(
+            "test-data/unpack_bzip2",
+            "actual_mmap",
+            &UnpackOptions {
+                use_mmap: true,
+                ..UnpackOptions::default()
+            },
+        );
+    }
+
+    #[test]
+    fn unpack_lzma() {
+        unpack_and_compare("test-data/unpack_lzma", "actual", &UnpackOptions::default());
+    }
+
+    #[test]
+    fn unpack_xz() {
+        unpack_and_compare("test-data/unpack_xz", "actual", &UnpackOptions::default());
+    }
+
+    #[test]
+    fn unpack_shrink() {
+        unpack_and_compare(
+            "test-data/unpack_shrink",
+            "actual",
+            &UnpackOptions::default(),
+        );
+    }
+
+    #[test]
+    fn unpack_reduce() {
+        unpack_and_compare(
+            "test-data/unpack_reduce",
+            "actual",
+            &UnpackOptions::default(),
+        );
+    }
+
+    #[test]
+    fn unpack_implode() {
+        unpack_and_compare(
+            "test-data/unpack_implode",
+            "actual",
+            &UnpackOptions::default(),
+        );
+    }
+
+    #[test]
+    fn unpack_ppmd() {
+        unpack_and_compare("test-data/unpack_ppmd", "actual", &UnpackOptions::default());
+    }
+
+    #[test]
+    fn unpack_zipcrypto() {
+        unpack_and_compare(
+            "test-data/unpack_zipcrypto",
+            "actual",
+            &UnpackOptions {
+                password: Some("pa55 wörd".as_bytes().to_vec()),
+                ..UnpackOptions::default()
+            },
+        );
+    }
+
+    #[test]
+    fn unpack_zipcrypto_wrong_password() {
+        let test_dir = Path::new("test-data/unpack_zipcrypto");
+        let out_folder = test_dir.join("actual_wrong_password");
+
+        if out_folder.exists() {
+            std::fs::remove_dir_all(&out_folder).unwrap();
+        }
+
+        std::fs::create_dir(&out_folder).unwrap();
+
+        for password in [None, Some(b"pa55 word".to_vec())].iter() {
+            let err = super::unpack_archive(
+                &test_dir.join("input.zip"),
+                &out_folder,
+                &UnpackOptions {
+                    password: password.clone(),
+                    ..UnpackOptions::default()
+                },
+            )
+            .unwrap_err();
+
+            assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+
+            std::fs::remove_dir_all(&out_folder).unwrap();
+            std::fs::create_dir(&out_folder).unwrap();
+        }
+
+        std::fs::remove_dir_all(&out_folder).unwrap();
+    }
+
+    #[test]
+    fn unpack_streamed_zipcrypto() {
+        let test_dir = Path::new("test-data/pack_encrypted");
+        let zip = test_dir.join("streamed.zip");
+        let out_folder = test_dir.join("actual_streamed");
+        let password = b"secret".to_vec();
+
+        packer::pack_dir(
+            &test_dir.join("expected"),
+            &zip,
+            &PackOptions {
+                password: Some(password.clone()),
+                encrypt: true,
+                encryption: Some(Encryption::ZipCrypto),
+                ..PackOptions::default()
+            },
+        )
+        .unwrap();
+
+        //as written by a zip that can't seek back: the CRC-32 and the sizes only in the data descriptor
+        let mut bytes = std::fs::read(&zip).unwrap();
+        for header in super::list_archive(&zip).unwrap() {
+            let crc_pos = header.local_header_rel_offset as usize + 14;
+            bytes[crc_pos..crc_pos + 12].copy_from_slice(&[0; 12]);
+        }
+        std::fs::write(&zip, bytes).unwrap();
+
+        if out_folder.exists() {
+            std::fs::remove_dir_all(&out_folder).unwrap();
+        }
+
+        std::fs::create_dir(&out_folder).unwrap();
+
+        super::unpack_archive(
+            &zip,
+            &out_folder,
+            &UnpackOptions {
+                password: Some(password),
+                ..UnpackOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(!dir_diff::is_different(&out_folder, test_dir.join("expected")).unwrap());
+
+        std::fs::remove_dir_all(&out_folder).unwrap();
+        std::fs::remove_file(&zip).unwrap();
+    }
+
+    #[test]
+    fn unpack_into_existing_dir() {
+        let zip = Path::new("test-data/sfx_archive/input_adjusted.zip");
+        let out_folder = Path::new("test-data/sfx_archive/actual_existing");
+
+        if out_folder.exists() {
             std::fs::remove_dir_all(out_folder).unwrap();
         }
 
         std::fs::create_dir(out_folder).unwrap();
 
+        super::unpack_archive(zip, out_folder, &UnpackOptions::default()).unwrap();
+        assert!(super::unpack_archive(zip, out_folder, &UnpackOptions::default()).is_err());
+
+        //the folders and files of the first run are there already
         super::unpack_archive(
-            Path::new("test-data/unpack_deflate/normal_dict32kb_word32/input.zip"),
+            zip,
             out_folder,
+            &UnpackOptions {
+                into_existing_dir: true,
+                ..UnpackOptions::default()
+            },
         )
         .unwrap();
 
-        assert!(!dir_diff::is_different(
-            out_folder,
-            Path::new("test-data/unpack_deflate/normal_dict32kb_word32/expected")
-        )
-        .unwrap());
+        assert!(!dir_diff::is_different(out_folder, "test-data/sfx_archive/expected").unwrap());
+
+        std::fs::remove_dir_all(out_folder).unwrap();
+    }
+
+    #[test]
+    fn unpack_winzip_aes() {
+        unpack_and_compare(
+            "test-data/unpack_winzip_aes",
+            "actual",
+            &UnpackOptions {
+                password: Some("pa55 wörd".as_bytes().to_vec()),
+                ..UnpackOptions::default()
+            },
+        );
+    }
+
+    #[test]
+    fn unpack_winzip_aes_mmap() {
+        unpack_and_compare(
+            "test-data/unpack_winzip_aes",
+            "actual_mmap",
+            &UnpackOptions {
+                use_mmap: true,
+                password: Some("pa55 wörd".as_bytes().to_vec()),
+                ..UnpackOptions::default()
+            },
+        );
+    }
+
+    #[test]
+    fn unpack_winzip_aes_rejected() {
+        let test_dir = Path::new("test-data/unpack_winzip_aes");
+        let out_folder = test_dir.join("actual_rejected");
+        let tampered_zip = test_dir.join("tampered.zip");
+
+        if out_folder.exists() {
+            std::fs::remove_dir_all(&out_folder).unwrap();
+        }
+
+        //the only ciphertext byte of short.txt
+        let mut bytes = std::fs::read(test_dir.join("input.zip")).unwrap();
+        bytes[60] ^= 1;
+        std::fs::write(&tampered_zip, bytes).unwrap();
+
+        let cases = [
+            (
+                "input.zip",
+                b"pa55 word".to_vec(),
+                ErrorKind::PermissionDenied,
+            ),
+            (
+                "tampered.zip",
+                "pa55 wörd".as_bytes().to_vec(),
+                ErrorKind::InvalidData,
+            ),
+        ];
+
+        for (zip, password, kind) in cases.iter() {
+            std::fs::create_dir(&out_folder).unwrap();
+
+            let err = super::unpack_archive(
+                &test_dir.join(zip),
+                &out_folder,
+                &UnpackOptions {
+                    password: Some(password.clone()),
+                    ..UnpackOptions::default()
+                },
+            )
+            .unwrap_err();
+
+            assert_eq!(err.kind(), *kind);
+
+            std::fs::remove_dir_all(&out_folder).unwrap();
+        }
 
-        std::fs::remove_dir_all(out_folder).unwrap();*/
+        std::fs::remove_file(&tampered_zip).unwrap();
+    }
+
+    #[test]
+    fn unpack_split() {
+        unpack_and_compare(
+            "test-data/unpack_split",
+            "actual",
+            &UnpackOptions::default(),
+        );
+    }
+
+    #[test]
+    fn split_central_dir() {
+        let mut source =
+            super::SplitSource::open(Path::new("test-data/unpack_split/input.zip")).unwrap();
+        let (_, headers) = super::read_central_dir(&mut source).unwrap();
+
+        //folder_1/random.bin starts on the second volume
+        let random_bin = &headers[7];
+        assert_eq!(random_bin.file_name, "folder_1/random.bin");
+        assert_eq!(random_bin.local_header_rel_offset, 68996);
+        assert_eq!(random_bin.disk_number_start, 0);
+
+        let err = super::read_central_dir(&mut std::io::BufReader::new(
+            std::fs::File::open("test-data/unpack_split/input.zip").unwrap(),
+        ))
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn unpack_prefixed() {
+        unpack_and_compare("test-data/sfx_archive", "actual", &UnpackOptions::default());
+
+        let mut source = std::io::BufReader::new(
+            std::fs::File::open("test-data/sfx_archive/input.zip").unwrap(),
+        );
+        let location = super::locate_central_dir(&mut source).unwrap();
+        assert_eq!(location.shift, 78);
+        assert_eq!(super::first_entry_pos(&mut source).unwrap(), 78);
+    }
+
+    #[test]
+    fn test_and_list() {
+        let zip = Path::new("test-data/unpack_zipcrypto/input.zip");
+        let options = UnpackOptions {
+            password: Some("pa55 wörd".as_bytes().to_vec()),
+            ..UnpackOptions::default()
+        };
+
+        assert_eq!(super::test_archive(zip, &options).unwrap(), 5);
+        assert_eq!(
+            super::test_archive(
+                zip,
+                &UnpackOptions {
+                    password: Some(b"wrong".to_vec()),
+                    ..UnpackOptions::default()
+                }
+            )
+            .unwrap_err()
+            .kind(),
+            ErrorKind::PermissionDenied
+        );
+
+        assert_eq!(
+            super::test_archive(
+                Path::new("test-data/unpack_split/input.zip"),
+                &UnpackOptions::default()
+            )
+            .unwrap(),
+            6
+        );
+
+        let names = super::list_archive(zip)
+            .unwrap()
+            .into_iter()
+            .map(|header| header.file_name)
+            .collect::<Vec<_>>();
+        assert_eq!(names[..2], ["short.txt", "text.txt"]);
+        assert_eq!(names.len(), 7);
+    }
+
+    #[test]
+    fn unpack_zstd() {
+        unpack_and_compare("test-data/unpack_zstd", "actual", &UnpackOptions::default());
+    }
+
+    #[test]
+    fn unpack_deflate_normal_dict32kb_word32() {
+        unpack_and_compare(
+            "test-data/unpack_deflate/normal_dict32kb_word32",
+            "actual",
+            &UnpackOptions::default(),
+        );
     }
 }
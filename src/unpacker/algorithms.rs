@@ -1,25 +1,91 @@
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{Read, Write};
 
-use crate::zip::compression_methods;
+use crate::crypto::winzip_aes::AesExtraField;
+use crate::zip::structs::LocalFileHeader;
+use crate::zip::{compression_methods, general_bit_flags};
 
-mod deflate;
-mod store;
+mod bcj;
+mod bzip2;
+pub(crate) mod deflate;
+mod deflate64;
+mod implode;
+mod lzma;
+mod lzma2;
+mod ppmd;
+mod reduce;
+mod shrink;
+pub mod store;
+mod xz;
+mod zstd;
 
 pub trait Decompressor {
     fn decompress(
         &self,
-        reader: &mut BufReader<File>,
-        writer: &mut BufWriter<&mut File>,
-        data_pos: &u64,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
         data_len: &u64,
     ) -> std::io::Result<()>;
 }
 
-pub fn decompressor(compression_method: &u16) -> Result<Box<dyn Decompressor>, &'static str> {
+pub fn decompressor(
+    local_file_header: &LocalFileHeader,
+) -> Result<Box<dyn Decompressor>, &'static str> {
+    let has_flag = |flag| local_file_header.general_bit_flag & flag != 0;
+
+    //Reduce, Implode and PPMd streams have no end marker; they stop at the uncompressed size
+    let known_size = || {
+        if has_flag(general_bit_flags::DATA_DESCRIPTOR) {
+            Err("Uncompressed size is needed but is only in the data descriptor.")
+        } else {
+            Ok(local_file_header.uncompressed_size)
+        }
+    };
+
+    //WinZip AES keeps the real method in its extra field
+    let compression_method = if local_file_header.compression_method == compression_methods::AES {
+        AesExtraField::from_extra_field(&local_file_header.extra_field)
+            .ok_or("Invalid WinZip AES extra field.")?
+            .compression_method
+    } else {
+        local_file_header.compression_method
+    };
+
     match compression_method {
-        &compression_methods::STORE => Ok(Box::new(store::StoreDecompressor)),
-        &compression_methods::DEFLATE => Ok(Box::new(deflate::DeflateDecompressor)),
+        compression_methods::STORE => Ok(Box::new(store::StoreDecompressor)),
+        compression_methods::SHRINK => Ok(Box::new(shrink::ShrinkDecompressor)),
+        compression_methods::REDUCE_1..=compression_methods::REDUCE_4 => {
+            Ok(Box::new(reduce::ReduceDecompressor {
+                factor: (compression_method - 1) as u32,
+                uncompressed_size: known_size()?,
+            }))
+        }
+        compression_methods::IMPLODE => Ok(Box::new(implode::ImplodeDecompressor {
+            large_window: has_flag(general_bit_flags::IMPLODE_8K_DICTIONARY),
+            literal_tree: has_flag(general_bit_flags::IMPLODE_LITERAL_TREE),
+            uncompressed_size: known_size()?,
+        })),
+        compression_methods::DEFLATE => Ok(Box::new(deflate::DeflateDecompressor)),
+        compression_methods::DEFLATE64 => Ok(Box::new(deflate64::Deflate64Decompressor)),
+        compression_methods::BZIP2 => Ok(Box::new(bzip2::Bzip2Decompressor)),
+        compression_methods::LZMA => Ok(Box::new(lzma::LzmaDecompressor {
+            end_marker: has_flag(general_bit_flags::LZMA_END_MARKER),
+            uncompressed_size: if has_flag(general_bit_flags::DATA_DESCRIPTOR) {
+                None
+            } else {
+                Some(local_file_header.uncompressed_size)
+            },
+        })),
+        compression_methods::XZ => Ok(Box::new(xz::XzDecompressor {
+            uncompressed_size: if has_flag(general_bit_flags::DATA_DESCRIPTOR) {
+                None
+            } else {
+                Some(local_file_header.uncompressed_size)
+            },
+        })),
+        compression_methods::PPMD => Ok(Box::new(ppmd::PpmdDecompressor {
+            uncompressed_size: known_size()?,
+        })),
+        compression_methods::ZSTD => Ok(Box::new(zstd::ZstdDecompressor)),
         _ => Err("Unknown compression method."),
     }
 }
@@ -0,0 +1,181 @@
+use std::io::Write;
+
+const ALLOWED_MASKS: [bool; 8] = [true, true, true, false, true, false, false, false];
+const MASK_TO_BIT_NUMBER: [u32; 8] = [0, 1, 2, 2, 3, 3, 3, 3];
+
+//an instruction is a one byte opcode and a four byte operand
+const INSTRUCTION_LEN: usize = 5;
+
+fn is_ms_byte(byte: u8) -> bool {
+    byte == 0x00 || byte == 0xFF
+}
+
+/// Undoes the x86 branch converter: CALL/JMP targets stored as absolute addresses
+/// are turned back into the relative ones the code had.
+pub struct X86Decoder<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    pos: u32,
+    prev_mask: u32,
+    prev_pos: u32,
+}
+
+impl<W: Write> X86Decoder<W> {
+    pub fn new(writer: W, start_offset: u32) -> X86Decoder<W> {
+        X86Decoder {
+            writer,
+            buffer: Vec::new(),
+            pos: start_offset,
+            prev_mask: 0,
+            prev_pos: start_offset.wrapping_sub(INSTRUCTION_LEN as u32),
+        }
+    }
+
+    /// Converts what it can of the buffer; returns how many bytes are final.
+    fn convert(&mut self) -> usize {
+        let buffer = &mut self.buffer;
+
+        if buffer.len() < INSTRUCTION_LEN {
+            return 0;
+        }
+
+        if self.pos.wrapping_sub(self.prev_pos) > INSTRUCTION_LEN as u32 {
+            self.prev_pos = self.pos.wrapping_sub(INSTRUCTION_LEN as u32);
+        }
+
+        let limit = buffer.len() - INSTRUCTION_LEN;
+        let mut n = 0;
+
+        while n <= limit {
+            if buffer[n] != 0xE8 && buffer[n] != 0xE9 {
+                n += 1;
+                continue;
+            }
+
+            let here = self.pos.wrapping_add(n as u32);
+            let offset = here.wrapping_sub(self.prev_pos);
+            self.prev_pos = here;
+
+            if offset > INSTRUCTION_LEN as u32 {
+                self.prev_mask = 0;
+            } else {
+                for _ in 0..offset {
+                    self.prev_mask &= 0x77;
+                    self.prev_mask <<= 1;
+                }
+            }
+
+            let ms_byte = buffer[n + 4];
+
+            if is_ms_byte(ms_byte)
+                && ALLOWED_MASKS[((self.prev_mask >> 1) & 7) as usize]
+                && (self.prev_mask >> 1) < 0x10
+            {
+                let mut src =
+                    u32::from_le_bytes([buffer[n + 1], buffer[n + 2], buffer[n + 3], ms_byte]);
+                let mut dest;
+
+                loop {
+                    dest = src.wrapping_sub(here.wrapping_add(INSTRUCTION_LEN as u32));
+
+                    if self.prev_mask == 0 {
+                        break;
+                    }
+
+                    let index = MASK_TO_BIT_NUMBER[(self.prev_mask >> 1) as usize];
+
+                    if !is_ms_byte((dest >> (24 - index * 8)) as u8) {
+                        break;
+                    }
+
+                    src = dest ^ ((1u32 << (32 - index * 8)) - 1);
+                }
+
+                let operand = (dest & 0x00FF_FFFF) | (0u32.wrapping_sub((dest >> 24) & 1) << 24);
+                buffer[n + 1..n + INSTRUCTION_LEN].copy_from_slice(&operand.to_le_bytes());
+
+                n += INSTRUCTION_LEN;
+                self.prev_mask = 0;
+            } else {
+                n += 1;
+                self.prev_mask |= 1;
+
+                if is_ms_byte(ms_byte) {
+                    self.prev_mask |= 0x10;
+                }
+            }
+        }
+
+        self.pos = self.pos.wrapping_add(n as u32);
+
+        n
+    }
+
+    /// Writes out the tail that was too short to hold an instruction.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.writer.write_all(&self.buffer)?;
+
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for X86Decoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        let done = self.convert();
+
+        self.writer.write_all(&self.buffer[..done])?;
+        self.buffer.drain(..done);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::unpacker::algorithms::bcj::X86Decoder;
+
+    fn decode(bytes: &[u8], chunk_len: usize) -> Vec<u8> {
+        let mut decoder = X86Decoder::new(Vec::new(), 0);
+
+        for chunk in bytes.chunks(chunk_len) {
+            decoder.write_all(chunk).unwrap();
+        }
+
+        decoder.finish().unwrap()
+    }
+
+    #[test]
+    fn call_target_is_made_relative() {
+        //a CALL at offset 0 with absolute target 0x105 decodes to relative 0x100
+        let encoded = [0xE8, 0x05, 0x01, 0x00, 0x00, 0x90, 0x90];
+
+        assert_eq!(
+            decode(&encoded, 7),
+            [0xE8, 0x00, 0x01, 0x00, 0x00, 0x90, 0x90]
+        );
+    }
+
+    #[test]
+    fn chunking_does_not_matter() {
+        let bytes: Vec<u8> = (0..4000u32)
+            .map(|n| {
+                if n % 7 == 0 {
+                    0xE8
+                } else {
+                    (n * 31 % 251) as u8
+                }
+            })
+            .collect();
+
+        assert_eq!(decode(&bytes, 1), decode(&bytes, bytes.len()));
+        assert_eq!(decode(&bytes, 3), decode(&bytes, 1000));
+    }
+}
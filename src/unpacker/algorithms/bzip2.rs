@@ -0,0 +1,579 @@
+use std::io::{BufReader, ErrorKind, Read, Write};
+
+use crate::unpacker::algorithms::Decompressor;
+
+const STREAM_MAGIC: [u8; 3] = *b"BZh";
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+const END_OF_STREAM_MAGIC: u64 = 0x1772_4538_5090;
+
+const MAX_GROUPS: usize = 6;
+const MIN_GROUPS: usize = 2;
+const GROUP_SIZE: usize = 50;
+const MAX_ALPHA_SIZE: usize = 258;
+const MAX_CODE_LEN: u32 = 20;
+const MAX_SELECTORS: usize = 18002;
+
+const RUN_A: u16 = 0;
+const RUN_B: u16 = 1;
+
+fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("Bzip2: {}", reason))
+}
+
+const fn make_crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = (n as u32) << 24;
+
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+
+            bit += 1;
+        }
+
+        table[n] = crc;
+        n += 1;
+    }
+
+    table
+}
+
+//bzip2 uses the non-reflected CRC-32, unlike the ZIP container
+static CRC_TABLE: [u32; 256] = make_crc_table();
+
+struct BitReader<R: Read> {
+    reader: R,
+    buffer: u64,
+    bit_count: u32,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(reader: R) -> BitReader<R> {
+        BitReader {
+            reader,
+            buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// Reads up to 32 bits, most significant first.
+    fn read_bits(&mut self, count: u32) -> std::io::Result<u32> {
+        while self.bit_count < count {
+            let mut byte = [0u8; 1];
+            self.reader.read_exact(&mut byte)?;
+
+            self.buffer = (self.buffer << 8) | byte[0] as u64;
+            self.bit_count += 8;
+        }
+
+        self.bit_count -= count;
+
+        Ok(((self.buffer >> self.bit_count) & ((1u64 << count) - 1)) as u32)
+    }
+
+    fn read_bit(&mut self) -> std::io::Result<bool> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    fn read_u48(&mut self) -> std::io::Result<u64> {
+        let high = self.read_bits(24)? as u64;
+        let low = self.read_bits(24)? as u64;
+
+        Ok((high << 24) | low)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.bit_count -= self.bit_count % 8;
+    }
+
+    /// Reads the next byte of a byte-aligned stream, or None at the end of input.
+    fn read_aligned_byte(&mut self) -> std::io::Result<Option<u8>> {
+        if self.bit_count >= 8 {
+            return self.read_bits(8).map(|byte| Some(byte as u8));
+        }
+
+        let mut byte = [0u8; 1];
+
+        match self.reader.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+}
+
+/// Canonical Huffman decoding table in the shape the reference implementation uses.
+struct HuffmanTable {
+    limit: [i32; MAX_CODE_LEN as usize + 2],
+    base: [i32; MAX_CODE_LEN as usize + 2],
+    perm: [u16; MAX_ALPHA_SIZE],
+    min_len: u32,
+    max_len: u32,
+}
+
+impl HuffmanTable {
+    fn new(lengths: &[u8]) -> HuffmanTable {
+        let min_len = *lengths.iter().min().unwrap() as u32;
+        let max_len = *lengths.iter().max().unwrap() as u32;
+
+        let mut perm = [0u16; MAX_ALPHA_SIZE];
+        let mut n = 0;
+
+        for len in min_len..=max_len {
+            for (symbol, symbol_len) in lengths.iter().enumerate() {
+                if *symbol_len as u32 == len {
+                    perm[n] = symbol as u16;
+                    n += 1;
+                }
+            }
+        }
+
+        let mut count = [0i32; MAX_CODE_LEN as usize + 2];
+        for len in lengths {
+            count[*len as usize] += 1;
+        }
+
+        let mut limit = [0i32; MAX_CODE_LEN as usize + 2];
+        let mut base = [0i32; MAX_CODE_LEN as usize + 2];
+
+        let mut code = 0i32;
+        let mut first_index = 0i32;
+
+        for len in min_len as usize..=max_len as usize {
+            base[len] = first_index - code;
+            code += count[len];
+            first_index += count[len];
+            limit[len] = code - 1;
+            code <<= 1;
+        }
+
+        HuffmanTable {
+            limit,
+            base,
+            perm,
+            min_len,
+            max_len,
+        }
+    }
+
+    fn decode<R: Read>(&self, bits: &mut BitReader<R>) -> std::io::Result<u16> {
+        let mut len = self.min_len;
+        let mut code = bits.read_bits(len)? as i32;
+
+        loop {
+            if code <= self.limit[len as usize] {
+                let index = code + self.base[len as usize];
+
+                return match self.perm.get(index as usize) {
+                    Some(symbol) if index >= 0 => Ok(*symbol),
+                    _ => Err(invalid_data("bad Huffman code.")),
+                };
+            }
+
+            len += 1;
+
+            if len > self.max_len {
+                return Err(invalid_data("bad Huffman code."));
+            }
+
+            code = (code << 1) | bits.read_bits(1)? as i32;
+        }
+    }
+}
+
+struct BlockDecoder {
+    max_block_len: usize,
+    //low byte holds the BWT output byte, the upper 24 bits link to the next position
+    tt: Vec<u32>,
+    output: Vec<u8>,
+}
+
+impl BlockDecoder {
+    fn new(level: u8) -> BlockDecoder {
+        BlockDecoder {
+            max_block_len: level as usize * 100_000,
+            tt: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    fn read_symbol_map<R: Read>(bits: &mut BitReader<R>) -> std::io::Result<Vec<u8>> {
+        let used_groups = bits.read_bits(16)?;
+        let mut symbols = Vec::new();
+
+        for group in 0..16 {
+            if used_groups & (0x8000 >> group) == 0 {
+                continue;
+            }
+
+            let used = bits.read_bits(16)?;
+
+            for n in 0..16 {
+                if used & (0x8000 >> n) != 0 {
+                    symbols.push((group * 16 + n) as u8);
+                }
+            }
+        }
+
+        if symbols.is_empty() {
+            return Err(invalid_data("block uses no symbols."));
+        }
+
+        Ok(symbols)
+    }
+
+    fn read_selectors<R: Read>(bits: &mut BitReader<R>, groups: usize) -> std::io::Result<Vec<u8>> {
+        let selector_count = bits.read_bits(15)? as usize;
+
+        if selector_count == 0 {
+            return Err(invalid_data("block has no selectors."));
+        }
+
+        let mut mtf: Vec<u8> = (0..groups as u8).collect();
+        let mut selectors = Vec::with_capacity(std::cmp::min(selector_count, MAX_SELECTORS));
+
+        for _ in 0..selector_count {
+            let mut index = 0;
+
+            while bits.read_bit()? {
+                index += 1;
+
+                if index >= groups {
+                    return Err(invalid_data("selector out of range."));
+                }
+            }
+
+            let selector = mtf.remove(index);
+            mtf.insert(0, selector);
+
+            //some encoders write more selectors than a block can use; they are ignored
+            if selectors.len() < MAX_SELECTORS {
+                selectors.push(selector);
+            }
+        }
+
+        Ok(selectors)
+    }
+
+    fn read_tables<R: Read>(
+        bits: &mut BitReader<R>,
+        groups: usize,
+        alpha_size: usize,
+    ) -> std::io::Result<Vec<HuffmanTable>> {
+        let mut tables = Vec::with_capacity(groups);
+
+        for _ in 0..groups {
+            let mut lengths = vec![0u8; alpha_size];
+            let mut len = bits.read_bits(5)?;
+
+            for symbol_len in lengths.iter_mut() {
+                loop {
+                    if !(1..=MAX_CODE_LEN).contains(&len) {
+                        return Err(invalid_data("bad code length."));
+                    }
+
+                    if !bits.read_bit()? {
+                        break;
+                    }
+
+                    if bits.read_bit()? {
+                        len -= 1;
+                    } else {
+                        len += 1;
+                    }
+                }
+
+                *symbol_len = len as u8;
+            }
+
+            tables.push(HuffmanTable::new(&lengths));
+        }
+
+        Ok(tables)
+    }
+
+    /// Decodes one block into `self.output` and returns its CRC.
+    fn decode<R: Read>(&mut self, bits: &mut BitReader<R>) -> std::io::Result<u32> {
+        let block_crc = bits.read_bits(32)?;
+
+        if bits.read_bit()? {
+            return Err(invalid_data("randomised blocks are not supported."));
+        }
+
+        let orig_ptr = bits.read_bits(24)? as usize;
+
+        let symbols = BlockDecoder::read_symbol_map(bits)?;
+        let alpha_size = symbols.len() + 2;
+        let end_of_block = (symbols.len() + 1) as u16;
+
+        let groups = bits.read_bits(3)? as usize;
+
+        if !(MIN_GROUPS..=MAX_GROUPS).contains(&groups) {
+            return Err(invalid_data("bad number of Huffman groups."));
+        }
+
+        let selectors = BlockDecoder::read_selectors(bits, groups)?;
+        let tables = BlockDecoder::read_tables(bits, groups, alpha_size)?;
+
+        self.tt.clear();
+
+        let mut counts = [0u32; 256];
+        let mut mtf: Vec<u8> = (0..=255).collect();
+        let mut run_len = 0usize;
+        let mut run_weight = 1usize;
+
+        let mut symbols_decoded = 0usize;
+
+        loop {
+            let selector = selectors
+                .get(symbols_decoded / GROUP_SIZE)
+                .ok_or_else(|| invalid_data("ran out of selectors."))?;
+
+            let symbol = tables[*selector as usize].decode(bits)?;
+            symbols_decoded += 1;
+
+            if symbol == RUN_A || symbol == RUN_B {
+                if run_weight > self.max_block_len {
+                    return Err(invalid_data("run is too long."));
+                }
+
+                run_len += run_weight * (symbol as usize + 1);
+                run_weight <<= 1;
+
+                continue;
+            }
+
+            if run_len > 0 {
+                let byte = symbols[mtf[0] as usize];
+
+                if self.tt.len() + run_len > self.max_block_len {
+                    return Err(invalid_data("block is too long."));
+                }
+
+                counts[byte as usize] += run_len as u32;
+                self.tt.extend(std::iter::repeat_n(byte as u32, run_len));
+
+                run_len = 0;
+                run_weight = 1;
+            }
+
+            if symbol == end_of_block {
+                break;
+            }
+
+            let index = (symbol - 1) as usize;
+
+            if index >= symbols.len() {
+                return Err(invalid_data("MTF index out of range."));
+            }
+
+            let value = mtf[index];
+            mtf.copy_within(0..index, 1);
+            mtf[0] = value;
+
+            let byte = symbols[value as usize];
+
+            if self.tt.len() >= self.max_block_len {
+                return Err(invalid_data("block is too long."));
+            }
+
+            counts[byte as usize] += 1;
+            self.tt.push(byte as u32);
+        }
+
+        if orig_ptr >= self.tt.len() {
+            return Err(invalid_data("BWT origin out of range."));
+        }
+
+        if self.inverse_bwt(&counts, orig_ptr) != block_crc {
+            return Err(invalid_data("block CRC mismatch."));
+        }
+
+        Ok(block_crc)
+    }
+
+    /// Undoes the BWT and the initial run-length encoding, computing the block CRC on the way.
+    fn inverse_bwt(&mut self, counts: &[u32; 256], orig_ptr: usize) -> u32 {
+        let mut starts = [0u32; 256];
+        let mut sum = 0;
+
+        for (start, count) in starts.iter_mut().zip(counts.iter()) {
+            *start = sum;
+            sum += count;
+        }
+
+        for n in 0..self.tt.len() {
+            let byte = (self.tt[n] & 0xFF) as usize;
+
+            self.tt[starts[byte] as usize] |= (n as u32) << 8;
+            starts[byte] += 1;
+        }
+
+        self.output.clear();
+
+        let mut crc = 0xFFFF_FFFFu32;
+        let mut pos = self.tt[orig_ptr] >> 8;
+
+        let mut last = None;
+        let mut repeats = 0;
+
+        for _ in 0..self.tt.len() {
+            let entry = self.tt[pos as usize];
+            let byte = entry as u8;
+            pos = entry >> 8;
+
+            if repeats == 4 {
+                for _ in 0..byte {
+                    crc = (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ last.unwrap_or(0)) as usize];
+                }
+
+                self.output
+                    .extend(std::iter::repeat_n(last.unwrap_or(0), byte as usize));
+
+                repeats = 0;
+                last = None;
+
+                continue;
+            }
+
+            if Some(byte) == last {
+                repeats += 1;
+            } else {
+                repeats = 1;
+                last = Some(byte);
+            }
+
+            crc = (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize];
+            self.output.push(byte);
+        }
+
+        !crc
+    }
+}
+
+fn decompress_stream<R: Read>(
+    bits: &mut BitReader<R>,
+    writer: &mut dyn Write,
+    level: u8,
+) -> std::io::Result<()> {
+    if !(b'1'..=b'9').contains(&level) {
+        return Err(invalid_data("bad block size."));
+    }
+
+    let mut block_decoder = BlockDecoder::new(level - b'0');
+    let mut combined_crc = 0u32;
+
+    loop {
+        match bits.read_u48()? {
+            BLOCK_MAGIC => {
+                let block_crc = block_decoder.decode(bits)?;
+
+                combined_crc = combined_crc.rotate_left(1) ^ block_crc;
+
+                writer.write_all(&block_decoder.output)?;
+            }
+            END_OF_STREAM_MAGIC => {
+                if bits.read_bits(32)? != combined_crc {
+                    return Err(invalid_data("stream CRC mismatch."));
+                }
+
+                bits.align_to_byte();
+
+                return Ok(());
+            }
+            _ => return Err(invalid_data("bad block signature.")),
+        }
+    }
+}
+
+pub struct Bzip2Decompressor;
+impl Decompressor for Bzip2Decompressor {
+    fn decompress(
+        &self,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        data_len: &u64,
+    ) -> std::io::Result<()> {
+        let mut bits = BitReader::new(BufReader::new(reader.take(*data_len)));
+
+        let mut streams = 0;
+
+        //concatenated streams are decoded one after another
+        loop {
+            let mut magic = [0u8; 4];
+
+            match bits.read_aligned_byte()? {
+                Some(byte) => magic[0] = byte,
+                None if streams > 0 => return Ok(()),
+                None => return Err(invalid_data("empty stream.")),
+            }
+
+            for byte in magic.iter_mut().skip(1) {
+                *byte = bits
+                    .read_aligned_byte()?
+                    .ok_or_else(|| invalid_data("truncated stream header."))?;
+            }
+
+            if magic[..3] != STREAM_MAGIC {
+                return Err(invalid_data("bad stream signature."));
+            }
+
+            decompress_stream(&mut bits, writer, magic[3])?;
+
+            streams += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::bzip2::Bzip2Decompressor;
+    use crate::unpacker::algorithms::Decompressor;
+
+    //bz2.compress(b"hello hello hello hello", 1)
+    const HELLO: [u8; 46] = [
+        66, 90, 104, 49, 49, 65, 89, 38, 83, 89, 2, 248, 176, 189, 0, 0, 3, 145, 0, 64, 0, 2, 68,
+        160, 0, 48, 205, 0, 84, 134, 150, 113, 155, 56, 163, 197, 220, 145, 78, 20, 36, 0, 190, 44,
+        47, 64,
+    ];
+
+    fn decompress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        Bzip2Decompressor.decompress(&mut &bytes[..], &mut out, &(bytes.len() as u64))?;
+
+        Ok(out)
+    }
+
+    #[test]
+    fn decompress_single_block() {
+        assert_eq!(decompress(&HELLO).unwrap(), b"hello hello hello hello");
+    }
+
+    #[test]
+    fn decompress_concatenated_streams() {
+        let mut bytes = HELLO.to_vec();
+        bytes.extend_from_slice(&HELLO);
+
+        assert_eq!(
+            decompress(&bytes).unwrap(),
+            b"hello hello hello hellohello hello hello hello"
+        );
+    }
+
+    #[test]
+    fn reject_corrupted_data() {
+        let mut bytes = HELLO.to_vec();
+        bytes[30] ^= 0x10;
+
+        assert!(decompress(&bytes).is_err());
+        assert!(decompress(&HELLO[..40]).is_err());
+        assert!(decompress(b"BZh0").is_err());
+    }
+}
@@ -1,40 +1,558 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+
+use crate::unpacker::algorithms::lzma::OutWindow;
 use crate::unpacker::algorithms::Decompressor;
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom};
 
-fn is_bit_set(byte: u8, n_bit: u8) -> bool {
-    let mask = 1u8 << (n_bit - 1);
+//Deflate codes stop at 15 bits, Implode codes go up to 16
+const MAX_CODE_LEN: usize = 16;
+const FAST_BITS: u32 = 10;
+
+const END_OF_BLOCK: u16 = 256;
+const MAX_LITERAL_LENGTH_CODES: usize = 286;
+
+//order in which code length code lengths are stored in a dynamic block header
+pub(crate) const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub(crate) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(crate) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+//codes 30 and 31 only exist in Deflate64
+pub(crate) const DISTANCE_BASE: [u32; 32] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577, 32769, 49153,
+];
+pub(crate) const DISTANCE_EXTRA: [u8; 32] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13, 14, 14,
+];
+
+/// What differs between Deflate and Deflate64.
+pub struct Format {
+    pub window_size: u32,
+    /// Base and extra bits of length code 285.
+    pub last_length_code: (u16, u8),
+    pub distance_codes: usize,
+}
+
+pub const DEFLATE: Format = Format {
+    window_size: 32 * 1024,
+    last_length_code: (258, 0),
+    distance_codes: 30,
+};
+
+pub fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("Deflate: {}", reason))
+}
+
+/// LSB-first bit reader, the bit order of Deflate and the older PKWARE methods.
+pub struct BitReader<R: BufRead> {
+    reader: R,
+    bits: u64,
+    bit_count: u32,
+}
+
+impl<R: BufRead> BitReader<R> {
+    pub fn new(reader: R) -> BitReader<R> {
+        BitReader {
+            reader,
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// Tops up the bit buffer; running out of input is only an error once the bits are needed.
+    fn refill(&mut self) -> std::io::Result<()> {
+        while self.bit_count <= 56 {
+            let buffer = self.reader.fill_buf()?;
+
+            if buffer.is_empty() {
+                break;
+            }
+
+            let take = std::cmp::min(buffer.len(), ((64 - self.bit_count) / 8) as usize);
+
+            for byte in &buffer[..take] {
+                self.bits |= (*byte as u64) << self.bit_count;
+                self.bit_count += 8;
+            }
+
+            self.reader.consume(take);
+        }
+
+        Ok(())
+    }
+
+    pub fn peek(&mut self, count: u32) -> std::io::Result<u32> {
+        if self.bit_count < count {
+            self.refill()?;
+        }
+
+        Ok((self.bits & ((1u64 << count) - 1)) as u32)
+    }
+
+    pub fn consume(&mut self, count: u32) -> std::io::Result<()> {
+        if self.bit_count < count {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "compressed data ends early.",
+            ));
+        }
+
+        self.bits >>= count;
+        self.bit_count -= count;
+
+        Ok(())
+    }
+
+    pub fn read(&mut self, count: u32) -> std::io::Result<u32> {
+        let value = self.peek(count)?;
+        self.consume(count)?;
+
+        Ok(value)
+    }
+
+    /// Like `read`, but None when fewer than `count` bits are left.
+    pub fn try_read(&mut self, count: u32) -> std::io::Result<Option<u32>> {
+        let value = self.peek(count)?;
+
+        if self.bit_count < count {
+            return Ok(None);
+        }
+
+        self.consume(count)?;
+
+        Ok(Some(value))
+    }
+
+    pub fn align_to_byte(&mut self) {
+        let skip = self.bit_count % 8;
+
+        self.bits >>= skip;
+        self.bit_count -= skip;
+    }
+
+    /// Drops the bits up to the next byte boundary, and returns the reader
+    /// along with the whole bytes already taken from it.
+    pub fn into_inner(mut self) -> (R, Vec<u8>) {
+        self.align_to_byte();
+
+        let bytes = self.bits.to_le_bytes()[..(self.bit_count / 8) as usize].to_vec();
+
+        (self.reader, bytes)
+    }
+}
 
-    (byte & mask) > 0
+/// Canonical Huffman decoder: a lookup table for short codes, a walk over the counts for the rest.
+pub struct Huffman {
+    fast: Vec<u16>,
+    counts: [u16; MAX_CODE_LEN + 1],
+    symbols: Vec<u16>,
+    //1 when the stream carries complemented codes, as Implode's Shannon-Fano trees do
+    invert: u32,
 }
 
-fn print_bytes(bytes: &[u8]) {
-    for byte in bytes {
-        print!("{:b} ", byte);
+impl Huffman {
+    pub fn new(lengths: &[u8]) -> std::io::Result<Huffman> {
+        let mut counts = [0u16; MAX_CODE_LEN + 1];
+
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+
+        counts[0] = 0;
+
+        let mut left = 1i32;
+        for count in &counts[1..] {
+            left = (left << 1) - *count as i32;
+
+            if left < 0 {
+                return Err(invalid_data("oversubscribed Huffman code."));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_CODE_LEN + 2];
+        for len in 1..=MAX_CODE_LEN {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0u16; offsets[MAX_CODE_LEN + 1] as usize];
+
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbols[offsets[*len as usize] as usize] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+
+        //an entry is symbol << 4 | length; length 0 sends the lookup to the slow path
+        let mut fast = vec![0u16; 1 << FAST_BITS];
+        let mut code = 0u32;
+        let mut index = 0usize;
+
+        for len in 1..=MAX_CODE_LEN as u32 {
+            for _ in 0..counts[len as usize] {
+                if len <= FAST_BITS {
+                    let reversed = code.reverse_bits() >> (32 - len);
+                    let entry = (symbols[index] << 4) | len as u16;
+
+                    let mut slot = reversed as usize;
+                    while slot < fast.len() {
+                        fast[slot] = entry;
+                        slot += 1 << len;
+                    }
+                }
+
+                code += 1;
+                index += 1;
+            }
+
+            code <<= 1;
+        }
+
+        Ok(Huffman {
+            fast,
+            counts,
+            symbols,
+            invert: 0,
+        })
+    }
+
+    /// A decoder for the bitwise complement of the canonical code with these lengths.
+    pub fn inverted(lengths: &[u8]) -> std::io::Result<Huffman> {
+        let mut huffman = Huffman::new(lengths)?;
+        huffman.invert = 1;
+
+        Ok(huffman)
     }
+
+    pub fn decode<R: BufRead>(&self, bits: &mut BitReader<R>) -> std::io::Result<u16> {
+        let fast_mask = (1 << FAST_BITS) - 1;
+        let entry = self.fast[(bits.peek(FAST_BITS)? ^ (self.invert * fast_mask)) as usize];
+
+        if entry & 0xF != 0 {
+            bits.consume((entry & 0xF) as u32)?;
+
+            return Ok(entry >> 4);
+        }
+
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+
+        for len in 1..=MAX_CODE_LEN {
+            code |= (bits.read(1)? ^ self.invert) as i32;
+
+            let count = self.counts[len] as i32;
+
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid_data("bad Huffman code."))
+    }
+}
+
+fn fixed_tables() -> std::io::Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 32])?))
 }
 
-fn print_first_block_header(bytes: &[u8]) {
-    let bit_1 = is_bit_set(bytes[0], 8);
-    let bit_2 = is_bit_set(bytes[0], 7);
-    let bit_3 = is_bit_set(bytes[0], 6);
+//where the inflater stands between two calls
+enum Block {
+    Header,
+    Stored {
+        remaining: u32,
+    },
+    Codes {
+        literals: Huffman,
+        distances: Huffman,
+    },
+    Done,
+}
+
+pub struct Inflater<'a, R: BufRead, W: Write> {
+    bits: BitReader<R>,
+    window: OutWindow<W>,
+    format: &'a Format,
+    block: Block,
+    last_block: bool,
+    //distance and length still to copy of a match cut short
+    pending_match: (usize, usize),
+}
+
+impl<'a, R: BufRead, W: Write> Inflater<'a, R, W> {
+    pub fn new(reader: R, writer: W, format: &'a Format) -> Inflater<'a, R, W> {
+        Inflater::with_bits(BitReader::new(reader), writer, format)
+    }
+
+    /// Goes on from bits that are already buffered, as when several streams follow one another.
+    pub fn with_bits(bits: BitReader<R>, writer: W, format: &'a Format) -> Inflater<'a, R, W> {
+        Inflater {
+            bits,
+            window: OutWindow::new(writer, format.window_size),
+            format,
+            block: Block::Header,
+            last_block: false,
+            pending_match: (0, 0),
+        }
+    }
+
+    /// Bytes written so far.
+    pub fn total_out(&self) -> u64 {
+        self.window.total_pos()
+    }
+
+    /// Lets the stream refer back into `dictionary` without it being written.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> std::io::Result<()> {
+        let window_size = self.format.window_size as usize;
+
+        self.window
+            .preset(&dictionary[dictionary.len().saturating_sub(window_size)..])
+    }
+
+    pub fn writer_mut(&mut self) -> &mut W {
+        self.window.writer_mut()
+    }
+
+    pub fn bits_mut(&mut self) -> &mut BitReader<R> {
+        &mut self.bits
+    }
+
+    /// The bits left after the stream, for what follows it, and the writer.
+    pub fn into_parts(self) -> std::io::Result<(BitReader<R>, W)> {
+        Ok((self.bits, self.window.into_inner()?))
+    }
+
+    fn read_dynamic_tables(&mut self) -> std::io::Result<(Huffman, Huffman)> {
+        let literal_count = self.bits.read(5)? as usize + 257;
+        let distance_count = self.bits.read(5)? as usize + 1;
+        let code_length_count = self.bits.read(4)? as usize + 4;
+
+        if literal_count > MAX_LITERAL_LENGTH_CODES || distance_count > self.format.distance_codes {
+            return Err(invalid_data("too many codes in a dynamic block."));
+        }
+
+        let mut code_length_lengths = [0u8; 19];
+        for index in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_length_lengths[*index] = self.bits.read(3)? as u8;
+        }
+
+        let code_lengths = Huffman::new(&code_length_lengths)?;
+
+        let mut lengths = vec![0u8; literal_count + distance_count];
+        let mut n = 0;
+
+        while n < lengths.len() {
+            let symbol = code_lengths.decode(&mut self.bits)?;
+
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    if n == 0 {
+                        return Err(invalid_data("repeated length without a previous one."));
+                    }
+
+                    (lengths[n - 1], 3 + self.bits.read(2)? as usize)
+                }
+                17 => (0, 3 + self.bits.read(3)? as usize),
+                _ => (0, 11 + self.bits.read(7)? as usize),
+            };
+
+            if n + repeat > lengths.len() {
+                return Err(invalid_data("code lengths overflow the table."));
+            }
+
+            lengths[n..n + repeat].fill(value);
+            n += repeat;
+        }
+
+        if lengths[END_OF_BLOCK as usize] == 0 {
+            return Err(invalid_data("block has no end code."));
+        }
+
+        let (literal_lengths, distance_lengths) = lengths.split_at(literal_count);
+
+        Ok((
+            Huffman::new(literal_lengths)?,
+            Huffman::new(distance_lengths)?,
+        ))
+    }
+
+    fn read_stored_header(&mut self) -> std::io::Result<Block> {
+        self.bits.align_to_byte();
+
+        let len = self.bits.read(16)?;
+        let inverted_len = self.bits.read(16)?;
+
+        if len != !inverted_len & 0xFFFF {
+            return Err(invalid_data("stored block length mismatch."));
+        }
+
+        Ok(Block::Stored { remaining: len })
+    }
+
+    fn read_block_header(&mut self) -> std::io::Result<Block> {
+        let header = self.bits.read(3)?;
+        self.last_block = header & 1 != 0;
+
+        match header >> 1 {
+            0 => self.read_stored_header(),
+            1 => {
+                let (literals, distances) = fixed_tables()?;
+                Ok(Block::Codes {
+                    literals,
+                    distances,
+                })
+            }
+            2 => {
+                let (literals, distances) = self.read_dynamic_tables()?;
+                Ok(Block::Codes {
+                    literals,
+                    distances,
+                })
+            }
+            _ => Err(invalid_data("reserved block type.")),
+        }
+    }
+
+    //returns true at the end of the block, false once the output reaches `end`
+    fn inflate_codes(
+        &mut self,
+        literals: &Huffman,
+        distances: &Huffman,
+        end: u64,
+    ) -> std::io::Result<bool> {
+        loop {
+            let (dist, len) = self.pending_match;
+
+            if len > 0 {
+                let now = std::cmp::min(len as u64, end - self.window.total_pos()) as usize;
+
+                self.window.copy_match(dist, now)?;
+                self.pending_match = (dist, len - now);
+            }
+
+            if self.window.total_pos() >= end {
+                return Ok(false);
+            }
+
+            let symbol = literals.decode(&mut self.bits)?;
+
+            if symbol < END_OF_BLOCK {
+                self.window.put_byte(symbol as u8)?;
+                continue;
+            }
 
-    println!();
+            if symbol == END_OF_BLOCK {
+                return Ok(true);
+            }
+
+            let code = (symbol - 257) as usize;
+
+            let (base, extra) = match code {
+                0..=27 => (LENGTH_BASE[code], LENGTH_EXTRA[code]),
+                28 => self.format.last_length_code,
+                _ => return Err(invalid_data("bad length code.")),
+            };
+
+            let len = base as usize + self.bits.read(extra as u32)? as usize;
+
+            let code = distances.decode(&mut self.bits)? as usize;
+
+            if code >= self.format.distance_codes {
+                return Err(invalid_data("bad distance code."));
+            }
+
+            let dist = DISTANCE_BASE[code] as usize
+                + self.bits.read(DISTANCE_EXTRA[code] as u32)? as usize;
+
+            if !self.window.has_distance(dist) {
+                return Err(invalid_data("distance is too far back."));
+            }
 
-    if bit_1 {
-        println!(":: Last block in the stream");
-    } else {
-        println!(":: There are more blocks in the stream");
+            self.pending_match = (dist, len);
+        }
     }
 
-    if !bit_2 && !bit_3 {
-        println!(":: Literal (raw) section");
-    } else if !bit_2 && bit_3 {
-        println!(":: Static Huffman compressed block");
-    } else if bit_2 && !bit_3 {
-        println!(":: Compressed block with Huffman table")
-    } else {
-        println!(":: Reserved")
+    /// Decodes until `limit` more bytes are written or the stream ends, and flushes them.
+    /// Returns true once the last block is done; the next call goes on where this one stopped.
+    pub fn inflate_some(&mut self, limit: u64) -> std::io::Result<bool> {
+        let end = self.window.total_pos().saturating_add(limit);
+
+        while self.window.total_pos() < end {
+            //an error leaves the block unfinished, the stream can't go on after it anyway
+            let block_done = match std::mem::replace(&mut self.block, Block::Header) {
+                Block::Header => {
+                    self.block = self.read_block_header()?;
+                    false
+                }
+                Block::Stored { remaining } => {
+                    let len = std::cmp::min(remaining as u64, end - self.window.total_pos());
+
+                    for _ in 0..len {
+                        let byte = self.bits.read(8)? as u8;
+                        self.window.put_byte(byte)?;
+                    }
+
+                    let remaining = remaining - len as u32;
+                    self.block = Block::Stored { remaining };
+
+                    remaining == 0
+                }
+                Block::Codes {
+                    literals,
+                    distances,
+                } => {
+                    let block_done = self.inflate_codes(&literals, &distances, end)?;
+                    self.block = Block::Codes {
+                        literals,
+                        distances,
+                    };
+
+                    block_done
+                }
+                Block::Done => {
+                    self.block = Block::Done;
+                    break;
+                }
+            };
+
+            if block_done {
+                self.block = if self.last_block {
+                    Block::Done
+                } else {
+                    Block::Header
+                };
+            }
+        }
+
+        self.window.flush()?;
+
+        Ok(matches!(self.block, Block::Done))
+    }
+
+    pub fn inflate(&mut self) -> std::io::Result<()> {
+        while !self.inflate_some(u64::MAX)? {}
+
+        Ok(())
     }
 }
 
@@ -42,40 +560,55 @@ pub struct DeflateDecompressor;
 impl Decompressor for DeflateDecompressor {
     fn decompress(
         &self,
-        reader: &mut BufReader<File>,
-        _writer: &mut BufWriter<&mut File>,
-        data_pos: &u64,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
         data_len: &u64,
     ) -> std::io::Result<()> {
-        reader.seek(SeekFrom::Start(*data_pos))?;
-
-        const CHUNK_SIZE: usize = 1024 * 1024;
-        let mut bytes_left = *data_len;
+        Inflater::new(BufReader::new(reader.take(*data_len)), writer, &DEFLATE).inflate()
+    }
+}
 
-        println!(">> Compressed data >>");
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::deflate::{DeflateDecompressor, Huffman};
+    use crate::unpacker::algorithms::Decompressor;
 
-        while bytes_left > 0 {
-            let next_bytes = std::cmp::min(bytes_left, CHUNK_SIZE as u64);
+    fn decompress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
 
-            if next_bytes == CHUNK_SIZE as u64 {
-                let mut bytes = [0u8; CHUNK_SIZE];
-                reader.read_exact(&mut bytes)?;
+        DeflateDecompressor.decompress(&mut &bytes[..], &mut out, &(bytes.len() as u64))?;
 
-                print_bytes(&bytes);
-                print_first_block_header(&bytes);
-            } else {
-                let mut bytes = vec![0u8; next_bytes as usize];
-                reader.read_exact(&mut bytes)?;
+        Ok(out)
+    }
 
-                print_bytes(&bytes);
-                print_first_block_header(&bytes);
-            }
+    #[test]
+    fn stored_block() {
+        assert_eq!(
+            decompress(&[0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c']).unwrap(),
+            b"abc"
+        );
+    }
 
-            bytes_left -= next_bytes;
-        }
+    #[test]
+    fn fixed_block() {
+        //zlib.compressobj(wbits=-15).compress(b"abcabcabcabc")
+        assert_eq!(
+            decompress(&[0x4B, 0x4C, 0x4A, 0x4E, 0x84, 0x21, 0x00]).unwrap(),
+            b"abcabcabcabc"
+        );
+    }
 
-        println!("<< Compressed data <<");
+    #[test]
+    fn reject_bad_streams() {
+        //distance before the start of the output
+        assert!(decompress(&[0x03, 0x02, 0x00]).is_err());
+        //reserved block type
+        assert!(decompress(&[0x07]).is_err());
+        assert!(decompress(&[0x01, 0x03, 0x00, 0xFC, 0xFF, b'a']).is_err());
+    }
 
-        Ok(())
+    #[test]
+    fn reject_oversubscribed_code() {
+        assert!(Huffman::new(&[1, 1, 1]).is_err());
     }
 }
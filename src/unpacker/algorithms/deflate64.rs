@@ -0,0 +1,68 @@
+use std::io::{BufReader, Read, Write};
+
+use crate::unpacker::algorithms::deflate::{Format, Inflater};
+use crate::unpacker::algorithms::Decompressor;
+
+/// Deflate with a 64 KiB window, a 16 bit extra for length code 285 and distance codes 30 and 31.
+pub const DEFLATE64: Format = Format {
+    window_size: 64 * 1024,
+    last_length_code: (3, 16),
+    distance_codes: 32,
+};
+
+pub struct Deflate64Decompressor;
+impl Decompressor for Deflate64Decompressor {
+    fn decompress(
+        &self,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        data_len: &u64,
+    ) -> std::io::Result<()> {
+        Inflater::new(BufReader::new(reader.take(*data_len)), writer, &DEFLATE64).inflate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::deflate::DeflateDecompressor;
+    use crate::unpacker::algorithms::deflate64::Deflate64Decompressor;
+    use crate::unpacker::algorithms::Decompressor;
+
+    //"a" followed by a 299 byte match: length code 285 carrying 16 extra bits
+    const LONG_MATCH: [u8; 6] = [0x4B, 0x1C, 0x45, 0x09, 0x00, 0x00];
+
+    #[test]
+    fn long_length_code() {
+        let mut out = Vec::new();
+
+        Deflate64Decompressor
+            .decompress(&mut &LONG_MATCH[..], &mut out, &6)
+            .unwrap();
+
+        assert_eq!(out, vec![b'a'; 300]);
+    }
+
+    //"a" followed by length code 285 with 16 zero extra bits and distance 1
+    const SHORT_MATCH: [u8; 6] = [0x4B, 0x1C, 0x05, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn deflate_reads_code_285_differently() {
+        let mut out = Vec::new();
+
+        Deflate64Decompressor
+            .decompress(&mut &SHORT_MATCH[..], &mut out, &6)
+            .unwrap();
+
+        //3 plus the extra bits
+        assert_eq!(out, vec![b'a'; 4]);
+
+        out.clear();
+
+        DeflateDecompressor
+            .decompress(&mut &SHORT_MATCH[..], &mut out, &6)
+            .unwrap();
+
+        //always 258, the zero bits are the distance code and the end of the block
+        assert_eq!(out, vec![b'a'; 259]);
+    }
+}
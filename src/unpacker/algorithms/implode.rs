@@ -0,0 +1,175 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+
+use crate::unpacker::algorithms::deflate::{BitReader, Huffman};
+use crate::unpacker::algorithms::lzma::OutWindow;
+use crate::unpacker::algorithms::Decompressor;
+
+const WINDOW_SIZE: u32 = 8 * 1024;
+
+//length code 63 is followed by an extra byte
+const LONG_LENGTH: u16 = 63;
+
+fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("Implode: {}", reason))
+}
+
+/// Reads a Shannon-Fano tree: a byte count, then (count - 1, length - 1) nibble pairs.
+fn read_tree<R: BufRead>(bits: &mut BitReader<R>, symbols: usize) -> std::io::Result<Huffman> {
+    let byte_count = bits.read(8)? as usize + 1;
+    let mut lengths = Vec::with_capacity(symbols);
+
+    for _ in 0..byte_count {
+        let byte = bits.read(8)?;
+        let repeat = (byte >> 4) as usize + 1;
+
+        if lengths.len() + repeat > symbols {
+            return Err(invalid_data("tree describes too many values."));
+        }
+
+        lengths.extend(std::iter::repeat_n((byte & 0xF) as u8 + 1, repeat));
+    }
+
+    if lengths.len() != symbols {
+        return Err(invalid_data("tree describes too few values."));
+    }
+
+    //Shannon-Fano codes are the complement of the canonical Huffman codes for the same lengths
+    Huffman::inverted(&lengths)
+}
+
+pub struct ImplodeDecompressor {
+    /// General purpose bit 1: 8K window instead of 4K.
+    pub large_window: bool,
+    /// General purpose bit 2: literals are coded with their own tree.
+    pub literal_tree: bool,
+    pub uncompressed_size: u64,
+}
+
+impl Decompressor for ImplodeDecompressor {
+    fn decompress(
+        &self,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        data_len: &u64,
+    ) -> std::io::Result<()> {
+        let mut bits = BitReader::new(BufReader::new(reader.take(*data_len)));
+
+        let literals = if self.literal_tree {
+            Some(read_tree(&mut bits, 256)?)
+        } else {
+            None
+        };
+        let lengths = read_tree(&mut bits, 64)?;
+        let distances = read_tree(&mut bits, 64)?;
+
+        let low_distance_bits = if self.large_window { 7 } else { 6 };
+        let min_match = if self.literal_tree { 3 } else { 2 };
+
+        let mut window = OutWindow::new(writer, WINDOW_SIZE);
+
+        while window.total_pos() < self.uncompressed_size {
+            if bits.read(1)? == 1 {
+                let byte = match &literals {
+                    Some(literals) => literals.decode(&mut bits)? as u8,
+                    None => bits.read(8)? as u8,
+                };
+
+                window.put_byte(byte)?;
+
+                continue;
+            }
+
+            let low = bits.read(low_distance_bits)? as usize;
+            let high = distances.decode(&mut bits)? as usize;
+            let dist = (high << low_distance_bits) + low + 1;
+
+            let mut len = lengths.decode(&mut bits)? as usize;
+
+            if len == LONG_LENGTH as usize {
+                len += bits.read(8)? as usize;
+            }
+
+            len += min_match;
+
+            let left = self.uncompressed_size - window.total_pos();
+
+            //matches may reach before the start of the data, which reads as zeros
+            window.copy_match(dist, std::cmp::min(len as u64, left) as usize)?;
+        }
+
+        window.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::implode::ImplodeDecompressor;
+    use crate::unpacker::algorithms::Decompressor;
+
+    struct BitWriter {
+        bytes: Vec<u8>,
+        acc: u64,
+        count: u32,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, value: u32, count: u32) {
+            self.acc |= (value as u64) << self.count;
+            self.count += count;
+
+            while self.count >= 8 {
+                self.bytes.push(self.acc as u8);
+                self.acc >>= 8;
+                self.count -= 8;
+            }
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            if self.count > 0 {
+                self.bytes.push(self.acc as u8);
+            }
+
+            self.bytes
+        }
+    }
+
+    #[test]
+    fn two_trees() {
+        let mut writer = BitWriter {
+            bytes: Vec::new(),
+            acc: 0,
+            count: 0,
+        };
+
+        //length and distance trees: 64 codes of 6 bits each, as 4 runs of 16
+        for _ in 0..2 {
+            writer.bits(3, 8);
+            for _ in 0..4 {
+                writer.bits(0xF5, 8);
+            }
+        }
+
+        //literal "a"
+        writer.bits(1, 1);
+        writer.bits(b'a' as u32, 8);
+
+        //match: low distance bits 0, distance code 0, length code 3; 6 bit codes are complemented
+        writer.bits(0, 1);
+        writer.bits(0, 6);
+        writer.bits(0x3F, 6);
+        writer.bits(0x3F ^ 0b110000, 6);
+
+        let bytes = writer.finish();
+        let mut out = Vec::new();
+
+        ImplodeDecompressor {
+            large_window: false,
+            literal_tree: false,
+            uncompressed_size: 6,
+        }
+        .decompress(&mut &bytes[..], &mut out, &(bytes.len() as u64))
+        .unwrap();
+
+        assert_eq!(out, b"aaaaaa");
+    }
+}
@@ -0,0 +1,713 @@
+use std::io::{BufReader, ErrorKind, Read, Write};
+
+use crate::stream_utils::byte_readers;
+use crate::unpacker::algorithms::Decompressor;
+
+/// Largest dictionary we agree to allocate, whatever the stream header asks for.
+pub const MAX_DICTIONARY_SIZE: u32 = 256 * 1024 * 1024;
+pub const MIN_DICTIONARY_SIZE: u32 = 4096;
+
+const PROPERTIES_SIZE: u16 = 5;
+
+const NUM_BIT_MODEL_TOTAL_BITS: u32 = 11;
+const BIT_MODEL_TOTAL: u16 = 1 << NUM_BIT_MODEL_TOTAL_BITS;
+const NUM_MOVE_BITS: u32 = 5;
+const TOP_VALUE: u32 = 1 << 24;
+
+const NUM_STATES: usize = 12;
+const NUM_POS_BITS_MAX: usize = 4;
+const NUM_LEN_TO_POS_STATES: usize = 4;
+const NUM_ALIGN_BITS: u32 = 4;
+const START_POS_MODEL_INDEX: u32 = 4;
+const END_POS_MODEL_INDEX: u32 = 14;
+const NUM_FULL_DISTANCES: usize = 1 << (END_POS_MODEL_INDEX >> 1);
+const MATCH_MIN_LEN: usize = 2;
+
+const PROB_INIT: u16 = BIT_MODEL_TOTAL / 2;
+
+pub fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("LZMA: {}", reason))
+}
+
+pub struct RangeDecoder<R: Read> {
+    reader: R,
+    range: u32,
+    code: u32,
+}
+
+impl<R: Read> RangeDecoder<R> {
+    pub fn new(mut reader: R) -> std::io::Result<RangeDecoder<R>> {
+        let mut bytes = [0u8; 5];
+        reader.read_exact(&mut bytes)?;
+
+        if bytes[0] != 0 {
+            return Err(invalid_data("bad range coder header."));
+        }
+
+        let code = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+
+        if code == 0xFFFF_FFFF {
+            return Err(invalid_data("bad range coder header."));
+        }
+
+        Ok(RangeDecoder {
+            reader,
+            range: 0xFFFF_FFFF,
+            code,
+        })
+    }
+
+    pub fn is_finished_ok(&self) -> bool {
+        self.code == 0
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn normalize(&mut self) -> std::io::Result<()> {
+        if self.range < TOP_VALUE {
+            let mut byte = [0u8; 1];
+            self.reader.read_exact(&mut byte)?;
+
+            self.range <<= 8;
+            self.code = (self.code << 8) | byte[0] as u32;
+        }
+
+        Ok(())
+    }
+
+    fn decode_direct_bits(&mut self, count: u32) -> std::io::Result<u32> {
+        let mut result = 0u32;
+
+        for _ in 0..count {
+            self.range >>= 1;
+            self.code = self.code.wrapping_sub(self.range);
+
+            let t = 0u32.wrapping_sub(self.code >> 31);
+            self.code = self.code.wrapping_add(self.range & t);
+
+            if self.code == self.range {
+                return Err(invalid_data("corrupted direct bits."));
+            }
+
+            self.normalize()?;
+
+            result = (result << 1).wrapping_add(t.wrapping_add(1));
+        }
+
+        Ok(result)
+    }
+
+    fn decode_bit(&mut self, prob: &mut u16) -> std::io::Result<u32> {
+        let bound = (self.range >> NUM_BIT_MODEL_TOTAL_BITS) * *prob as u32;
+
+        let bit = if self.code < bound {
+            *prob += (BIT_MODEL_TOTAL - *prob) >> NUM_MOVE_BITS;
+            self.range = bound;
+
+            0
+        } else {
+            *prob -= *prob >> NUM_MOVE_BITS;
+            self.code -= bound;
+            self.range -= bound;
+
+            1
+        };
+
+        self.normalize()?;
+
+        Ok(bit)
+    }
+
+    fn decode_tree(&mut self, probs: &mut [u16], num_bits: u32) -> std::io::Result<u32> {
+        let mut m = 1usize;
+
+        for _ in 0..num_bits {
+            m = (m << 1) + self.decode_bit(&mut probs[m])? as usize;
+        }
+
+        Ok(m as u32 - (1 << num_bits))
+    }
+
+    fn decode_reverse_tree(&mut self, probs: &mut [u16], num_bits: u32) -> std::io::Result<u32> {
+        let mut m = 1usize;
+        let mut symbol = 0u32;
+
+        for i in 0..num_bits {
+            let bit = self.decode_bit(&mut probs[m])?;
+
+            m = (m << 1) + bit as usize;
+            symbol |= bit << i;
+        }
+
+        Ok(symbol)
+    }
+}
+
+/// The sliding dictionary; everything that leaves it goes to the writer.
+pub struct OutWindow<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    pos: usize,
+    flushed_pos: usize,
+    is_full: bool,
+    total_pos: u64,
+}
+
+impl<W: Write> OutWindow<W> {
+    pub fn new(writer: W, size: u32) -> OutWindow<W> {
+        OutWindow {
+            writer,
+            buffer: vec![0u8; size as usize],
+            pos: 0,
+            flushed_pos: 0,
+            is_full: false,
+            total_pos: 0,
+        }
+    }
+
+    /// Forgets the history, as an LZMA2 dictionary reset requires; pending bytes are flushed first.
+    pub fn reset(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+
+        self.pos = 0;
+        self.flushed_pos = 0;
+        self.is_full = false;
+        self.total_pos = 0;
+
+        Ok(())
+    }
+
+    pub fn total_pos(&self) -> u64 {
+        self.total_pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == 0 && !self.is_full
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer
+            .write_all(&self.buffer[self.flushed_pos..self.pos])?;
+        self.flushed_pos = self.pos;
+
+        Ok(())
+    }
+
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Puts `bytes` into the history without passing them on, as a preset dictionary.
+    pub fn preset(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.flush()?;
+
+        for byte in bytes {
+            self.buffer[self.pos] = *byte;
+            self.pos += 1;
+
+            if self.pos == self.buffer.len() {
+                self.pos = 0;
+                self.is_full = true;
+            }
+        }
+
+        self.flushed_pos = self.pos;
+
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> std::io::Result<W> {
+        self.flush()?;
+
+        Ok(self.writer)
+    }
+
+    pub fn put_byte(&mut self, byte: u8) -> std::io::Result<()> {
+        self.total_pos += 1;
+        self.buffer[self.pos] = byte;
+        self.pos += 1;
+
+        if self.pos == self.buffer.len() {
+            self.flush()?;
+
+            self.pos = 0;
+            self.flushed_pos = 0;
+            self.is_full = true;
+        }
+
+        Ok(())
+    }
+
+    /// Byte `dist` positions back; 1 is the last byte written.
+    pub fn get_byte(&self, dist: usize) -> u8 {
+        let index = if dist <= self.pos {
+            self.pos - dist
+        } else {
+            self.buffer.len() - dist + self.pos
+        };
+
+        self.buffer[index]
+    }
+
+    pub fn has_distance(&self, dist: usize) -> bool {
+        dist <= self.pos || (self.is_full && dist <= self.buffer.len())
+    }
+
+    pub fn copy_match(&mut self, dist: usize, len: usize) -> std::io::Result<()> {
+        for _ in 0..len {
+            let byte = self.get_byte(dist);
+            self.put_byte(byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LzmaProperties {
+    pub lc: u32,
+    pub lp: u32,
+    pub pb: u32,
+}
+
+impl LzmaProperties {
+    pub fn from_byte(byte: u8) -> std::io::Result<LzmaProperties> {
+        if byte >= 9 * 5 * 5 {
+            return Err(invalid_data("bad properties."));
+        }
+
+        let byte = byte as u32;
+
+        Ok(LzmaProperties {
+            lc: byte % 9,
+            lp: (byte / 9) % 5,
+            pb: byte / 45,
+        })
+    }
+}
+
+struct LenDecoder {
+    choice: u16,
+    choice_2: u16,
+    low: [[u16; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+    mid: [[u16; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+    high: [u16; 1 << 8],
+}
+
+impl LenDecoder {
+    fn new() -> LenDecoder {
+        LenDecoder {
+            choice: PROB_INIT,
+            choice_2: PROB_INIT,
+            low: [[PROB_INIT; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+            mid: [[PROB_INIT; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+            high: [PROB_INIT; 1 << 8],
+        }
+    }
+
+    fn decode<R: Read>(
+        &mut self,
+        rc: &mut RangeDecoder<R>,
+        pos_state: usize,
+    ) -> std::io::Result<usize> {
+        if rc.decode_bit(&mut self.choice)? == 0 {
+            return Ok(rc.decode_tree(&mut self.low[pos_state], 3)? as usize);
+        }
+
+        if rc.decode_bit(&mut self.choice_2)? == 0 {
+            return Ok(8 + rc.decode_tree(&mut self.mid[pos_state], 3)? as usize);
+        }
+
+        Ok(16 + rc.decode_tree(&mut self.high, 8)? as usize)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeEnd {
+    /// The requested amount of output was produced.
+    Limit,
+    /// The stream ended with an end marker.
+    EndMarker,
+}
+
+/// LZMA model state; kept between calls so LZMA2 chunks can continue a stream.
+pub struct LzmaDecoder {
+    properties: LzmaProperties,
+    dictionary_size: u32,
+
+    literal_probs: Vec<u16>,
+    pos_slot: [[u16; 1 << 6]; NUM_LEN_TO_POS_STATES],
+    pos_decoders: [u16; 1 + NUM_FULL_DISTANCES - END_POS_MODEL_INDEX as usize],
+    align: [u16; 1 << NUM_ALIGN_BITS],
+    len_decoder: LenDecoder,
+    rep_len_decoder: LenDecoder,
+
+    is_match: [u16; NUM_STATES << NUM_POS_BITS_MAX],
+    is_rep: [u16; NUM_STATES],
+    is_rep_g0: [u16; NUM_STATES],
+    is_rep_g1: [u16; NUM_STATES],
+    is_rep_g2: [u16; NUM_STATES],
+    is_rep0_long: [u16; NUM_STATES << NUM_POS_BITS_MAX],
+
+    state: usize,
+    reps: [u32; 4],
+}
+
+impl LzmaDecoder {
+    pub fn new(properties: LzmaProperties, dictionary_size: u32) -> LzmaDecoder {
+        LzmaDecoder {
+            properties,
+            dictionary_size,
+
+            literal_probs: vec![PROB_INIT; 0x300 << (properties.lc + properties.lp)],
+            pos_slot: [[PROB_INIT; 1 << 6]; NUM_LEN_TO_POS_STATES],
+            pos_decoders: [PROB_INIT; 1 + NUM_FULL_DISTANCES - END_POS_MODEL_INDEX as usize],
+            align: [PROB_INIT; 1 << NUM_ALIGN_BITS],
+            len_decoder: LenDecoder::new(),
+            rep_len_decoder: LenDecoder::new(),
+
+            is_match: [PROB_INIT; NUM_STATES << NUM_POS_BITS_MAX],
+            is_rep: [PROB_INIT; NUM_STATES],
+            is_rep_g0: [PROB_INIT; NUM_STATES],
+            is_rep_g1: [PROB_INIT; NUM_STATES],
+            is_rep_g2: [PROB_INIT; NUM_STATES],
+            is_rep0_long: [PROB_INIT; NUM_STATES << NUM_POS_BITS_MAX],
+
+            state: 0,
+            reps: [0; 4],
+        }
+    }
+
+    fn decode_literal<R: Read, W: Write>(
+        &mut self,
+        rc: &mut RangeDecoder<R>,
+        window: &mut OutWindow<W>,
+    ) -> std::io::Result<()> {
+        let LzmaProperties { lc, lp, .. } = self.properties;
+
+        let prev_byte = if window.is_empty() {
+            0
+        } else {
+            window.get_byte(1) as u32
+        };
+
+        let lit_state =
+            (((window.total_pos() as u32) & ((1 << lp) - 1)) << lc) + (prev_byte >> (8 - lc));
+        let probs = &mut self.literal_probs[0x300 * lit_state as usize..][..0x300];
+
+        let mut symbol = 1usize;
+
+        if self.state >= 7 {
+            let mut match_byte = window.get_byte(self.reps[0] as usize + 1) as usize;
+
+            while symbol < 0x100 {
+                let match_bit = (match_byte >> 7) & 1;
+                match_byte <<= 1;
+
+                let bit = rc.decode_bit(&mut probs[((1 + match_bit) << 8) + symbol])? as usize;
+                symbol = (symbol << 1) | bit;
+
+                if match_bit != bit {
+                    break;
+                }
+            }
+        }
+
+        while symbol < 0x100 {
+            symbol = (symbol << 1) | rc.decode_bit(&mut probs[symbol])? as usize;
+        }
+
+        window.put_byte((symbol - 0x100) as u8)
+    }
+
+    fn decode_distance<R: Read>(
+        &mut self,
+        rc: &mut RangeDecoder<R>,
+        len: usize,
+    ) -> std::io::Result<u32> {
+        let len_state = std::cmp::min(len, NUM_LEN_TO_POS_STATES - 1);
+        let pos_slot = rc.decode_tree(&mut self.pos_slot[len_state], 6)?;
+
+        if pos_slot < START_POS_MODEL_INDEX {
+            return Ok(pos_slot);
+        }
+
+        let num_direct_bits = (pos_slot >> 1) - 1;
+        let mut dist = (2 | (pos_slot & 1)) << num_direct_bits;
+
+        if pos_slot < END_POS_MODEL_INDEX {
+            let probs = &mut self.pos_decoders[(dist - pos_slot) as usize..];
+            dist += rc.decode_reverse_tree(probs, num_direct_bits)?;
+        } else {
+            dist = dist.wrapping_add(
+                rc.decode_direct_bits(num_direct_bits - NUM_ALIGN_BITS)? << NUM_ALIGN_BITS,
+            );
+            dist = dist.wrapping_add(rc.decode_reverse_tree(&mut self.align, NUM_ALIGN_BITS)?);
+        }
+
+        Ok(dist)
+    }
+
+    /// Decodes until `limit` more bytes are produced or, if allowed, an end marker shows up.
+    /// Without a limit the stream must end with a marker.
+    pub fn decode<R: Read, W: Write>(
+        &mut self,
+        rc: &mut RangeDecoder<R>,
+        window: &mut OutWindow<W>,
+        limit: Option<u64>,
+        allow_end_marker: bool,
+    ) -> std::io::Result<DecodeEnd> {
+        let pb_mask = (1u64 << self.properties.pb) - 1;
+        let mut bytes_left = limit.unwrap_or(u64::MAX);
+
+        loop {
+            //a stream of known size may still carry an end marker after the last byte
+            if bytes_left == 0 && (!allow_end_marker || rc.is_finished_ok()) {
+                return Ok(DecodeEnd::Limit);
+            }
+
+            let pos_state = (window.total_pos() & pb_mask) as usize;
+            let state_index = (self.state << NUM_POS_BITS_MAX) + pos_state;
+
+            if rc.decode_bit(&mut self.is_match[state_index])? == 0 {
+                if bytes_left == 0 {
+                    return Err(invalid_data("data after the end of the stream."));
+                }
+
+                self.decode_literal(rc, window)?;
+
+                self.state = match self.state {
+                    0..=3 => 0,
+                    4..=9 => self.state - 3,
+                    _ => self.state - 6,
+                };
+
+                bytes_left -= 1;
+
+                continue;
+            }
+
+            let mut len;
+
+            if rc.decode_bit(&mut self.is_rep[self.state])? != 0 {
+                if bytes_left == 0 {
+                    return Err(invalid_data("data after the end of the stream."));
+                }
+
+                if window.is_empty() {
+                    return Err(invalid_data("repeated match in an empty window."));
+                }
+
+                if rc.decode_bit(&mut self.is_rep_g0[self.state])? == 0 {
+                    if rc.decode_bit(&mut self.is_rep0_long[state_index])? == 0 {
+                        self.state = if self.state < 7 { 9 } else { 11 };
+
+                        let byte = window.get_byte(self.reps[0] as usize + 1);
+                        window.put_byte(byte)?;
+
+                        bytes_left -= 1;
+
+                        continue;
+                    }
+                } else {
+                    let dist;
+
+                    if rc.decode_bit(&mut self.is_rep_g1[self.state])? == 0 {
+                        dist = self.reps[1];
+                    } else {
+                        if rc.decode_bit(&mut self.is_rep_g2[self.state])? == 0 {
+                            dist = self.reps[2];
+                        } else {
+                            dist = self.reps[3];
+                            self.reps[3] = self.reps[2];
+                        }
+
+                        self.reps[2] = self.reps[1];
+                    }
+
+                    self.reps[1] = self.reps[0];
+                    self.reps[0] = dist;
+                }
+
+                len = self.rep_len_decoder.decode(rc, pos_state)?;
+                self.state = if self.state < 7 { 8 } else { 11 };
+            } else {
+                self.reps[3] = self.reps[2];
+                self.reps[2] = self.reps[1];
+                self.reps[1] = self.reps[0];
+
+                len = self.len_decoder.decode(rc, pos_state)?;
+                self.state = if self.state < 7 { 7 } else { 10 };
+                self.reps[0] = self.decode_distance(rc, len)?;
+
+                if self.reps[0] == 0xFFFF_FFFF {
+                    if !allow_end_marker {
+                        return Err(invalid_data("unexpected end marker."));
+                    }
+
+                    if !rc.is_finished_ok() {
+                        return Err(invalid_data("data after the end marker."));
+                    }
+
+                    return Ok(DecodeEnd::EndMarker);
+                }
+
+                if bytes_left == 0 {
+                    return Err(invalid_data("data after the end of the stream."));
+                }
+
+                if self.reps[0] >= self.dictionary_size
+                    || !window.has_distance(self.reps[0] as usize + 1)
+                {
+                    return Err(invalid_data("match distance is out of the window."));
+                }
+            }
+
+            len += MATCH_MIN_LEN;
+
+            if (len as u64) > bytes_left {
+                return Err(invalid_data("match runs past the end of the stream."));
+            }
+
+            window.copy_match(self.reps[0] as usize + 1, len)?;
+
+            bytes_left -= len as u64;
+        }
+    }
+}
+
+/// Window size for a stream: the dictionary, but never more than the data can reference.
+pub fn window_size(dictionary_size: u32, uncompressed_size: Option<u64>) -> std::io::Result<u32> {
+    let dictionary_size = std::cmp::max(dictionary_size, MIN_DICTIONARY_SIZE);
+
+    let size = match uncompressed_size {
+        Some(size) if size < dictionary_size as u64 => {
+            std::cmp::max(size as u32, MIN_DICTIONARY_SIZE)
+        }
+        _ => dictionary_size,
+    };
+
+    if size > MAX_DICTIONARY_SIZE {
+        return Err(invalid_data("dictionary is too large."));
+    }
+
+    Ok(size)
+}
+
+pub struct LzmaDecompressor {
+    /// Set by general purpose bit 1: the stream is terminated by an end marker.
+    pub end_marker: bool,
+    /// None when the size is not known up front (it is in the data descriptor).
+    pub uncompressed_size: Option<u64>,
+}
+
+impl Decompressor for LzmaDecompressor {
+    fn decompress(
+        &self,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        data_len: &u64,
+    ) -> std::io::Result<()> {
+        let mut reader = BufReader::new(reader.take(*data_len));
+
+        //ZIP prefixes the stream with the LZMA SDK version and the size of the properties
+        let mut header = [0u8; 9];
+        reader.read_exact(&mut header)?;
+
+        let mut offset = 2;
+
+        if byte_readers::read_to::<u16>(&header, &mut offset)? != PROPERTIES_SIZE {
+            return Err(invalid_data("unexpected properties size."));
+        }
+
+        let properties = LzmaProperties::from_byte(header[4])?;
+
+        offset += 1;
+        let dictionary_size = byte_readers::read_to::<u32>(&header, &mut offset)?;
+
+        let window_size = window_size(dictionary_size, self.uncompressed_size)?;
+
+        let mut rc = RangeDecoder::new(reader)?;
+        let mut window = OutWindow::new(writer, window_size);
+        let mut decoder = LzmaDecoder::new(properties, window_size);
+
+        let end = decoder.decode(
+            &mut rc,
+            &mut window,
+            self.uncompressed_size,
+            self.end_marker || self.uncompressed_size.is_none(),
+        )?;
+
+        if self.end_marker && end != DecodeEnd::EndMarker {
+            return Err(invalid_data("missing end marker."));
+        }
+
+        window.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::lzma::{window_size, LzmaDecompressor, LzmaProperties};
+    use crate::unpacker::algorithms::Decompressor;
+
+    //ZIP LZMA header followed by lzma.compress(b"abracadabra abracadabra", FORMAT_RAW) with an end marker
+    const ABRACADABRA: [u8; 31] = [
+        9, 20, 5, 0, 93, 0, 0, 1, 0, 0, 48, 152, 138, 170, 154, 89, 245, 17, 216, 62, 177, 21, 136,
+        11, 143, 255, 255, 234, 67, 0, 0,
+    ];
+
+    fn decompress(bytes: &[u8], uncompressed_size: Option<u64>) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        LzmaDecompressor {
+            end_marker: true,
+            uncompressed_size,
+        }
+        .decompress(&mut &bytes[..], &mut out, &(bytes.len() as u64))?;
+
+        Ok(out)
+    }
+
+    #[test]
+    fn decompress_with_end_marker() {
+        assert_eq!(
+            decompress(&ABRACADABRA, Some(23)).unwrap(),
+            b"abracadabra abracadabra"
+        );
+        assert_eq!(
+            decompress(&ABRACADABRA, None).unwrap(),
+            b"abracadabra abracadabra"
+        );
+    }
+
+    #[test]
+    fn reject_wrong_size() {
+        assert!(decompress(&ABRACADABRA, Some(22)).is_err());
+        assert!(decompress(&ABRACADABRA[..26], Some(23)).is_err());
+    }
+
+    #[test]
+    fn properties() {
+        assert_eq!(
+            LzmaProperties::from_byte(93).unwrap(),
+            LzmaProperties {
+                lc: 3,
+                lp: 0,
+                pb: 2
+            }
+        );
+        assert!(LzmaProperties::from_byte(225).is_err());
+    }
+
+    #[test]
+    fn window_is_capped() {
+        assert_eq!(window_size(1 << 20, Some(100)).unwrap(), 4096);
+        assert_eq!(window_size(1 << 20, Some(300_000)).unwrap(), 300_000);
+        assert_eq!(window_size(1 << 20, None).unwrap(), 1 << 20);
+        assert_eq!(window_size(1 << 30, Some(1 << 20)).unwrap(), 1 << 20);
+        assert!(window_size(1 << 30, None).is_err());
+    }
+}
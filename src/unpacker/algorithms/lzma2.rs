@@ -0,0 +1,161 @@
+use std::io::{Read, Write};
+
+use crate::unpacker::algorithms::lzma::{
+    invalid_data, LzmaDecoder, LzmaProperties, OutWindow, RangeDecoder,
+};
+
+const CONTROL_END: u8 = 0x00;
+const CONTROL_UNCOMPRESSED_RESET: u8 = 0x01;
+const CONTROL_UNCOMPRESSED: u8 = 0x02;
+const CONTROL_LZMA: u8 = 0x80;
+
+//reset levels carried in bits 5 and 6 of an LZMA chunk control byte
+const RESET_STATE: u8 = 1;
+const RESET_PROPERTIES: u8 = 2;
+const RESET_DICTIONARY: u8 = 3;
+
+/// Dictionary size encoded in the single LZMA2 properties byte.
+pub fn dictionary_size(byte: u8) -> std::io::Result<u32> {
+    match byte {
+        0..=39 => Ok((2 | (byte as u32 & 1)) << (byte / 2 + 11)),
+        40 => Ok(0xFFFF_FFFF),
+        _ => Err(invalid_data("bad LZMA2 dictionary size.")),
+    }
+}
+
+fn read_u16_be<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u16::from_be_bytes(bytes) as u32)
+}
+
+/// Decodes LZMA2 chunks up to and including the end marker chunk.
+pub fn decode<R: Read, W: Write>(
+    reader: &mut R,
+    writer: W,
+    window_size: u32,
+) -> std::io::Result<()> {
+    let mut window = OutWindow::new(writer, window_size);
+    let mut decoder: Option<LzmaDecoder> = None;
+    let mut properties = None;
+    let mut needs_dictionary_reset = true;
+
+    loop {
+        let mut control = [0u8; 1];
+        reader.read_exact(&mut control)?;
+        let control = control[0];
+
+        if control == CONTROL_END {
+            break;
+        }
+
+        if control == CONTROL_UNCOMPRESSED_RESET || control == CONTROL_UNCOMPRESSED {
+            if control == CONTROL_UNCOMPRESSED_RESET {
+                window.reset()?;
+                needs_dictionary_reset = false;
+            } else if needs_dictionary_reset {
+                return Err(invalid_data(
+                    "LZMA2 stream does not start with a dictionary reset.",
+                ));
+            }
+
+            let size = read_u16_be(reader)? as u64 + 1;
+            let mut chunk = reader.by_ref().take(size);
+
+            let mut byte = [0u8; 1];
+            for _ in 0..size {
+                chunk.read_exact(&mut byte)?;
+                window.put_byte(byte[0])?;
+            }
+
+            continue;
+        }
+
+        if control < CONTROL_LZMA {
+            return Err(invalid_data("bad LZMA2 control byte."));
+        }
+
+        let reset = (control >> 5) & 3;
+
+        let unpacked_size = (((control & 0x1F) as u64) << 16) + read_u16_be(reader)? as u64 + 1;
+        let packed_size = read_u16_be(reader)? as u64 + 1;
+
+        if reset == RESET_DICTIONARY {
+            window.reset()?;
+            needs_dictionary_reset = false;
+        } else if needs_dictionary_reset {
+            return Err(invalid_data(
+                "LZMA2 stream does not start with a dictionary reset.",
+            ));
+        }
+
+        if reset >= RESET_PROPERTIES {
+            let mut byte = [0u8; 1];
+            reader.read_exact(&mut byte)?;
+
+            let new_properties = LzmaProperties::from_byte(byte[0])?;
+
+            if new_properties.lc + new_properties.lp > 4 {
+                return Err(invalid_data("LZMA2 allows at most 4 literal context bits."));
+            }
+
+            properties = Some(new_properties);
+        }
+
+        let properties =
+            properties.ok_or_else(|| invalid_data("LZMA2 chunk without properties."))?;
+
+        if reset >= RESET_STATE || decoder.is_none() {
+            if reset < RESET_STATE {
+                return Err(invalid_data("LZMA2 chunk without a state reset."));
+            }
+
+            decoder = Some(LzmaDecoder::new(properties, window_size));
+        }
+
+        let decoder = decoder.as_mut().unwrap();
+
+        let mut rc = RangeDecoder::new(reader.by_ref().take(packed_size))?;
+        decoder.decode(&mut rc, &mut window, Some(unpacked_size), false)?;
+
+        if !rc.is_finished_ok() || rc.into_inner().limit() != 0 {
+            return Err(invalid_data("LZMA2 chunk size mismatch."));
+        }
+    }
+
+    window.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::lzma2::{decode, dictionary_size};
+
+    #[test]
+    fn dictionary_sizes() {
+        assert_eq!(dictionary_size(0).unwrap(), 4096);
+        assert_eq!(dictionary_size(1).unwrap(), 6144);
+        assert_eq!(dictionary_size(16).unwrap(), 1 << 20);
+        assert_eq!(dictionary_size(40).unwrap(), 0xFFFF_FFFF);
+        assert!(dictionary_size(41).is_err());
+    }
+
+    #[test]
+    fn uncompressed_chunks() {
+        let stream = [
+            0x01, 0x00, 0x02, b'a', b'b', b'c', 0x02, 0x00, 0x00, b'd', 0x00,
+        ];
+        let mut out = Vec::new();
+
+        decode(&mut &stream[..], &mut out, 4096).unwrap();
+
+        assert_eq!(out, b"abcd");
+    }
+
+    #[test]
+    fn reject_missing_dictionary_reset() {
+        let stream = [0x02, 0x00, 0x00, b'd', 0x00];
+
+        assert!(decode(&mut &stream[..], Vec::new(), 4096).is_err());
+    }
+}
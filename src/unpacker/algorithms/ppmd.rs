@@ -0,0 +1,1747 @@
+use std::io::{BufReader, ErrorKind, Read, Write};
+
+use crate::unpacker::algorithms::Decompressor;
+
+//ZIP method 98 is PPMd variant I revision 1, the model that has a restoration method
+const MIN_ORDER: u32 = 2;
+const RESTORE_METHOD_RESTART: u32 = 0;
+const RESTORE_METHOD_CUT_OFF: u32 = 1;
+
+const MAX_FREQ: u32 = 124;
+const UNIT_SIZE: u32 = 12;
+const NUM_INDEXES: usize = 38;
+
+const INT_BITS: u32 = 7;
+const PERIOD_BITS: u32 = 7;
+const BIN_SCALE: u32 = 1 << (INT_BITS + PERIOD_BITS);
+
+const EMPTY_NODE: u32 = 0xFFFF_FFFF;
+
+const TOP: u32 = 1 << 24;
+const BOT: u32 = 1 << 15;
+
+const EXP_ESCAPE: [u8; 16] = [25, 14, 9, 7, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2];
+const INIT_BIN_ESC: [u16; 8] = [
+    0x3CDD, 0x1F3F, 0x59BF, 0x48F3, 0x64A1, 0x5ABC, 0x6632, 0x6051,
+];
+
+const OUT_BUFFER_SIZE: usize = 64 * 1024;
+
+fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("PPMd: {}", reason))
+}
+
+fn get_mean(prob: u32) -> u32 {
+    (prob + (1 << (PERIOD_BITS - 2))) >> PERIOD_BITS
+}
+
+/// Secondary escape estimation.
+#[derive(Clone, Copy, Default)]
+struct See {
+    summ: u16,
+    shift: u8,
+    count: u8,
+}
+
+impl See {
+    fn update(&mut self) {
+        if (self.shift as u32) < PERIOD_BITS {
+            self.count = self.count.wrapping_sub(1);
+
+            if self.count == 0 {
+                self.summ = self.summ.wrapping_shl(1);
+                self.count = (3u32 << self.shift) as u8;
+                self.shift += 1;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct State {
+    symbol: u8,
+    freq: u8,
+    successor: u32,
+}
+
+/// Dmitry Subbotin's carryless range coder.
+struct RangeDecoder<R: Read> {
+    reader: R,
+    range: u32,
+    code: u32,
+    low: u32,
+}
+
+impl<R: Read> RangeDecoder<R> {
+    fn new(mut reader: R) -> std::io::Result<RangeDecoder<R>> {
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes)?;
+
+        let code = u32::from_be_bytes(bytes);
+
+        if code == 0xFFFF_FFFF {
+            return Err(invalid_data("bad range coder header."));
+        }
+
+        Ok(RangeDecoder {
+            reader,
+            range: 0xFFFF_FFFF,
+            code,
+            low: 0,
+        })
+    }
+
+    fn threshold(&mut self, total: u32) -> u32 {
+        self.range /= total;
+        self.code / self.range
+    }
+
+    fn decode(&mut self, start: u32, size: u32) -> std::io::Result<()> {
+        let start = start.wrapping_mul(self.range);
+        self.low = self.low.wrapping_add(start);
+        self.code = self.code.wrapping_sub(start);
+        self.range = self.range.wrapping_mul(size);
+
+        loop {
+            if (self.low ^ self.low.wrapping_add(self.range)) >= TOP {
+                if self.range >= BOT {
+                    break;
+                }
+
+                self.range = self.low.wrapping_neg() & (BOT - 1);
+            }
+
+            let mut byte = [0u8; 1];
+            self.reader.read_exact(&mut byte)?;
+
+            self.code = (self.code << 8) | byte[0] as u32;
+            self.range <<= 8;
+            self.low <<= 8;
+        }
+
+        Ok(())
+    }
+}
+
+/// The PPMd model lives in one block of the declared size; contexts, symbol
+/// lists and the text history are offsets into it, like in the reference coder.
+struct Model {
+    mem: Vec<u8>,
+    size: u32,
+    align_offset: u32,
+
+    min_context: u32,
+    max_context: u32,
+    found_state: u32,
+    order_fall: u32,
+    init_esc: u32,
+    prev_success: u32,
+    max_order: u32,
+    run_length: i32,
+    init_rl: i32,
+    restore_method: u32,
+
+    glue_count: u32,
+    lo_unit: u32,
+    hi_unit: u32,
+    text: u32,
+    units_start: u32,
+
+    indx2units: [u8; NUM_INDEXES],
+    units2indx: [u8; 128],
+    free_list: [u32; NUM_INDEXES],
+    stamps: [u32; NUM_INDEXES],
+    ns2indx: [u8; 260],
+    ns2bs_indx: [u8; 256],
+
+    see: [[See; 32]; 24],
+    bin_summ: [[u16; 64]; 25],
+}
+
+impl Model {
+    fn new(max_order: u32, size: u32, restore_method: u32) -> Model {
+        let align_offset = 4 - (size & 3);
+
+        let mut model = Model {
+            mem: vec![0u8; (align_offset + size) as usize],
+            size,
+            align_offset,
+            min_context: 0,
+            max_context: 0,
+            found_state: 0,
+            order_fall: 0,
+            init_esc: 0,
+            prev_success: 0,
+            max_order,
+            run_length: 0,
+            init_rl: 0,
+            restore_method,
+            glue_count: 0,
+            lo_unit: 0,
+            hi_unit: 0,
+            text: 0,
+            units_start: 0,
+            indx2units: [0; NUM_INDEXES],
+            units2indx: [0; 128],
+            free_list: [0; NUM_INDEXES],
+            stamps: [0; NUM_INDEXES],
+            ns2indx: [0; 260],
+            ns2bs_indx: [0; 256],
+            see: [[See::default(); 32]; 24],
+            bin_summ: [[0; 64]; 25],
+        };
+
+        //block sizes in units: 1-4 step 1, then step 2, 3, and 4 up to 128
+        let mut k = 0;
+        for i in 0..NUM_INDEXES {
+            let step = if i >= 12 { 4 } else { (i >> 2) + 1 };
+
+            for _ in 0..step {
+                model.units2indx[k] = i as u8;
+                k += 1;
+            }
+
+            model.indx2units[i] = k as u8;
+        }
+
+        model.ns2bs_indx[0] = 0;
+        model.ns2bs_indx[1] = 2;
+        model.ns2bs_indx[2..11].fill(4);
+        model.ns2bs_indx[11..].fill(6);
+
+        let mut m = 5;
+        let mut k = 1;
+        for i in 0..260 {
+            if i < 5 {
+                model.ns2indx[i] = i as u8;
+                continue;
+            }
+
+            model.ns2indx[i] = m as u8;
+            k -= 1;
+
+            if k == 0 {
+                m += 1;
+                k = m - 4;
+            }
+        }
+
+        model.restart();
+
+        model
+    }
+
+    fn byte(&self, at: u32) -> u8 {
+        self.mem[at as usize]
+    }
+
+    fn set_byte(&mut self, at: u32, value: u8) {
+        self.mem[at as usize] = value;
+    }
+
+    fn word(&self, at: u32) -> u16 {
+        let at = at as usize;
+        u16::from_le_bytes([self.mem[at], self.mem[at + 1]])
+    }
+
+    fn set_word(&mut self, at: u32, value: u16) {
+        let at = at as usize;
+        self.mem[at..at + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn dword(&self, at: u32) -> u32 {
+        let at = at as usize;
+        u32::from_le_bytes([
+            self.mem[at],
+            self.mem[at + 1],
+            self.mem[at + 2],
+            self.mem[at + 3],
+        ])
+    }
+
+    fn set_dword(&mut self, at: u32, value: u32) {
+        let at = at as usize;
+        self.mem[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    //context: num_stats (symbols - 1), flags, summ_freq, stats, suffix;
+    //a context with one symbol keeps its state in place of summ_freq and stats
+    fn num_stats(&self, ctx: u32) -> u32 {
+        self.byte(ctx) as u32
+    }
+
+    fn set_num_stats(&mut self, ctx: u32, value: u32) {
+        self.set_byte(ctx, value as u8);
+    }
+
+    fn flags(&self, ctx: u32) -> u8 {
+        self.byte(ctx + 1)
+    }
+
+    fn set_flags(&mut self, ctx: u32, value: u8) {
+        self.set_byte(ctx + 1, value);
+    }
+
+    fn summ_freq(&self, ctx: u32) -> u32 {
+        self.word(ctx + 2) as u32
+    }
+
+    fn set_summ_freq(&mut self, ctx: u32, value: u32) {
+        self.set_word(ctx + 2, value as u16);
+    }
+
+    fn stats(&self, ctx: u32) -> u32 {
+        self.dword(ctx + 4)
+    }
+
+    fn set_stats(&mut self, ctx: u32, value: u32) {
+        self.set_dword(ctx + 4, value);
+    }
+
+    fn suffix(&self, ctx: u32) -> u32 {
+        self.dword(ctx + 8)
+    }
+
+    fn set_suffix(&mut self, ctx: u32, value: u32) {
+        self.set_dword(ctx + 8, value);
+    }
+
+    fn one_state(ctx: u32) -> u32 {
+        ctx + 2
+    }
+
+    //state: symbol, freq, successor
+    fn symbol(&self, s: u32) -> u8 {
+        self.byte(s)
+    }
+
+    fn freq(&self, s: u32) -> u32 {
+        self.byte(s + 1) as u32
+    }
+
+    fn set_freq(&mut self, s: u32, value: u32) {
+        self.set_byte(s + 1, value as u8);
+    }
+
+    fn successor(&self, s: u32) -> u32 {
+        self.dword(s + 2)
+    }
+
+    fn set_successor(&mut self, s: u32, value: u32) {
+        self.set_dword(s + 2, value);
+    }
+
+    fn state(&self, s: u32) -> State {
+        State {
+            symbol: self.symbol(s),
+            freq: self.byte(s + 1),
+            successor: self.successor(s),
+        }
+    }
+
+    fn set_state(&mut self, s: u32, state: State) {
+        self.set_byte(s, state.symbol);
+        self.set_byte(s + 1, state.freq);
+        self.set_successor(s, state.successor);
+    }
+
+    fn swap_states(&mut self, s1: u32, s2: u32) {
+        let state = self.state(s1);
+        self.set_state(s1, self.state(s2));
+        self.set_state(s2, state);
+    }
+
+    fn high_bit_flag(symbol: u8) -> u8 {
+        if symbol >= 0x40 {
+            0x08
+        } else {
+            0
+        }
+    }
+
+    //free blocks: stamp, next, number of units
+    fn nu(&self, node: u32) -> u32 {
+        self.dword(node + 8)
+    }
+
+    fn set_nu(&mut self, node: u32, value: u32) {
+        self.set_dword(node + 8, value);
+    }
+
+    fn next(&self, node: u32) -> u32 {
+        self.dword(node + 4)
+    }
+
+    fn i2u(&self, indx: usize) -> u32 {
+        self.indx2units[indx] as u32
+    }
+
+    fn u2i(&self, nu: u32) -> usize {
+        self.units2indx[nu as usize - 1] as usize
+    }
+
+    fn copy_units(&mut self, dest: u32, src: u32, nu: u32) {
+        let src = src as usize;
+        self.mem
+            .copy_within(src..src + (nu * UNIT_SIZE) as usize, dest as usize);
+    }
+
+    fn insert_node(&mut self, node: u32, indx: usize) {
+        self.set_dword(node, EMPTY_NODE);
+        self.set_dword(node + 4, self.free_list[indx]);
+        self.set_nu(node, self.i2u(indx));
+        self.free_list[indx] = node;
+        self.stamps[indx] = self.stamps[indx].wrapping_add(1);
+    }
+
+    fn remove_node(&mut self, indx: usize) -> u32 {
+        let node = self.free_list[indx];
+        self.free_list[indx] = self.next(node);
+        self.stamps[indx] = self.stamps[indx].wrapping_sub(1);
+
+        node
+    }
+
+    fn split_block(&mut self, ptr: u32, old_indx: usize, new_indx: usize) {
+        let nu = self.i2u(old_indx) - self.i2u(new_indx);
+        let ptr = ptr + self.i2u(new_indx) * UNIT_SIZE;
+        let mut i = self.u2i(nu);
+
+        if self.i2u(i) != nu {
+            i -= 1;
+            let k = self.i2u(i);
+            self.insert_node(ptr + k * UNIT_SIZE, (nu - k - 1) as usize);
+        }
+
+        self.insert_node(ptr, i);
+    }
+
+    fn glue_free_blocks(&mut self) {
+        self.glue_count = 1 << 13;
+        self.stamps = [0; NUM_INDEXES];
+
+        //a zero stamp at lo_unit stops the merging of blocks below it
+        if self.lo_unit != self.hi_unit {
+            self.set_dword(self.lo_unit, 0);
+        }
+
+        //chain every free block together, merging it with the free blocks right after it
+        let mut head = 0;
+        let mut prev: Option<u32> = None;
+
+        for i in 0..NUM_INDEXES {
+            let mut next = self.free_list[i];
+            self.free_list[i] = 0;
+
+            while next != 0 {
+                let node = next;
+                let mut nu = self.nu(node);
+
+                match prev {
+                    Some(prev) => self.set_dword(prev, node),
+                    None => head = node,
+                }
+
+                next = self.next(node);
+
+                if nu != 0 {
+                    prev = Some(node + 4);
+
+                    loop {
+                        let node2 = node + nu * UNIT_SIZE;
+
+                        if self.dword(node2) != EMPTY_NODE {
+                            break;
+                        }
+
+                        nu += self.nu(node2);
+                        self.set_nu(node2, 0);
+                    }
+
+                    self.set_nu(node, nu);
+                }
+            }
+        }
+
+        match prev {
+            Some(prev) => self.set_dword(prev, 0),
+            None => head = 0,
+        }
+
+        let mut next = head;
+
+        while next != 0 {
+            let mut node = next;
+            let mut nu = self.nu(node);
+            next = self.next(node);
+
+            if nu == 0 {
+                continue;
+            }
+
+            while nu > 128 {
+                self.insert_node(node, NUM_INDEXES - 1);
+                nu -= 128;
+                node += 128 * UNIT_SIZE;
+            }
+
+            let mut i = self.u2i(nu);
+
+            if self.i2u(i) != nu {
+                i -= 1;
+                let k = self.i2u(i);
+                self.insert_node(node + k * UNIT_SIZE, (nu - k - 1) as usize);
+            }
+
+            self.insert_node(node, i);
+        }
+    }
+
+    /// Returns 0 when the memory is exhausted.
+    fn alloc_units_rare(&mut self, indx: usize) -> u32 {
+        if self.glue_count == 0 {
+            self.glue_free_blocks();
+
+            if self.free_list[indx] != 0 {
+                return self.remove_node(indx);
+            }
+        }
+
+        let mut i = indx;
+
+        loop {
+            i += 1;
+
+            if i == NUM_INDEXES {
+                //take the units from the end of the text area
+                let num_bytes = self.i2u(indx) * UNIT_SIZE;
+                self.glue_count = self.glue_count.wrapping_sub(1);
+
+                return if self.units_start - self.text > num_bytes {
+                    self.units_start -= num_bytes;
+                    self.units_start
+                } else {
+                    0
+                };
+            }
+
+            if self.free_list[i] != 0 {
+                break;
+            }
+        }
+
+        let block = self.remove_node(i);
+        self.split_block(block, i, indx);
+
+        block
+    }
+
+    fn alloc_units(&mut self, indx: usize) -> u32 {
+        if self.free_list[indx] != 0 {
+            return self.remove_node(indx);
+        }
+
+        let num_bytes = self.i2u(indx) * UNIT_SIZE;
+
+        if num_bytes <= self.hi_unit - self.lo_unit {
+            let block = self.lo_unit;
+            self.lo_unit += num_bytes;
+
+            return block;
+        }
+
+        self.alloc_units_rare(indx)
+    }
+
+    fn shrink_units(&mut self, old: u32, old_nu: u32, new_nu: u32) -> u32 {
+        let i0 = self.u2i(old_nu);
+        let i1 = self.u2i(new_nu);
+
+        if i0 == i1 {
+            return old;
+        }
+
+        if self.free_list[i1] != 0 {
+            let block = self.remove_node(i1);
+            self.copy_units(block, old, new_nu);
+            self.insert_node(old, i0);
+
+            return block;
+        }
+
+        self.split_block(old, i0, i1);
+
+        old
+    }
+
+    fn free_units(&mut self, ptr: u32, nu: u32) {
+        let indx = self.u2i(nu);
+        self.insert_node(ptr, indx);
+    }
+
+    fn special_free_unit(&mut self, ptr: u32) {
+        if ptr != self.units_start {
+            self.insert_node(ptr, 0);
+        } else {
+            self.units_start += UNIT_SIZE;
+        }
+    }
+
+    fn move_units_up(&mut self, old: u32, nu: u32) -> u32 {
+        let indx = self.u2i(nu);
+
+        if old > self.units_start + 16 * 1024 || old > self.free_list[indx] {
+            return old;
+        }
+
+        let block = self.remove_node(indx);
+        self.copy_units(block, old, nu);
+
+        if old != self.units_start {
+            self.insert_node(old, indx);
+        } else {
+            self.units_start += self.i2u(indx) * UNIT_SIZE;
+        }
+
+        block
+    }
+
+    /// Gives the free blocks at the start of the units area back to the text.
+    fn expand_text_area(&mut self) {
+        let mut count = [0u32; NUM_INDEXES];
+
+        if self.lo_unit != self.hi_unit {
+            self.set_dword(self.lo_unit, 0);
+        }
+
+        let mut node = self.units_start;
+
+        while self.dword(node) == EMPTY_NODE {
+            let nu = self.nu(node);
+            self.set_dword(node, 0);
+            count[self.u2i(nu)] += 1;
+            node += nu * UNIT_SIZE;
+        }
+
+        self.units_start = node;
+
+        for (i, &count) in count.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+
+            let mut left = count;
+            let mut prev: Option<u32> = None;
+            let mut next = self.free_list[i];
+
+            self.stamps[i] = self.stamps[i].wrapping_sub(count);
+
+            loop {
+                let node = next;
+                next = self.next(node);
+
+                if self.dword(node) != 0 {
+                    prev = Some(node + 4);
+                    continue;
+                }
+
+                match prev {
+                    Some(prev) => self.set_dword(prev, next),
+                    None => self.free_list[i] = next,
+                }
+
+                left -= 1;
+
+                if left == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn used_memory(&self) -> u32 {
+        let units = (0..NUM_INDEXES).fold(0u32, |units, i| {
+            units.wrapping_add(self.stamps[i].wrapping_mul(self.i2u(i)))
+        });
+
+        self.size
+            .wrapping_sub(self.hi_unit - self.lo_unit)
+            .wrapping_sub(self.units_start - self.text)
+            .wrapping_sub(units.wrapping_mul(UNIT_SIZE))
+    }
+
+    fn restart(&mut self) {
+        self.free_list = [0; NUM_INDEXES];
+        self.stamps = [0; NUM_INDEXES];
+
+        self.text = self.align_offset;
+        self.hi_unit = self.text + self.size;
+        self.lo_unit = self.hi_unit - self.size / 8 / UNIT_SIZE * 7 * UNIT_SIZE;
+        self.units_start = self.lo_unit;
+        self.glue_count = 0;
+
+        self.order_fall = self.max_order;
+        self.init_rl = -(std::cmp::min(self.max_order, 12) as i32) - 1;
+        self.run_length = self.init_rl;
+        self.prev_success = 0;
+
+        //the order 0 context sits in the last unit and holds all 256 symbols
+        self.hi_unit -= UNIT_SIZE;
+        let root = self.hi_unit;
+        self.min_context = root;
+        self.max_context = root;
+        self.set_suffix(root, 0);
+        self.set_num_stats(root, 255);
+        self.set_flags(root, 0);
+        self.set_summ_freq(root, 256 + 1);
+
+        self.found_state = self.lo_unit;
+        self.set_stats(root, self.lo_unit);
+        self.lo_unit += 256 / 2 * UNIT_SIZE;
+
+        for i in 0..256 {
+            self.set_state(
+                self.found_state + i * 6,
+                State {
+                    symbol: i as u8,
+                    freq: 1,
+                    successor: 0,
+                },
+            );
+        }
+
+        let mut i = 0;
+        for m in 0..25 {
+            while self.ns2indx[i] as usize == m {
+                i += 1;
+            }
+
+            for (k, &esc) in INIT_BIN_ESC.iter().enumerate() {
+                let value = (BIN_SCALE - esc as u32 / (i as u32 + 1)) as u16;
+
+                for r in (0..64).step_by(8) {
+                    self.bin_summ[m][k + r] = value;
+                }
+            }
+        }
+
+        let mut i = 0;
+        for m in 0..24 {
+            while self.ns2indx[i + 3] as usize == m + 3 {
+                i += 1;
+            }
+
+            for see in self.see[m].iter_mut() {
+                see.shift = (PERIOD_BITS - 4) as u8;
+                see.summ = ((2 * i as u32 + 5) << see.shift) as u16;
+                see.count = 7;
+            }
+        }
+    }
+
+    fn refresh(&mut self, ctx: u32, old_nu: u32, scale: u32) {
+        let mut i = self.num_stats(ctx);
+        let mut s = self.shrink_units(self.stats(ctx), old_nu, (i + 2) >> 1);
+        self.set_stats(ctx, s);
+
+        let mut flags =
+            (self.flags(ctx) & (0x10 + 0x04 * scale as u8)) + Model::high_bit_flag(self.symbol(s));
+        let mut esc_freq = self.summ_freq(ctx).wrapping_sub(self.freq(s));
+        let mut sum_freq = (self.freq(s) + scale) >> scale;
+        self.set_freq(s, sum_freq);
+
+        loop {
+            s += 6;
+            esc_freq = esc_freq.wrapping_sub(self.freq(s));
+
+            let freq = (self.freq(s) + scale) >> scale;
+            self.set_freq(s, freq);
+            sum_freq += freq;
+            flags |= Model::high_bit_flag(self.symbol(s));
+
+            i -= 1;
+            if i == 0 {
+                break;
+            }
+        }
+
+        self.set_summ_freq(ctx, sum_freq + (esc_freq.wrapping_add(scale) >> scale));
+        self.set_flags(ctx, flags);
+    }
+
+    /// Drops the successors that point into the text area and the contexts above the maximum order.
+    fn cut_off(&mut self, ctx: u32, order: u32) -> u32 {
+        if self.num_stats(ctx) == 0 {
+            let s = Model::one_state(ctx);
+
+            if self.successor(s) >= self.units_start {
+                let successor = if order < self.max_order {
+                    self.cut_off(self.successor(s), order + 1)
+                } else {
+                    0
+                };
+
+                self.set_successor(s, successor);
+
+                if successor != 0 || order <= 9 {
+                    return ctx;
+                }
+            }
+
+            self.special_free_unit(ctx);
+
+            return 0;
+        }
+
+        let nu = (self.num_stats(ctx) + 2) >> 1;
+        let stats = self.move_units_up(self.stats(ctx), nu);
+        self.set_stats(ctx, stats);
+
+        let mut i = self.num_stats(ctx) as i32;
+        let mut s = stats + i as u32 * 6;
+
+        loop {
+            if self.successor(s) < self.units_start {
+                let s2 = stats + i as u32 * 6;
+                i -= 1;
+                self.set_successor(s, 0);
+                self.swap_states(s, s2);
+            } else if order < self.max_order {
+                let successor = self.cut_off(self.successor(s), order + 1);
+                self.set_successor(s, successor);
+            } else {
+                self.set_successor(s, 0);
+            }
+
+            if s == stats {
+                break;
+            }
+
+            s -= 6;
+        }
+
+        if i != self.num_stats(ctx) as i32 && order != 0 {
+            self.set_num_stats(ctx, i as u32);
+
+            if i < 0 {
+                self.free_units(stats, nu);
+                self.special_free_unit(ctx);
+
+                return 0;
+            }
+
+            if i == 0 {
+                let state = self.state(stats);
+                self.set_flags(
+                    ctx,
+                    (self.flags(ctx) & 0x10) + Model::high_bit_flag(state.symbol),
+                );
+                self.set_state(Model::one_state(ctx), state);
+                self.free_units(stats, nu);
+
+                let one = Model::one_state(ctx);
+                self.set_freq(one, (self.freq(one) + 11) >> 3);
+            } else {
+                let scale = (self.summ_freq(ctx) > 16 * i as u32) as u32;
+                self.refresh(ctx, nu, scale);
+            }
+        }
+
+        ctx
+    }
+
+    /// Recovers from exhausted memory by restarting or by cutting the model down.
+    fn restore(&mut self, c1: u32) {
+        self.text = self.align_offset;
+
+        //contexts from max_context to c1 were already given the new symbol; take it back
+        let mut c = self.max_context;
+
+        while c != c1 {
+            let num_stats = self.num_stats(c).wrapping_sub(1) & 0xFF;
+            self.set_num_stats(c, num_stats);
+
+            if num_stats == 0 {
+                let s = self.stats(c);
+                let state = self.state(s);
+                self.set_flags(
+                    c,
+                    (self.flags(c) & 0x10) + Model::high_bit_flag(state.symbol),
+                );
+                self.set_state(Model::one_state(c), state);
+                self.special_free_unit(s);
+
+                let one = Model::one_state(c);
+                self.set_freq(one, (self.freq(one) + 11) >> 3);
+            } else {
+                self.refresh(c, (num_stats + 3) >> 1, 0);
+            }
+
+            c = self.suffix(c);
+        }
+
+        while c != self.min_context {
+            if self.num_stats(c) == 0 {
+                let one = Model::one_state(c);
+                let freq = self.freq(one);
+                self.set_freq(one, freq - (freq >> 1));
+            } else {
+                let summ_freq = (self.summ_freq(c) + 4) & 0xFFFF;
+                self.set_summ_freq(c, summ_freq);
+
+                if summ_freq > 128 + 4 * self.num_stats(c) {
+                    self.refresh(c, (self.num_stats(c) + 2) >> 1, 1);
+                }
+            }
+
+            c = self.suffix(c);
+        }
+
+        if self.restore_method == RESTORE_METHOD_RESTART || self.used_memory() < (self.size >> 1) {
+            self.restart();
+        } else {
+            while self.suffix(self.max_context) != 0 {
+                self.max_context = self.suffix(self.max_context);
+            }
+
+            loop {
+                self.cut_off(self.max_context, 0);
+                self.expand_text_area();
+
+                if self.used_memory() <= 3 * (self.size >> 2) {
+                    break;
+                }
+            }
+
+            self.glue_count = 0;
+            self.order_fall = self.max_order;
+        }
+    }
+
+    /// Returns 0 when the memory is exhausted.
+    fn create_successors(&mut self, skip: bool, s1: u32, ctx: u32) -> u32 {
+        let found_symbol = self.symbol(self.found_state);
+        let up_branch = self.successor(self.found_state);
+
+        let mut ps = Vec::with_capacity(self.max_order as usize + 1);
+        let mut c = ctx;
+        let mut s1 = s1;
+
+        if !skip {
+            ps.push(self.found_state);
+        }
+
+        while self.suffix(c) != 0 {
+            c = self.suffix(c);
+
+            let s = if s1 != 0 {
+                std::mem::replace(&mut s1, 0)
+            } else if self.num_stats(c) != 0 {
+                let mut s = self.stats(c);
+
+                while self.symbol(s) != found_symbol {
+                    s += 6;
+                }
+
+                if self.freq(s) < MAX_FREQ - 9 {
+                    self.set_freq(s, self.freq(s) + 1);
+                    self.set_summ_freq(c, self.summ_freq(c) + 1);
+                }
+
+                s
+            } else {
+                let s = Model::one_state(c);
+                let suffix_is_binary = self.num_stats(self.suffix(c)) == 0;
+                let freq = self.freq(s) + (suffix_is_binary && self.freq(s) < 24) as u32;
+                self.set_freq(s, freq);
+
+                s
+            };
+
+            let successor = self.successor(s);
+
+            if successor != up_branch {
+                c = successor;
+
+                if ps.is_empty() {
+                    return c;
+                }
+
+                break;
+            }
+
+            ps.push(s);
+        }
+
+        //the new contexts predict the symbol that followed in the text
+        let up_symbol = self.byte(up_branch);
+        let flags = (Model::high_bit_flag(found_symbol) << 1) + Model::high_bit_flag(up_symbol);
+
+        let up_freq = if self.num_stats(c) == 0 {
+            self.freq(Model::one_state(c))
+        } else {
+            let mut s = self.stats(c);
+
+            while self.symbol(s) != up_symbol {
+                s += 6;
+            }
+
+            let cf = self.freq(s) - 1;
+            let s0 = self.summ_freq(c) - self.num_stats(c) - cf;
+
+            1 + if 2 * cf <= s0 {
+                (5 * cf > s0) as u32
+            } else {
+                (cf + 2 * s0 - 3) / s0
+            }
+        };
+
+        let up_state = State {
+            symbol: up_symbol,
+            freq: up_freq as u8,
+            successor: up_branch + 1,
+        };
+
+        while let Some(s) = ps.pop() {
+            let c1 = if self.hi_unit != self.lo_unit {
+                self.hi_unit -= UNIT_SIZE;
+                self.hi_unit
+            } else if self.free_list[0] != 0 {
+                self.remove_node(0)
+            } else {
+                match self.alloc_units_rare(0) {
+                    0 => return 0,
+                    c1 => c1,
+                }
+            };
+
+            self.set_num_stats(c1, 0);
+            self.set_flags(c1, flags);
+            self.set_state(Model::one_state(c1), up_state);
+            self.set_suffix(c1, c);
+            self.set_successor(s, c1);
+
+            c = c1;
+        }
+
+        c
+    }
+
+    /// Returns 0 when the memory is exhausted.
+    fn reduce_order(&mut self, s1: u32, ctx: u32) -> u32 {
+        let found_symbol = self.symbol(self.found_state);
+        let up_branch = self.text;
+
+        let mut c = ctx;
+        let mut s1 = s1;
+        let mut s;
+
+        self.set_successor(self.found_state, up_branch);
+        self.order_fall += 1;
+
+        loop {
+            if s1 != 0 {
+                c = self.suffix(c);
+                s = std::mem::replace(&mut s1, 0);
+            } else {
+                if self.suffix(c) == 0 {
+                    return c;
+                }
+
+                c = self.suffix(c);
+
+                if self.num_stats(c) != 0 {
+                    s = self.stats(c);
+
+                    while self.symbol(s) != found_symbol {
+                        s += 6;
+                    }
+
+                    if self.freq(s) < MAX_FREQ - 9 {
+                        self.set_freq(s, self.freq(s) + 2);
+                        self.set_summ_freq(c, self.summ_freq(c) + 2);
+                    }
+                } else {
+                    s = Model::one_state(c);
+                    self.set_freq(s, self.freq(s) + (self.freq(s) < 32) as u32);
+                }
+            }
+
+            if self.successor(s) != 0 {
+                break;
+            }
+
+            self.set_successor(s, up_branch);
+            self.order_fall += 1;
+        }
+
+        if self.successor(s) <= up_branch {
+            let found_state = std::mem::replace(&mut self.found_state, s);
+            let successor = self.create_successors(false, 0, c);
+            self.set_successor(s, successor);
+            self.found_state = found_state;
+        }
+
+        if self.order_fall == 1 && ctx == self.max_context {
+            self.set_successor(self.found_state, self.successor(s));
+            self.text -= 1;
+        }
+
+        self.successor(s)
+    }
+
+    fn update_model(&mut self) {
+        let found_symbol = self.symbol(self.found_state);
+        let found_freq = self.freq(self.found_state);
+        let mut found_successor = self.successor(self.found_state);
+
+        //the state of the found symbol in the suffix context, if it was updated
+        let mut s = 0;
+
+        if found_freq < MAX_FREQ / 4 && self.suffix(self.min_context) != 0 {
+            let c = self.suffix(self.min_context);
+
+            if self.num_stats(c) == 0 {
+                s = Model::one_state(c);
+
+                if self.freq(s) < 32 {
+                    self.set_freq(s, self.freq(s) + 1);
+                }
+            } else {
+                s = self.stats(c);
+
+                if self.symbol(s) != found_symbol {
+                    loop {
+                        s += 6;
+
+                        if self.symbol(s) == found_symbol {
+                            break;
+                        }
+                    }
+
+                    if self.freq(s) >= self.freq(s - 6) {
+                        self.swap_states(s, s - 6);
+                        s -= 6;
+                    }
+                }
+
+                if self.freq(s) < MAX_FREQ - 9 {
+                    self.set_freq(s, self.freq(s) + 2);
+                    self.set_summ_freq(c, self.summ_freq(c) + 2);
+                }
+            }
+        }
+
+        let max_context = self.max_context;
+
+        if self.order_fall == 0 && found_successor != 0 {
+            let cs = self.create_successors(true, s, self.min_context);
+            self.set_successor(self.found_state, cs);
+
+            if cs == 0 {
+                self.restore(max_context);
+            } else {
+                self.max_context = cs;
+            }
+
+            return;
+        }
+
+        self.set_byte(self.text, found_symbol);
+        self.text += 1;
+        let mut successor = self.text;
+
+        if self.text >= self.units_start {
+            self.restore(max_context);
+            return;
+        }
+
+        if found_successor == 0 {
+            found_successor = self.reduce_order(s, self.min_context);
+        } else if found_successor < self.units_start {
+            found_successor = self.create_successors(false, s, self.min_context);
+        }
+
+        if found_successor == 0 {
+            self.restore(max_context);
+            return;
+        }
+
+        self.order_fall -= 1;
+
+        if self.order_fall == 0 {
+            successor = found_successor;
+
+            if self.max_context != self.min_context {
+                self.text -= 1;
+            }
+        }
+
+        let ns = self.num_stats(self.min_context);
+        let s0 = self
+            .summ_freq(self.min_context)
+            .wrapping_sub(ns)
+            .wrapping_sub(found_freq);
+        let flag = Model::high_bit_flag(found_symbol);
+
+        //add the symbol to every context between max_context and min_context
+        let mut c = max_context;
+
+        while c != self.min_context {
+            let ns1 = self.num_stats(c);
+
+            if ns1 != 0 {
+                if ns1 & 1 != 0 {
+                    let old_nu = (ns1 + 1) >> 1;
+                    let i = self.u2i(old_nu);
+
+                    if i != self.u2i(old_nu + 1) {
+                        let block = self.alloc_units(i + 1);
+
+                        if block == 0 {
+                            self.restore(c);
+                            return;
+                        }
+
+                        let old = self.stats(c);
+                        self.copy_units(block, old, old_nu);
+                        self.insert_node(old, i);
+                        self.set_stats(c, block);
+                    }
+                }
+
+                let summ_freq = self.summ_freq(c) + (3 * ns1 + 1 < ns) as u32;
+                self.set_summ_freq(c, summ_freq);
+            } else {
+                let s2 = self.alloc_units(0);
+
+                if s2 == 0 {
+                    self.restore(c);
+                    return;
+                }
+
+                let state = self.state(Model::one_state(c));
+                self.set_state(s2, state);
+                self.set_stats(c, s2);
+
+                let freq = state.freq as u32;
+                let freq = if freq < MAX_FREQ / 4 - 1 {
+                    freq << 1
+                } else {
+                    MAX_FREQ - 4
+                };
+
+                self.set_freq(s2, freq);
+                self.set_summ_freq(c, freq + self.init_esc + (ns > 2) as u32);
+            }
+
+            let summ_freq = self.summ_freq(c);
+            let mut cf = 2 * found_freq * (summ_freq + 6);
+            let sf = s0.wrapping_add(summ_freq);
+
+            if cf < 6 * sf {
+                cf = 1 + (cf > sf) as u32 + (cf >= 4 * sf) as u32;
+                self.set_summ_freq(c, summ_freq + 4);
+            } else {
+                cf = 4 + (cf > 9 * sf) as u32 + (cf > 12 * sf) as u32 + (cf > 15 * sf) as u32;
+                self.set_summ_freq(c, summ_freq + cf);
+            }
+
+            let s2 = self.stats(c) + (ns1 + 1) * 6;
+            self.set_state(
+                s2,
+                State {
+                    symbol: found_symbol,
+                    freq: cf as u8,
+                    successor,
+                },
+            );
+            self.set_flags(c, self.flags(c) | flag);
+            self.set_num_stats(c, ns1 + 1);
+
+            c = self.suffix(c);
+        }
+
+        self.max_context = found_successor;
+        self.min_context = found_successor;
+    }
+
+    fn rescale(&mut self) {
+        let mc = self.min_context;
+        let stats = self.stats(mc);
+        let mut s = self.found_state;
+
+        //move the found state to the front
+        if s != stats {
+            let state = self.state(s);
+
+            while s != stats {
+                self.set_state(s, self.state(s - 6));
+                s -= 6;
+            }
+
+            self.set_state(s, state);
+        }
+
+        let mut esc_freq = self.summ_freq(mc).wrapping_sub(self.freq(s));
+        let adder = (self.order_fall != 0) as u32;
+
+        let freq = (self.freq(s) + 4 + adder) >> 1;
+        self.set_freq(s, freq);
+        let mut sum_freq = freq;
+
+        let mut i = self.num_stats(mc);
+
+        loop {
+            s += 6;
+            esc_freq = esc_freq.wrapping_sub(self.freq(s));
+
+            let freq = (self.freq(s) + adder) >> 1;
+            self.set_freq(s, freq);
+            sum_freq += freq;
+
+            //keep the list sorted by frequency
+            if freq > self.freq(s - 6) {
+                let state = self.state(s);
+                let mut s1 = s;
+
+                loop {
+                    self.set_state(s1, self.state(s1 - 6));
+                    s1 -= 6;
+
+                    if s1 == stats || state.freq as u32 <= self.freq(s1 - 6) {
+                        break;
+                    }
+                }
+
+                self.set_state(s1, state);
+            }
+
+            i -= 1;
+            if i == 0 {
+                break;
+            }
+        }
+
+        if self.freq(s) == 0 {
+            let num_stats = self.num_stats(mc);
+
+            loop {
+                i += 1;
+                s -= 6;
+
+                if self.freq(s) != 0 {
+                    break;
+                }
+            }
+
+            esc_freq = esc_freq.wrapping_add(i);
+            self.set_num_stats(mc, num_stats - i);
+
+            if num_stats == i {
+                let mut state = self.state(stats);
+                let freq = (2 * state.freq as u32).div_ceil(esc_freq);
+                state.freq = std::cmp::min(freq, MAX_FREQ / 3) as u8;
+
+                self.free_units(stats, (num_stats + 2) >> 1);
+                self.set_flags(
+                    mc,
+                    (self.flags(mc) & 0x10) + Model::high_bit_flag(state.symbol),
+                );
+                self.found_state = Model::one_state(mc);
+                self.set_state(self.found_state, state);
+
+                return;
+            }
+
+            let n0 = (num_stats + 2) >> 1;
+            let n1 = (self.num_stats(mc) + 2) >> 1;
+
+            if n0 != n1 {
+                let stats = self.shrink_units(stats, n0, n1);
+                self.set_stats(mc, stats);
+            }
+
+            let stats = self.stats(mc);
+            let mut flags = self.flags(mc) & !0x08;
+
+            for k in 0..=self.num_stats(mc) {
+                flags |= Model::high_bit_flag(self.symbol(stats + k * 6));
+            }
+
+            self.set_flags(mc, flags);
+        }
+
+        self.set_summ_freq(mc, sum_freq + esc_freq - (esc_freq >> 1));
+        self.set_flags(mc, self.flags(mc) | 0x04);
+        self.found_state = self.stats(mc);
+    }
+
+    fn next_context(&mut self) {
+        let c = self.successor(self.found_state);
+
+        if self.order_fall == 0 && c >= self.units_start {
+            self.min_context = c;
+            self.max_context = c;
+        } else {
+            self.update_model();
+            self.min_context = self.max_context;
+        }
+    }
+
+    fn update1(&mut self) {
+        let mut s = self.found_state;
+        self.set_freq(s, self.freq(s) + 4);
+        self.set_summ_freq(self.min_context, self.summ_freq(self.min_context) + 4);
+
+        if self.freq(s) > self.freq(s - 6) {
+            self.swap_states(s, s - 6);
+            s -= 6;
+            self.found_state = s;
+
+            if self.freq(s) > MAX_FREQ {
+                self.rescale();
+            }
+        }
+
+        self.next_context();
+    }
+
+    fn update1_0(&mut self) {
+        let s = self.found_state;
+        let mc = self.min_context;
+
+        self.prev_success = (2 * self.freq(s) >= self.summ_freq(mc)) as u32;
+        self.run_length += self.prev_success as i32;
+        self.set_summ_freq(mc, self.summ_freq(mc) + 4);
+        self.set_freq(s, self.freq(s) + 4);
+
+        if self.freq(s) > MAX_FREQ {
+            self.rescale();
+        }
+
+        self.next_context();
+    }
+
+    fn update_bin(&mut self) {
+        let s = self.found_state;
+        self.set_freq(s, self.freq(s) + (self.freq(s) < 196) as u32);
+        self.prev_success = 1;
+        self.run_length += 1;
+
+        self.next_context();
+    }
+
+    fn update2(&mut self) {
+        let s = self.found_state;
+        let mc = self.min_context;
+
+        self.set_summ_freq(mc, self.summ_freq(mc) + 4);
+        self.set_freq(s, self.freq(s) + 4);
+
+        if self.freq(s) > MAX_FREQ {
+            self.rescale();
+        }
+
+        self.run_length = self.init_rl;
+        self.update_model();
+        self.min_context = self.max_context;
+    }
+
+    fn bin_summ_index(&self) -> (usize, usize) {
+        let mc = self.min_context;
+        let freq = self.freq(Model::one_state(mc));
+        let suffix_stats = self.num_stats(self.suffix(mc));
+
+        (
+            self.ns2indx[freq as usize - 1] as usize,
+            self.ns2bs_indx[suffix_stats as usize] as usize
+                + self.prev_success as usize
+                + self.flags(mc) as usize
+                + ((self.run_length >> 26) & 0x20) as usize,
+        )
+    }
+
+    /// Picks the escape estimator for a context entered after an escape; None is the dummy one.
+    fn make_esc_freq(&mut self, num_masked: u32) -> (Option<(usize, usize)>, u32) {
+        let mc = self.min_context;
+        let num_stats = self.num_stats(mc);
+
+        if num_stats == 0xFF {
+            return (None, 1);
+        }
+
+        let m = self.ns2indx[num_stats as usize + 2] as usize - 3;
+        let k = (self.summ_freq(mc) > 11 * (num_stats + 1)) as usize
+            + 2 * ((2 * num_stats) < self.num_stats(self.suffix(mc)) + num_masked) as usize
+            + self.flags(mc) as usize;
+
+        let see = &mut self.see[m][k];
+        let r = (see.summ >> see.shift) as u32;
+        see.summ = see.summ.wrapping_sub(r as u16);
+
+        (Some((m, k)), r + (r == 0) as u32)
+    }
+
+    /// Returns None for the end marker.
+    fn decode_symbol<R: Read>(&mut self, rc: &mut RangeDecoder<R>) -> std::io::Result<Option<u8>> {
+        let mut masked = [false; 256];
+        let mc = self.min_context;
+
+        if self.num_stats(mc) != 0 {
+            let mut s = self.stats(mc);
+            let summ_freq = self.summ_freq(mc);
+            let count = rc.threshold(summ_freq);
+            let mut hi_cnt = self.freq(s);
+
+            if count < hi_cnt {
+                rc.decode(0, hi_cnt)?;
+                self.found_state = s;
+                let symbol = self.symbol(s);
+                self.update1_0();
+
+                return Ok(Some(symbol));
+            }
+
+            self.prev_success = 0;
+
+            for _ in 0..self.num_stats(mc) {
+                s += 6;
+                let freq = self.freq(s);
+                hi_cnt += freq;
+
+                if hi_cnt > count {
+                    rc.decode(hi_cnt - freq, freq)?;
+                    self.found_state = s;
+                    let symbol = self.symbol(s);
+                    self.update1();
+
+                    return Ok(Some(symbol));
+                }
+            }
+
+            if count >= summ_freq {
+                return Err(invalid_data("corrupted data."));
+            }
+
+            rc.decode(hi_cnt, summ_freq - hi_cnt)?;
+
+            let stats = self.stats(mc);
+            for k in 0..=self.num_stats(mc) {
+                masked[self.symbol(stats + k * 6) as usize] = true;
+            }
+        } else {
+            let (m, k) = self.bin_summ_index();
+            let prob = self.bin_summ[m][k] as u32;
+
+            rc.range >>= INT_BITS + PERIOD_BITS;
+
+            if rc.code / rc.range < prob {
+                rc.decode(0, prob)?;
+                self.bin_summ[m][k] = (prob + (1 << INT_BITS) - get_mean(prob)) as u16;
+                self.found_state = Model::one_state(mc);
+                let symbol = self.symbol(self.found_state);
+                self.update_bin();
+
+                return Ok(Some(symbol));
+            }
+
+            rc.decode(prob, BIN_SCALE - prob)?;
+
+            let prob = prob - get_mean(prob);
+            self.bin_summ[m][k] = prob as u16;
+            self.init_esc = EXP_ESCAPE[(prob >> 10) as usize] as u32;
+            masked[self.symbol(Model::one_state(mc)) as usize] = true;
+            self.prev_success = 0;
+        }
+
+        let mut ps = Vec::with_capacity(256);
+
+        loop {
+            let num_masked = self.num_stats(self.min_context);
+
+            loop {
+                self.order_fall += 1;
+
+                let suffix = self.suffix(self.min_context);
+
+                //escaping from the order 0 context is the end marker
+                if suffix == 0 {
+                    return Ok(None);
+                }
+
+                self.min_context = suffix;
+
+                if self.num_stats(suffix) != num_masked {
+                    break;
+                }
+            }
+
+            let mc = self.min_context;
+            let num = (self.num_stats(mc) - num_masked) as usize;
+            let mut hi_cnt = 0;
+            let mut s = self.stats(mc);
+
+            ps.clear();
+
+            while ps.len() != num {
+                if !masked[self.symbol(s) as usize] {
+                    hi_cnt += self.freq(s);
+                    ps.push(s);
+                }
+
+                s += 6;
+            }
+
+            let (see, esc_freq) = self.make_esc_freq(num_masked);
+            let freq_sum = esc_freq + hi_cnt;
+            let count = rc.threshold(freq_sum);
+
+            if count < hi_cnt {
+                let mut hi_cnt = 0;
+                let mut found = ps[0];
+
+                for &s in ps.iter() {
+                    found = s;
+                    hi_cnt += self.freq(s);
+
+                    if hi_cnt > count {
+                        break;
+                    }
+                }
+
+                let freq = self.freq(found);
+                rc.decode(hi_cnt - freq, freq)?;
+
+                if let Some((m, k)) = see {
+                    self.see[m][k].update();
+                }
+
+                self.found_state = found;
+                let symbol = self.symbol(found);
+                self.update2();
+
+                return Ok(Some(symbol));
+            }
+
+            if count >= freq_sum {
+                return Err(invalid_data("corrupted data."));
+            }
+
+            rc.decode(hi_cnt, freq_sum - hi_cnt)?;
+
+            if let Some((m, k)) = see {
+                let see = &mut self.see[m][k];
+                see.summ = see.summ.wrapping_add(freq_sum as u16);
+            }
+
+            for &s in ps.iter() {
+                masked[self.symbol(s) as usize] = true;
+            }
+        }
+    }
+}
+
+pub struct PpmdDecompressor {
+    pub uncompressed_size: u64,
+}
+
+impl Decompressor for PpmdDecompressor {
+    fn decompress(
+        &self,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        data_len: &u64,
+    ) -> std::io::Result<()> {
+        if self.uncompressed_size == 0 {
+            return Ok(());
+        }
+
+        let mut reader = BufReader::new(reader.take(*data_len));
+
+        //order - 1 in bits 0-3, memory size in MB - 1 in bits 4-11, restoration method in bits 12-15
+        let mut header = [0u8; 2];
+        reader.read_exact(&mut header)?;
+
+        let parameters = u16::from_le_bytes(header) as u32;
+        let order = (parameters & 0x0F) + 1;
+        let memory_size = (((parameters >> 4) & 0xFF) + 1) << 20;
+        let restore_method = parameters >> 12;
+
+        if order < MIN_ORDER {
+            return Err(invalid_data("model order is too small."));
+        }
+
+        if restore_method != RESTORE_METHOD_RESTART && restore_method != RESTORE_METHOD_CUT_OFF {
+            return Err(invalid_data("unsupported restoration method."));
+        }
+
+        let mut rc = RangeDecoder::new(reader)?;
+        let mut model = Model::new(order, memory_size, restore_method);
+
+        let mut out = Vec::with_capacity(OUT_BUFFER_SIZE);
+        let mut left = self.uncompressed_size;
+
+        while left > 0 {
+            match model.decode_symbol(&mut rc)? {
+                Some(symbol) => out.push(symbol),
+                None => return Err(invalid_data("unexpected end marker.")),
+            }
+
+            left -= 1;
+
+            if out.len() == OUT_BUFFER_SIZE {
+                writer.write_all(&out)?;
+                out.clear();
+            }
+        }
+
+        writer.write_all(&out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::ppmd::PpmdDecompressor;
+    use crate::unpacker::algorithms::Decompressor;
+
+    //order 6, 1 MB, restart
+    const HELLO: [u8; 14] = [5, 0, 103, 251, 131, 84, 215, 53, 151, 77, 36, 71, 10, 0];
+
+    //order 2, 1 MB, cut off
+    const ABRACADABRA: [u8; 17] = [
+        1, 16, 97, 3, 124, 18, 12, 229, 19, 172, 130, 127, 242, 62, 70, 0, 0,
+    ];
+
+    fn decompress(bytes: &[u8], uncompressed_size: u64) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        PpmdDecompressor { uncompressed_size }.decompress(
+            &mut &bytes[..],
+            &mut out,
+            &(bytes.len() as u64),
+        )?;
+
+        Ok(out)
+    }
+
+    #[test]
+    fn decompress_short_texts() {
+        assert_eq!(decompress(&HELLO, 18).unwrap(), b"hello hello hello\n");
+        assert_eq!(
+            decompress(&ABRACADABRA, 35).unwrap(),
+            b"abracadabra abracadabra abracadabra"
+        );
+    }
+
+    #[test]
+    fn reject_bad_parameters() {
+        let mut freeze = HELLO;
+        freeze[1] |= 0x20;
+        assert!(decompress(&freeze, 18).is_err());
+
+        let mut order_1 = HELLO;
+        order_1[0] = 0;
+        assert!(decompress(&order_1, 18).is_err());
+    }
+}
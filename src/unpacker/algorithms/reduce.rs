@@ -0,0 +1,173 @@
+use std::io::{BufReader, ErrorKind, Read, Write};
+
+use crate::unpacker::algorithms::deflate::BitReader;
+use crate::unpacker::algorithms::lzma::OutWindow;
+use crate::unpacker::algorithms::Decompressor;
+
+const DLE: u8 = 144;
+
+//the farthest a match can reach with compression factor 4
+const WINDOW_SIZE: u32 = 16 * 256;
+
+fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("Reduce: {}", reason))
+}
+
+/// Bits needed to index a follower set of `len` bytes.
+fn index_bits(len: usize) -> u32 {
+    match len {
+        0..=2 => 1,
+        len => usize::BITS - (len - 1).leading_zeros(),
+    }
+}
+
+enum State {
+    Literal,
+    AfterDle,
+    LongLength { v: u8 },
+    Distance { v: u8, len: usize },
+}
+
+pub struct ReduceDecompressor {
+    /// 1 to 4, for methods 2 to 5.
+    pub factor: u32,
+    pub uncompressed_size: u64,
+}
+
+impl Decompressor for ReduceDecompressor {
+    fn decompress(
+        &self,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        data_len: &u64,
+    ) -> std::io::Result<()> {
+        let mut bits = BitReader::new(BufReader::new(reader.take(*data_len)));
+
+        //followers[c] are the bytes most likely to come after c; the sets are stored from 255 down
+        let mut followers = vec![Vec::new(); 256];
+
+        for set in followers.iter_mut().rev() {
+            let len = bits.read(6)? as usize;
+
+            for _ in 0..len {
+                set.push(bits.read(8)? as u8);
+            }
+        }
+
+        let length_mask = 0xFFu8 >> self.factor;
+
+        let mut window = OutWindow::new(writer, WINDOW_SIZE);
+        let mut last = 0u8;
+        let mut state = State::Literal;
+
+        while window.total_pos() < self.uncompressed_size {
+            let set: &Vec<u8> = &followers[last as usize];
+
+            let byte = if set.is_empty() || bits.read(1)? == 1 {
+                bits.read(8)? as u8
+            } else {
+                let index = bits.read(index_bits(set.len()))? as usize;
+
+                *set.get(index)
+                    .ok_or_else(|| invalid_data("follower index out of range."))?
+            };
+
+            last = byte;
+
+            state = match state {
+                State::Literal if byte == DLE => State::AfterDle,
+                State::Literal => {
+                    window.put_byte(byte)?;
+                    State::Literal
+                }
+                State::AfterDle if byte == 0 => {
+                    window.put_byte(DLE)?;
+                    State::Literal
+                }
+                State::AfterDle if byte & length_mask == length_mask => {
+                    State::LongLength { v: byte }
+                }
+                State::AfterDle => State::Distance {
+                    v: byte,
+                    len: (byte & length_mask) as usize,
+                },
+                State::LongLength { v } => State::Distance {
+                    v,
+                    len: length_mask as usize + byte as usize,
+                },
+                State::Distance { v, len } => {
+                    let dist = ((v >> (8 - self.factor)) as usize) * 256 + byte as usize + 1;
+                    let left = self.uncompressed_size - window.total_pos();
+
+                    //matches may reach before the start of the data, which reads as zeros
+                    window.copy_match(dist, std::cmp::min(len as u64 + 3, left) as usize)?;
+
+                    State::Literal
+                }
+            };
+        }
+
+        window.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::reduce::{index_bits, ReduceDecompressor};
+    use crate::unpacker::algorithms::Decompressor;
+
+    fn decompress(bytes: &[u8], factor: u32, size: u64) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        ReduceDecompressor {
+            factor,
+            uncompressed_size: size,
+        }
+        .decompress(&mut &bytes[..], &mut out, &(bytes.len() as u64))?;
+
+        Ok(out)
+    }
+
+    //empty follower sets, then the given bytes as plain 8 bit values
+    fn plain_stream(bytes: &[u8]) -> Vec<u8> {
+        let mut stream = vec![0u8; 256 * 6 / 8];
+        stream.extend_from_slice(bytes);
+
+        stream
+    }
+
+    #[test]
+    fn follower_index_bits() {
+        assert_eq!(index_bits(1), 1);
+        assert_eq!(index_bits(2), 1);
+        assert_eq!(index_bits(3), 2);
+        assert_eq!(index_bits(4), 2);
+        assert_eq!(index_bits(5), 3);
+        assert_eq!(index_bits(32), 5);
+        assert_eq!(index_bits(33), 6);
+    }
+
+    #[test]
+    fn literals_and_matches() {
+        //"abc", a match of 3 + 2 at distance 3, an escaped DLE
+        let stream = plain_stream(&[b'a', b'b', b'c', 144, 0x02, 0x02, 144, 0x00]);
+
+        assert_eq!(
+            decompress(&stream, 1, 9).unwrap(),
+            [b'a', b'b', b'c', b'a', b'b', b'c', b'a', b'b', 144]
+        );
+    }
+
+    #[test]
+    fn long_length() {
+        //"x" then a match of 3 + 15 + 2 at distance 1 with factor 4
+        let stream = plain_stream(&[b'x', 144, 0x0F, 0x02, 0x00]);
+
+        assert_eq!(decompress(&stream, 4, 21).unwrap(), vec![b'x'; 21]);
+    }
+
+    #[test]
+    fn reject_truncated_stream() {
+        assert!(decompress(&plain_stream(b"ab"), 1, 3).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use std::io::{BufReader, ErrorKind, Read, Write};
+
+use crate::unpacker::algorithms::deflate::BitReader;
+use crate::unpacker::algorithms::Decompressor;
+
+const MIN_CODE_SIZE: u32 = 9;
+const MAX_CODE_SIZE: u32 = 13;
+const MAX_CODE: usize = (1 << MAX_CODE_SIZE) - 1;
+
+const CONTROL_CODE: usize = 256;
+const CONTROL_INCREASE_CODE_SIZE: u32 = 1;
+const CONTROL_PARTIAL_CLEAR: u32 = 2;
+
+const FREE: u16 = u16::MAX;
+
+fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("Shrink: {}", reason))
+}
+
+/// The LZW string table; codes below 256 are the literals themselves.
+struct CodeTable {
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    //lowest code that may be free; new strings always take the lowest free code
+    next_free: usize,
+}
+
+impl CodeTable {
+    fn new() -> CodeTable {
+        let mut prefix = vec![FREE; MAX_CODE + 1];
+        let mut suffix = vec![0u8; MAX_CODE + 1];
+
+        for code in 0..CONTROL_CODE {
+            prefix[code] = code as u16;
+            suffix[code] = code as u8;
+        }
+
+        CodeTable {
+            prefix,
+            suffix,
+            next_free: CONTROL_CODE + 1,
+        }
+    }
+
+    fn is_free(&self, code: usize) -> bool {
+        code > CONTROL_CODE && self.prefix[code] == FREE
+    }
+
+    fn free_code(&mut self) -> Option<usize> {
+        while self.next_free <= MAX_CODE && !self.is_free(self.next_free) {
+            self.next_free += 1;
+        }
+
+        if self.next_free > MAX_CODE {
+            None
+        } else {
+            Some(self.next_free)
+        }
+    }
+
+    /// Frees every string that is not the prefix of another one.
+    fn partial_clear(&mut self) {
+        let mut is_prefix = vec![false; MAX_CODE + 1];
+
+        for code in CONTROL_CODE + 1..=MAX_CODE {
+            if self.prefix[code] != FREE {
+                is_prefix[self.prefix[code] as usize] = true;
+            }
+        }
+
+        for (code, is_prefix) in is_prefix.iter().enumerate().skip(CONTROL_CODE + 1) {
+            if !is_prefix {
+                self.prefix[code] = FREE;
+            }
+        }
+
+        self.next_free = CONTROL_CODE + 1;
+    }
+
+    /// Writes the string for `code` into `out`, first byte first.
+    fn expand(&self, mut code: usize, out: &mut Vec<u8>) -> std::io::Result<()> {
+        out.clear();
+
+        while code >= CONTROL_CODE {
+            if self.prefix[code] == FREE || out.len() > MAX_CODE {
+                return Err(invalid_data("code refers to a free entry."));
+            }
+
+            out.push(self.suffix[code]);
+            code = self.prefix[code] as usize;
+        }
+
+        out.push(code as u8);
+        out.reverse();
+
+        Ok(())
+    }
+}
+
+pub struct ShrinkDecompressor;
+impl Decompressor for ShrinkDecompressor {
+    fn decompress(
+        &self,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        data_len: &u64,
+    ) -> std::io::Result<()> {
+        let mut bits = BitReader::new(BufReader::new(reader.take(*data_len)));
+        let mut table = CodeTable::new();
+        let mut code_size = MIN_CODE_SIZE;
+
+        let mut string = Vec::new();
+
+        let mut prev_code = match bits.try_read(code_size)? {
+            Some(code) if (code as usize) < CONTROL_CODE => code as usize,
+            Some(_) => return Err(invalid_data("stream must start with a literal.")),
+            None => return Ok(()),
+        };
+
+        writer.write_all(&[prev_code as u8])?;
+        let mut prev_first = prev_code as u8;
+
+        //the stream ends when a whole code no longer fits in what is left
+        while let Some(code) = bits.try_read(code_size)? {
+            let code = code as usize;
+
+            if code == CONTROL_CODE {
+                match bits.try_read(code_size)? {
+                    Some(CONTROL_INCREASE_CODE_SIZE) if code_size < MAX_CODE_SIZE => {
+                        code_size += 1;
+                    }
+                    Some(CONTROL_PARTIAL_CLEAR) => table.partial_clear(),
+                    _ => return Err(invalid_data("bad control code.")),
+                }
+
+                continue;
+            }
+
+            let new_code = table.free_code();
+
+            if table.is_free(code) {
+                //KwKwK: the code being defined right now, previous string plus its first byte
+                if Some(code) != new_code {
+                    return Err(invalid_data("code refers to a free entry."));
+                }
+
+                table.expand(prev_code, &mut string)?;
+                string.push(prev_first);
+            } else {
+                table.expand(code, &mut string)?;
+            }
+
+            if let Some(new_code) = new_code {
+                //a partial clear may have freed the previous string; nothing can extend it then
+                if table.is_free(prev_code) {
+                    return Err(invalid_data("previous code was cleared."));
+                }
+
+                table.prefix[new_code] = prev_code as u16;
+                table.suffix[new_code] = string[0];
+            }
+
+            writer.write_all(&string)?;
+
+            prev_code = code;
+            prev_first = string[0];
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::shrink::ShrinkDecompressor;
+    use crate::unpacker::algorithms::Decompressor;
+
+    fn pack_codes(codes: &[(u32, u32)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut acc = 0u64;
+        let mut count = 0;
+
+        for (code, size) in codes {
+            acc |= (*code as u64) << count;
+            count += size;
+
+            while count >= 8 {
+                bytes.push(acc as u8);
+                acc >>= 8;
+                count -= 8;
+            }
+        }
+
+        if count > 0 {
+            bytes.push(acc as u8);
+        }
+
+        bytes
+    }
+
+    fn decompress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        ShrinkDecompressor.decompress(&mut &bytes[..], &mut out, &(bytes.len() as u64))?;
+
+        Ok(out)
+    }
+
+    #[test]
+    fn lzw_strings() {
+        //a, b, ab (257), aba (KwKwK, 259)
+        let bytes = pack_codes(&[(97, 9), (98, 9), (257, 9), (259, 9)]);
+
+        assert_eq!(decompress(&bytes).unwrap(), b"abababa");
+    }
+
+    #[test]
+    fn control_codes() {
+        //a, increase to 10 bits, b, partial clear, a
+        let bytes = pack_codes(&[
+            (97, 9),
+            (256, 9),
+            (1, 9),
+            (98, 10),
+            (256, 10),
+            (2, 10),
+            (97, 10),
+        ]);
+
+        assert_eq!(decompress(&bytes).unwrap(), b"aba");
+    }
+
+    #[test]
+    fn reject_bad_codes() {
+        assert!(decompress(&pack_codes(&[(97, 9), (300, 9)])).is_err());
+        assert!(decompress(&pack_codes(&[(97, 9), (256, 9), (3, 9)])).is_err());
+        assert!(decompress(&pack_codes(&[(257, 9)])).is_err());
+    }
+}
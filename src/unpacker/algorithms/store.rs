@@ -1,41 +1,265 @@
-use crate::unpacker::algorithms::Decompressor;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::io::{ErrorKind, Read, Write};
+
+use crate::checksums::crc32::Crc32;
+use crate::unpacker::algorithms::Decompressor;
+
+pub const CHUNK_SIZE: usize = 1024 * 1024;
 
 pub struct StoreDecompressor;
 impl Decompressor for StoreDecompressor {
     fn decompress(
         &self,
-        reader: &mut BufReader<File>,
-        writer: &mut BufWriter<&mut File>,
-        data_pos: &u64,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
         data_len: &u64,
     ) -> std::io::Result<()> {
-        reader.seek(SeekFrom::Start(*data_pos))?;
+        let copied = std::io::copy(&mut reader.take(*data_len), writer)?;
 
-        const CHUNK_SIZE: usize = 1024 * 1024;
-        let mut bytes_left = *data_len;
+        if copied < *data_len {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Stored data is truncated.",
+            ));
+        }
+
+        Ok(())
+    }
+}
 
-        //TODO: verify checksum
-        while bytes_left > 0 {
-            let next_bytes = std::cmp::min(bytes_left, CHUNK_SIZE as u64);
+/// Copies `data_len` bytes through the caller's buffer, feeding them to `crc` on the way.
+pub fn copy_buffered(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    data_len: u64,
+    buffer: &mut [u8],
+    mut crc: Option<&mut Crc32>,
+) -> std::io::Result<()> {
+    let mut bytes_left = data_len;
 
-            if next_bytes == CHUNK_SIZE as u64 {
-                let mut bytes = [0u8; CHUNK_SIZE];
-                reader.read_exact(&mut bytes)?;
+    while bytes_left > 0 {
+        let next_bytes = std::cmp::min(bytes_left, buffer.len() as u64) as usize;
+        let chunk = &mut buffer[..next_bytes];
+
+        reader.read_exact(chunk)?;
+
+        if let Some(crc) = crc.as_deref_mut() {
+            crc.update(chunk);
+        }
+
+        writer.write_all(chunk)?;
+
+        bytes_left -= next_bytes as u64;
+    }
+
+    Ok(())
+}
 
-                writer.write(&bytes)?;
-            } else {
-                //TODO: too many allocations here; consider passing reusable external buffer
-                let mut bytes = vec![0u8; next_bytes as usize];
-                reader.read_exact(&mut bytes)?;
+/// Lets the kernel copy `data_len` bytes at `src_pos` to the current position of `dst`.
+/// Returns the number of bytes copied; the caller finishes whatever is left.
+#[cfg(target_os = "linux")]
+pub fn copy_in_kernel(src: &File, src_pos: u64, dst: &File, data_len: u64) -> std::io::Result<u64> {
+    use std::os::unix::io::AsRawFd;
 
-                writer.write(&bytes)?;
+    const MAX_CHUNK: u64 = 1 << 30;
+
+    let mut use_copy_file_range = true;
+    let mut copied = 0u64;
+
+    while copied < data_len {
+        let mut src_offset = (src_pos + copied) as libc::off_t;
+        let next_bytes = std::cmp::min(data_len - copied, MAX_CHUNK) as usize;
+
+        let result = if use_copy_file_range {
+            let mut src_offset = src_offset as libc::loff_t;
+
+            unsafe {
+                libc::copy_file_range(
+                    src.as_raw_fd(),
+                    &mut src_offset,
+                    dst.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    next_bytes,
+                    0,
+                )
             }
+        } else {
+            unsafe {
+                libc::sendfile(
+                    dst.as_raw_fd(),
+                    src.as_raw_fd(),
+                    &mut src_offset,
+                    next_bytes,
+                )
+            }
+        };
+
+        if result < 0 {
+            let err = std::io::Error::last_os_error();
 
-            bytes_left -= next_bytes;
+            match err.raw_os_error() {
+                Some(libc::ENOSYS)
+                | Some(libc::EXDEV)
+                | Some(libc::EINVAL)
+                | Some(libc::EOPNOTSUPP)
+                | Some(libc::EPERM)
+                    if use_copy_file_range =>
+                {
+                    use_copy_file_range = false;
+                }
+                Some(libc::ENOSYS) | Some(libc::EINVAL) | Some(libc::EOPNOTSUPP) => break,
+                Some(libc::EINTR) => {}
+                _ => return Err(err),
+            }
+        } else if result == 0 {
+            //source ended early; the buffered copy will report it
+            break;
+        } else {
+            copied += result as u64;
         }
+    }
 
-        Ok(())
+    Ok(copied)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn copy_in_kernel(
+    _src: &File,
+    _src_pos: u64,
+    _dst: &File,
+    _data_len: u64,
+) -> std::io::Result<u64> {
+    Ok(0)
+}
+
+/// Feeds a file range to `crc` without moving the file position.
+#[cfg(unix)]
+pub fn checksum_file_range(
+    src: &File,
+    src_pos: u64,
+    data_len: u64,
+    buffer: &mut [u8],
+    crc: &mut Crc32,
+) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+
+    let mut done = 0u64;
+
+    while done < data_len {
+        let next_bytes = std::cmp::min(data_len - done, buffer.len() as u64) as usize;
+        let chunk = &mut buffer[..next_bytes];
+
+        src.read_exact_at(chunk, src_pos + done)?;
+        crc.update(chunk);
+
+        done += next_bytes as u64;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn checksum_file_range(
+    src: &File,
+    src_pos: u64,
+    data_len: u64,
+    buffer: &mut [u8],
+    crc: &mut Crc32,
+) -> std::io::Result<()> {
+    use std::io::{Seek, SeekFrom};
+
+    let mut src = src;
+    let old_pos = src.stream_position()?;
+
+    src.seek(SeekFrom::Start(src_pos))?;
+    copy_buffered(&mut src, &mut std::io::sink(), data_len, buffer, Some(crc))?;
+    src.seek(SeekFrom::Start(old_pos))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    use crate::checksums::crc32::{checksum, Crc32};
+    use crate::unpacker::algorithms::store::{
+        checksum_file_range, copy_buffered, copy_in_kernel, StoreDecompressor,
+    };
+    use crate::unpacker::algorithms::Decompressor;
+
+    fn sample_bytes(len: usize) -> Vec<u8> {
+        (0..len).map(|n| (n * 31 + n / 7) as u8).collect()
+    }
+
+    #[test]
+    fn buffered_copy_with_small_buffer() {
+        let bytes = sample_bytes(10_000);
+
+        let mut out = Vec::new();
+        let mut buffer = [0u8; 333];
+        let mut crc = Crc32::new();
+
+        copy_buffered(
+            &mut &bytes[..],
+            &mut out,
+            bytes.len() as u64,
+            &mut buffer,
+            Some(&mut crc),
+        )
+        .unwrap();
+
+        assert_eq!(out, bytes);
+        assert_eq!(crc.value(), checksum(&bytes));
+    }
+
+    #[test]
+    fn decompressor_reports_truncated_data() {
+        let bytes = sample_bytes(100);
+        let mut out = Vec::new();
+
+        assert!(StoreDecompressor
+            .decompress(&mut &bytes[..], &mut out, &101)
+            .is_err());
+    }
+
+    #[test]
+    fn kernel_copy_between_files() {
+        let dir = std::env::temp_dir().join(format!("simpzip_store_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let bytes = sample_bytes(300_000);
+        std::fs::write(dir.join("src.bin"), &bytes).unwrap();
+
+        let src = File::open(dir.join("src.bin")).unwrap();
+        let mut dst = File::create(dir.join("dst.bin")).unwrap();
+        dst.write_all(b"head").unwrap();
+
+        let copied = copy_in_kernel(&src, 1000, &dst, 200_000).unwrap();
+
+        dst.write_all(&bytes[1000 + copied as usize..201_000])
+            .unwrap();
+        drop(dst);
+
+        let mut result = Vec::new();
+        File::open(dir.join("dst.bin"))
+            .unwrap()
+            .read_to_end(&mut result)
+            .unwrap();
+
+        assert_eq!(&result[..4], b"head");
+        assert_eq!(&result[4..], &bytes[1000..201_000]);
+
+        let mut src = src;
+        src.seek(SeekFrom::Start(7)).unwrap();
+
+        let mut crc = Crc32::new();
+        checksum_file_range(&src, 1000, 200_000, &mut [0u8; 4096], &mut crc).unwrap();
+
+        assert_eq!(crc.value(), checksum(&bytes[1000..201_000]));
+        assert_eq!(src.stream_position().unwrap(), 7);
+
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
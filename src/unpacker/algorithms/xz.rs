@@ -0,0 +1,569 @@
+use std::io::{BufReader, ErrorKind, Read, Write};
+
+use crate::checksums::crc32::{self, Crc32};
+use crate::checksums::crc64::Crc64;
+use crate::checksums::sha256::{self, Sha256};
+use crate::unpacker::algorithms::bcj::X86Decoder;
+use crate::unpacker::algorithms::{lzma, lzma2, Decompressor};
+
+const HEADER_MAGIC: [u8; 6] = [0xFD, b'7', b'z', b'X', b'Z', 0x00];
+const FOOTER_MAGIC: [u8; 2] = [b'Y', b'Z'];
+
+const FILTER_X86: u64 = 0x04;
+const FILTER_LZMA2: u64 = 0x21;
+
+const CHECK_NONE: u8 = 0x00;
+const CHECK_CRC32: u8 = 0x01;
+const CHECK_CRC64: u8 = 0x04;
+const CHECK_SHA256: u8 = 0x0A;
+
+fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("XZ: {}", reason))
+}
+
+/// Counts consumed bytes and, while `crc` is set, checksums them.
+struct CountingReader<R: Read> {
+    reader: R,
+    count: u64,
+    crc: Option<Crc32>,
+}
+
+impl<R: Read> CountingReader<R> {
+    fn read_vli(&mut self) -> std::io::Result<u64> {
+        let mut value = 0u64;
+
+        for n in 0..9 {
+            let mut byte = [0u8; 1];
+            self.read_exact(&mut byte)?;
+
+            value |= ((byte[0] & 0x7F) as u64) << (7 * n);
+
+            if byte[0] & 0x80 == 0 {
+                if byte[0] == 0 && n > 0 {
+                    return Err(invalid_data("non-minimal integer encoding."));
+                }
+
+                return Ok(value);
+            }
+        }
+
+        Err(invalid_data("integer is too long."))
+    }
+
+    /// Skips zero padding up to the next multiple of four bytes.
+    fn skip_padding(&mut self) -> std::io::Result<()> {
+        while !self.count.is_multiple_of(4) {
+            let mut byte = [0u8; 1];
+            self.read_exact(&mut byte)?;
+
+            if byte[0] != 0 {
+                return Err(invalid_data("non-zero padding."));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+
+        self.count += read as u64;
+
+        if let Some(crc) = self.crc.as_mut() {
+            crc.update(&buf[..read]);
+        }
+
+        Ok(read)
+    }
+}
+
+enum Check {
+    None,
+    Crc32(Crc32),
+    Crc64(Crc64),
+    Sha256(Box<Sha256>),
+    /// A check type we cannot compute; it is skipped, as xz itself does.
+    Unsupported(usize),
+}
+
+impl Check {
+    fn new(check_type: u8) -> Check {
+        match check_type {
+            CHECK_NONE => Check::None,
+            CHECK_CRC32 => Check::Crc32(Crc32::new()),
+            CHECK_CRC64 => Check::Crc64(Crc64::new()),
+            CHECK_SHA256 => Check::Sha256(Box::default()),
+            //sizes are fixed per group of three type ids
+            other => Check::Unsupported(match other {
+                0..=3 => 4,
+                4..=6 => 8,
+                7..=9 => 16,
+                10..=12 => 32,
+                _ => 64,
+            }),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Check::None => 0,
+            Check::Crc32(_) => 4,
+            Check::Crc64(_) => 8,
+            Check::Sha256(_) => sha256::DIGEST_LEN,
+            Check::Unsupported(size) => *size,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Check::Crc32(crc) => crc.update(bytes),
+            Check::Crc64(crc) => crc.update(bytes),
+            Check::Sha256(sha) => sha.update(bytes),
+            Check::None | Check::Unsupported(_) => {}
+        }
+    }
+
+    /// The value as stored after the block, or None if it cannot be computed.
+    fn finish(self) -> Option<Vec<u8>> {
+        match self {
+            Check::None => Some(Vec::new()),
+            Check::Crc32(crc) => Some(crc.value().to_le_bytes().to_vec()),
+            Check::Crc64(crc) => Some(crc.value().to_le_bytes().to_vec()),
+            Check::Sha256(sha) => Some(sha.finalize().to_vec()),
+            Check::Unsupported(_) => None,
+        }
+    }
+}
+
+/// Feeds everything written through the block check and counts it.
+struct CheckedWriter<'a> {
+    writer: &'a mut dyn Write,
+    check: Check,
+    written: u64,
+}
+
+impl Write for CheckedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.write(buf)?;
+
+        self.check.update(&buf[..written]);
+        self.written += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn read_stream_flags(bytes: &[u8]) -> std::io::Result<u8> {
+    if bytes[0] != 0 || bytes[1] & 0xF0 != 0 {
+        return Err(invalid_data("unsupported stream flags."));
+    }
+
+    Ok(bytes[1])
+}
+
+struct BlockHeader {
+    compressed_size: Option<u64>,
+    uncompressed_size: Option<u64>,
+    dictionary_size: u32,
+    x86_start_offset: Option<u32>,
+    header_size: u64,
+}
+
+/// Parses a block header whose size byte (`first_byte`) is already consumed.
+fn read_block_header<R: Read>(
+    reader: &mut CountingReader<R>,
+    first_byte: u8,
+) -> std::io::Result<BlockHeader> {
+    let header_size = (first_byte as u64 + 1) * 4;
+
+    let mut header = vec![0u8; header_size as usize];
+    header[0] = first_byte;
+    reader.read_exact(&mut header[1..])?;
+
+    let (body, stored_crc) = header.split_at(header.len() - 4);
+
+    if crc32::checksum(body)
+        != u32::from_le_bytes([stored_crc[0], stored_crc[1], stored_crc[2], stored_crc[3]])
+    {
+        return Err(invalid_data("block header CRC mismatch."));
+    }
+
+    let flags = body[1];
+
+    if flags & 0x3C != 0 {
+        return Err(invalid_data("unsupported block flags."));
+    }
+
+    let mut fields = CountingReader {
+        reader: &body[2..],
+        count: 0,
+        crc: None,
+    };
+
+    let compressed_size = if flags & 0x40 != 0 {
+        Some(fields.read_vli()?)
+    } else {
+        None
+    };
+
+    let uncompressed_size = if flags & 0x80 != 0 {
+        Some(fields.read_vli()?)
+    } else {
+        None
+    };
+
+    let filter_count = (flags & 0x03) as usize + 1;
+    let mut dictionary_size = None;
+    let mut x86_start_offset = None;
+
+    for n in 0..filter_count {
+        let id = fields.read_vli()?;
+        let properties_size = fields.read_vli()?;
+
+        if properties_size > body.len() as u64 {
+            return Err(invalid_data("truncated filter properties."));
+        }
+
+        let mut properties = vec![0u8; properties_size as usize];
+        fields.read_exact(&mut properties)?;
+
+        let is_last = n + 1 == filter_count;
+
+        match (id, is_last) {
+            (FILTER_LZMA2, true) if properties.len() == 1 => {
+                dictionary_size = Some(lzma2::dictionary_size(properties[0])?);
+            }
+            (FILTER_X86, false) if x86_start_offset.is_none() => {
+                x86_start_offset = Some(match properties.len() {
+                    0 => 0,
+                    4 => u32::from_le_bytes([
+                        properties[0],
+                        properties[1],
+                        properties[2],
+                        properties[3],
+                    ]),
+                    _ => return Err(invalid_data("bad x86 filter properties.")),
+                });
+            }
+            (FILTER_LZMA2, _) | (FILTER_X86, _) => {
+                return Err(invalid_data("unsupported filter chain."));
+            }
+            _ => return Err(invalid_data("unsupported filter.")),
+        }
+    }
+
+    if fields.reader.iter().any(|byte| *byte != 0) {
+        return Err(invalid_data("non-zero block header padding."));
+    }
+
+    Ok(BlockHeader {
+        compressed_size,
+        uncompressed_size,
+        //the chain was checked to end with LZMA2
+        dictionary_size: dictionary_size.unwrap(),
+        x86_start_offset,
+        header_size,
+    })
+}
+
+/// Decodes one block; returns its (unpadded size, uncompressed size) index record.
+fn decompress_block<R: Read>(
+    reader: &mut CountingReader<R>,
+    writer: &mut dyn Write,
+    first_byte: u8,
+    check_type: u8,
+    size_limit: Option<u64>,
+) -> std::io::Result<(u64, u64)> {
+    let header = read_block_header(reader, first_byte)?;
+
+    let window_size = lzma::window_size(
+        header.dictionary_size,
+        match (header.uncompressed_size, size_limit) {
+            (Some(size), _) | (None, Some(size)) => Some(size),
+            (None, None) => None,
+        },
+    )?;
+
+    let mut checked = CheckedWriter {
+        writer,
+        check: Check::new(check_type),
+        written: 0,
+    };
+
+    let data_start = reader.count;
+
+    match header.x86_start_offset {
+        Some(start_offset) => {
+            let mut filter = X86Decoder::new(&mut checked, start_offset);
+            lzma2::decode(reader, &mut filter, window_size)?;
+            filter.finish()?;
+        }
+        None => lzma2::decode(reader, &mut checked, window_size)?,
+    }
+
+    let compressed_size = reader.count - data_start;
+
+    if header
+        .compressed_size
+        .is_some_and(|size| size != compressed_size)
+        || header
+            .uncompressed_size
+            .is_some_and(|size| size != checked.written)
+    {
+        return Err(invalid_data("block size mismatch."));
+    }
+
+    reader.skip_padding()?;
+
+    let check_size = checked.check.size();
+    let mut stored_check = vec![0u8; check_size];
+    reader.read_exact(&mut stored_check)?;
+
+    if let Some(value) = checked.check.finish() {
+        if value != stored_check {
+            return Err(invalid_data("block check mismatch."));
+        }
+    }
+
+    Ok((
+        header.header_size + compressed_size + check_size as u64,
+        checked.written,
+    ))
+}
+
+/// Reads the index (its indicator byte is already consumed) and compares it to the decoded blocks.
+fn read_index<R: Read>(
+    reader: &mut CountingReader<R>,
+    records: &[(u64, u64)],
+) -> std::io::Result<u64> {
+    let index_start = reader.count - 1;
+
+    let mut crc = Crc32::new();
+    crc.update(&[0]);
+    reader.crc = Some(crc);
+
+    let count = reader.read_vli()?;
+
+    if count != records.len() as u64 {
+        return Err(invalid_data("index does not match the blocks."));
+    }
+
+    for record in records {
+        let unpadded_size = reader.read_vli()?;
+        let uncompressed_size = reader.read_vli()?;
+
+        if (unpadded_size, uncompressed_size) != *record {
+            return Err(invalid_data("index does not match the blocks."));
+        }
+    }
+
+    reader.skip_padding()?;
+
+    let crc = reader.crc.take().unwrap().value();
+
+    let mut stored_crc = [0u8; 4];
+    reader.read_exact(&mut stored_crc)?;
+
+    if crc != u32::from_le_bytes(stored_crc) {
+        return Err(invalid_data("index CRC mismatch."));
+    }
+
+    Ok(reader.count - index_start)
+}
+
+fn decompress_stream<R: Read>(
+    reader: &mut CountingReader<R>,
+    writer: &mut dyn Write,
+    header_start: &[u8; 4],
+    size_limit: Option<u64>,
+) -> std::io::Result<()> {
+    let mut header = [0u8; 12];
+    header[..4].copy_from_slice(header_start);
+    reader.read_exact(&mut header[4..])?;
+
+    if header[..6] != HEADER_MAGIC {
+        return Err(invalid_data("bad stream signature."));
+    }
+
+    let check_type = read_stream_flags(&header[6..8])?;
+
+    if crc32::checksum(&header[6..8])
+        != u32::from_le_bytes([header[8], header[9], header[10], header[11]])
+    {
+        return Err(invalid_data("stream header CRC mismatch."));
+    }
+
+    let mut records = Vec::new();
+
+    loop {
+        let mut first_byte = [0u8; 1];
+        reader.read_exact(&mut first_byte)?;
+
+        if first_byte[0] == 0 {
+            break;
+        }
+
+        records.push(decompress_block(
+            reader,
+            writer,
+            first_byte[0],
+            check_type,
+            size_limit,
+        )?);
+    }
+
+    let index_size = read_index(reader, &records)?;
+
+    let mut footer = [0u8; 12];
+    reader.read_exact(&mut footer)?;
+
+    if footer[10..] != FOOTER_MAGIC {
+        return Err(invalid_data("bad stream footer signature."));
+    }
+
+    if crc32::checksum(&footer[4..10])
+        != u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]])
+    {
+        return Err(invalid_data("stream footer CRC mismatch."));
+    }
+
+    let backward_size =
+        (u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]) as u64 + 1) * 4;
+
+    if backward_size != index_size || read_stream_flags(&footer[8..10])? != check_type {
+        return Err(invalid_data("stream footer does not match."));
+    }
+
+    Ok(())
+}
+
+pub struct XzDecompressor {
+    /// Caps the dictionary when the entry size is known up front.
+    pub uncompressed_size: Option<u64>,
+}
+
+impl Decompressor for XzDecompressor {
+    fn decompress(
+        &self,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        data_len: &u64,
+    ) -> std::io::Result<()> {
+        let mut reader = CountingReader {
+            reader: BufReader::new(reader.take(*data_len)),
+            count: 0,
+            crc: None,
+        };
+
+        let mut streams = 0;
+
+        //streams may be concatenated, with zero padding in multiples of four between them
+        loop {
+            let mut start = [0u8; 4];
+            let mut start_len = 0;
+
+            while start_len < start.len() {
+                match reader.read(&mut start[start_len..])? {
+                    0 => break,
+                    read => start_len += read,
+                }
+            }
+
+            match start_len {
+                0 if streams > 0 => return Ok(()),
+                4 => {}
+                _ => return Err(invalid_data("truncated stream.")),
+            }
+
+            if start == [0; 4] && streams > 0 {
+                continue;
+            }
+
+            decompress_stream(&mut reader, writer, &start, self.uncompressed_size)?;
+
+            streams += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unpacker::algorithms::xz::XzDecompressor;
+    use crate::unpacker::algorithms::Decompressor;
+
+    //lzma.compress(b"abracadabra abracadabra", check=lzma.CHECK_CRC64)
+    const ABRACADABRA: [u8; 76] = [
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x04, 0xe6, 0xd6, 0xb4, 0x46, 0x02, 0x00, 0x21,
+        0x01, 0x16, 0x00, 0x00, 0x00, 0x74, 0x2f, 0xe5, 0xa3, 0xe0, 0x00, 0x16, 0x00, 0x10, 0x5d,
+        0x00, 0x30, 0x98, 0x8a, 0xaa, 0x9a, 0x59, 0xf5, 0x11, 0xd8, 0x3e, 0xb1, 0x15, 0x58, 0x50,
+        0x00, 0x00, 0x00, 0x89, 0xcc, 0xf4, 0x36, 0x85, 0x1a, 0x99, 0x5b, 0x00, 0x01, 0x2c, 0x17,
+        0x42, 0x5b, 0x64, 0x9a, 0x1f, 0xb6, 0xf3, 0x7d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x04, 0x59,
+        0x5a,
+    ];
+
+    //lzma.compress(b"abc", check=lzma.CHECK_SHA256) through the x86 filter and LZMA2
+    const ABC_X86_SHA256: [u8; 84] = [
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x0a, 0xe1, 0xfb, 0x0c, 0xa1, 0x02, 0x01, 0x04,
+        0x00, 0x21, 0x01, 0x0c, 0x00, 0xd6, 0x7c, 0x18, 0xaf, 0x01, 0x00, 0x02, 0x61, 0x62, 0x63,
+        0x00, 0x00, 0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d,
+        0xae, 0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+        0xf2, 0x00, 0x15, 0xad, 0x00, 0x01, 0x33, 0x03, 0xa1, 0x81, 0xe4, 0x4d, 0x18, 0x9b, 0x4b,
+        0x9a, 0x01, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x59, 0x5a,
+    ];
+
+    fn decompress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        XzDecompressor {
+            uncompressed_size: None,
+        }
+        .decompress(&mut &bytes[..], &mut out, &(bytes.len() as u64))?;
+
+        Ok(out)
+    }
+
+    #[test]
+    fn decompress_stream() {
+        assert_eq!(
+            decompress(&ABRACADABRA).unwrap(),
+            b"abracadabra abracadabra"
+        );
+        assert_eq!(decompress(&ABC_X86_SHA256).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn concatenated_streams_with_padding() {
+        let mut bytes = ABC_X86_SHA256.to_vec();
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&ABRACADABRA);
+
+        assert_eq!(decompress(&bytes).unwrap(), b"abcabracadabra abracadabra");
+    }
+
+    #[test]
+    fn reject_corrupted_data() {
+        //the block check
+        let mut bytes = ABC_X86_SHA256.to_vec();
+        bytes[40] ^= 1;
+        assert!(decompress(&bytes).is_err());
+
+        //the block header
+        let mut bytes = ABRACADABRA.to_vec();
+        bytes[14] ^= 1;
+        assert!(decompress(&bytes).is_err());
+
+        //the footer flags
+        let mut bytes = ABRACADABRA.to_vec();
+        let len = bytes.len();
+        bytes[len - 3] = 1;
+        assert!(decompress(&bytes).is_err());
+
+        assert!(decompress(&ABRACADABRA[..ABRACADABRA.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,1285 @@
+use std::io::{BufReader, ErrorKind, Read, Write};
+
+use crate::checksums::xxhash64::XxHash64;
+use crate::unpacker::algorithms::Decompressor;
+
+const FRAME_MAGIC: u32 = 0xFD2F_B528;
+const SKIPPABLE_MAGIC_MASK: u32 = 0xFFFF_FFF0;
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+
+/// Largest window we agree to keep around; the reference decoder defaults to the same limit.
+pub const MAX_WINDOW_SIZE: u64 = 1 << 27;
+
+const MAX_BLOCK_SIZE: usize = 128 * 1024;
+
+const MAX_LITERALS_LENGTH_LOG: u32 = 9;
+const MAX_MATCH_LENGTH_LOG: u32 = 9;
+const MAX_OFFSET_LOG: u32 = 8;
+const MAX_HUFFMAN_WEIGHTS_LOG: u32 = 6;
+const MAX_HUFFMAN_BITS: u32 = 11;
+
+const LITERALS_LENGTH_DEFAULT_LOG: u32 = 6;
+const LITERALS_LENGTH_DEFAULT: [i16; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1,
+    -1, -1, -1, -1,
+];
+
+const MATCH_LENGTH_DEFAULT_LOG: u32 = 6;
+const MATCH_LENGTH_DEFAULT: [i16; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+];
+
+const OFFSET_DEFAULT_LOG: u32 = 5;
+const OFFSET_DEFAULT: [i16; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+];
+
+//(baseline, extra bits) for literal length codes 16 and up
+const LITERALS_LENGTH_CODES: [(u32, u32); 20] = [
+    (16, 1),
+    (18, 1),
+    (20, 1),
+    (22, 1),
+    (24, 2),
+    (28, 2),
+    (32, 3),
+    (40, 3),
+    (48, 4),
+    (64, 6),
+    (128, 7),
+    (256, 8),
+    (512, 9),
+    (1024, 10),
+    (2048, 11),
+    (4096, 12),
+    (8192, 13),
+    (16384, 14),
+    (32768, 15),
+    (65536, 16),
+];
+
+//(baseline, extra bits) for match length codes 32 and up
+const MATCH_LENGTH_CODES: [(u32, u32); 21] = [
+    (35, 1),
+    (37, 1),
+    (39, 1),
+    (41, 1),
+    (43, 2),
+    (47, 2),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 5),
+    (131, 7),
+    (259, 8),
+    (515, 9),
+    (1027, 10),
+    (2051, 11),
+    (4099, 12),
+    (8195, 13),
+    (16387, 14),
+    (32771, 15),
+    (65539, 16),
+];
+
+fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("Zstandard: {}", reason))
+}
+
+fn highest_bit(value: u32) -> u32 {
+    31 - value.leading_zeros()
+}
+
+/// Little-endian bit reader used for FSE table descriptions.
+struct ForwardBitReader<'a> {
+    bytes: &'a [u8],
+    bit_pos: usize,
+}
+
+impl<'a> ForwardBitReader<'a> {
+    fn new(bytes: &'a [u8]) -> ForwardBitReader<'a> {
+        ForwardBitReader { bytes, bit_pos: 0 }
+    }
+
+    fn peek(&self, count: u32) -> std::io::Result<u32> {
+        let mut value = 0u64;
+        let first_byte = self.bit_pos / 8;
+
+        for n in 0..5 {
+            if let Some(byte) = self.bytes.get(first_byte + n) {
+                value |= (*byte as u64) << (n * 8);
+            }
+        }
+
+        if self.bit_pos + count as usize > self.bytes.len() * 8 + 32 {
+            return Err(invalid_data("truncated table description."));
+        }
+
+        Ok(((value >> (self.bit_pos % 8)) & ((1u64 << count) - 1)) as u32)
+    }
+
+    fn skip(&mut self, count: u32) -> std::io::Result<()> {
+        self.bit_pos += count as usize;
+
+        if self.bit_pos > self.bytes.len() * 8 {
+            return Err(invalid_data("truncated table description."));
+        }
+
+        Ok(())
+    }
+
+    fn bytes_consumed(&self) -> usize {
+        self.bit_pos.div_ceil(8)
+    }
+}
+
+/// Reads a bitstream backwards from its end, as FSE and Huffman streams are written.
+struct BackwardBitReader<'a> {
+    bytes: &'a [u8],
+    //bits not consumed yet; goes negative once the reader runs past the start
+    bits_left: isize,
+}
+
+impl<'a> BackwardBitReader<'a> {
+    fn new(bytes: &'a [u8]) -> std::io::Result<BackwardBitReader<'a>> {
+        let last = match bytes.last() {
+            Some(last) if *last != 0 => *last,
+            _ => return Err(invalid_data("bitstream has no end mark.")),
+        };
+
+        Ok(BackwardBitReader {
+            bytes,
+            bits_left: (bytes.len() * 8) as isize - 8 + highest_bit(last as u32) as isize,
+        })
+    }
+
+    /// Bits `[start, start + count)` of the stream; positions before the start read as zeros.
+    fn bits_at(&self, start: isize, count: u32) -> u64 {
+        if count == 0 {
+            return 0;
+        }
+
+        if start < 0 {
+            let available = count as isize + start;
+
+            return if available > 0 {
+                self.bits_at(0, available as u32) << (-start)
+            } else {
+                0
+            };
+        }
+
+        let first_byte = start as usize / 8;
+        let mut value = 0u64;
+
+        for n in 0..8 {
+            if let Some(byte) = self.bytes.get(first_byte + n) {
+                value |= (*byte as u64) << (n * 8);
+            }
+        }
+
+        (value >> (start % 8)) & ((1u64 << count) - 1)
+    }
+
+    fn read(&mut self, count: u32) -> u64 {
+        self.bits_left -= count as isize;
+
+        self.bits_at(self.bits_left, count)
+    }
+
+    fn peek(&self, count: u32) -> u64 {
+        self.bits_at(self.bits_left - count as isize, count)
+    }
+
+    fn consume(&mut self, count: u32) {
+        self.bits_left -= count as isize;
+    }
+
+    fn is_overflowed(&self) -> bool {
+        self.bits_left < 0
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct FseEntry {
+    symbol: u8,
+    bits: u8,
+    base: u16,
+}
+
+#[derive(Clone)]
+struct FseTable {
+    accuracy_log: u32,
+    entries: Vec<FseEntry>,
+}
+
+impl FseTable {
+    fn from_distribution(distribution: &[i16], accuracy_log: u32) -> std::io::Result<FseTable> {
+        let table_size = 1usize << accuracy_log;
+        let mut entries = vec![FseEntry::default(); table_size];
+        let mut next_state = vec![0u32; distribution.len()];
+
+        let mut high_threshold = table_size - 1;
+
+        for (symbol, probability) in distribution.iter().enumerate() {
+            if *probability == -1 {
+                entries[high_threshold].symbol = symbol as u8;
+                high_threshold = high_threshold.wrapping_sub(1);
+                next_state[symbol] = 1;
+            } else {
+                next_state[symbol] = *probability as u32;
+            }
+        }
+
+        let step = (table_size >> 1) + (table_size >> 3) + 3;
+        let mask = table_size - 1;
+        let mut position = 0usize;
+
+        for (symbol, probability) in distribution.iter().enumerate() {
+            for _ in 0..std::cmp::max(*probability, 0) {
+                entries[position].symbol = symbol as u8;
+
+                loop {
+                    position = (position + step) & mask;
+
+                    if high_threshold == usize::MAX || position <= high_threshold {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if position != 0 {
+            return Err(invalid_data("bad FSE distribution."));
+        }
+
+        for entry in entries.iter_mut() {
+            let state = next_state[entry.symbol as usize];
+            next_state[entry.symbol as usize] += 1;
+
+            let bits = accuracy_log - highest_bit(state);
+
+            entry.bits = bits as u8;
+            entry.base = ((state << bits) as usize - table_size) as u16;
+        }
+
+        Ok(FseTable {
+            accuracy_log,
+            entries,
+        })
+    }
+
+    fn rle(symbol: u8) -> FseTable {
+        FseTable {
+            accuracy_log: 0,
+            entries: vec![FseEntry {
+                symbol,
+                bits: 0,
+                base: 0,
+            }],
+        }
+    }
+
+    /// Parses a table description; returns the table and the number of bytes it took.
+    fn read(
+        bytes: &[u8],
+        max_accuracy_log: u32,
+        max_symbol: usize,
+    ) -> std::io::Result<(FseTable, usize)> {
+        let mut bits = ForwardBitReader::new(bytes);
+
+        let accuracy_log = bits.peek(4)? + 5;
+        bits.skip(4)?;
+
+        if accuracy_log > max_accuracy_log {
+            return Err(invalid_data("FSE accuracy log is too large."));
+        }
+
+        let mut distribution = Vec::new();
+        let mut remaining = (1i32 << accuracy_log) + 1;
+        let mut threshold = 1i32 << accuracy_log;
+        let mut bit_count = accuracy_log + 1;
+
+        while remaining > 1 {
+            if distribution.len() > max_symbol {
+                return Err(invalid_data("too many FSE symbols."));
+            }
+
+            let max = 2 * threshold - 1 - remaining;
+            let value = bits.peek(bit_count)? as i32;
+
+            let mut count = if (value & (threshold - 1)) < max {
+                bits.skip(bit_count - 1)?;
+
+                value & (threshold - 1)
+            } else {
+                bits.skip(bit_count)?;
+
+                let count = value & (2 * threshold - 1);
+
+                if count >= threshold {
+                    count - max
+                } else {
+                    count
+                }
+            };
+
+            count -= 1;
+            remaining -= count.abs();
+            distribution.push(count as i16);
+
+            if count == 0 {
+                loop {
+                    let repeat = bits.peek(2)?;
+                    bits.skip(2)?;
+
+                    distribution.extend(std::iter::repeat_n(0, repeat as usize));
+
+                    if repeat != 3 {
+                        break;
+                    }
+                }
+            }
+
+            while remaining < threshold && threshold > 1 {
+                bit_count -= 1;
+                threshold >>= 1;
+            }
+        }
+
+        if remaining != 1 || distribution.len() > max_symbol + 1 {
+            return Err(invalid_data("bad FSE distribution."));
+        }
+
+        let table = FseTable::from_distribution(&distribution, accuracy_log)?;
+
+        Ok((table, bits.bytes_consumed()))
+    }
+
+    fn init_state(&self, bits: &mut BackwardBitReader) -> usize {
+        bits.read(self.accuracy_log) as usize
+    }
+
+    fn symbol(&self, state: usize) -> u8 {
+        self.entries[state].symbol
+    }
+
+    fn update_state(&self, state: usize, bits: &mut BackwardBitReader) -> usize {
+        let entry = self.entries[state];
+
+        entry.base as usize + bits.read(entry.bits as u32) as usize
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct HuffmanEntry {
+    symbol: u8,
+    bits: u8,
+}
+
+#[derive(Clone)]
+struct HuffmanTable {
+    max_bits: u32,
+    entries: Vec<HuffmanEntry>,
+}
+
+impl HuffmanTable {
+    fn from_weights(weights: &[u8]) -> std::io::Result<HuffmanTable> {
+        let mut weight_sum = 0u32;
+
+        for weight in weights {
+            if *weight as u32 > MAX_HUFFMAN_BITS {
+                return Err(invalid_data("Huffman weight is too large."));
+            }
+
+            if *weight > 0 {
+                weight_sum += 1 << (weight - 1);
+            }
+        }
+
+        if weight_sum == 0 {
+            return Err(invalid_data("empty Huffman tree."));
+        }
+
+        //the last weight is implied: it completes the sum to the next power of two
+        let max_bits = highest_bit(weight_sum) + 1;
+        let left_over = (1u32 << max_bits) - weight_sum;
+
+        if !left_over.is_power_of_two() || max_bits > MAX_HUFFMAN_BITS {
+            return Err(invalid_data("bad Huffman weights."));
+        }
+
+        let mut weights = weights.to_vec();
+        weights.push((highest_bit(left_over) + 1) as u8);
+
+        if weights.len() > 256 {
+            return Err(invalid_data("too many Huffman symbols."));
+        }
+
+        let mut rank_count = [0u32; MAX_HUFFMAN_BITS as usize + 2];
+        for weight in &weights {
+            rank_count[*weight as usize] += 1;
+        }
+
+        let mut rank_start = [0u32; MAX_HUFFMAN_BITS as usize + 2];
+        let mut next_start = 0;
+
+        for weight in 1..=max_bits as usize {
+            rank_start[weight] = next_start;
+            next_start += rank_count[weight] << (weight - 1);
+        }
+
+        let mut entries = vec![HuffmanEntry::default(); 1 << max_bits];
+
+        for (symbol, weight) in weights.iter().enumerate() {
+            if *weight == 0 {
+                continue;
+            }
+
+            let len = 1u32 << (weight - 1);
+            let start = rank_start[*weight as usize] as usize;
+
+            for entry in &mut entries[start..start + len as usize] {
+                *entry = HuffmanEntry {
+                    symbol: symbol as u8,
+                    bits: (max_bits + 1 - *weight as u32) as u8,
+                };
+            }
+
+            rank_start[*weight as usize] += len;
+        }
+
+        Ok(HuffmanTable { max_bits, entries })
+    }
+
+    /// Parses a tree description; returns the table and the number of bytes it took.
+    fn read(bytes: &[u8]) -> std::io::Result<(HuffmanTable, usize)> {
+        let header = *bytes
+            .first()
+            .ok_or_else(|| invalid_data("missing Huffman tree."))? as usize;
+
+        let mut weights = Vec::new();
+
+        let size = if header < 128 {
+            let compressed = bytes
+                .get(1..1 + header)
+                .ok_or_else(|| invalid_data("truncated Huffman tree."))?;
+
+            let (table, table_size) = FseTable::read(compressed, MAX_HUFFMAN_WEIGHTS_LOG, 255)?;
+            let mut bits = BackwardBitReader::new(&compressed[table_size..])?;
+
+            let mut state_1 = table.init_state(&mut bits);
+            let mut state_2 = table.init_state(&mut bits);
+
+            //two interleaved states until the stream runs dry
+            loop {
+                weights.push(table.symbol(state_1));
+                state_1 = table.update_state(state_1, &mut bits);
+
+                if bits.is_overflowed() {
+                    weights.push(table.symbol(state_2));
+                    break;
+                }
+
+                weights.push(table.symbol(state_2));
+                state_2 = table.update_state(state_2, &mut bits);
+
+                if bits.is_overflowed() {
+                    weights.push(table.symbol(state_1));
+                    break;
+                }
+
+                if weights.len() > 255 {
+                    return Err(invalid_data("too many Huffman weights."));
+                }
+            }
+
+            1 + header
+        } else {
+            let count = header - 127;
+            let packed = bytes
+                .get(1..1 + count.div_ceil(2))
+                .ok_or_else(|| invalid_data("truncated Huffman tree."))?;
+
+            for n in 0..count {
+                let byte = packed[n / 2];
+                weights.push(if n % 2 == 0 { byte >> 4 } else { byte & 0xF });
+            }
+
+            1 + packed.len()
+        };
+
+        Ok((HuffmanTable::from_weights(&weights)?, size))
+    }
+
+    fn decode_stream(&self, bytes: &[u8], out: &mut Vec<u8>, count: usize) -> std::io::Result<()> {
+        let mut bits = BackwardBitReader::new(bytes)?;
+
+        for _ in 0..count {
+            let entry = self.entries[bits.peek(self.max_bits) as usize];
+
+            bits.consume(entry.bits as u32);
+            out.push(entry.symbol);
+        }
+
+        if bits.bits_left != 0 {
+            return Err(invalid_data("Huffman stream size mismatch."));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Sequence {
+    literals_length: u32,
+    match_length: u32,
+    offset_value: u32,
+}
+
+/// State carried from block to block within a frame.
+struct FrameDecoder {
+    window_size: u64,
+    history: Vec<u8>,
+    huffman: Option<HuffmanTable>,
+    literals_length_table: Option<FseTable>,
+    offset_table: Option<FseTable>,
+    match_length_table: Option<FseTable>,
+    reps: [u32; 3],
+    literals: Vec<u8>,
+    sequences: Vec<Sequence>,
+}
+
+impl FrameDecoder {
+    fn new(window_size: u64) -> FrameDecoder {
+        FrameDecoder {
+            window_size,
+            history: Vec::new(),
+            huffman: None,
+            literals_length_table: None,
+            offset_table: None,
+            match_length_table: None,
+            reps: [1, 4, 8],
+            literals: Vec::new(),
+            sequences: Vec::new(),
+        }
+    }
+
+    /// Keeps no more history than matches can reach, so memory stays bounded by the window.
+    fn trim_history(&mut self) {
+        let keep = self.window_size as usize;
+
+        if self.history.len() > 2 * keep + MAX_BLOCK_SIZE {
+            self.history.drain(..self.history.len() - keep);
+        }
+    }
+
+    /// Decodes the literals section; returns the number of bytes it took.
+    fn read_literals(&mut self, block: &[u8]) -> std::io::Result<usize> {
+        let header = *block
+            .first()
+            .ok_or_else(|| invalid_data("missing literals section."))?;
+
+        let literals_type = header & 3;
+        let size_format = (header >> 2) & 3;
+
+        let byte = |n: usize| -> std::io::Result<u32> {
+            block
+                .get(n)
+                .map(|byte| *byte as u32)
+                .ok_or_else(|| invalid_data("truncated literals header."))
+        };
+
+        self.literals.clear();
+
+        if literals_type < 2 {
+            let (header_size, regenerated_size) = match size_format {
+                0 | 2 => (1, byte(0)? >> 3),
+                1 => (2, (byte(0)? >> 4) + (byte(1)? << 4)),
+                _ => (3, (byte(0)? >> 4) + (byte(1)? << 4) + (byte(2)? << 12)),
+            };
+
+            let regenerated_size = regenerated_size as usize;
+
+            if regenerated_size > MAX_BLOCK_SIZE {
+                return Err(invalid_data("too many literals."));
+            }
+
+            if literals_type == 0 {
+                let raw = block
+                    .get(header_size..header_size + regenerated_size)
+                    .ok_or_else(|| invalid_data("truncated literals."))?;
+
+                self.literals.extend_from_slice(raw);
+
+                return Ok(header_size + regenerated_size);
+            }
+
+            let value = *block
+                .get(header_size)
+                .ok_or_else(|| invalid_data("truncated literals."))?;
+
+            self.literals
+                .extend(std::iter::repeat_n(value, regenerated_size));
+
+            return Ok(header_size + 1);
+        }
+
+        let (header_size, streams, size_bits) = match size_format {
+            0 => (3, 1, 10),
+            1 => (3, 4, 10),
+            2 => (4, 4, 14),
+            _ => (5, 4, 18),
+        };
+
+        let mut header_value = 0u64;
+        for n in 0..header_size {
+            header_value |= (byte(n)? as u64) << (8 * n);
+        }
+
+        let mask = (1u64 << size_bits) - 1;
+        let regenerated_size = ((header_value >> 4) & mask) as usize;
+        let compressed_size = ((header_value >> (4 + size_bits)) & mask) as usize;
+
+        if regenerated_size > MAX_BLOCK_SIZE {
+            return Err(invalid_data("too many literals."));
+        }
+
+        let mut data = block
+            .get(header_size..header_size + compressed_size)
+            .ok_or_else(|| invalid_data("truncated literals."))?;
+
+        if literals_type == 2 {
+            let (table, table_size) = HuffmanTable::read(data)?;
+
+            self.huffman = Some(table);
+            data = &data[table_size..];
+        }
+
+        let huffman = self
+            .huffman
+            .as_ref()
+            .ok_or_else(|| invalid_data("treeless literals without a previous tree."))?;
+
+        if streams == 1 {
+            huffman.decode_stream(data, &mut self.literals, regenerated_size)?;
+        } else {
+            if data.len() < 6 {
+                return Err(invalid_data("truncated jump table."));
+            }
+
+            let stream_sizes = [
+                u16::from_le_bytes([data[0], data[1]]) as usize,
+                u16::from_le_bytes([data[2], data[3]]) as usize,
+                u16::from_le_bytes([data[4], data[5]]) as usize,
+            ];
+
+            let mut streams_data = &data[6..];
+            let per_stream = regenerated_size.div_ceil(4);
+
+            for (n, stream_size) in stream_sizes.iter().enumerate() {
+                if *stream_size > streams_data.len() || per_stream * (n + 1) > regenerated_size {
+                    return Err(invalid_data("bad jump table."));
+                }
+
+                huffman.decode_stream(
+                    &streams_data[..*stream_size],
+                    &mut self.literals,
+                    per_stream,
+                )?;
+
+                streams_data = &streams_data[*stream_size..];
+            }
+
+            huffman.decode_stream(
+                streams_data,
+                &mut self.literals,
+                regenerated_size - 3 * per_stream,
+            )?;
+        }
+
+        Ok(header_size + compressed_size)
+    }
+
+    fn read_table(
+        mode: u8,
+        data: &[u8],
+        previous: &mut Option<FseTable>,
+        default: (&[i16], u32),
+        max_accuracy_log: u32,
+    ) -> std::io::Result<usize> {
+        match mode {
+            0 => {
+                *previous = Some(FseTable::from_distribution(default.0, default.1)?);
+
+                Ok(0)
+            }
+            1 => {
+                let symbol = *data
+                    .first()
+                    .ok_or_else(|| invalid_data("truncated RLE table."))?;
+
+                if symbol as usize >= default.0.len() {
+                    return Err(invalid_data("RLE symbol out of range."));
+                }
+
+                *previous = Some(FseTable::rle(symbol));
+
+                Ok(1)
+            }
+            2 => {
+                let (table, size) = FseTable::read(data, max_accuracy_log, default.0.len() - 1)?;
+                *previous = Some(table);
+
+                Ok(size)
+            }
+            _ => {
+                if previous.is_none() {
+                    return Err(invalid_data("repeated table without a previous one."));
+                }
+
+                Ok(0)
+            }
+        }
+    }
+
+    fn read_sequences(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.sequences.clear();
+
+        let byte = |n: usize| -> std::io::Result<usize> {
+            data.get(n)
+                .map(|byte| *byte as usize)
+                .ok_or_else(|| invalid_data("truncated sequences header."))
+        };
+
+        let (count, mut offset) = match byte(0)? {
+            0 => return Ok(()),
+            first @ 1..=127 => (first, 1),
+            first @ 128..=254 => (((first - 128) << 8) + byte(1)?, 2),
+            _ => (byte(1)? + (byte(2)? << 8) + 0x7F00, 3),
+        };
+
+        let modes = byte(offset)? as u8;
+        offset += 1;
+
+        if modes & 3 != 0 {
+            return Err(invalid_data("reserved bits set in sequence modes."));
+        }
+
+        offset += FrameDecoder::read_table(
+            modes >> 6,
+            &data[offset..],
+            &mut self.literals_length_table,
+            (&LITERALS_LENGTH_DEFAULT, LITERALS_LENGTH_DEFAULT_LOG),
+            MAX_LITERALS_LENGTH_LOG,
+        )?;
+        offset += FrameDecoder::read_table(
+            (modes >> 4) & 3,
+            &data[offset..],
+            &mut self.offset_table,
+            (&OFFSET_DEFAULT, OFFSET_DEFAULT_LOG),
+            MAX_OFFSET_LOG,
+        )?;
+        offset += FrameDecoder::read_table(
+            (modes >> 2) & 3,
+            &data[offset..],
+            &mut self.match_length_table,
+            (&MATCH_LENGTH_DEFAULT, MATCH_LENGTH_DEFAULT_LOG),
+            MAX_MATCH_LENGTH_LOG,
+        )?;
+
+        let (literals_length_table, offset_table, match_length_table) = match (
+            &self.literals_length_table,
+            &self.offset_table,
+            &self.match_length_table,
+        ) {
+            (Some(ll), Some(of), Some(ml)) => (ll, of, ml),
+            _ => return Err(invalid_data("missing sequence tables.")),
+        };
+
+        let mut bits = BackwardBitReader::new(
+            data.get(offset..)
+                .ok_or_else(|| invalid_data("truncated sequences."))?,
+        )?;
+
+        let mut ll_state = literals_length_table.init_state(&mut bits);
+        let mut of_state = offset_table.init_state(&mut bits);
+        let mut ml_state = match_length_table.init_state(&mut bits);
+
+        for n in 0..count {
+            let offset_code = offset_table.symbol(of_state) as u32;
+            let match_length_code = match_length_table.symbol(ml_state) as u32;
+            let literals_length_code = literals_length_table.symbol(ll_state) as u32;
+
+            if offset_code > 31 {
+                return Err(invalid_data("offset code out of range."));
+            }
+
+            let offset_value = (1u32 << offset_code) + bits.read(offset_code) as u32;
+
+            let match_length = match match_length_code {
+                0..=31 => match_length_code + 3,
+                code => {
+                    let (baseline, extra) = MATCH_LENGTH_CODES[code as usize - 32];
+                    baseline + bits.read(extra) as u32
+                }
+            };
+
+            let literals_length = match literals_length_code {
+                0..=15 => literals_length_code,
+                code => {
+                    let (baseline, extra) = LITERALS_LENGTH_CODES[code as usize - 16];
+                    baseline + bits.read(extra) as u32
+                }
+            };
+
+            self.sequences.push(Sequence {
+                literals_length,
+                match_length,
+                offset_value,
+            });
+
+            if n + 1 < count {
+                ll_state = literals_length_table.update_state(ll_state, &mut bits);
+                ml_state = match_length_table.update_state(ml_state, &mut bits);
+                of_state = offset_table.update_state(of_state, &mut bits);
+            }
+
+            if bits.is_overflowed() {
+                return Err(invalid_data("sequence bitstream overrun."));
+            }
+        }
+
+        if bits.bits_left != 0 {
+            return Err(invalid_data("sequence bitstream size mismatch."));
+        }
+
+        Ok(())
+    }
+
+    fn resolve_offset(&mut self, sequence: &Sequence) -> u32 {
+        if sequence.offset_value > 3 {
+            let offset = sequence.offset_value - 3;
+
+            self.reps = [offset, self.reps[0], self.reps[1]];
+
+            return offset;
+        }
+
+        let index = sequence.offset_value - 1 + (sequence.literals_length == 0) as u32;
+
+        let offset = match index {
+            0 => return self.reps[0],
+            3 => self.reps[0].wrapping_sub(1),
+            index => self.reps[index as usize],
+        };
+
+        //a corrupted stream can ask for offset 0; treat it like the reference decoder does
+        let offset = std::cmp::max(offset, 1);
+
+        if index > 1 {
+            self.reps[2] = self.reps[1];
+        }
+
+        self.reps[1] = self.reps[0];
+        self.reps[0] = offset;
+
+        offset
+    }
+
+    /// Appends what the sequences decode to, refusing to go over `max_size` bytes for the block.
+    fn execute_sequences(&mut self, max_size: usize) -> std::io::Result<()> {
+        let block_start = self.history.len();
+        let mut literals_pos = 0usize;
+
+        for n in 0..self.sequences.len() {
+            let sequence = self.sequences[n];
+
+            //checked before appending, a few KB of sequences can ask for gigabytes
+            let decoded = self.history.len() - block_start;
+
+            if decoded + sequence.literals_length as usize + sequence.match_length as usize
+                > max_size
+            {
+                return Err(invalid_data("block decodes to too much data."));
+            }
+
+            let literals_end = literals_pos + sequence.literals_length as usize;
+            let literals = self
+                .literals
+                .get(literals_pos..literals_end)
+                .ok_or_else(|| invalid_data("sequence needs more literals than decoded."))?;
+
+            self.history.extend_from_slice(literals);
+            literals_pos = literals_end;
+
+            let offset = self.resolve_offset(&sequence) as usize;
+            let match_length = sequence.match_length as usize;
+
+            if offset > self.history.len() || offset as u64 > self.window_size {
+                return Err(invalid_data("match offset is out of the window."));
+            }
+
+            let start = self.history.len() - offset;
+
+            if match_length <= offset {
+                self.history.extend_from_within(start..start + match_length);
+            } else {
+                for n in 0..match_length {
+                    let byte = self.history[start + n];
+                    self.history.push(byte);
+                }
+            }
+        }
+
+        if self.history.len() - block_start + self.literals.len() - literals_pos > max_size {
+            return Err(invalid_data("block decodes to too much data."));
+        }
+
+        self.history
+            .extend_from_slice(&self.literals[literals_pos..]);
+
+        Ok(())
+    }
+
+    fn decode_compressed_block(&mut self, block: &[u8], max_size: usize) -> std::io::Result<()> {
+        let literals_size = self.read_literals(block)?;
+
+        self.read_sequences(&block[literals_size..])?;
+        self.execute_sequences(max_size)
+    }
+}
+
+struct FrameHeader {
+    window_size: u64,
+    content_size: Option<u64>,
+    has_checksum: bool,
+}
+
+fn read_frame_header<R: Read>(reader: &mut R) -> std::io::Result<FrameHeader> {
+    let mut descriptor = [0u8; 1];
+    reader.read_exact(&mut descriptor)?;
+
+    let descriptor = descriptor[0];
+    let content_size_flag = descriptor >> 6;
+    let single_segment = descriptor & 0x20 != 0;
+    let has_checksum = descriptor & 0x04 != 0;
+    let dictionary_id_flag = descriptor & 3;
+
+    if descriptor & 0x08 != 0 {
+        return Err(invalid_data("reserved bit set in frame header."));
+    }
+
+    let mut window_size = 0;
+
+    if !single_segment {
+        let mut window_descriptor = [0u8; 1];
+        reader.read_exact(&mut window_descriptor)?;
+
+        let window_log = 10 + (window_descriptor[0] >> 3) as u32;
+        let window_base = 1u64 << window_log;
+
+        window_size = window_base + (window_base / 8) * (window_descriptor[0] & 7) as u64;
+    }
+
+    let mut read_le = |len: usize| -> std::io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        reader.read_exact(&mut bytes[..len])?;
+
+        Ok(u64::from_le_bytes(bytes))
+    };
+
+    let dictionary_id = match dictionary_id_flag {
+        0 => 0,
+        1 => read_le(1)?,
+        2 => read_le(2)?,
+        _ => read_le(4)?,
+    };
+
+    if dictionary_id != 0 {
+        return Err(invalid_data("frames using a dictionary are not supported."));
+    }
+
+    let content_size = match (content_size_flag, single_segment) {
+        (0, false) => None,
+        (0, true) => Some(read_le(1)?),
+        (1, _) => Some(read_le(2)? + 256),
+        (2, _) => Some(read_le(4)?),
+        _ => Some(read_le(8)?),
+    };
+
+    if single_segment {
+        window_size = content_size.unwrap_or(0);
+    }
+
+    if window_size > MAX_WINDOW_SIZE {
+        return Err(invalid_data("window is too large."));
+    }
+
+    Ok(FrameHeader {
+        window_size,
+        content_size,
+        has_checksum,
+    })
+}
+
+fn decompress_frame<R: Read>(
+    reader: &mut R,
+    writer: &mut dyn Write,
+    block: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    let header = read_frame_header(reader)?;
+
+    let mut decoder = FrameDecoder::new(header.window_size);
+    let mut hasher = XxHash64::new(0);
+    let mut content_size = 0u64;
+
+    let max_block_size = std::cmp::min(header.window_size, MAX_BLOCK_SIZE as u64) as usize;
+
+    loop {
+        let mut block_header = [0u8; 3];
+        reader.read_exact(&mut block_header)?;
+
+        let block_header =
+            u32::from_le_bytes([block_header[0], block_header[1], block_header[2], 0]);
+
+        let last_block = block_header & 1 != 0;
+        let block_type = (block_header >> 1) & 3;
+        let block_size = (block_header >> 3) as usize;
+
+        let block_start = decoder.history.len();
+
+        //no block decodes to more than what is left of the frame when its size is known
+        let max_decoded = match header.content_size {
+            Some(size) => std::cmp::min(size.saturating_sub(content_size), MAX_BLOCK_SIZE as u64),
+            None => MAX_BLOCK_SIZE as u64,
+        } as usize;
+
+        match block_type {
+            0 | 2 => {
+                if block_size > max_block_size {
+                    return Err(invalid_data("block is too large."));
+                }
+
+                block.resize(block_size, 0);
+                reader.read_exact(block)?;
+
+                if block_type == 0 {
+                    decoder.history.extend_from_slice(block);
+                } else {
+                    decoder.decode_compressed_block(block, max_decoded)?;
+                }
+            }
+            1 => {
+                if block_size > max_block_size {
+                    return Err(invalid_data("block is too large."));
+                }
+
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+
+                decoder
+                    .history
+                    .extend(std::iter::repeat_n(value[0], block_size));
+            }
+            _ => return Err(invalid_data("reserved block type.")),
+        }
+
+        let decoded = &decoder.history[block_start..];
+
+        if decoded.len() > MAX_BLOCK_SIZE {
+            return Err(invalid_data("block decodes to too much data."));
+        }
+
+        hasher.update(decoded);
+        writer.write_all(decoded)?;
+        content_size += decoded.len() as u64;
+
+        decoder.trim_history();
+
+        if last_block {
+            break;
+        }
+    }
+
+    if let Some(expected_size) = header.content_size {
+        if expected_size != content_size {
+            return Err(invalid_data("frame content size mismatch."));
+        }
+    }
+
+    if header.has_checksum {
+        let mut checksum = [0u8; 4];
+        reader.read_exact(&mut checksum)?;
+
+        if u32::from_le_bytes(checksum) != hasher.digest() as u32 {
+            return Err(invalid_data("frame checksum mismatch."));
+        }
+    }
+
+    Ok(())
+}
+
+pub struct ZstdDecompressor;
+impl Decompressor for ZstdDecompressor {
+    fn decompress(
+        &self,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        data_len: &u64,
+    ) -> std::io::Result<()> {
+        let mut reader = BufReader::new(reader.take(*data_len));
+        let mut block = Vec::with_capacity(MAX_BLOCK_SIZE);
+        let mut frames = 0;
+
+        loop {
+            let mut magic = [0u8; 4];
+            let mut magic_len = 0;
+
+            while magic_len < magic.len() {
+                match reader.read(&mut magic[magic_len..])? {
+                    0 => break,
+                    read => magic_len += read,
+                }
+            }
+
+            match magic_len {
+                0 if frames > 0 => return Ok(()),
+                4 => {}
+                _ => return Err(invalid_data("truncated frame.")),
+            }
+
+            let magic = u32::from_le_bytes(magic);
+
+            if magic & SKIPPABLE_MAGIC_MASK == SKIPPABLE_MAGIC {
+                let mut frame_size = [0u8; 4];
+                reader.read_exact(&mut frame_size)?;
+
+                let frame_size = u32::from_le_bytes(frame_size) as u64;
+
+                if std::io::copy(&mut (&mut reader).take(frame_size), &mut std::io::sink())?
+                    != frame_size
+                {
+                    return Err(invalid_data("truncated skippable frame."));
+                }
+            } else if magic == FRAME_MAGIC {
+                decompress_frame(&mut reader, writer, &mut block)?;
+            } else {
+                return Err(invalid_data("bad frame signature."));
+            }
+
+            frames += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use crate::unpacker::algorithms::zstd::{FseTable, ZstdDecompressor, LITERALS_LENGTH_DEFAULT};
+    use crate::unpacker::algorithms::Decompressor;
+
+    fn decompress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        ZstdDecompressor.decompress(&mut &bytes[..], &mut out, &(bytes.len() as u64))?;
+
+        Ok(out)
+    }
+
+    #[test]
+    fn predefined_table_is_complete() {
+        let table = FseTable::from_distribution(&LITERALS_LENGTH_DEFAULT, 6).unwrap();
+
+        assert_eq!(table.entries.len(), 64);
+        assert_eq!(table.entries[63].symbol, 32);
+    }
+
+    #[test]
+    fn raw_and_rle_blocks() {
+        let frame = [
+            0x28, 0xB5, 0x2F, 0xFD, //magic
+            0x20, 0x08, //single segment, content size 8
+            0x18, 0x00, 0x00, b'a', b'b', b'c', //raw block of 3
+            0x2B, 0x00, 0x00, b'z', //last RLE block of 5
+        ];
+
+        assert_eq!(decompress(&frame).unwrap(), b"abczzzzz");
+    }
+
+    #[test]
+    fn skippable_and_multiple_frames() {
+        let mut bytes = vec![0x50, 0x2A, 0x4D, 0x18, 0x02, 0x00, 0x00, 0x00, 0xAA, 0xBB];
+
+        for _ in 0..2 {
+            bytes.extend_from_slice(&[
+                0x28, 0xB5, 0x2F, 0xFD, 0x20, 0x02, 0x11, 0x00, 0x00, b'x', b'y',
+            ]);
+        }
+
+        assert_eq!(decompress(&bytes).unwrap(), b"xyxy");
+    }
+
+    #[test]
+    fn reject_bad_frames() {
+        //content size does not match
+        assert!(
+            decompress(&[0x28, 0xB5, 0x2F, 0xFD, 0x20, 0x03, 0x11, 0x00, 0x00, b'x', b'y'])
+                .is_err()
+        );
+        //window larger than allowed
+        assert!(
+            decompress(&[0x28, 0xB5, 0x2F, 0xFD, 0x00, 0xF8, 0x11, 0x00, 0x00, b'x', b'y'])
+                .is_err()
+        );
+        //reserved block type
+        assert!(
+            decompress(&[0x28, 0xB5, 0x2F, 0xFD, 0x20, 0x02, 0x17, 0x00, 0x00, b'x', b'y'])
+                .is_err()
+        );
+        assert!(decompress(b"").is_err());
+    }
+
+    #[test]
+    fn reject_decompression_bomb() {
+        let mut frame = vec![
+            0x28, 0xB5, 0x2F, 0xFD, //magic
+            0x00, 0x38, //no content size, 128 KiB window
+            0x08, 0x00, 0x00, b'a', //raw block of 1
+        ];
+
+        //50000 sequences with offset 1 and match length 65539, with their extra bits all zero:
+        //about 110 KB that would decode to more than 3 GB
+        let mut block = vec![
+            0x00, //no literals
+            0xFF, 0x50, 0x44, //50000 sequences
+            //RLE tables: literals length 0, offset code 2, match length code 52
+            0x54, 0x00, 0x02, 0x34,
+        ];
+        block.extend(std::iter::repeat_n(0u8, 50000 * 18 / 8));
+        block.push(0x01);
+
+        let block_header = (block.len() as u32) << 3 | 2 << 1 | 1;
+        frame.extend_from_slice(&block_header.to_le_bytes()[..3]);
+        frame.extend(block);
+
+        let err = decompress(&frame).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("too much data"));
+
+        //a single match already larger than what is left of the frame
+        let frame = [
+            0x28, 0xB5, 0x2F, 0xFD, 0x20, 0x0A, //single segment, content size 10
+            0x08, 0x00, 0x00, b'a', //raw block of 1
+            0x3D, 0x00, 0x00, //last compressed block of 7
+            //one sequence with offset 1 and match length 35
+            0x00, 0x01, 0x54, 0x00, 0x02, 0x20, 0x08,
+        ];
+
+        let err = decompress(&frame).unwrap_err();
+        assert!(err.to_string().contains("too much data"));
+    }
+}
@@ -0,0 +1,263 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+
+use crate::zip::signatures;
+
+pub trait ArchiveSource: Read + Seek {
+    /// The whole archive as a slice, if it is addressable in memory.
+    fn mapped_bytes(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// The underlying file, if the kernel can copy out of it directly.
+    fn file(&self) -> Option<&File> {
+        None
+    }
+
+    /// Where the volume numbered `disk` starts in the stream.
+    fn volume_start(&self, disk: u32) -> Option<u64> {
+        (disk == 0).then_some(0)
+    }
+}
+
+impl ArchiveSource for BufReader<File> {
+    fn file(&self) -> Option<&File> {
+        Some(self.get_ref())
+    }
+}
+
+pub struct MappedSource {
+    cursor: Cursor<Mmap>,
+}
+
+impl MappedSource {
+    pub fn open(path: &Path) -> std::io::Result<MappedSource> {
+        let file = File::open(path)?;
+
+        //the map is read-only; truncating the file under us is the caller's problem, as with any reader
+        let map = unsafe { Mmap::map(&file)? };
+
+        Ok(MappedSource {
+            cursor: Cursor::new(map),
+        })
+    }
+}
+
+impl Read for MappedSource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.cursor.read(buf)
+    }
+}
+
+impl Seek for MappedSource {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.cursor.seek(pos)
+    }
+}
+
+impl ArchiveSource for MappedSource {
+    fn mapped_bytes(&self) -> Option<&[u8]> {
+        Some(self.cursor.get_ref())
+    }
+}
+
+/// Bytes already in memory, such as those of a `MappedSource` that is scanned at the same time.
+impl ArchiveSource for Cursor<&[u8]> {
+    fn mapped_bytes(&self) -> Option<&[u8]> {
+        Some(self.get_ref())
+    }
+}
+
+/// The volumes of a split archive (`.z01`, `.z02`, ..., `.zip`) read as one stream.
+pub struct SplitSource {
+    volumes: Vec<BufReader<File>>,
+    //start of every volume in the stream, followed by the stream length
+    starts: Vec<u64>,
+    position: u64,
+    current: usize,
+    //the current volume isn't at `position` anymore
+    moved: bool,
+}
+
+impl SplitSource {
+    /// Path of the volume numbered `disk`, the last one being the archive itself.
+    fn volume_path(path: &Path, disk: u32) -> PathBuf {
+        let upper_case = path
+            .extension()
+            .is_some_and(|extension| extension.to_string_lossy().starts_with('Z'));
+
+        path.with_extension(format!(
+            "{}{:02}",
+            if upper_case { 'Z' } else { 'z' },
+            disk + 1
+        ))
+    }
+
+    pub fn is_split(path: &Path) -> bool {
+        SplitSource::volume_path(path, 0).is_file()
+    }
+
+    /// Opens the last volume at `path` along with the volumes before it.
+    pub fn open(path: &Path) -> std::io::Result<SplitSource> {
+        let mut last_volume = BufReader::new(File::open(path)?);
+
+        let end_pos = super::find_end_of_central_dir(&mut last_volume)?;
+        last_volume.seek(SeekFrom::Start(end_pos + 4))?;
+        let mut last_disk = super::read_end_of_central_dir(&mut last_volume)?.number_of_this_disk;
+
+        if let Some(locator) = super::read_zip64_locator(&mut last_volume, end_pos)? {
+            last_disk = locator.total_number_of_disks.saturating_sub(1);
+        }
+
+        //volumes are only counted as they are found
+        let mut volumes = Vec::new();
+
+        for disk in 0..last_disk {
+            let volume_path = SplitSource::volume_path(path, disk);
+
+            match File::open(&volume_path) {
+                Ok(volume) => volumes.push(BufReader::new(volume)),
+                Err(err) if err.kind() == ErrorKind::NotFound => {
+                    return Err(std::io::Error::new(
+                        ErrorKind::NotFound,
+                        format!("Volume {} is missing.", volume_path.display()),
+                    ))
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        volumes.push(last_volume);
+
+        let mut signature = [0u8; 4];
+        volumes[0].seek(SeekFrom::Start(0))?;
+        volumes[0].read_exact(&mut signature)?;
+
+        if last_disk > 0 && u32::from_le_bytes(signature) != signatures::SIGNATURE_SPLIT_ARCHIVE {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "Bad split archive signature.",
+            ));
+        }
+
+        let mut starts = vec![0];
+
+        for volume in &volumes {
+            starts.push(starts[starts.len() - 1] + volume.get_ref().metadata()?.len());
+        }
+
+        Ok(SplitSource {
+            volumes,
+            starts,
+            position: 0,
+            current: 0,
+            moved: true,
+        })
+    }
+}
+
+impl Read for SplitSource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let volume = self.starts.partition_point(|&start| start <= self.position) - 1;
+
+        if volume >= self.volumes.len() {
+            return Ok(0);
+        }
+
+        if self.moved || volume != self.current {
+            self.volumes[volume].seek(SeekFrom::Start(self.position - self.starts[volume]))?;
+            self.current = volume;
+            self.moved = false;
+        }
+
+        //reads stop at the end of the volume; the next read goes on with the next one
+        let len = std::cmp::min(buf.len() as u64, self.starts[volume + 1] - self.position);
+        let read = self.volumes[volume].read(&mut buf[..len as usize])?;
+
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Seek for SplitSource {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.starts[self.volumes.len()].checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        }
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Invalid seek position."))?;
+
+        if new_position != self.position {
+            self.position = new_position;
+            self.moved = true;
+        }
+
+        Ok(self.position)
+    }
+}
+
+impl ArchiveSource for SplitSource {
+    fn volume_start(&self, disk: u32) -> Option<u64> {
+        self.starts[..self.volumes.len()]
+            .get(disk as usize)
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom};
+    use std::path::Path;
+
+    use crate::stream_utils::stream_nav::{current_position, length};
+    use crate::unpacker::source::{ArchiveSource, MappedSource, SplitSource};
+
+    #[test]
+    fn mapped_source_navigation() {
+        let mut source = MappedSource::open(Path::new("test-data/streams/streams_0.txt")).unwrap();
+
+        assert_eq!(length(&mut source).unwrap(), 8);
+
+        let mut bytes = [0u8; 4];
+        source.read_exact(&mut bytes).unwrap();
+
+        assert_eq!(current_position(&mut source).unwrap(), 4);
+        assert_eq!(&source.mapped_bytes().unwrap()[..4], &bytes);
+    }
+
+    #[test]
+    fn split_source_navigation() {
+        let path = Path::new("test-data/unpack_split/input.zip");
+        assert!(SplitSource::is_split(path));
+        assert!(!SplitSource::is_split(Path::new(
+            "test-data/unpack_store_0/input.zip"
+        )));
+
+        let mut source = SplitSource::open(path).unwrap();
+        let mut volumes = Vec::new();
+
+        for extension in ["z01", "z02", "zip"].iter() {
+            volumes.extend(std::fs::read(path.with_extension(extension)).unwrap());
+        }
+
+        assert_eq!(length(&mut source).unwrap(), volumes.len() as u64);
+        assert_eq!(source.volume_start(1), Some(65536));
+        assert_eq!(source.volume_start(3), None);
+
+        //across the boundary of the first two volumes
+        let mut bytes = [0u8; 100];
+        source.seek(SeekFrom::Start(65500)).unwrap();
+        source.read_exact(&mut bytes).unwrap();
+        assert_eq!(&bytes[..], &volumes[65500..65600]);
+
+        let mut rest = Vec::new();
+        source.seek(SeekFrom::End(-70000)).unwrap();
+        source.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &volumes[volumes.len() - 70000..]);
+    }
+}
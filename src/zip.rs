@@ -1,4 +1,9 @@
 pub mod compression_methods;
+pub mod dos_time;
+pub mod extra_fields;
 pub mod feature_versions;
+pub mod general_bit_flags;
+pub mod host_systems;
 pub mod signatures;
 pub mod structs;
+pub mod wildcard;
@@ -1,2 +1,15 @@
 pub const STORE: u16 = 0;
+pub const SHRINK: u16 = 1;
+pub const REDUCE_1: u16 = 2;
+pub const REDUCE_2: u16 = 3;
+pub const REDUCE_3: u16 = 4;
+pub const REDUCE_4: u16 = 5;
+pub const IMPLODE: u16 = 6;
 pub const DEFLATE: u16 = 8;
+pub const DEFLATE64: u16 = 9;
+pub const BZIP2: u16 = 12;
+pub const LZMA: u16 = 14;
+pub const ZSTD: u16 = 93;
+pub const XZ: u16 = 95;
+pub const PPMD: u16 = 98;
+pub const AES: u16 = 99;
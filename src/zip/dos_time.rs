@@ -0,0 +1,95 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Converts a timestamp to MS-DOS (date, time) fields in UTC. DOS dates start in 1980,
+/// earlier times are clamped to its first second; seconds are rounded down to even.
+pub fn from_system_time(time: SystemTime) -> (u16, u16) {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let days = (seconds / 86400) as i64;
+    let seconds_of_day = seconds % 86400;
+
+    //civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let shifted = days + 719468;
+    let era = shifted.div_euclid(146097);
+    let day_of_era = shifted - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    if year < 1980 {
+        return (1 << 5 | 1, 0);
+    }
+
+    let year = std::cmp::min(year, 2107);
+
+    let date = ((year - 1980) << 9 | month << 5 | day) as u16;
+    let time = (seconds_of_day / 3600) << 11
+        | (seconds_of_day / 60 % 60) << 5
+        | ((seconds_of_day % 60) / 2);
+
+    (date, time as u16)
+}
+
+/// Converts MS-DOS (date, time) fields, taken as UTC, to seconds since the epoch.
+pub fn to_unix_time(date: u16, time: u16) -> u64 {
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0x0F).clamp(1, 12) as i64;
+    let day = std::cmp::max(date & 0x1F, 1) as i64;
+
+    //days since the epoch from a civil date, the inverse of the above
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds_of_day =
+        (time >> 11) as i64 * 3600 + ((time >> 5) & 0x3F) as i64 * 60 + (time & 0x1F) as i64 * 2;
+
+    (days * 86400 + seconds_of_day) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::zip::dos_time::{from_system_time, to_unix_time};
+
+    #[test]
+    fn convert_timestamps() {
+        //2020-04-14 12:00:00
+        assert_eq!(
+            from_system_time(UNIX_EPOCH + Duration::from_secs(1586865600)),
+            (0x508e, 0x6000)
+        );
+        //2000-02-29 23:59:59
+        assert_eq!(
+            from_system_time(UNIX_EPOCH + Duration::from_secs(951868799)),
+            (20 << 9 | 2 << 5 | 29, 23 << 11 | 59 << 5 | 29)
+        );
+        assert_eq!(from_system_time(UNIX_EPOCH), (0x0021, 0));
+    }
+
+    #[test]
+    fn convert_back() {
+        assert_eq!(to_unix_time(0x508e, 0x6000), 1586865600);
+        assert_eq!(
+            to_unix_time(20 << 9 | 2 << 5 | 29, 23 << 11 | 59 << 5 | 29),
+            951868798
+        );
+        assert_eq!(to_unix_time(0x0021, 0), 315532800);
+    }
+}
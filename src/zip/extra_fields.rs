@@ -0,0 +1,216 @@
+use crate::checksums::crc32;
+use crate::stream_utils::{byte_readers, byte_writers};
+
+pub const ZIP64: u16 = 0x0001;
+pub const WINZIP_AES: u16 = 0x9901;
+pub const UNICODE_COMMENT: u16 = 0x6375;
+pub const EXTENDED_TIMESTAMP: u16 = 0x5455;
+/// Padding of a local file header, as Android's zipalign and apksigner write it.
+pub const ALIGNMENT: u16 = 0xD935;
+
+const UNICODE_VERSION: u8 = 1;
+
+/// Value of a 32-bit header field whose real value lives in the ZIP64 extra field.
+pub const ZIP64_MARKER: u64 = 0xFFFF_FFFF;
+/// The same for 16-bit fields, such as disk numbers.
+pub const ZIP64_SHORT_MARKER: u64 = 0xFFFF;
+
+/// Returns the data of the first extra field block with the given header id.
+pub fn find(extra_field: &[u8], header_id: u16) -> Option<&[u8]> {
+    let mut offset = 0;
+
+    while offset + 4 <= extra_field.len() {
+        let id = byte_readers::read_to::<u16>(extra_field, &mut offset).ok()?;
+        let size = byte_readers::read_to::<u16>(extra_field, &mut offset).ok()? as usize;
+
+        let data = extra_field.get(offset..offset + size)?;
+
+        if id == header_id {
+            return Some(data);
+        }
+
+        offset += size;
+    }
+
+    None
+}
+
+/// Returns the extra field without the blocks with the given header id.
+pub fn remove(extra_field: &[u8], header_id: u16) -> Vec<u8> {
+    let mut result = Vec::with_capacity(extra_field.len());
+    let mut offset = 0;
+
+    while offset + 4 <= extra_field.len() {
+        let id = u16::from_le_bytes([extra_field[offset], extra_field[offset + 1]]);
+        let size = u16::from_le_bytes([extra_field[offset + 2], extra_field[offset + 3]]) as usize;
+        let end = std::cmp::min(offset + 4 + size, extra_field.len());
+
+        if id != header_id {
+            result.extend_from_slice(&extra_field[offset..end]);
+        }
+
+        offset = end;
+    }
+
+    result
+}
+
+/// UTF-8 text of an Info-ZIP Unicode block, as long as the CRC-32 it keeps of `standard`,
+/// the field it stands in for, still matches.
+pub fn unicode_text(extra_field: &[u8], header_id: u16, standard: &[u8]) -> Option<String> {
+    let data = find(extra_field, header_id)?;
+
+    if data.len() < 5 || data[0] != UNICODE_VERSION {
+        return None;
+    }
+
+    let crc = byte_readers::read_to::<u32>(data, &mut 1).ok()?;
+
+    if crc != crc32::checksum(standard) {
+        return None;
+    }
+
+    String::from_utf8(data[5..].to_vec()).ok()
+}
+
+/// An Info-ZIP Unicode block holding `text` for the field whose bytes are `standard`.
+pub fn unicode_block(header_id: u16, standard: &[u8], text: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    byte_writers::write_to(&mut bytes, header_id);
+    byte_writers::write_to(&mut bytes, (5 + text.len()) as u16);
+    bytes.push(UNICODE_VERSION);
+    byte_writers::write_to(&mut bytes, crc32::checksum(standard));
+    bytes.extend_from_slice(text.as_bytes());
+
+    bytes
+}
+
+/// Modification time in seconds since the epoch from an extended timestamp block.
+pub fn modification_time(extra_field: &[u8]) -> Option<i64> {
+    let data = find(extra_field, EXTENDED_TIMESTAMP)?;
+
+    //the flags tell which times follow, the modification time comes first
+    if data.first()? & 1 == 0 {
+        return None;
+    }
+
+    //signed, as Unix times were
+    Some(byte_readers::read_to::<u32>(data, &mut 1).ok()? as i32 as i64)
+}
+
+/// An extended timestamp block with the modification time only.
+pub fn modification_time_block(mtime: i64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    byte_writers::write_to(&mut bytes, EXTENDED_TIMESTAMP);
+    byte_writers::write_to(&mut bytes, 5u16);
+    bytes.push(1);
+    byte_writers::write_to(
+        &mut bytes,
+        mtime.clamp(i32::MIN as i64, i32::MAX as i64) as i32 as u32,
+    );
+
+    bytes
+}
+
+/// Length of an alignment block without padding.
+pub const ALIGNMENT_BLOCK_LEN: usize = 6;
+
+/// Largest alignment an alignment block can record.
+pub const MAX_ALIGNMENT: u64 = 0xFFFF;
+
+/// An alignment block that takes `padding` bytes more than the smallest one.
+/// `alignment` is at most `MAX_ALIGNMENT`.
+pub fn alignment_block(alignment: u64, padding: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(ALIGNMENT_BLOCK_LEN + padding);
+
+    byte_writers::write_to(&mut bytes, ALIGNMENT);
+    byte_writers::write_to(&mut bytes, (2 + padding) as u16);
+    byte_writers::write_to(&mut bytes, alignment as u16);
+    bytes.resize(ALIGNMENT_BLOCK_LEN + padding, 0);
+
+    bytes
+}
+
+/// Returns the extra field without alignment blocks and the zero bytes older zipalign pads with.
+pub fn remove_padding(extra_field: &[u8]) -> Vec<u8> {
+    //zero bytes read as empty blocks with header id 0, and less than a block is dropped anyway
+    remove(&remove(extra_field, ALIGNMENT), 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zip::extra_fields::{
+        alignment_block, find, modification_time, modification_time_block, remove, remove_padding,
+        unicode_block, unicode_text, ALIGNMENT, UNICODE_COMMENT, ZIP64,
+    };
+
+    #[test]
+    fn find_blocks() {
+        let extra_field = [
+            0x55, 0x54, 0x05, 0x00, 0x03, 0x01, 0x02, 0x03, 0x04, //extended timestamp
+            0x01, 0x00, 0x08, 0x00, 1, 2, 3, 4, 5, 6, 7, 8, //ZIP64
+        ];
+
+        assert_eq!(
+            find(&extra_field, ZIP64),
+            Some(&[1u8, 2, 3, 4, 5, 6, 7, 8][..])
+        );
+        assert_eq!(find(&extra_field, 0x5455), Some(&[3u8, 1, 2, 3, 4][..]));
+        assert_eq!(find(&extra_field, 0x7875), None);
+        assert_eq!(find(&extra_field[..15], ZIP64), None);
+
+        assert_eq!(modification_time(&extra_field), Some(0x04030201));
+        assert_eq!(
+            modification_time(&modification_time_block(1_700_000_000)),
+            Some(1_700_000_000)
+        );
+        assert_eq!(modification_time(&[0x55, 0x54, 0x01, 0x00, 0x02]), None);
+    }
+
+    #[test]
+    fn unicode_comment() {
+        //"Größe" in code page 437
+        let standard = [0x47, 0x72, 0xF6, 0xE1, 0x65];
+
+        let mut extra_field = vec![0x55, 0x54, 0x01, 0x00, 0x03];
+        extra_field.extend(unicode_block(UNICODE_COMMENT, &standard, "Größe"));
+
+        assert_eq!(
+            unicode_text(&extra_field, UNICODE_COMMENT, &standard),
+            Some("Größe".to_owned())
+        );
+
+        //stale once the standard field changes
+        assert_eq!(unicode_text(&extra_field, UNICODE_COMMENT, b"Grosse"), None);
+
+        assert_eq!(
+            remove(&extra_field, UNICODE_COMMENT),
+            vec![0x55, 0x54, 0x01, 0x00, 0x03]
+        );
+        assert_eq!(remove(&extra_field, 0x5455), extra_field[5..].to_vec());
+    }
+
+    #[test]
+    fn alignment_padding() {
+        let block = alignment_block(4096, 3);
+
+        assert_eq!(block, vec![0x35, 0xD9, 0x05, 0x00, 0x00, 0x10, 0, 0, 0]);
+        assert_eq!(find(&block, ALIGNMENT), Some(&[0x00u8, 0x10, 0, 0, 0][..]));
+
+        let mut extra_field = vec![0x55, 0x54, 0x01, 0x00, 0x03];
+        extra_field.extend(block);
+        assert_eq!(
+            remove_padding(&extra_field),
+            vec![0x55, 0x54, 0x01, 0x00, 0x03]
+        );
+
+        //what older zipalign leaves behind
+        let extra_field = [0x55, 0x54, 0x01, 0x00, 0x03, 0, 0, 0, 0, 0, 0];
+        assert_eq!(
+            remove_padding(&extra_field),
+            vec![0x55, 0x54, 0x01, 0x00, 0x03]
+        );
+    }
+}
@@ -0,0 +1,6 @@
+pub const ENCRYPTED: u16 = 0x0001;
+pub const LZMA_END_MARKER: u16 = 0x0002;
+pub const IMPLODE_8K_DICTIONARY: u16 = 0x0002;
+pub const IMPLODE_LITERAL_TREE: u16 = 0x0004;
+pub const DATA_DESCRIPTOR: u16 = 0x0008;
+pub const UTF8: u16 = 0x0800;
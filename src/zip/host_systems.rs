@@ -0,0 +1,3 @@
+/// Upper byte of "version made by", telling how to read the external file attributes.
+pub const MS_DOS: u16 = 0;
+pub const UNIX: u16 = 3;
@@ -1,3 +1,8 @@
-pub const SIGNATURE_FILE_HEADER : u32 = 0x04034b50;
+pub const SIGNATURE_FILE_HEADER: u32 = 0x04034b50;
 pub const SIGNATURE_CENTRAL_DIR_HEADER: u32 = 0x02014b50;
-pub const SIGNATURE_CENTRAL_DIR_END: u32 = 0x06054b50;
\ No newline at end of file
+pub const SIGNATURE_CENTRAL_DIR_END: u32 = 0x06054b50;
+pub const SIGNATURE_ZIP64_CENTRAL_DIR_END: u32 = 0x06064b50;
+pub const SIGNATURE_ZIP64_CENTRAL_DIR_END_LOCATOR: u32 = 0x07064b50;
+pub const SIGNATURE_DATA_DESCRIPTOR: u32 = 0x08074b50;
+//first 4 bytes of a split archive, the same value as the data descriptor signature
+pub const SIGNATURE_SPLIT_ARCHIVE: u32 = 0x08074b50;
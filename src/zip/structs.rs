@@ -1,7 +1,10 @@
 //TODO: implement a builder instead of using public fields
 //TODO: move structs into their own separate files
 
-#[derive(Debug)]
+use crate::stream_utils::byte_writers;
+use crate::zip::{extra_fields, feature_versions, signatures};
+
+#[derive(Debug, Clone)]
 pub struct LocalFileHeader {
     pub version_to_extract: u16,
     pub general_bit_flag: u16,
@@ -9,15 +12,15 @@ pub struct LocalFileHeader {
     pub last_mod_file_time: u16,
     pub last_mod_file_date: u16,
     pub crc_32: u32,
-    pub compressed_size: u64,   //can be 4 byte
-    pub uncompressed_size: u64, //can be 4 byte
+    pub compressed_size: u64, //4 bytes unless overridden by the ZIP64 extra field
+    pub uncompressed_size: u64, //4 bytes unless overridden by the ZIP64 extra field
     pub file_name_length: u16,
     pub extra_field_length: u16,
     pub file_name: String,
-    //extra_field: skip for now
+    pub extra_field: Vec<u8>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CentralDirFileHeader {
     pub version_made_by: u16,
     pub version_to_extract: u16,
@@ -26,28 +29,176 @@ pub struct CentralDirFileHeader {
     pub last_mod_file_time: u16,
     pub last_mod_file_date: u16,
     pub crc_32: u32,
-    pub compressed_size: u64,   //can be 4 byte
-    pub uncompressed_size: u64, //can be 4 byte
+    pub compressed_size: u64, //4 bytes unless overridden by the ZIP64 extra field
+    pub uncompressed_size: u64, //4 bytes unless overridden by the ZIP64 extra field
     pub file_name_length: u16,
     pub extra_field_length: u16,
     pub file_comment_length: u16,
-    pub disk_number_start: u16,
+    pub disk_number_start: u32, //2 bytes unless overridden by the ZIP64 extra field
     pub internal_file_attribs: u16,
     pub external_file_attribs: u32,
-    pub local_header_rel_offset: u32,
+    pub local_header_rel_offset: u64, //4 bytes unless overridden by the ZIP64 extra field
     pub file_name: String,
-    //extra_field: skip for now
+    pub extra_field: Vec<u8>,
     pub file_comment: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EndOfCentralDir {
-    pub number_of_this_disk: u16,
-    pub number_of_disk_with_start_central_dir: u16,
-    pub total_entries_in_central_dir_on_this_disk: u16,
-    pub total_entries_in_central_dir: u16,
-    pub central_dir_size: u32,
-    pub central_dir_offset_from_starting_disk_num: u32,
+    //the numbers are 2 or 4 bytes unless overridden by the ZIP64 end of central dir record
+    pub number_of_this_disk: u32,
+    pub number_of_disk_with_start_central_dir: u32,
+    pub total_entries_in_central_dir_on_this_disk: u64,
+    pub total_entries_in_central_dir: u64,
+    pub central_dir_size: u64,
+    pub central_dir_offset_from_starting_disk_num: u64,
     pub zip_file_comment_length: u16,
     pub zip_file_comment: String,
 }
+
+/// Points from the end of the archive to the ZIP64 end of central dir record.
+#[derive(Debug, Clone)]
+pub struct Zip64EndOfCentralDirLocator {
+    pub number_of_disk_with_zip64_end: u32,
+    pub zip64_end_offset: u64,
+    pub total_number_of_disks: u32,
+}
+
+//values that don't fit 32 bits are written as the ZIP64 marker; their real values are
+//expected in the ZIP64 extra field
+fn to_u32_or_marker(value: u64) -> u32 {
+    std::cmp::min(value, extra_fields::ZIP64_MARKER) as u32
+}
+
+fn to_u16_or_marker(value: u64) -> u16 {
+    std::cmp::min(value, extra_fields::ZIP64_SHORT_MARKER) as u16
+}
+
+impl LocalFileHeader {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        byte_writers::write_to(&mut bytes, signatures::SIGNATURE_FILE_HEADER);
+        byte_writers::write_to(&mut bytes, self.version_to_extract);
+        byte_writers::write_to(&mut bytes, self.general_bit_flag);
+        byte_writers::write_to(&mut bytes, self.compression_method);
+        byte_writers::write_to(&mut bytes, self.last_mod_file_time);
+        byte_writers::write_to(&mut bytes, self.last_mod_file_date);
+        byte_writers::write_to(&mut bytes, self.crc_32);
+        byte_writers::write_to(&mut bytes, to_u32_or_marker(self.compressed_size));
+        byte_writers::write_to(&mut bytes, to_u32_or_marker(self.uncompressed_size));
+        byte_writers::write_to(&mut bytes, self.file_name.len() as u16);
+        byte_writers::write_to(&mut bytes, self.extra_field.len() as u16);
+        bytes.extend_from_slice(self.file_name.as_bytes());
+        bytes.extend_from_slice(&self.extra_field);
+
+        bytes
+    }
+}
+
+impl CentralDirFileHeader {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        byte_writers::write_to(&mut bytes, signatures::SIGNATURE_CENTRAL_DIR_HEADER);
+        byte_writers::write_to(&mut bytes, self.version_made_by);
+        byte_writers::write_to(&mut bytes, self.version_to_extract);
+        byte_writers::write_to(&mut bytes, self.general_bit_flag);
+        byte_writers::write_to(&mut bytes, self.compression_method);
+        byte_writers::write_to(&mut bytes, self.last_mod_file_time);
+        byte_writers::write_to(&mut bytes, self.last_mod_file_date);
+        byte_writers::write_to(&mut bytes, self.crc_32);
+        byte_writers::write_to(&mut bytes, to_u32_or_marker(self.compressed_size));
+        byte_writers::write_to(&mut bytes, to_u32_or_marker(self.uncompressed_size));
+        byte_writers::write_to(&mut bytes, self.file_name.len() as u16);
+        byte_writers::write_to(&mut bytes, self.extra_field.len() as u16);
+        byte_writers::write_to(&mut bytes, self.file_comment.len() as u16);
+        byte_writers::write_to(&mut bytes, to_u16_or_marker(self.disk_number_start as u64));
+        byte_writers::write_to(&mut bytes, self.internal_file_attribs);
+        byte_writers::write_to(&mut bytes, self.external_file_attribs);
+        byte_writers::write_to(&mut bytes, to_u32_or_marker(self.local_header_rel_offset));
+        bytes.extend_from_slice(self.file_name.as_bytes());
+        bytes.extend_from_slice(&self.extra_field);
+        bytes.extend_from_slice(self.file_comment.as_bytes());
+
+        bytes
+    }
+}
+
+impl EndOfCentralDir {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        byte_writers::write_to(&mut bytes, signatures::SIGNATURE_CENTRAL_DIR_END);
+        byte_writers::write_to(
+            &mut bytes,
+            to_u16_or_marker(self.number_of_this_disk as u64),
+        );
+        byte_writers::write_to(
+            &mut bytes,
+            to_u16_or_marker(self.number_of_disk_with_start_central_dir as u64),
+        );
+        byte_writers::write_to(
+            &mut bytes,
+            to_u16_or_marker(self.total_entries_in_central_dir_on_this_disk),
+        );
+        byte_writers::write_to(
+            &mut bytes,
+            to_u16_or_marker(self.total_entries_in_central_dir),
+        );
+        byte_writers::write_to(&mut bytes, to_u32_or_marker(self.central_dir_size));
+        byte_writers::write_to(
+            &mut bytes,
+            to_u32_or_marker(self.central_dir_offset_from_starting_disk_num),
+        );
+        byte_writers::write_to(&mut bytes, self.zip_file_comment.len() as u16);
+        bytes.extend_from_slice(self.zip_file_comment.as_bytes());
+
+        bytes
+    }
+
+    /// Whether some value only fits the ZIP64 end of central dir record.
+    pub fn needs_zip64(&self) -> bool {
+        self.number_of_this_disk as u64 >= extra_fields::ZIP64_SHORT_MARKER
+            || self.number_of_disk_with_start_central_dir as u64 >= extra_fields::ZIP64_SHORT_MARKER
+            || self.total_entries_in_central_dir_on_this_disk >= extra_fields::ZIP64_SHORT_MARKER
+            || self.total_entries_in_central_dir >= extra_fields::ZIP64_SHORT_MARKER
+            || self.central_dir_size >= extra_fields::ZIP64_MARKER
+            || self.central_dir_offset_from_starting_disk_num >= extra_fields::ZIP64_MARKER
+    }
+
+    /// The ZIP64 end of central dir record with the same values, without extensible data.
+    pub fn to_zip64_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        byte_writers::write_to(&mut bytes, signatures::SIGNATURE_ZIP64_CENTRAL_DIR_END);
+        //size of the rest of the record
+        byte_writers::write_to(&mut bytes, 44u64);
+        byte_writers::write_to(&mut bytes, feature_versions::ZIP64);
+        byte_writers::write_to(&mut bytes, feature_versions::ZIP64);
+        byte_writers::write_to(&mut bytes, self.number_of_this_disk);
+        byte_writers::write_to(&mut bytes, self.number_of_disk_with_start_central_dir);
+        byte_writers::write_to(&mut bytes, self.total_entries_in_central_dir_on_this_disk);
+        byte_writers::write_to(&mut bytes, self.total_entries_in_central_dir);
+        byte_writers::write_to(&mut bytes, self.central_dir_size);
+        byte_writers::write_to(&mut bytes, self.central_dir_offset_from_starting_disk_num);
+
+        bytes
+    }
+}
+
+impl Zip64EndOfCentralDirLocator {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        byte_writers::write_to(
+            &mut bytes,
+            signatures::SIGNATURE_ZIP64_CENTRAL_DIR_END_LOCATOR,
+        );
+        byte_writers::write_to(&mut bytes, self.number_of_disk_with_zip64_end);
+        byte_writers::write_to(&mut bytes, self.zip64_end_offset);
+        byte_writers::write_to(&mut bytes, self.total_number_of_disks);
+
+        bytes
+    }
+}
@@ -0,0 +1,53 @@
+/// Matches an entry name against a pattern where `*` stands for any run of characters,
+/// slashes included, and `?` for exactly one character.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                //let the last star swallow one more character
+                Some((star, star_n)) => {
+                    backtrack = Some((star, star_n + 1));
+                    p = star + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zip::wildcard::matches;
+
+    #[test]
+    fn match_patterns() {
+        assert!(matches("*.txt", "text.txt"));
+        assert!(matches("*.txt", "folder_1/empty.txt"));
+        assert!(matches("folder_1/*", "folder_1/folder_11/runs.bin"));
+        assert!(matches("?hort.t?t", "short.txt"));
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "aXbYbZc"));
+
+        assert!(!matches("*.txt", "random.bin"));
+        assert!(!matches("?", ""));
+        assert!(!matches("folder_1/*.bin", "folder_2/random.bin"));
+        assert!(!matches("a*b*c", "aXbYbZ"));
+    }
+}
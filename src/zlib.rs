@@ -0,0 +1,444 @@
+use std::io::{BufRead, ErrorKind, Read, Write};
+
+use crate::checksums::adler32::{self, Adler32};
+use crate::packer::deflate::Deflater;
+use crate::unpacker::algorithms::deflate::{Inflater, DEFLATE};
+
+pub use crate::packer::deflate::DEFAULT_LEVEL;
+
+//Deflate with a 32 KiB window
+const CMF: u8 = 0x78;
+const FLAG_DICTIONARY: u8 = 0x20;
+
+fn invalid_data(reason: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, format!("zlib: {}", reason))
+}
+
+/// Compresses what is written into a raw Deflate stream (RFC 1951).
+pub struct DeflateEncoder<W: Write> {
+    deflater: Deflater<W>,
+}
+
+impl<W: Write> DeflateEncoder<W> {
+    /// `level` goes from 0, storing only, to 9.
+    pub fn new(writer: W, level: u32) -> DeflateEncoder<W> {
+        DeflateEncoder {
+            deflater: Deflater::new(writer, level),
+        }
+    }
+
+    /// Like `new`, with `dictionary` as history the data can refer back to.
+    pub fn with_dictionary(writer: W, level: u32, dictionary: &[u8]) -> DeflateEncoder<W> {
+        let mut deflater = Deflater::new(writer, level);
+        deflater.set_dictionary(dictionary);
+
+        DeflateEncoder { deflater }
+    }
+
+    /// Ends the stream and returns the inner writer.
+    pub fn finish(self) -> std::io::Result<W> {
+        self.deflater.finish()
+    }
+}
+
+impl<W: Write> Write for DeflateEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.deflater.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.deflater.flush()
+    }
+}
+
+/// Reads the data of a raw Deflate stream (RFC 1951) from `reader`.
+/// Up to 8 bytes after the end of the stream may be taken from `reader` as well; `into_inner` gives them back.
+pub struct DeflateDecoder<R: BufRead> {
+    inflater: Inflater<'static, R, Vec<u8>>,
+    //how much of the last decoded chunk has been read
+    pos: usize,
+    done: bool,
+}
+
+impl<R: BufRead> DeflateDecoder<R> {
+    pub fn new(reader: R) -> DeflateDecoder<R> {
+        DeflateDecoder {
+            inflater: Inflater::new(reader, Vec::new(), &DEFLATE),
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// Like `new`, for a stream made with `dictionary` as history.
+    pub fn with_dictionary(reader: R, dictionary: &[u8]) -> std::io::Result<DeflateDecoder<R>> {
+        let mut decoder = DeflateDecoder::new(reader);
+        decoder.inflater.set_dictionary(dictionary)?;
+
+        Ok(decoder)
+    }
+
+    /// Bytes decoded so far.
+    pub fn total_out(&self) -> u64 {
+        self.inflater.total_out()
+    }
+
+    /// Returns `reader` and the bytes after the end of the stream that were taken from it.
+    /// Only meaningful once the whole stream has been read.
+    pub fn into_inner(self) -> std::io::Result<(R, Vec<u8>)> {
+        let (bits, _) = self.inflater.into_parts()?;
+
+        Ok(bits.into_inner())
+    }
+}
+
+impl<R: BufRead> Read for DeflateDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let out = self.inflater.writer_mut();
+
+            if self.pos < out.len() {
+                let len = std::cmp::min(buf.len(), out.len() - self.pos);
+                buf[..len].copy_from_slice(&out[self.pos..self.pos + len]);
+                self.pos += len;
+
+                return Ok(len);
+            }
+
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+
+            //no more than the caller asks for, so output stays bounded however big a block is
+            let limit = std::cmp::min(buf.len(), DEFLATE.window_size as usize);
+
+            out.clear();
+            self.pos = 0;
+            self.done = self.inflater.inflate_some(limit as u64)?;
+        }
+    }
+}
+
+//FLEVEL of the header, only informative
+fn level_flags(level: u32) -> u8 {
+    match level {
+        0..=1 => 0,
+        2..=5 => 1,
+        6 => 2,
+        _ => 3,
+    }
+}
+
+/// Compresses what is written into a zlib stream (RFC 1950).
+pub struct ZlibEncoder<W: Write> {
+    encoder: DeflateEncoder<W>,
+    adler: Adler32,
+}
+
+impl<W: Write> ZlibEncoder<W> {
+    pub fn new(writer: W, level: u32) -> std::io::Result<ZlibEncoder<W>> {
+        ZlibEncoder::with_header(writer, level, None)
+    }
+
+    /// Like `new`, with a preset dictionary; the stream records its Adler-32.
+    pub fn with_dictionary(
+        writer: W,
+        level: u32,
+        dictionary: &[u8],
+    ) -> std::io::Result<ZlibEncoder<W>> {
+        ZlibEncoder::with_header(writer, level, Some(dictionary))
+    }
+
+    fn with_header(
+        mut writer: W,
+        level: u32,
+        dictionary: Option<&[u8]>,
+    ) -> std::io::Result<ZlibEncoder<W>> {
+        let mut flags = level_flags(level) << 6;
+
+        if dictionary.is_some() {
+            flags |= FLAG_DICTIONARY;
+        }
+
+        //the check bits make the header a multiple of 31
+        flags |= (31 - ((CMF as u16) << 8 | flags as u16) % 31) as u8 % 31;
+
+        writer.write_all(&[CMF, flags])?;
+
+        let encoder = match dictionary {
+            Some(dictionary) => {
+                writer.write_all(&adler32::checksum(dictionary).to_be_bytes())?;
+                DeflateEncoder::with_dictionary(writer, level, dictionary)
+            }
+            None => DeflateEncoder::new(writer, level),
+        };
+
+        Ok(ZlibEncoder {
+            encoder,
+            adler: Adler32::new(),
+        })
+    }
+
+    /// Ends the stream with the Adler-32 of the data, and returns the inner writer.
+    pub fn finish(self) -> std::io::Result<W> {
+        let mut writer = self.encoder.finish()?;
+
+        writer.write_all(&self.adler.value().to_be_bytes())?;
+        writer.flush()?;
+
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for ZlibEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.encoder.write(buf)?;
+        self.adler.update(&buf[..written]);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+/// Reads the data of a zlib stream (RFC 1950) from `reader`, checking its Adler-32 at the end.
+pub struct ZlibDecoder<R: BufRead> {
+    decoder: DeflateDecoder<R>,
+    adler: Adler32,
+    checked: bool,
+}
+
+impl<R: BufRead> ZlibDecoder<R> {
+    /// Reads the header; streams that need a preset dictionary are refused.
+    pub fn new(reader: R) -> std::io::Result<ZlibDecoder<R>> {
+        ZlibDecoder::with_dictionary(reader, None)
+    }
+
+    /// Like `new`, with the dictionary to use when the stream asks for one.
+    /// It has to be the one the stream was made with, as its Adler-32 tells.
+    pub fn with_dictionary(
+        mut reader: R,
+        dictionary: Option<&[u8]>,
+    ) -> std::io::Result<ZlibDecoder<R>> {
+        let mut header = [0u8; 2];
+        reader.read_exact(&mut header)?;
+
+        if header[0] & 0x0F != 8 || header[0] >> 4 > 7 {
+            return Err(invalid_data("not a Deflate stream."));
+        }
+
+        if u16::from_be_bytes(header) % 31 != 0 {
+            return Err(invalid_data("header check failed."));
+        }
+
+        let decoder = if header[1] & FLAG_DICTIONARY != 0 {
+            let mut dictionary_id = [0u8; 4];
+            reader.read_exact(&mut dictionary_id)?;
+
+            let dictionary = dictionary
+                .filter(|dictionary| {
+                    adler32::checksum(dictionary) == u32::from_be_bytes(dictionary_id)
+                })
+                .ok_or_else(|| invalid_data("stream needs a dictionary that wasn't given."))?;
+
+            DeflateDecoder::with_dictionary(reader, dictionary)?
+        } else {
+            DeflateDecoder::new(reader)
+        };
+
+        Ok(ZlibDecoder {
+            decoder,
+            adler: Adler32::new(),
+            checked: false,
+        })
+    }
+
+    /// Bytes decoded so far.
+    pub fn total_out(&self) -> u64 {
+        self.decoder.total_out()
+    }
+}
+
+impl<R: BufRead> Read for ZlibDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.decoder.read(buf)?;
+        self.adler.update(&buf[..len]);
+
+        if len == 0 && !buf.is_empty() && !self.checked {
+            let bits = self.decoder.inflater.bits_mut();
+            bits.align_to_byte();
+
+            //the only big-endian number read LSB first
+            if bits.read(32)?.swap_bytes() != self.adler.value() {
+                return Err(invalid_data("Adler-32 doesn't match."));
+            }
+
+            self.checked = true;
+        }
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::zlib::{DeflateDecoder, DeflateEncoder, ZlibDecoder, ZlibEncoder};
+
+    #[test]
+    fn raw_deflate() {
+        let data = std::fs::read("test-data/sfx_archive/expected/text.txt").unwrap();
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), 6);
+        encoder.write_all(&data).unwrap();
+        let mut compressed = encoder.finish().unwrap();
+
+        //what follows the stream comes back from into_inner
+        compressed.extend_from_slice(b"trailer");
+
+        let mut decoder = DeflateDecoder::new(&compressed[..]);
+        let mut out = Vec::new();
+        decoder.read_to_end(&mut out).unwrap();
+
+        assert_eq!(out, data);
+        assert_eq!(decoder.total_out(), data.len() as u64);
+
+        let (mut reader, mut rest) = decoder.into_inner().unwrap();
+        assert!(rest.len() <= 8);
+
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"trailer");
+
+        //small reads
+        let mut decoder = DeflateDecoder::new(&compressed[..]);
+        let mut out = Vec::new();
+        let mut buf = [0u8; 7];
+
+        loop {
+            match decoder.read(&mut buf).unwrap() {
+                0 => break,
+                len => out.extend_from_slice(&buf[..len]),
+            }
+        }
+
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn large_block_in_small_reads() {
+        //one fixed Huffman block: "a", then matches of 258 bytes at distance 1
+        let matches = 100_000;
+        let mut compressed = Vec::new();
+        let mut bits = 0u64;
+        let mut bit_count = 0;
+
+        let mut push = |value: u32, count: u32, compressed: &mut Vec<u8>| {
+            bits |= (value as u64) << bit_count;
+            bit_count += count;
+
+            while bit_count >= 8 {
+                compressed.push(bits as u8);
+                bits >>= 8;
+                bit_count -= 8;
+            }
+        };
+
+        //Huffman codes go MSB first
+        let code = |value: u32, count: u32| value.reverse_bits() >> (32 - count);
+
+        push(0b011, 3, &mut compressed);
+        push(code(0x30 + b'a' as u32, 8), 8, &mut compressed);
+
+        for _ in 0..matches {
+            push(code(0xC5, 8), 8, &mut compressed);
+            push(0, 5, &mut compressed);
+        }
+
+        push(0, 7 + 7, &mut compressed);
+
+        let mut decoder = DeflateDecoder::new(&compressed[..]);
+        let mut buf = [0u8; 10];
+
+        for _ in 0..1000 {
+            decoder.read_exact(&mut buf).unwrap();
+
+            assert_eq!(buf, [b'a'; 10]);
+            assert!(decoder.inflater.writer_mut().capacity() <= 64);
+        }
+
+        let mut out = Vec::new();
+        decoder.read_to_end(&mut out).unwrap();
+
+        assert_eq!(out.len() + 10_000, 1 + 258 * matches);
+        assert!(out.iter().all(|&byte| byte == b'a'));
+    }
+
+    #[test]
+    fn zlib_streams() {
+        //zlib.compress(b"hello hello hello hello")
+        let compressed = [
+            0x78, 0x9C, 0xCB, 0x48, 0xCD, 0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x27, 0x01, 0x68, 0x03,
+            0x08, 0xB1,
+        ];
+
+        let mut out = String::new();
+        ZlibDecoder::new(&compressed[..])
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "hello hello hello hello");
+
+        let mut corrupted = compressed;
+        corrupted[15] ^= 1;
+        assert!(ZlibDecoder::new(&corrupted[..])
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .is_err());
+
+        assert!(ZlibDecoder::new(&[0x78, 0x9D][..]).is_err());
+
+        let data =
+            std::fs::read("test-data/sfx_archive/expected/folder_1/folder_11/runs.bin").unwrap();
+
+        for level in [0, 1, 9].iter() {
+            let mut encoder = ZlibEncoder::new(Vec::new(), *level).unwrap();
+            encoder.write_all(&data).unwrap();
+            let compressed = encoder.finish().unwrap();
+
+            let mut out = Vec::new();
+            ZlibDecoder::new(&compressed[..])
+                .unwrap()
+                .read_to_end(&mut out)
+                .unwrap();
+
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn preset_dictionary() {
+        let dictionary = b"Content-Type: application/json\r\nContent-Length: ";
+        let data = b"Content-Type: application/json\r\nContent-Length: 42\r\n";
+
+        let mut encoder = ZlibEncoder::with_dictionary(Vec::new(), 9, dictionary).unwrap();
+        encoder.write_all(data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut plain = ZlibEncoder::new(Vec::new(), 9).unwrap();
+        plain.write_all(data).unwrap();
+        assert!(compressed.len() < plain.finish().unwrap().len());
+
+        assert!(ZlibDecoder::new(&compressed[..]).is_err());
+        assert!(ZlibDecoder::with_dictionary(&compressed[..], Some(&b"other"[..])).is_err());
+
+        let mut out = Vec::new();
+        ZlibDecoder::with_dictionary(&compressed[..], Some(&dictionary[..]))
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+
+        assert_eq!(out, data);
+    }
+}